@@ -1,52 +1,123 @@
-use std::{
-    io::Write,
-    process::{Command, Stdio},
-    task::Poll,
-};
+use std::{io::Write, process::Command, sync::mpsc, task::Poll, thread};
 
 use crossterm::{
     handle_command,
-    style::{Print, SetForegroundColor},
+    style::{Color, Print},
     Result,
 };
 
 use crate::{
     async_process::{AsyncChild, ChildOutput, Executor},
-    tui_util::{AvailableSize, LOG_COLORS},
+    command_log, profile,
+    temp_file::TempFile,
+    theme,
+    tui_util::{fg, AvailableSize},
 };
 
 pub type ActionResult = ChildOutput;
 
+/// Follows the names of bookmarks when listing branches
+pub const BOOKMARK_SUFFIX: &str = " (bookmark)";
+
 #[derive(PartialEq, Eq, Hash, Clone, Copy)]
 pub enum ActionKind {
     Quit,
     Help,
+    GoBack,
+    GoForward,
     Status,
+    RefreshStatus,
     Log,
     LogCount,
+    LogMore,
+    LogSignatures,
+    LogGraph,
     CurrentFullRevision,
     CurrentDiffAll,
     CurrentDiffSelected,
+    RevisionDetails,
     RevisionChanges,
     RevisionDiffAll,
     RevisionDiffSelected,
     CommitAll,
     CommitSelected,
+    CommitHunks,
+    UndoLastCommit,
+    CommitAndPush,
+    Absorb,
     Update,
+    UpdateSubmodules,
     Merge,
+    CherryPick,
+    ContinueCherryPick,
+    AbortCherryPick,
+    Rebase,
+    ContinueRebase,
+    AbortRebase,
     RevertAll,
     RevertSelected,
+    ResetSelected,
     UnresolvedConflicts,
     MergeTakingOther,
     MergeTakingLocal,
+    AbortOperation,
     Fetch,
     Pull,
     Push,
+    RunInTerminal,
+    ListRemotes,
+    NewRemote,
+    DeleteRemote,
     NewTag,
+    CheckoutTag,
+    DeleteTag,
     ListBranches,
+    CheckoutBranch,
     NewBranch,
     DeleteBranch,
+    DeletePickedBranch,
+    NewBookmark,
+    DeleteBookmark,
+    ShowPhase,
+    SetPhase,
+    EditHistory,
+    ContinueHistoryEdit,
+    AbortHistoryEdit,
+    ListMergedBranches,
+    DeleteMergedBranches,
+    Stash,
+    Blame,
+    BlameParent,
+    BlameBack,
+    BlameIgnoreRevs,
+    ListStashes,
+    StashShow,
+    StashPop,
+    ToggleSideBySide,
+    ToggleStatusAfterChanges,
+    ToggleSignOff,
+    ToggleGpgSign,
+    MoreContext,
+    LessContext,
+    ToggleIgnoreWhitespace,
+    NextDiffAlgorithm,
+    ToggleDetectMoves,
+    PreviousHunk,
+    NextHunk,
+    CopyHunk,
+    CopyTarget,
+    CopyRoot,
+    CopyBranch,
+    PreviousFile,
+    NextFile,
+    JumpToFile,
+    SessionLog,
+    PageOutput,
+    ReloadConfig,
     CustomAction,
+    OpenExplorer,
+    OpenEditor,
+    SwitchRepository,
 }
 
 impl ActionKind {
@@ -54,42 +125,242 @@ impl ActionKind {
         match self {
             Self::Quit => "quit",
             Self::Help => "help",
+            Self::GoBack => "back to the previous view",
+            Self::GoForward => "forward to the next view",
             Self::Status => "status",
+            Self::RefreshStatus => "refresh status",
             Self::Log => "log",
             Self::LogCount => "log count",
+            Self::LogMore => "more log",
+            Self::LogSignatures => "toggle log signatures",
+            Self::LogGraph => "toggle log graph",
             Self::CurrentFullRevision => "revision full contents",
             Self::CurrentDiffAll => "current diff all",
             Self::CurrentDiffSelected => "current diff selected",
+            Self::RevisionDetails => "revision details",
             Self::RevisionChanges => "revision changes",
             Self::RevisionDiffAll => "revision diff all",
             Self::RevisionDiffSelected => "revision diff selected",
             Self::CommitAll => "commit all",
+            Self::CommitAndPush => "commit all and push",
             Self::CommitSelected => "commit selected",
+            Self::CommitHunks => "commit hunks",
+            Self::UndoLastCommit => "undo last commit",
+            Self::Absorb => "absorb into draft revisions",
             Self::Update => "update/checkout",
+            Self::UpdateSubmodules => "update submodules",
             Self::Merge => "merge",
+            Self::CherryPick => "cherry-pick",
+            Self::ContinueCherryPick => "continue cherry-pick",
+            Self::AbortCherryPick => "abort cherry-pick",
+            Self::Rebase => "rebase",
+            Self::ContinueRebase => "continue rebase",
+            Self::AbortRebase => "abort rebase",
             Self::RevertAll => "revert all",
             Self::RevertSelected => "revert selected",
+            Self::ResetSelected => "reset selected to revision",
             Self::UnresolvedConflicts => "unresolved conflicts",
             Self::MergeTakingOther => "merge taking other",
             Self::MergeTakingLocal => "merge taking local",
+            Self::AbortOperation => "abort merge, rebase or cherry-pick",
             Self::Fetch => "fetch",
             Self::Pull => "pull",
             Self::Push => "push",
+            Self::RunInTerminal => "run again in terminal",
+            Self::ListRemotes => "list remotes",
+            Self::NewRemote => "new remote",
+            Self::DeleteRemote => "delete remote",
             Self::NewTag => "new tag",
+            Self::CheckoutTag => "checkout tag",
+            Self::DeleteTag => "delete tag",
             Self::ListBranches => "list branches",
+            Self::CheckoutBranch => "checkout branch",
             Self::NewBranch => "new branch",
             Self::DeleteBranch => "delete branch",
+            Self::DeletePickedBranch => "delete picked branch",
+            Self::NewBookmark => "new bookmark",
+            Self::DeleteBookmark => "delete bookmark",
+            Self::ShowPhase => "show phase",
+            Self::SetPhase => "set phase",
+            Self::EditHistory => "edit history",
+            Self::ContinueHistoryEdit => "continue editing history",
+            Self::AbortHistoryEdit => "abort editing history",
+            Self::ListMergedBranches => "list merged branches",
+            Self::DeleteMergedBranches => "delete merged branches",
+            Self::Stash => "stash",
+            Self::Blame => "blame",
+            Self::BlameParent => "blame before the line's revision",
+            Self::BlameBack => "back to the previous blame",
+            Self::BlameIgnoreRevs => "toggle blame ignore-revs",
+            Self::ListStashes => "list stashes",
+            Self::StashShow => "show stash",
+            Self::StashPop => "pop stash",
+            Self::ToggleSideBySide => "toggle side-by-side diff",
+            Self::ToggleStatusAfterChanges => "toggle status after changes",
+            Self::ToggleSignOff => "toggle signing off commits",
+            Self::ToggleGpgSign => "toggle gpg signing commits",
+            Self::MoreContext => "more diff context",
+            Self::LessContext => "less diff context",
+            Self::ToggleIgnoreWhitespace => "toggle ignoring whitespace",
+            Self::NextDiffAlgorithm => "next diff algorithm",
+            Self::ToggleDetectMoves => "toggle detecting moves",
+            Self::PreviousHunk => "previous hunk",
+            Self::NextHunk => "next hunk",
+            Self::CopyHunk => "copy hunk",
+            Self::CopyTarget => "copy revision under the cursor",
+            Self::CopyRoot => "copy repository path",
+            Self::CopyBranch => "copy current branch",
+            Self::PreviousFile => "previous file",
+            Self::NextFile => "next file",
+            Self::JumpToFile => "jump to file",
+            Self::SessionLog => "session log",
+            Self::PageOutput => "page output",
+            Self::ReloadConfig => "reload config",
             Self::CustomAction => "custom action",
+            Self::OpenExplorer => "open in file explorer",
+            Self::OpenEditor => "open in editor",
+            Self::SwitchRepository => "switch repository",
         }
     }
 
+    /// Whether running the action changes the repository or working tree
+    pub fn is_mutating(self) -> bool {
+        matches!(
+            self,
+            Self::CommitAll
+                | Self::CommitSelected
+                | Self::CommitHunks
+                | Self::UndoLastCommit
+                | Self::CommitAndPush
+                | Self::Absorb
+                | Self::Update
+                | Self::UpdateSubmodules
+                | Self::Merge
+                | Self::CherryPick
+                | Self::ContinueCherryPick
+                | Self::AbortCherryPick
+                | Self::Rebase
+                | Self::ContinueRebase
+                | Self::AbortRebase
+                | Self::RevertAll
+                | Self::RevertSelected
+                | Self::ResetSelected
+                | Self::MergeTakingOther
+                | Self::MergeTakingLocal
+                | Self::AbortOperation
+                | Self::Fetch
+                | Self::Pull
+                | Self::Push
+                | Self::RunInTerminal
+                | Self::NewRemote
+                | Self::DeleteRemote
+                | Self::NewTag
+                | Self::CheckoutTag
+                | Self::DeleteTag
+                | Self::CheckoutBranch
+                | Self::NewBranch
+                | Self::DeleteBranch
+                | Self::DeletePickedBranch
+                | Self::NewBookmark
+                | Self::DeleteBookmark
+                | Self::SetPhase
+                | Self::EditHistory
+                | Self::ContinueHistoryEdit
+                | Self::AbortHistoryEdit
+                | Self::DeleteMergedBranches
+                | Self::Stash
+                | Self::StashPop
+        )
+    }
+
+    /// Whether the files changed in the working tree are worth listing after
+    /// it ran
+    pub fn changes_working_tree(self) -> bool {
+        matches!(
+            self,
+            Self::CommitAll
+                | Self::CommitSelected
+                | Self::CommitHunks
+                | Self::UndoLastCommit
+                | Self::CommitAndPush
+                | Self::Absorb
+                | Self::Update
+                | Self::UpdateSubmodules
+                | Self::Merge
+                | Self::CherryPick
+                | Self::ContinueCherryPick
+                | Self::AbortCherryPick
+                | Self::Rebase
+                | Self::ContinueRebase
+                | Self::AbortRebase
+                | Self::RevertAll
+                | Self::RevertSelected
+                | Self::ResetSelected
+                | Self::MergeTakingOther
+                | Self::MergeTakingLocal
+                | Self::AbortOperation
+                | Self::Pull
+                | Self::RunInTerminal
+                | Self::CheckoutTag
+                | Self::CheckoutBranch
+                | Self::Stash
+                | Self::StashPop
+                | Self::EditHistory
+                | Self::ContinueHistoryEdit
+                | Self::AbortHistoryEdit
+        )
+    }
+
+    /// Whether the output is made of unified diffs
+    pub fn is_diff(self) -> bool {
+        matches!(
+            self,
+            Self::CurrentFullRevision
+                | Self::CurrentDiffAll
+                | Self::CurrentDiffSelected
+                | Self::RevisionDiffAll
+                | Self::RevisionDiffSelected
+                | Self::StashShow
+        )
+    }
+
+    pub fn is_blame(self) -> bool {
+        matches!(
+            self,
+            Self::Blame
+                | Self::BlameParent
+                | Self::BlameBack
+                | Self::BlameIgnoreRevs
+        )
+    }
+
     pub fn can_select_output(self) -> bool {
         match self {
-            Self::Log | Self::LogCount | Self::ListBranches => true,
+            Self::Log
+            | Self::LogCount
+            | Self::LogSignatures
+            | Self::LogGraph
+            | Self::ListBranches
+            | Self::ListMergedBranches
+            | Self::Blame
+            | Self::BlameParent
+            | Self::BlameBack
+            | Self::BlameIgnoreRevs
+            | Self::ListStashes => true,
             _ => false,
         }
     }
 
+    /// What to show instead of a successful but empty output
+    pub fn empty_output_message(self) -> Option<&'static str> {
+        match self {
+            Self::ListStashes => Some("no stashes"),
+            Self::UpdateSubmodules => Some("submodules are up to date"),
+            Self::SessionLog => Some("nothing ran yet"),
+            _ => None,
+        }
+    }
+
     pub fn line_formatter<W>(
         self,
     ) -> fn(&mut W, &str, AvailableSize) -> Result<()>
@@ -97,26 +368,88 @@ impl ActionKind {
         W: Write,
     {
         match self {
-            Self::Log | Self::LogCount => |write, line, available_size| {
-                let mut slice_end = line
-                    .char_indices()
-                    .take(available_size.width - 1)
-                    .last()
-                    .map(|(i, _)| i + 1)
-                    .unwrap_or(0);
-                while !line.is_char_boundary(slice_end) {
-                    slice_end += 1;
-                }
+            Self::Log
+            | Self::LogCount
+            | Self::LogSignatures
+            | Self::LogGraph => {
+                |write, line, available_size| {
+                    let mut slice_end = line
+                        .char_indices()
+                        .take(available_size.width - 1)
+                        .last()
+                        .map(|(i, _)| i + 1)
+                        .unwrap_or(0);
+                    while !line.is_char_boundary(slice_end) {
+                        slice_end += 1;
+                    }
 
-                let line = &line[..slice_end];
-                for (part, color) in
-                    line.splitn(LOG_COLORS.len(), '\x1e').zip(LOG_COLORS.iter())
-                {
-                    handle_command!(write, SetForegroundColor(*color))?;
-                    handle_command!(write, Print(part))?;
-                    handle_command!(write, Print(' '))?;
+                    let line = &line[..slice_end];
+                    let theme = theme::current();
+                    let colors = &theme.log;
+
+                    // with signatures there is one extra field right after the hash
+                    let mut parts: Vec<_> = line.split('\x1e').collect();
+                    let signature = if parts.len() > colors.len() {
+                        Some(parts.remove(2))
+                    } else {
+                        None
+                    };
+                    for (i, (part, color)) in
+                        parts.iter().zip(colors).enumerate()
+                    {
+                        handle_command!(write, fg(*color))?;
+                        handle_command!(write, Print(part))?;
+                        handle_command!(write, Print(' '))?;
+
+                        if let (1, Some(signature)) = (i, signature) {
+                            let color = match signature {
+                                "G" => theme.added,
+                                "B" | "R" => theme.error,
+                                "N" => Color::Reset,
+                                _ => theme.warning,
+                            };
+                            handle_command!(write, fg(color))?;
+                            handle_command!(write, Print(signature))?;
+                            handle_command!(write, Print(' '))?;
+                        }
+                    }
+                    Ok(())
+                }
+            }
+            Self::Blame
+            | Self::BlameParent
+            | Self::BlameBack
+            | Self::BlameIgnoreRevs => {
+                |write, line, _available_size| {
+                    // each revision keeps the same color all over the file
+                    let hash = line.split(' ').next().unwrap_or("");
+                    let colors = &theme::current().log[1..5];
+                    let index = hash.bytes().map(usize::from).sum::<usize>();
+                    handle_command!(write, fg(colors[index % colors.len()]))?;
+                    handle_command!(write, Print(hash))?;
+                    handle_command!(write, fg(Color::Reset))?;
+                    handle_command!(write, Print(&line[hash.len()..]))
+                }
+            }
+            Self::ListMergedBranches => |write, line, _available_size| {
+                let branch = MergedBranch::parse(line);
+                if branch.name.is_empty() {
+                    return Ok(());
                 }
-                Ok(())
+                let (status, color) = if branch.merged {
+                    ("merged", theme::current().added)
+                } else {
+                    ("unmerged", theme::current().modified)
+                };
+                handle_command!(write, fg(color))?;
+                handle_command!(write, Print(format!("{:<10}", status)))?;
+                handle_command!(write, fg(Color::Reset))?;
+                handle_command!(
+                    write,
+                    Print(if branch.current { '*' } else { ' ' })
+                )?;
+                handle_command!(write, Print(' '))?;
+                handle_command!(write, Print(branch.name))
             },
             _ => |write, line, _available_size| {
                 handle_command!(write, Print(line))
@@ -126,44 +459,288 @@ impl ActionKind {
 
     pub fn parse_target(self, line: &str) -> Option<&str> {
         match self {
-            Self::Log | Self::LogCount => line.split('\x1e').nth(1),
-            Self::ListBranches => Some(line),
+            Self::Log
+            | Self::LogCount
+            | Self::LogSignatures
+            | Self::LogGraph => line.split('\x1e').nth(1),
+            // hg lists bookmarks along with its branches
+            Self::ListBranches => Some(line.trim_end_matches(BOOKMARK_SUFFIX))
+                .filter(|name| !name.is_empty()),
+            Self::ListMergedBranches => Some(MergedBranch::parse(line).name)
+                .filter(|name| !name.is_empty()),
+            // boundary revisions start with '^' and lines not committed yet
+            // only have zeros
+            Self::Blame
+            | Self::BlameParent
+            | Self::BlameBack
+            | Self::BlameIgnoreRevs => line
+                .split(' ')
+                .next()
+                .map(|hash| hash.trim_start_matches('^'))
+                .filter(|hash| {
+                    !hash.is_empty() && !hash.bytes().all(|b| b == b'0')
+                }),
+            // "stash@{0}: WIP on master" for git, "default  (5m ago) ..." for hg
+            Self::ListStashes => line
+                .split(|c: char| c == ':' || c.is_whitespace())
+                .next()
+                .filter(|name| !name.is_empty()),
             _ => None,
         }
     }
 }
 
+/// A line of the merged branches listing, made of the fields
+/// `merged|unmerged`, the branch name and `*` for the current branch, each
+/// separated by '\x1e'
+pub struct MergedBranch<'a> {
+    pub name: &'a str,
+    pub merged: bool,
+    pub current: bool,
+}
+
+impl<'a> MergedBranch<'a> {
+    pub fn parse(line: &'a str) -> Self {
+        let mut fields = line.split('\x1e');
+        let merged = fields.next() == Some("merged");
+        let name = fields.next().unwrap_or("").trim();
+        let current = fields.next().map(str::trim) == Some("*");
+        Self {
+            name,
+            merged,
+            current,
+        }
+    }
+}
+
 pub trait ActionTask: Send {
     fn poll(&mut self, executor: &mut Executor) -> Poll<ActionResult>;
+    /// Kills any running process and prevents pending ones from starting
+    fn cancel(&mut self);
+    /// What was printed until now while the task is still running. `None`
+    /// when it has nothing to show before it is done
+    fn output_so_far(&self) -> Option<String> {
+        None
+    }
 }
 
 pub enum CommandTask {
     Waiting(Command),
     Running(AsyncChild),
+    Canceled,
 }
 
 impl ActionTask for CommandTask {
     fn poll(&mut self, executor: &mut Executor) -> Poll<ActionResult> {
         match self {
-            CommandTask::Waiting(command) => {
-                let child = command
-                    .stdin(Stdio::null())
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped())
-                    .spawn();
-                match child {
-                    Ok(child) => {
-                        let async_child = executor.run_child_async(child);
-                        *self = CommandTask::Running(async_child);
-                        Poll::Pending
+            CommandTask::Waiting(command) => match command.spawn() {
+                Ok(child) => {
+                    let async_child = executor.run_child_async(
+                        child,
+                        profile::label(command),
+                        command_log::label(command),
+                    );
+                    *self = CommandTask::Running(async_child);
+                    Poll::Pending
+                }
+                Err(e) => {
+                    if let Some(label) = command_log::label(command) {
+                        command_log::record(&label, None, false);
                     }
-                    Err(e) => {
-                        Poll::Ready(ActionResult::from_err(e.to_string()))
+                    Poll::Ready(ActionResult::from_err(e.to_string()))
+                }
+            },
+            CommandTask::Running(child) => child.poll(),
+            CommandTask::Canceled => Poll::Ready(ActionResult::from_canceled()),
+        }
+    }
+
+    fn cancel(&mut self) {
+        match self {
+            CommandTask::Running(child) => child.cancel(),
+            _ => *self = CommandTask::Canceled,
+        }
+    }
+
+    fn output_so_far(&self) -> Option<String> {
+        match self {
+            CommandTask::Running(child) => Some(child.output_so_far()),
+            _ => None,
+        }
+    }
+}
+
+/// A task that is done from the start
+pub fn ready(result: ActionResult) -> Box<dyn ActionTask> {
+    Box::new(ReadyTask(Some(result)))
+}
+
+struct ReadyTask(Option<ActionResult>);
+
+impl ActionTask for ReadyTask {
+    fn poll(&mut self, _executor: &mut Executor) -> Poll<ActionResult> {
+        Poll::Ready(self.0.take().unwrap_or_else(ActionResult::from_canceled))
+    }
+
+    fn cancel(&mut self) {}
+}
+
+/// The output of `task` after `before`, like more lines for a list that was
+/// already shown. A failure only shows its error
+pub fn appended(
+    before: String,
+    task: Box<dyn ActionTask>,
+) -> Box<dyn ActionTask> {
+    Box::new(AppendedTask { before, task })
+}
+
+struct AppendedTask {
+    before: String,
+    task: Box<dyn ActionTask>,
+}
+
+impl ActionTask for AppendedTask {
+    fn poll(&mut self, executor: &mut Executor) -> Poll<ActionResult> {
+        match self.task.poll(executor) {
+            Poll::Ready(mut result) if result.success => {
+                let mut output = std::mem::take(&mut self.before);
+                if !result.output.trim().is_empty() {
+                    if !output.is_empty() && !output.ends_with('\n') {
+                        output.push('\n');
                     }
+                    output.push_str(&result.output);
                 }
+                result.output = output;
+                Poll::Ready(result)
             }
-            CommandTask::Running(child) => child.poll(),
+            poll => poll,
+        }
+    }
+
+    fn cancel(&mut self) {
+        self.task.cancel();
+    }
+}
+
+/// Keeps `file` around until `task` is done, for a command that reads it
+pub fn keeping(
+    file: TempFile,
+    task: Box<dyn ActionTask>,
+) -> Box<dyn ActionTask> {
+    Box::new(KeepingTask {
+        file: Some(file),
+        task,
+    })
+}
+
+struct KeepingTask {
+    file: Option<TempFile>,
+    task: Box<dyn ActionTask>,
+}
+
+impl ActionTask for KeepingTask {
+    fn poll(&mut self, executor: &mut Executor) -> Poll<ActionResult> {
+        let poll = self.task.poll(executor);
+        if poll.is_ready() {
+            self.file = None;
         }
+        poll
+    }
+
+    fn cancel(&mut self) {
+        self.task.cancel();
+    }
+
+    fn output_so_far(&self) -> Option<String> {
+        self.task.output_so_far()
+    }
+}
+
+/// Runs `first` and then the task `next` makes out of its result, when a
+/// command needs what an earlier one printed
+pub fn then<F>(first: Box<dyn ActionTask>, next: F) -> Box<dyn ActionTask>
+where
+    F: 'static + Send + FnOnce(ActionResult) -> Box<dyn ActionTask>,
+{
+    Box::new(ThenTask {
+        task: first,
+        next: Some(Box::new(next)),
+    })
+}
+
+type NextTask = Box<dyn Send + FnOnce(ActionResult) -> Box<dyn ActionTask>>;
+
+struct ThenTask {
+    task: Box<dyn ActionTask>,
+    next: Option<NextTask>,
+}
+
+impl ActionTask for ThenTask {
+    fn poll(&mut self, executor: &mut Executor) -> Poll<ActionResult> {
+        loop {
+            let result = match self.task.poll(executor) {
+                Poll::Ready(result) => result,
+                Poll::Pending => return Poll::Pending,
+            };
+            match self.next.take() {
+                Some(next) => self.task = next(result),
+                None => return Poll::Ready(result),
+            }
+        }
+    }
+
+    fn cancel(&mut self) {
+        self.next = None;
+        self.task.cancel();
+    }
+
+    /// Only of the last task, as the first one is run for its result
+    fn output_so_far(&self) -> Option<String> {
+        if self.next.is_some() {
+            None
+        } else {
+            self.task.output_so_far()
+        }
+    }
+}
+
+/// Does `work` on a thread of its own, for the few actions that are not a
+/// command. A canceled task still lets `work` finish but drops its result
+pub fn background<F>(work: F) -> Box<dyn ActionTask>
+where
+    F: 'static + Send + FnOnce() -> ActionResult,
+{
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(work());
+    });
+    Box::new(BackgroundTask {
+        receiver,
+        canceled: false,
+    })
+}
+
+struct BackgroundTask {
+    receiver: mpsc::Receiver<ActionResult>,
+    canceled: bool,
+}
+
+impl ActionTask for BackgroundTask {
+    fn poll(&mut self, _: &mut Executor) -> Poll<ActionResult> {
+        if self.canceled {
+            return Poll::Ready(ActionResult::from_canceled());
+        }
+        match self.receiver.try_recv() {
+            Ok(result) => Poll::Ready(result),
+            Err(mpsc::TryRecvError::Empty) => Poll::Pending,
+            Err(mpsc::TryRecvError::Disconnected) => Poll::Ready(
+                ActionResult::from_err("the action stopped early".into()),
+            ),
+        }
+    }
+
+    fn cancel(&mut self) {
+        self.canceled = true;
     }
 }
 
@@ -183,6 +760,16 @@ pub fn serial(tasks: Vec<Box<dyn ActionTask>>) -> Box<dyn ActionTask> {
     Box::new(SerialTasks {
         tasks,
         cached_results: Vec::new(),
+        stop_at_failure: false,
+    })
+}
+
+/// Like `serial` but the tasks after one that failed never start
+pub fn serial_while_ok(tasks: Vec<Box<dyn ActionTask>>) -> Box<dyn ActionTask> {
+    Box::new(SerialTasks {
+        tasks,
+        cached_results: Vec::new(),
+        stop_at_failure: true,
     })
 }
 
@@ -214,25 +801,56 @@ impl ActionTask for ParallelTasks {
             Poll::Pending
         }
     }
+
+    fn cancel(&mut self) {
+        for task in &mut self.tasks {
+            task.cancel();
+        }
+    }
 }
 
 struct SerialTasks {
     tasks: Vec<Box<dyn ActionTask>>,
     cached_results: Vec<ActionResult>,
+    stop_at_failure: bool,
 }
 
 impl ActionTask for SerialTasks {
     fn poll(&mut self, executor: &mut Executor) -> Poll<ActionResult> {
-        match self.tasks[self.cached_results.len()].poll(executor) {
-            Poll::Ready(result) => self.cached_results.push(result),
-            Poll::Pending => return Poll::Pending,
+        // tasks that are done right away do not wait for the next poll
+        while self.cached_results.len() < self.tasks.len() {
+            match self.tasks[self.cached_results.len()].poll(executor) {
+                Poll::Ready(result) => {
+                    let failed = !result.success;
+                    self.cached_results.push(result);
+                    if failed && self.stop_at_failure {
+                        break;
+                    }
+                }
+                Poll::Pending => return Poll::Pending,
+            }
         }
+        Poll::Ready(aggregate_results(self.cached_results.drain(..)))
+    }
 
-        if self.cached_results.len() == self.tasks.len() {
-            Poll::Ready(aggregate_results(self.cached_results.drain(..)))
-        } else {
-            Poll::Pending
+    fn cancel(&mut self) {
+        for task in &mut self.tasks {
+            task.cancel();
+        }
+    }
+
+    fn output_so_far(&self) -> Option<String> {
+        let current = self.tasks.get(self.cached_results.len())?;
+        let mut output = String::new();
+        for result in &self.cached_results {
+            output.push('\n');
+            output.push_str(&result.output[..]);
+        }
+        if let Some(current) = current.output_so_far() {
+            output.push('\n');
+            output.push_str(&current);
         }
+        Some(output)
     }
 }
 
@@ -242,14 +860,16 @@ where
 {
     let mut all_success = true;
     let mut aggregated = String::new();
+    let mut warnings = String::new();
     for result in iter {
         all_success = all_success && result.success;
-        let result = result.output;
         aggregated.push('\n');
-        aggregated.push_str(&result[..]);
+        aggregated.push_str(&result.output[..]);
+        warnings.push_str(&result.warnings[..]);
     }
     ActionResult {
         success: all_success,
         output: aggregated,
+        warnings,
     }
 }
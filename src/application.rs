@@ -1,44 +1,215 @@
-use std::{collections::HashMap, task::Poll};
+use std::{
+    collections::HashMap,
+    task::Poll,
+    time::{Duration, Instant},
+};
 
 use crate::{
     action::{ActionKind, ActionResult, ActionTask},
     async_process::Executor,
+    config::Config,
     custom_actions::CustomAction,
-    version_control_actions::VersionControlActions,
+    editor,
+    key_bindings::KeyBindings,
+    notification, open,
+    repositories::Repository,
+    scrollback::{self, OutputMode, Scrollback},
+    select::{Entry, StatusCounts},
+    theme, tui_util,
+    vcs_error::VcsError,
+    version_control_actions::{
+        backend_name, CommitOptions, DiffOptions, VersionControlActions,
+    },
 };
 
+pub const DEFAULT_LOG_COUNT: usize = 50;
+
 pub struct ActionFuture {
     pub kind: ActionKind,
     pub task: Box<dyn 'static + ActionTask>,
 }
 
+struct PendingAction {
+    future: ActionFuture,
+    start: Instant,
+    canceled: bool,
+}
+
 pub struct Application {
     pub version_control: Box<dyn 'static + VersionControlActions>,
     pub custom_actions: Vec<CustomAction>,
+    pub launch_subdir: String,
+    /// Which version control is in use and its version, for the header
+    pub backend: String,
+    pub protected_branches: Vec<String>,
+    /// Whether the log verifies the signature of each revision
+    pub log_signatures: bool,
+    /// Whether the log draws how revisions branch and merge
+    pub log_graph: bool,
+    /// Revisions the log lists at once, and loads more of when paging
+    pub log_count: usize,
+    pub diff_options: DiffOptions,
+    pub commit_options: CommitOptions,
+    /// Whether diffs wrap their long lines like other output does
+    pub wrap_diffs: bool,
+    pub output_mode: OutputMode,
+    pub scrollback: Scrollback,
+    /// Actions taking at least this long show a desktop notification once
+    /// they finish
+    pub notify_after: Option<Duration>,
+    /// Whether running actions are shown without an animated spinner, so the
+    /// header is only drawn again once something changes
+    pub quiet: bool,
+    /// Whether clicks and the mouse wheel reach the ui
+    pub mouse: bool,
+    /// Names of the actions the help lists first
+    pub quick_actions: Vec<String>,
+    /// Templates like `code {path}` to open the repository with
+    /// `None` when `of` is turned off
+    pub open_command: Option<String>,
+    pub editor_command: String,
+    /// Writes messages that span several lines, from `VISUAL` or `EDITOR`
+    pub message_editor: String,
+    /// Whether `of` and `oe` ask before opening the repository
+    pub confirm_open: bool,
+    /// Whether questions are answered with yes without asking them, apart
+    /// from those whose yes loses work
+    pub no_confirm: bool,
+    /// Template like `less -R {path}` to page the output shown with
+    pub pager: String,
+    /// Options set on the command line, which win over the others every
+    /// time the configuration is loaded
+    pub config_options: Vec<(String, String)>,
+    /// The backend paths and date format found at startup, for opening
+    /// another repository the same way as the one in use
+    pub repository_config: Config,
+    pub key_bindings: KeyBindings,
+    /// Of the last status, shown in the header. `None` when it failed
+    pub status_counts: Option<StatusCounts>,
+    /// Branch and ahead/behind counts shown in the header
+    pub head: Option<String>,
+    changed_files: Option<Vec<Entry>>,
 
     executor: Executor,
-    pending_actions: Vec<ActionFuture>,
+    pending_actions: Vec<PendingAction>,
     action_results: HashMap<ActionKind, ActionResult>,
+    action_durations: HashMap<ActionKind, Duration>,
 }
 
 impl Application {
     pub fn new(
-        version_control: Box<dyn 'static + VersionControlActions>,
+        repository: Repository,
         custom_actions: Vec<CustomAction>,
     ) -> Self {
         Self {
-            version_control,
+            version_control: repository.version_control,
             custom_actions,
+            launch_subdir: repository.launch_subdir,
+            backend: String::new(),
+            protected_branches: Vec::new(),
+            log_signatures: false,
+            log_graph: true,
+            log_count: DEFAULT_LOG_COUNT,
+            diff_options: DiffOptions::default(),
+            commit_options: CommitOptions::default(),
+            wrap_diffs: true,
+            output_mode: OutputMode::Clear,
+            scrollback: Scrollback::default(),
+            notify_after: None,
+            quiet: false,
+            mouse: false,
+            quick_actions: Vec::new(),
+            open_command: Some(open::default_explorer().into()),
+            editor_command: editor::program(),
+            message_editor: editor::program(),
+            confirm_open: false,
+            no_confirm: false,
+            pager: open::default_pager(),
+            config_options: Vec::new(),
+            repository_config: Config::default(),
+            key_bindings: KeyBindings::default(),
+            status_counts: None,
+            head: None,
+            changed_files: None,
             executor: Executor::new(2),
             pending_actions: Vec::new(),
             action_results: HashMap::new(),
+            action_durations: HashMap::new(),
+        }
+    }
+
+    /// Takes the settings of `config` that can change while running. The
+    /// executables and repository paths keep the ones found at startup
+    pub fn apply_config(&mut self, config: Config) {
+        theme::set(config.build_theme());
+        self.protected_branches = config.protected_branches;
+        self.log_signatures = config.log_signatures;
+        self.log_graph = config.log_graph;
+        self.log_count = config.log_count.unwrap_or(DEFAULT_LOG_COUNT);
+        if self.version_control.has_diff_algorithms() {
+            self.diff_options.algorithm = config.diff_algorithm;
+        }
+        self.diff_options.move_threshold = config
+            .move_threshold
+            .unwrap_or(DiffOptions::default().move_threshold);
+        self.commit_options = CommitOptions {
+            sign_off: config.sign_off,
+            gpg_sign: config.gpg_sign,
+        };
+        self.wrap_diffs = config.wrap_diffs;
+        self.output_mode = config.output_mode;
+        self.scrollback.max_lines = config
+            .scrollback_lines
+            .unwrap_or(scrollback::DEFAULT_MAX_LINES);
+        self.notify_after = config.notify_after;
+        self.quick_actions = config.quick_actions;
+        self.open_command = match config.open_command {
+            Some(command) if command == "false" => None,
+            Some(command) => Some(command),
+            None => Some(open::default_explorer().into()),
+        };
+        self.editor_command =
+            config.editor_command.unwrap_or_else(editor::program);
+        self.confirm_open = config.confirm_open;
+        self.no_confirm = config.no_confirm;
+        self.pager = config.pager.unwrap_or_else(open::default_pager);
+        tui_util::set_navigation(config.navigation);
+        self.quiet = config.quiet.unwrap_or_else(tui_util::is_slow_terminal);
+        self.mouse = config.mouse;
+    }
+
+    /// Applies `config` unless it does not validate, in which case the
+    /// current settings stay
+    pub fn reload_config(&mut self, config: Config) -> Result<(), String> {
+        config.validate()?;
+        self.apply_config(config);
+        Ok(())
+    }
+
+    /// Uses `repository` from now on, forgetting the outputs of the previous
+    /// one. Only call it when no actions are running
+    pub fn switch_repository(
+        &mut self,
+        repository: Repository,
+        custom_actions: Vec<CustomAction>,
+    ) {
+        self.version_control = repository.version_control;
+        self.launch_subdir = repository.launch_subdir;
+        self.custom_actions = custom_actions;
+        self.backend = backend_name(&*self.version_control);
+        if !self.version_control.has_diff_algorithms() {
+            self.diff_options.algorithm = None;
         }
+        self.action_results.clear();
+        self.action_durations.clear();
+        self.refresh_status();
     }
 
     pub fn get_cached_action_result(&self, kind: ActionKind) -> &ActionResult {
         static EMPTY_ACTION_RESULT: ActionResult = ActionResult {
             success: true,
             output: String::new(),
+            warnings: String::new(),
         };
 
         match self.action_results.get(&kind) {
@@ -47,48 +218,171 @@ impl Application {
         }
     }
 
+    /// `count` revisions older than the newest `skip` ones, with signatures
+    /// and the graph as toggled for this session
+    pub fn log(&self, skip: usize, count: usize) -> Box<dyn ActionTask> {
+        self.version_control.log(
+            skip,
+            count,
+            self.log_signatures,
+            self.log_graph,
+        )
+    }
+
+    /// Whether `kind` ran this session, even if it did not finish yet
+    pub fn has_action_result(&self, kind: ActionKind) -> bool {
+        self.action_results.contains_key(&kind)
+            || self.has_pending_action_of_type(kind)
+    }
+
     pub fn set_cached_action_result(
         &mut self,
         kind: ActionKind,
         result: ActionResult,
     ) {
         self.action_results.insert(kind, result);
+        self.action_durations.remove(&kind);
+    }
+
+    /// How long `kind` took the last time it ran to the end
+    pub fn action_duration(&self, kind: ActionKind) -> Option<Duration> {
+        self.action_durations.get(&kind).copied()
+    }
+
+    /// The changed files of the last status, which is only run again once
+    /// the working tree changed or it gets refreshed. A failed status leaves
+    /// no counts rather than those of an earlier one
+    pub fn current_changed_files(&mut self) -> Result<Vec<Entry>, VcsError> {
+        if let Some(entries) = &self.changed_files {
+            return Ok(entries.clone());
+        }
+
+        let entries = match self.version_control.get_current_changed_files() {
+            Ok(entries) => entries,
+            Err(error) => {
+                self.status_counts = None;
+                return Err(error);
+            }
+        };
+        let staging_area = self.version_control.has_staging_area();
+        self.status_counts =
+            Some(StatusCounts::from_entries(&entries, staging_area));
+        self.changed_files = Some(entries.clone());
+        Ok(entries)
+    }
+
+    /// Forgets the last status and runs it again, along with the branch
+    pub fn refresh_status(&mut self) {
+        self.changed_files = None;
+        self.head = self.version_control.head_summary();
+        let _ = self.current_changed_files();
     }
 
     pub fn poll_and_check_action(&mut self, kind: ActionKind) -> bool {
         let mut just_finished = false;
+        let mut tree_changed = false;
         for i in (0..self.pending_actions.len()).rev() {
-            if let Poll::Ready(result) =
-                self.pending_actions[i].task.poll(&mut self.executor)
+            if let Poll::Ready(mut result) =
+                self.pending_actions[i].future.task.poll(&mut self.executor)
             {
                 let action = self.pending_actions.swap_remove(i);
-                if action.kind == kind {
+                let action_kind = action.future.kind;
+                if action_kind == kind {
                     just_finished = true;
                 }
-                self.action_results.insert(action.kind, result);
+                if action.canceled {
+                    result = ActionResult::from_canceled();
+                    if action_kind.is_mutating() {
+                        result.output.push_str(concat!(
+                            "\n\nthe action was interrupted and may have ",
+                            "partially completed.\n",
+                            "check the status before continuing.",
+                        ));
+                    }
+                }
+                if !action.canceled
+                    && self
+                        .notify_after
+                        .map_or(false, |after| action.start.elapsed() >= after)
+                {
+                    let outcome =
+                        if result.success { "finished" } else { "failed" };
+                    notification::notify(
+                        env!("CARGO_PKG_NAME"),
+                        &format!("{} {}", action_kind.name(), outcome),
+                    );
+                }
+                tree_changed |= action_kind.is_mutating();
+                if !action.canceled {
+                    self.action_durations
+                        .insert(action_kind, action.start.elapsed());
+                }
+                self.scrollback.push(action_kind, &result.output);
+                self.action_results.insert(action_kind, result);
             }
         }
+        if tree_changed {
+            self.refresh_status();
+        }
 
         just_finished
     }
 
     pub fn run_action(&mut self, action: ActionFuture) {
         for i in (0..self.pending_actions.len()).rev() {
-            if self.pending_actions[i].kind == action.kind {
+            if self.pending_actions[i].future.kind == action.kind {
                 return;
             }
         }
 
-        self.pending_actions.push(action);
+        self.pending_actions.push(PendingAction {
+            future: action,
+            start: Instant::now(),
+            canceled: false,
+        });
     }
 
     pub fn has_pending_action_of_type(&self, kind: ActionKind) -> bool {
-        for action in &self.pending_actions {
-            if action.kind == kind {
-                return true;
+        self.pending_action_elapsed(kind).is_some()
+    }
+
+    pub fn pending_action_elapsed(&self, kind: ActionKind) -> Option<Duration> {
+        self.pending_actions
+            .iter()
+            .find(|a| a.future.kind == kind)
+            .map(|a| a.start.elapsed())
+    }
+
+    /// What the running action of `kind` printed until now, if it can tell
+    pub fn pending_action_output(&self, kind: ActionKind) -> Option<String> {
+        self.pending_actions
+            .iter()
+            .find(|a| a.future.kind == kind && !a.canceled)
+            .and_then(|a| a.future.task.output_so_far())
+    }
+
+    /// Names of the actions still running that were not canceled
+    pub fn running_action_names(&self) -> Vec<&'static str> {
+        self.pending_actions
+            .iter()
+            .filter(|a| !a.canceled)
+            .map(|a| a.future.kind.name())
+            .collect()
+    }
+
+    /// Cancels a pending action. Returns false if there was none
+    pub fn cancel_action(&mut self, kind: ActionKind) -> bool {
+        match self
+            .pending_actions
+            .iter_mut()
+            .find(|a| a.future.kind == kind)
+        {
+            Some(action) => {
+                action.future.task.cancel();
+                action.canceled = true;
+                true
             }
+            None => false,
         }
-
-        false
     }
 }
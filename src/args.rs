@@ -0,0 +1,91 @@
+use std::env;
+
+use crate::{action::ActionKind, cli};
+
+pub const USAGE: &str = concat!(
+    "usage: ",
+    env!("CARGO_PKG_NAME"),
+    " [--profile] [--script <file>] [-c <option>=<value>]... [<directory>]\n",
+    "       [<action>]\n",
+    "\n",
+    "  <directory>      repository to open instead of the current directory\n",
+    "  <action>         print what the action shows, like `status` or\n",
+    "                   `list branches`, instead of opening the ui\n",
+    "  --profile        print how long each command took to stderr on exit\n",
+    "  --script <file>  replay keys and prompt answers from <file> instead\n",
+    "                   of reading them from the terminal\n",
+    "  -c <option>=<value>\n",
+    "                   set a config option, winning over config.txt and\n",
+    "                   VERCO_ environment variables",
+);
+
+#[derive(Default)]
+pub struct Args {
+    /// Directory to look for a repository in instead of the current one
+    pub directory: Option<String>,
+    /// Prints the time every command took once the ui is closed
+    pub profile: bool,
+    /// File with scripted input to replay
+    pub script: Option<String>,
+    /// Action to print the output of without the ui
+    pub action: Option<ActionKind>,
+    /// Config options and their values, which win over the other sources
+    pub options: Vec<(String, String)>,
+}
+
+impl Args {
+    pub fn parse() -> Result<Self, String> {
+        let mut parsed = Self::default();
+
+        let mut words = Vec::new();
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match &arg[..] {
+                "--profile" => parsed.profile = true,
+                "--script" => match args.next() {
+                    Some(path) => parsed.script = Some(path),
+                    None => return Err("--script needs a file".into()),
+                },
+                "-c" => match args.next().as_deref().map(parse_option) {
+                    Some(Some(option)) => parsed.options.push(option),
+                    _ => return Err("-c needs an <option>=<value>".into()),
+                },
+                _ if arg.starts_with('-') => {
+                    return Err(format!("unknown argument '{}'", arg))
+                }
+                _ => words.push(arg),
+            }
+        }
+
+        // action names may have spaces and come after the directory
+        if let Some(kind) = cli::find(&words.join(" ")) {
+            parsed.action = Some(kind);
+        } else if let Some((directory, action)) = words.split_first() {
+            parsed.directory = Some(directory.clone());
+            if !action.is_empty() {
+                let action = action.join(" ");
+                match cli::find(&action) {
+                    Some(kind) => parsed.action = Some(kind),
+                    None => {
+                        return Err(format!(
+                            "unknown action '{}'. it can be one of {}",
+                            action,
+                            cli::names()
+                        ))
+                    }
+                }
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+fn parse_option(arg: &str) -> Option<(String, String)> {
+    let (option, value) = arg.split_once('=')?;
+    let option = option.trim();
+    if option.is_empty() {
+        return None;
+    }
+    Some((option.into(), value.into()))
+}
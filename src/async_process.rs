@@ -1,12 +1,29 @@
 use std::{
-    process::Child,
-    sync::mpsc::{
-        channel, sync_channel, Receiver, Sender, SyncSender, TryRecvError,
+    io::{ErrorKind, Read},
+    process::{Child, ExitStatus},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{
+            channel, sync_channel, Receiver, Sender, SyncSender, TryRecvError,
+        },
+        Arc, Mutex,
     },
     task::Poll,
     thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
+use crate::{command_log, profile};
+
+/// Output kept of each pipe of a command. Past it the pipe is closed, which
+/// stops commands like a huge diff from writing the rest
+const MAX_OUTPUT_BYTES: usize = 32 * 1024 * 1024;
+/// Output of a running command kept to be shown before it exits
+const MAX_OUTPUT_SO_FAR_BYTES: usize = 1024 * 1024;
+
+/// What a running command printed so far to any of its pipes
+type OutputSoFar = Arc<Mutex<Vec<u8>>>;
+
 struct ExecutorThread {
     pub handle: JoinHandle<()>,
     pub async_child_executor_sender: Sender<AsyncChildExecutor>,
@@ -45,12 +62,27 @@ impl Executor {
         }
     }
 
-    pub fn run_child_async(&mut self, child: Child) -> AsyncChild {
+    /// Waits for `child` on a pool thread. When given a `profile_label`, the
+    /// time until it exits gets recorded under it, and with a `log_label` how
+    /// it exited goes to the command log
+    pub fn run_child_async(
+        &mut self,
+        child: Child,
+        profile_label: Option<String>,
+        log_label: Option<String>,
+    ) -> AsyncChild {
         let (output_sender, output_receiver) = sync_channel(1);
+        let canceled = Arc::new(AtomicBool::new(false));
+        let output_so_far = OutputSoFar::default();
 
         let child = AsyncChildExecutor {
             child,
+            started: Instant::now(),
+            profile_label,
+            log_label,
             output_sender,
+            canceled: canceled.clone(),
+            output_so_far: output_so_far.clone(),
         };
 
         let thread = &mut self.thread_pool[self.next_thread_index];
@@ -58,7 +90,11 @@ impl Executor {
         self.next_thread_index =
             (self.next_thread_index + 1) % self.thread_pool.len();
 
-        AsyncChild { output_receiver }
+        AsyncChild {
+            output_receiver,
+            canceled,
+            output_so_far,
+        }
     }
 }
 
@@ -66,6 +102,8 @@ impl Executor {
 pub struct ChildOutput {
     pub success: bool,
     pub output: String,
+    /// What a successful command printed to stderr
+    pub warnings: String,
 }
 
 impl ChildOutput {
@@ -73,6 +111,7 @@ impl ChildOutput {
         Self {
             success: true,
             output,
+            warnings: String::new(),
         }
     }
 
@@ -80,34 +119,162 @@ impl ChildOutput {
         Self {
             success: false,
             output,
+            warnings: String::new(),
         }
     }
 
-    pub fn from_child(child: Child) -> Self {
-        let success;
-        let output;
+    pub fn from_canceled() -> Self {
+        Self::from_err("canceled".into())
+    }
+
+    /// Waits for the child to exit while watching for cancellation, in which
+    /// case the child is killed. Its pipes are copied to `output_so_far` as
+    /// they are read. Comes with the exit code, which is `None` for a killed
+    /// child
+    fn from_child(
+        child: Child,
+        canceled: &AtomicBool,
+        output_so_far: &OutputSoFar,
+    ) -> (Self, Option<i32>) {
+        Self::from_child_limited(
+            child,
+            canceled,
+            output_so_far,
+            MAX_OUTPUT_BYTES,
+        )
+    }
+
+    /// Like `from_child` but keeping only the lines that fit in `limit` bytes
+    /// of each pipe. A child cut off that way counts as successful
+    fn from_child_limited(
+        mut child: Child,
+        canceled: &AtomicBool,
+        output_so_far: &OutputSoFar,
+        limit: usize,
+    ) -> (Self, Option<i32>) {
+        fn read_pipe<R>(
+            pipe: Option<R>,
+            output_so_far: OutputSoFar,
+            limit: usize,
+        ) -> JoinHandle<Pipe>
+        where
+            R: 'static + Read + Send,
+        {
+            thread::spawn(move || {
+                let mut bytes = Vec::new();
+                if let Some(mut pipe) = pipe {
+                    let mut buf = [0; 8 * 1024];
+                    while bytes.len() <= limit {
+                        let len = match pipe.read(&mut buf) {
+                            Ok(0) => break,
+                            Ok(len) => len.min(limit + 1 - bytes.len()),
+                            Err(e) if e.kind() == ErrorKind::Interrupted => {
+                                continue
+                            }
+                            Err(_) => break,
+                        };
+                        bytes.extend_from_slice(&buf[..len]);
+                        if let Ok(mut output) = output_so_far.lock() {
+                            if output.len() < MAX_OUTPUT_SO_FAR_BYTES {
+                                output.extend_from_slice(&buf[..len]);
+                            }
+                        }
+                    }
+                }
+                let truncated = bytes.len() > limit;
+                if truncated {
+                    let end = bytes[..limit]
+                        .iter()
+                        .rposition(|&b| b == b'\n')
+                        .map_or(limit, |i| i + 1);
+                    bytes.truncate(end);
+                }
+                Pipe { bytes, truncated }
+            })
+        }
+
+        let stdout =
+            read_pipe(child.stdout.take(), output_so_far.clone(), limit);
+        let stderr =
+            read_pipe(child.stderr.take(), output_so_far.clone(), limit);
+
+        let status = loop {
+            if canceled.load(Ordering::Relaxed) {
+                let _ = child.kill();
+                let _ = child.wait();
+                return (Self::from_canceled(), None);
+            }
 
-        match child.wait_with_output() {
-            Ok(out) => {
-                success = out.status.success();
-                let bytes = if success { out.stdout } else { out.stderr };
-                output = String::from_utf8_lossy(&bytes[..]).into_owned();
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+                Err(error) => return (Self::from_err(error.to_string()), None),
             }
-            Err(error) => {
-                success = false;
-                output = error.to_string();
+        };
+
+        let stdout = stdout.join().unwrap_or_default();
+        let stderr = stderr.join().unwrap_or_default();
+        let output = if status.success() || closed_early(status, &stdout) {
+            Self {
+                success: true,
+                output: stdout.into_string(),
+                warnings: stderr.into_string(),
             }
+        } else {
+            Self::from_err(stderr.into_string())
         };
+        (output, status.code())
+    }
+}
+
+/// Whether the child only failed because its stdout was closed once the limit
+/// was reached, which kills it with SIGPIPE the next time it writes
+fn closed_early(status: ExitStatus, stdout: &Pipe) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        const SIGPIPE: i32 = 13;
+        stdout.truncated && status.signal() == Some(SIGPIPE)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        stdout.truncated
+    }
+}
+
+#[derive(Default)]
+struct Pipe {
+    bytes: Vec<u8>,
+    truncated: bool,
+}
 
-        Self { success, output }
+impl Pipe {
+    /// Ending with a note on how many lines are left when it was cut off
+    fn into_string(self) -> String {
+        let mut text = String::from_utf8_lossy(&self.bytes[..]).into_owned();
+        if self.truncated {
+            let lines = text.lines().count();
+            text.push_str(&format!(
+                "\noutput too large, only its first {} lines are shown\n",
+                lines
+            ));
+        }
+        text
     }
 }
 
 pub struct AsyncChild {
     output_receiver: Receiver<ChildOutput>,
+    canceled: Arc<AtomicBool>,
+    output_so_far: OutputSoFar,
 }
 
 impl AsyncChild {
+    pub fn cancel(&self) {
+        self.canceled.store(true, Ordering::Relaxed);
+    }
+
     pub fn poll(&self) -> Poll<ChildOutput> {
         match self.output_receiver.try_recv() {
             Ok(result) => Poll::Ready(result),
@@ -117,16 +284,124 @@ impl AsyncChild {
             }
         }
     }
+
+    /// What the child printed to stdout and stderr until now, in the order
+    /// it was read
+    pub fn output_so_far(&self) -> String {
+        match self.output_so_far.lock() {
+            Ok(output) => String::from_utf8_lossy(&output[..]).into_owned(),
+            Err(_) => String::new(),
+        }
+    }
 }
 
 struct AsyncChildExecutor {
     pub child: Child,
+    pub started: Instant,
+    pub profile_label: Option<String>,
+    pub log_label: Option<String>,
     pub output_sender: SyncSender<ChildOutput>,
+    pub canceled: Arc<AtomicBool>,
+    pub output_so_far: OutputSoFar,
 }
 
 impl AsyncChildExecutor {
     fn wait_for_output(self) -> Result<(), ()> {
-        let output = ChildOutput::from_child(self.child);
+        let (output, code) = ChildOutput::from_child(
+            self.child,
+            &self.canceled,
+            &self.output_so_far,
+        );
+        if let Some(label) = self.profile_label {
+            profile::record(label, self.started.elapsed(), output.success);
+        }
+        if let Some(label) = self.log_label {
+            command_log::record(&label, code, output.success);
+        }
         self.output_sender.send(output).map_err(|_| ())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::process::{Command, Stdio};
+
+    #[test]
+    fn endless_output_is_cut_off_at_the_limit() {
+        if cfg!(windows) {
+            return;
+        }
+        let child = Command::new("yes")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let (output, _) = ChildOutput::from_child_limited(
+            child,
+            &AtomicBool::new(false),
+            &OutputSoFar::default(),
+            9,
+        );
+
+        assert!(output.success);
+        assert_eq!(
+            output.output,
+            "y\ny\ny\ny\n\noutput too large, only its first 4 lines are shown\n"
+        );
+    }
+
+    #[test]
+    fn cut_off_outputs_keep_the_exit_status() {
+        if cfg!(windows) {
+            return;
+        }
+        let child = Command::new("sh")
+            .args(["-c", "yes | head -c 100; echo failed >&2; exit 3"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let (output, code) = ChildOutput::from_child_limited(
+            child,
+            &AtomicBool::new(false),
+            &OutputSoFar::default(),
+            9,
+        );
+
+        assert!(!output.success);
+        assert_eq!(output.output, "failed\n");
+        assert_eq!(code, Some(3));
+    }
+
+    #[test]
+    fn output_is_readable_before_the_child_exits() {
+        if cfg!(windows) {
+            return;
+        }
+        let child = Command::new("sh")
+            .args(["-c", "echo first; echo second >&2; sleep 5; echo last"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut executor = Executor::new(1);
+        let child = executor.run_child_async(child, None, None);
+
+        let started = Instant::now();
+        while !child.output_so_far().contains("second") {
+            assert!(started.elapsed() < Duration::from_secs(4));
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(child.output_so_far().contains("first\n"));
+        assert!(child.poll().is_pending());
+
+        child.cancel();
+        while child.poll().is_pending() {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
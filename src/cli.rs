@@ -0,0 +1,119 @@
+use std::{
+    io::{stdout, Write},
+    task::Poll,
+    thread,
+    time::Duration,
+};
+
+use crossterm::tty::IsTty;
+
+use crate::{
+    action::{ActionKind, ActionTask},
+    application::Application,
+    async_process::Executor,
+    diff,
+};
+
+type Run = fn(&Application) -> Box<dyn ActionTask>;
+
+/// Actions that ask nothing, so they can run from the command line by the
+/// name the help lists them with
+const ACTIONS: &[(ActionKind, Run)] = &[
+    (ActionKind::Status, |app| app.version_control.status()),
+    (ActionKind::Log, |app| app.log(0, app.log_count)),
+    (ActionKind::CurrentDiffAll, |app| {
+        app.version_control.current_diff_all(&app.diff_options)
+    }),
+    (ActionKind::CurrentFullRevision, |app| {
+        app.version_control.current_export(&app.diff_options)
+    }),
+    (ActionKind::Fetch, |app| app.version_control.fetch(None)),
+    (ActionKind::Pull, |app| app.version_control.pull(None)),
+    (ActionKind::Push, |app| app.version_control.push(None)),
+    (ActionKind::ListBranches, |app| {
+        app.version_control.list_branches()
+    }),
+    (ActionKind::ListRemotes, |app| {
+        app.version_control.list_remotes()
+    }),
+    (ActionKind::ListStashes, |app| {
+        app.version_control.list_stashes()
+    }),
+];
+
+pub fn find(name: &str) -> Option<ActionKind> {
+    ACTIONS
+        .iter()
+        .map(|&(kind, _)| kind)
+        .find(|kind| kind.name() == name)
+}
+
+/// The names `find` knows, separated by commas
+pub fn names() -> String {
+    let names: Vec<_> = ACTIONS.iter().map(|(kind, _)| kind.name()).collect();
+    names.join(", ")
+}
+
+/// Runs `kind` to the end, printing its output to stdout or its error to
+/// stderr. Returns the exit code
+pub fn run(app: &Application, kind: ActionKind) -> i32 {
+    let run = match ACTIONS.iter().find(|&&(k, _)| k == kind) {
+        Some(&(_, run)) => run,
+        None => return 2,
+    };
+    let mut task = run(app);
+    let mut executor = Executor::new(1);
+    let result = loop {
+        match task.poll(&mut executor) {
+            Poll::Ready(result) => break result,
+            Poll::Pending => thread::sleep(Duration::from_millis(10)),
+        }
+    };
+
+    let output = printable(kind, result.output.trim(), stdout().is_tty());
+    if result.success {
+        if !output.is_empty() {
+            println!("{}", output);
+        }
+        let warnings = result.warnings.trim();
+        if !warnings.is_empty() {
+            eprintln!("{}", warnings);
+        }
+        let _ = stdout().flush();
+        0
+    } else {
+        eprintln!("{}", output);
+        1
+    }
+}
+
+/// `output` without the field separators of the log, and without colors
+/// when they would end up in a file or another program
+fn printable(kind: ActionKind, output: &str, colored: bool) -> String {
+    let output = if kind == ActionKind::Log {
+        output.replace('\x1e', " ")
+    } else {
+        output.into()
+    };
+    if colored {
+        output
+    } else {
+        diff::strip_ansi(&output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn actions_are_found_by_their_help_name() {
+        assert!(find("status") == Some(ActionKind::Status));
+        assert!(find("list branches") == Some(ActionKind::ListBranches));
+        assert!(find("commit all").is_none());
+        assert_eq!(
+            printable(ActionKind::Log, "* \x1e1234\x1e\x1b[1msubject", false),
+            "*  1234 subject"
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// Where copied text goes
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Clipboard {
+    /// The system clipboard, or the terminal's when there is no program
+    /// to set it
+    System,
+    /// Asks the terminal to set the clipboard with OSC 52. This also works
+    /// over ssh but some terminals ignore it
+    Terminal,
+}
+
+impl Clipboard {
+    /// Over ssh the clipboard programs would set the remote clipboard, so
+    /// the terminal is asked instead
+    pub fn detect() -> Self {
+        if std::env::var_os("SSH_TTY").is_some()
+            || std::env::var_os("SSH_CONNECTION").is_some()
+        {
+            Clipboard::Terminal
+        } else {
+            Clipboard::System
+        }
+    }
+
+    /// Copies `text`, writing to `terminal` when the system clipboard can not
+    /// be used
+    pub fn copy<W>(self, text: &str, terminal: &mut W) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        if self == Clipboard::System
+            && programs()
+                .iter()
+                .any(|&(name, args)| copy_with(name, args, text))
+        {
+            return Ok(());
+        }
+        write!(terminal, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+        terminal.flush()
+    }
+}
+
+/// Programs that set the clipboard to their stdin, in the order they are tried
+fn programs() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        return vec![("pbcopy", &[])];
+    }
+    if cfg!(windows) {
+        return vec![("clip", &[])];
+    }
+
+    let mut programs = Vec::new();
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        programs.push(("wl-copy", &[][..]));
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        programs.push(("xclip", &["-selection", "clipboard"][..]));
+        programs.push(("xsel", &["--clipboard", "--input"][..]));
+    }
+    programs
+}
+
+fn copy_with(name: &str, args: &[&str], text: &str) -> bool {
+    let mut child = match Command::new(name)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(_) => return false,
+    };
+    let written = match child.stdin.take() {
+        Some(mut stdin) => stdin.write_all(text.as_bytes()).is_ok(),
+        None => false,
+    };
+    child.wait().map(|s| s.success()).unwrap_or(false) && written
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[n >> (18 - 6 * i) & 0x3f] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_pads_the_last_chunk() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+}
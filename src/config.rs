@@ -0,0 +1,459 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use crate::{
+    command_log,
+    scrollback::OutputMode,
+    theme::{self, Background, Preset, Theme},
+    tui_util::Navigation,
+    version_control_actions::{DateFormat, DiffAlgorithm},
+};
+
+pub struct Config {
+    pub git_path: String,
+    pub hg_path: String,
+    /// Where git keeps the repository when it is not `.git` in the work tree
+    pub git_dir: Option<String>,
+    pub git_work_tree: Option<String>,
+    /// Bare repository tracking files in the home directory, used when no
+    /// other repository is found
+    pub dotfiles: Option<String>,
+    /// Branches that bulk deleting merged branches never touches
+    pub protected_branches: Vec<String>,
+    pub log_signatures: bool,
+    pub log_graph: bool,
+    pub diff_algorithm: Option<DiffAlgorithm>,
+    /// Percent of similarity for a file to be detected as moved or copied
+    pub move_threshold: Option<usize>,
+    /// Whether commits get a `Signed-off-by` trailer
+    pub sign_off: bool,
+    /// Whether git commits get signed with gpg
+    pub gpg_sign: bool,
+    /// Whether diffs wrap lines wider than the terminal instead of cutting
+    /// them off
+    pub wrap_diffs: bool,
+    pub output_mode: OutputMode,
+    pub date_format: DateFormat,
+    /// Lines of output the session log keeps
+    pub scrollback_lines: Option<usize>,
+    /// Revisions the log lists at once
+    pub log_count: Option<usize>,
+    /// Actions taking at least this long show a desktop notification once
+    /// they finish
+    pub notify_after: Option<Duration>,
+    /// Whether the header skips its spinner. `None` to decide from the
+    /// terminal
+    pub quiet: Option<bool>,
+    /// Whether lists of files take clicks and the mouse wheel, which keeps
+    /// the terminal from selecting text while `verco` runs
+    pub mouse: bool,
+    /// Opens the repository in a file explorer, with `{path}` for its root.
+    /// `false` turns opening it off
+    pub open_command: Option<String>,
+    /// Opens the repository in an editor, which may run in the terminal
+    pub editor_command: Option<String>,
+    /// Whether opening the repository in an explorer or editor asks first
+    pub confirm_open: bool,
+    /// Whether questions are answered with yes without asking them, apart
+    /// from those whose yes loses work
+    pub no_confirm: bool,
+    /// Pages the output shown, with `{path}` for a file holding it
+    pub pager: Option<String>,
+    pub navigation: Navigation,
+    /// File every command that runs gets appended to
+    pub command_log: Option<PathBuf>,
+    /// Names of the actions the help lists first, like `commit all`
+    pub quick_actions: Vec<String>,
+    pub background: Background,
+    pub theme: Preset,
+    /// Colors replacing the ones of `theme`, as pairs of role and value from
+    /// the `color_<role>` options
+    pub colors: Vec<(String, String)>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            git_path: "git".into(),
+            hg_path: "hg".into(),
+            git_dir: None,
+            git_work_tree: None,
+            dotfiles: None,
+            protected_branches: ["main", "master", "develop", "default"]
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            log_signatures: false,
+            log_graph: true,
+            diff_algorithm: None,
+            move_threshold: None,
+            sign_off: false,
+            gpg_sign: false,
+            wrap_diffs: true,
+            output_mode: OutputMode::Clear,
+            date_format: DateFormat::default(),
+            scrollback_lines: None,
+            log_count: None,
+            notify_after: None,
+            quiet: None,
+            mouse: false,
+            open_command: None,
+            editor_command: None,
+            confirm_open: false,
+            no_confirm: false,
+            pager: None,
+            navigation: Navigation::Wrap,
+            command_log: None,
+            quick_actions: Vec::new(),
+            background: Background::Dark,
+            theme: Preset::Default,
+            colors: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Defaults, then `config.txt`, then the environment and then `options`
+    /// from the command line. Each one wins over the ones before
+    pub fn load(options: &[(String, String)]) -> Self {
+        let file = config_path()
+            .filter(|path| path.exists())
+            .and_then(|path| File::open(path).ok())
+            .map(BufReader::new);
+        Self::from_sources(file, env::vars_os(), options)
+    }
+
+    /// Variables that are not UTF-8 are skipped, as no option could use them
+    fn from_sources<R, V>(
+        file: Option<R>,
+        vars: V,
+        options: &[(String, String)],
+    ) -> Self
+    where
+        R: BufRead,
+        V: Iterator<Item = (OsString, OsString)>,
+    {
+        let mut config = Self::default();
+        if let Some(mut reader) = file {
+            let mut line = String::new();
+            while next_line(&mut reader, &mut line) {
+                let line = line.trim();
+                let mut it = line.splitn(2, char::is_whitespace);
+                let key = it.next().unwrap_or("");
+                let value = it.next().unwrap_or("").trim();
+                config.set(key, value);
+            }
+        }
+        for (name, value) in vars {
+            if let (Ok(name), Ok(value)) =
+                (name.into_string(), value.into_string())
+            {
+                config.set_from_env(&name, value.trim());
+            }
+        }
+        for (key, value) in options {
+            config.set(key, value.trim());
+        }
+        config
+    }
+
+    /// Takes the `value` of the option called `key`. Unknown options and
+    /// empty values are ignored
+    fn set(&mut self, key: &str, value: &str) {
+        if value.is_empty() {
+            return;
+        }
+
+        match key {
+            "git_path" => self.git_path = value.into(),
+            "hg_path" => self.hg_path = value.into(),
+            "git_dir" => self.git_dir = Some(expand_home(value)),
+            "git_work_tree" => self.git_work_tree = Some(expand_home(value)),
+            "dotfiles" => self.dotfiles = Some(expand_home(value)),
+            "protected_branches" => {
+                self.protected_branches =
+                    value.split_whitespace().map(Into::into).collect();
+            }
+            "log_signatures" => self.log_signatures = value == "true",
+            "log_graph" => self.log_graph = value == "true",
+            "diff_algorithm" => {
+                self.diff_algorithm = DiffAlgorithm::parse(value)
+            }
+            "move_threshold" => {
+                self.move_threshold = value
+                    .trim_end_matches('%')
+                    .parse()
+                    .ok()
+                    .filter(|&percent| percent <= 100)
+            }
+            "sign_off" => self.sign_off = value == "true",
+            "gpg_sign" => self.gpg_sign = value == "true",
+            "wrap_diffs" => self.wrap_diffs = value != "false",
+            "output_mode" => {
+                if let Some(mode) = OutputMode::parse(value) {
+                    self.output_mode = mode;
+                }
+            }
+            "scrollback_lines" => {
+                self.scrollback_lines =
+                    value.parse().ok().filter(|&lines| lines > 0)
+            }
+            "log_count" => {
+                self.log_count = value.parse().ok().filter(|&count| count > 0)
+            }
+            "notify_after" => {
+                self.notify_after = value
+                    .trim_end_matches('s')
+                    .parse()
+                    .ok()
+                    .map(Duration::from_secs)
+            }
+            "quiet" => self.quiet = Some(value == "true"),
+            "mouse" => self.mouse = value == "true",
+            "open_command" => self.open_command = Some(value.into()),
+            "editor_command" => self.editor_command = Some(value.into()),
+            "confirm_open" => self.confirm_open = value == "true",
+            "no_confirm" => self.no_confirm = value == "true",
+            "pager" => self.pager = Some(value.into()),
+            "navigation" => {
+                if let Some(navigation) = Navigation::parse(value) {
+                    self.navigation = navigation;
+                }
+            }
+            "command_log" => {
+                self.command_log = match value {
+                    "true" => command_log::default_path(),
+                    "false" => None,
+                    path => Some(expand_home(path).into()),
+                }
+            }
+            "quick_actions" => {
+                self.quick_actions = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(Into::into)
+                    .collect();
+            }
+            "date_format" => {
+                if let Some(format) = DateFormat::parse(value) {
+                    self.date_format = format;
+                }
+            }
+            "theme" => {
+                if let Some(theme) = Preset::parse(value) {
+                    self.theme = theme;
+                }
+            }
+            "background" => {
+                if let Some(background) = Background::parse(value) {
+                    self.background = background;
+                }
+            }
+            _ => {
+                if let Some(role) = key.strip_prefix("color_") {
+                    self.colors.retain(|(r, _)| r != role);
+                    self.colors.push((role.into(), value.into()));
+                }
+            }
+        }
+    }
+
+    /// `VERCO_<OPTION>` sets any option, like `VERCO_THEME` does `theme`
+    fn set_from_env(&mut self, name: &str, value: &str) {
+        match name {
+            "VERCO_GIT" => self.set("git_path", value),
+            "VERCO_HG" => self.set("hg_path", value),
+            "GIT_DIR" => self.git_dir = Some(value.into()),
+            "GIT_WORK_TREE" => self.git_work_tree = Some(value.into()),
+            _ => {
+                if let Some(key) = name.strip_prefix("VERCO_") {
+                    self.set(&key.to_lowercase(), value);
+                }
+            }
+        }
+    }
+
+    /// Its theme preset for its background with its colors on top
+    pub fn build_theme(&self) -> Theme {
+        self.theme.theme(self.background).with_colors(&self.colors)
+    }
+
+    /// Checks that the configured executables can actually be spawned
+    pub fn validate(&self) -> Result<(), String> {
+        let defaults = Self::default();
+        if self.git_path != defaults.git_path {
+            validate_executable(&self.git_path)?;
+        }
+        if self.hg_path != defaults.hg_path {
+            validate_executable(&self.hg_path)?;
+        }
+        for dir in self
+            .git_dir
+            .iter()
+            .chain(self.git_work_tree.iter())
+            .chain(self.dotfiles.iter())
+        {
+            if !Path::new(dir).is_dir() {
+                return Err(format!("'{}' is not a directory", dir));
+            }
+        }
+        theme::validate_colors(&self.colors)
+    }
+}
+
+/// Directory where the user wide configuration files live
+pub fn config_dir() -> Option<PathBuf> {
+    let mut path = if let Some(dir) = env::var_os("XDG_CONFIG_HOME") {
+        PathBuf::from(dir)
+    } else if let Some(dir) = env::var_os("APPDATA") {
+        PathBuf::from(dir)
+    } else if let Some(dir) = env::var_os("HOME") {
+        let mut path = PathBuf::from(dir);
+        path.push(".config");
+        path
+    } else {
+        return None;
+    };
+    path.push(env!("CARGO_PKG_NAME"));
+    Some(path)
+}
+
+/// Directory where files `verco` writes for itself, like logs, live
+pub fn cache_dir() -> Option<PathBuf> {
+    let mut path = if let Some(dir) = env::var_os("XDG_CACHE_HOME") {
+        PathBuf::from(dir)
+    } else if let Some(dir) = env::var_os("LOCALAPPDATA") {
+        PathBuf::from(dir)
+    } else if let Some(dir) = env::var_os("HOME") {
+        let mut path = PathBuf::from(dir);
+        path.push(".cache");
+        path
+    } else {
+        return None;
+    };
+    path.push(env!("CARGO_PKG_NAME"));
+    Some(path)
+}
+
+fn config_path() -> Option<PathBuf> {
+    let mut path = config_dir()?;
+    path.push("config.txt");
+    Some(path)
+}
+
+pub fn home_dir() -> Option<String> {
+    env::var("HOME").or_else(|_| env::var("USERPROFILE")).ok()
+}
+
+/// Replaces a leading `~` with the home directory
+fn expand_home(path: &str) -> String {
+    let rest = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => rest,
+        _ => return path.into(),
+    };
+    match home_dir() {
+        Some(home) => format!("{}{}", home, rest),
+        None => path.into(),
+    }
+}
+
+fn validate_executable(path: &str) -> Result<(), String> {
+    let status = Command::new(path)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    match status {
+        Ok(_) => Ok(()),
+        Err(error) => Err(format!("could not execute '{}': {}", path, error)),
+    }
+}
+
+fn next_line<R: BufRead>(reader: &mut R, line: &mut String) -> bool {
+    line.clear();
+    reader.read_line(line).unwrap_or(0) > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(
+        pairs: &[(&str, &str)],
+    ) -> impl Iterator<Item = (OsString, OsString)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (OsString::from(name), OsString::from(value)))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn environment_wins_over_the_file() {
+        let file = "quiet true\nscrollback_lines 20\nhg_path /bin/hg\n";
+        let config = Config::from_sources(
+            Some(file.as_bytes()),
+            vars(&[
+                ("VERCO_QUIET", "false"),
+                ("VERCO_QUICK_ACTIONS", "push, log"),
+                ("VERCO_HG", "hg2"),
+                ("VERCO_UNKNOWN", "1"),
+            ]),
+            &[],
+        );
+
+        assert_eq!(config.quiet, Some(false));
+        assert_eq!(config.scrollback_lines, Some(20));
+        assert_eq!(config.quick_actions, ["push", "log"]);
+        assert_eq!(config.hg_path, "hg2");
+        assert_eq!(config.git_path, "git");
+    }
+
+    #[test]
+    fn command_line_wins_over_the_environment() {
+        let file = "no_confirm false\npager less\n";
+        let config = Config::from_sources(
+            Some(file.as_bytes()),
+            vars(&[
+                ("VERCO_NO_CONFIRM", "true"),
+                ("VERCO_PAGER", "more"),
+                ("VERCO_NAVIGATION", "stop"),
+            ]),
+            &[("pager".into(), "most".into())],
+        );
+
+        assert!(config.no_confirm);
+        assert_eq!(config.pager.as_deref(), Some("most"));
+        assert_eq!(config.navigation, Navigation::Stop);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn variables_that_are_not_utf8_are_skipped() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let mut env = vars(&[("VERCO_PAGER", "more")]).collect::<Vec<_>>();
+        env.insert(
+            0,
+            (
+                OsString::from("VERCO_HG"),
+                OsString::from_vec(vec![b'h', 0xff, b'g']),
+            ),
+        );
+        env.insert(0, (OsString::from_vec(vec![0xfe]), OsString::from("x")));
+
+        let config = Config::from_sources(None::<&[u8]>, env.into_iter(), &[]);
+
+        assert_eq!(config.hg_path, "hg");
+        assert_eq!(config.pager.as_deref(), Some("more"));
+    }
+}
@@ -1,7 +1,7 @@
 use std::{
-    env,
     fs::File,
     io::{self, BufRead, BufReader},
+    path::PathBuf,
     process::Command,
 };
 
@@ -14,12 +14,12 @@ pub struct CustomAction {
 }
 
 impl CustomAction {
-    pub fn load_custom_actions() -> Vec<CustomAction> {
-        Self::try_load_custom_actions().unwrap_or(Vec::new())
+    pub fn load_custom_actions(root: &str) -> Vec<CustomAction> {
+        Self::try_load_custom_actions(root).unwrap_or_default()
     }
 
-    fn try_load_custom_actions() -> io::Result<Vec<CustomAction>> {
-        let mut path = env::current_dir()?;
+    fn try_load_custom_actions(root: &str) -> io::Result<Vec<CustomAction>> {
+        let mut path = PathBuf::from(root);
         path.push(concat!(".", env!("CARGO_PKG_NAME"), "/custom_actions.txt"));
         if !path.exists() {
             return Ok(Vec::new());
@@ -0,0 +1,398 @@
+use std::ops::Range;
+
+use crossterm::style::{style, Color};
+
+use crate::{select::State, theme, tui_util::fit_color};
+
+const SEPARATOR: &str = " | ";
+const TAB: &str = "    ";
+
+/// Removes the escape sequences git and hg color their output with
+pub fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // skip "[" and everything up to the final letter
+            for c in &mut chars {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// The lines of each hunk in `diff`, colored or not. A hunk goes from its
+/// `@@` header up to the next hunk or file header. This also holds for
+/// diffs rendered side by side
+pub fn hunks(diff: &str) -> Vec<Range<usize>> {
+    let mut hunks = Vec::new();
+    let mut start = None;
+    let mut end = 0;
+    for (i, line) in diff.lines().enumerate() {
+        let line = strip_ansi(line);
+        if line.starts_with("@@") || line.starts_with("diff ") {
+            if let Some(start) = start.take() {
+                hunks.push(start..end);
+            }
+            if line.starts_with("@@") {
+                start = Some(i);
+            }
+        }
+        if !line.trim().is_empty() {
+            end = i + 1;
+        }
+    }
+    if let Some(start) = start {
+        hunks.push(start..end);
+    }
+    hunks
+}
+
+/// A plain diff of one file split into what goes before its first hunk and
+/// each hunk, every line with its newline, to build patches from
+pub struct FilePatch {
+    pub header: String,
+    pub hunks: Vec<String>,
+}
+
+impl FilePatch {
+    pub fn parse(diff: &str) -> Self {
+        let mut header = String::new();
+        let mut hunks: Vec<String> = Vec::new();
+        for line in diff.split_inclusive('\n') {
+            if line.starts_with("@@") {
+                hunks.push(String::new());
+            }
+            match hunks.last_mut() {
+                Some(hunk) => hunk.push_str(line),
+                None => header.push_str(line),
+            }
+        }
+        Self { header, hunks }
+    }
+
+    /// The patch with only the hunks `picked` at the same index. Empty when
+    /// none is
+    pub fn with_hunks(&self, picked: &[bool]) -> String {
+        let mut patch = String::new();
+        for (hunk, _) in self.hunks.iter().zip(picked).filter(|(_, &p)| p) {
+            if patch.is_empty() {
+                patch.push_str(&self.header);
+            }
+            patch.push_str(hunk);
+        }
+        patch
+    }
+}
+
+/// Where the changes of a file start in a diff
+pub struct FileSection {
+    /// Line of the `diff` header
+    pub start: usize,
+    pub name: String,
+    pub state: State,
+}
+
+/// The files in `diff`, colored or not, in the order they show up
+pub fn files(diff: &str) -> Vec<FileSection> {
+    let mut files: Vec<FileSection> = Vec::new();
+    let mut in_header = false;
+    for (i, line) in diff.lines().enumerate() {
+        let line = strip_ansi(line);
+        if let Some(header) = line.strip_prefix("diff ") {
+            files.push(FileSection {
+                start: i,
+                name: header_name(header).into(),
+                state: State::Modified,
+            });
+            in_header = true;
+            continue;
+        }
+        let file = match files.last_mut() {
+            Some(file) if in_header => file,
+            _ => continue,
+        };
+
+        if line.starts_with("@@") {
+            in_header = false;
+        } else if line.starts_with("new file mode") {
+            file.state = State::Added;
+        } else if line.starts_with("deleted file mode") {
+            file.state = State::Deleted;
+        } else if line.starts_with("rename from") {
+            file.state = State::Renamed;
+        } else if let Some(name) = line
+            .strip_prefix("+++ ")
+            .or_else(|| line.strip_prefix("--- "))
+        {
+            // hg follows the name with a tab and the date
+            let name = name.split('\t').next().unwrap_or(name);
+            if name != "/dev/null" {
+                let name = name
+                    .strip_prefix("b/")
+                    .or_else(|| name.strip_prefix("a/"))
+                    .unwrap_or(name);
+                file.name = name.into();
+            }
+        }
+    }
+    files
+}
+
+/// The file name in `diff --git a/name b/name` or `diff -r rev name`
+fn header_name(header: &str) -> &str {
+    if let Some(paths) = header.strip_prefix("--git ") {
+        return match paths.rfind(" b/") {
+            Some(i) => &paths[i + 3..],
+            None => paths,
+        };
+    }
+
+    let mut rest = header;
+    while let Some(revision) = rest.strip_prefix("-r ") {
+        rest = match revision.find(' ') {
+            Some(i) => &revision[i + 1..],
+            None => "",
+        };
+    }
+    rest
+}
+
+/// Renders a unified diff with its removed lines in a left column next to
+/// the added lines they were replaced with in a right one. Everything that
+/// is not part of a hunk spans both columns
+pub fn side_by_side(diff: &str, width: usize) -> String {
+    let diff = strip_ansi(diff).replace('\t', TAB);
+    let column = width.saturating_sub(SEPARATOR.len() + 1) / 2;
+    if column < 8 {
+        return diff;
+    }
+
+    let mut output = SideBySide {
+        output: String::with_capacity(diff.len() * 2),
+        column,
+        removed: Vec::new(),
+        added: Vec::new(),
+    };
+    let mut in_hunk = false;
+    for line in diff.lines() {
+        if line.starts_with("@@") {
+            output.flush();
+            output.push_full(line);
+            in_hunk = true;
+            continue;
+        }
+        if !in_hunk {
+            output.push_full(line);
+            continue;
+        }
+
+        match line.chars().next() {
+            Some('-') => output.removed.push(&line[1..]),
+            Some('+') => output.added.push(&line[1..]),
+            Some(' ') | None => {
+                output.flush();
+                let line = line.get(1..).unwrap_or("");
+                output.push_row(Some(line), Some(line), false);
+            }
+            // "\ No newline at end of file"
+            Some('\\') => {
+                output.flush();
+                output.push_full(line);
+            }
+            Some(_) => {
+                output.flush();
+                output.push_full(line);
+                in_hunk = false;
+            }
+        }
+    }
+    output.flush();
+    output.output
+}
+
+struct SideBySide<'a> {
+    output: String,
+    column: usize,
+    removed: Vec<&'a str>,
+    added: Vec<&'a str>,
+}
+
+impl<'a> SideBySide<'a> {
+    fn push_full(&mut self, line: &str) {
+        self.output.push_str(line);
+        self.output.push('\n');
+    }
+
+    /// Pairs up the pending removed and added lines
+    fn flush(&mut self) {
+        let removed = std::mem::take(&mut self.removed);
+        let added = std::mem::take(&mut self.added);
+        for i in 0..removed.len().max(added.len()) {
+            self.push_row(removed.get(i).copied(), added.get(i).copied(), true);
+        }
+    }
+
+    fn push_row(
+        &mut self,
+        left: Option<&str>,
+        right: Option<&str>,
+        changed: bool,
+    ) {
+        let theme = theme::current();
+        let left_color = if changed { theme.deleted } else { Color::Reset };
+        let right_color = if changed { theme.added } else { Color::Reset };
+
+        let left_rows = wrap(left.unwrap_or(""), self.column);
+        let right_rows = wrap(right.unwrap_or(""), self.column);
+        for i in 0..left_rows.len().max(right_rows.len()) {
+            let left = left_rows.get(i).map(String::as_str).unwrap_or("");
+            let right = right_rows.get(i).map(String::as_str).unwrap_or("");
+
+            let left = format!("{:<width$}", left, width = self.column);
+            self.output
+                .push_str(&style(left).with(fit_color(left_color)).to_string());
+            self.output.push_str(SEPARATOR);
+            self.output.push_str(
+                &style(right).with(fit_color(right_color)).to_string(),
+            );
+            self.output.push('\n');
+        }
+    }
+}
+
+/// Splits `line` into rows at most `width` chars long
+fn wrap(line: &str, width: usize) -> Vec<String> {
+    let chars: Vec<_> = line.chars().collect();
+    if chars.is_empty() {
+        return vec![String::new()];
+    }
+    chars
+        .chunks(width)
+        .map(|row| row.iter().collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_lines_are_paired_up_next_to_each_other() {
+        let diff = "\
+diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1,3 +1,3 @@
+ same
+-old
++new
++more
+";
+        let output = strip_ansi(&side_by_side(diff, 25));
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "diff --git a/a.txt b/a.txt",
+                "--- a/a.txt",
+                "+++ b/a.txt",
+                "@@ -1,3 +1,3 @@",
+                "same       | same",
+                "old        | new",
+                "           | more",
+            ]
+        );
+    }
+
+    #[test]
+    fn patches_keep_the_header_and_picked_hunks() {
+        let diff = "\
+diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1 +1 @@
+-a
++b
+@@ -5 +5 @@
+-e
++f
+ 
+";
+        let patch = FilePatch::parse(diff);
+        assert_eq!(patch.hunks.len(), 2);
+        assert_eq!(patch.hunks[1], "@@ -5 +5 @@\n-e\n+f\n \n");
+        assert_eq!(
+            patch.with_hunks(&[false, true]),
+            "diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n@@ -5 +5 @@\n-e\n+f\n \n"
+        );
+        assert_eq!(patch.with_hunks(&[false, false]), "");
+    }
+
+    #[test]
+    fn hunks_end_at_the_next_hunk_or_file() {
+        let diff = "\
+diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+\x1b[36m@@ -1 +1 @@\x1b[m
+-a
++b
+@@ -9 +9 @@
+ c
+diff --git a/b.txt b/b.txt
+@@ -1 +1 @@
+-d
+";
+        assert_eq!(hunks(diff), [3..6, 6..8, 9..11]);
+        // the removed and added lines share rows side by side
+        assert_eq!(hunks(&side_by_side(diff, 25)), [3..5, 5..7, 8..10]);
+    }
+
+    #[test]
+    fn files_are_named_after_their_headers() {
+        let diff = "\
+commit 0123abc
+diff --git a/old name.txt b/new name.txt
+similarity index 90%
+rename from old name.txt
+rename to new name.txt
+diff --git a/gone.txt b/gone.txt
+deleted file mode 100644
+--- a/gone.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-gone
+diff -r 1a2b3c -r 4d5e6f dir/hg.txt
+--- a/dir/hg.txt\tThu Jan 01 00:00:00 1970 +0000
++++ b/dir/hg.txt\tThu Jan 01 00:00:00 1970 +0000
+@@ -1 +1 @@
++--- not a header
+";
+        let files: Vec<_> = files(diff)
+            .into_iter()
+            .map(|f| (f.start, f.name, format!("{:?}", f.state)))
+            .collect();
+        assert_eq!(
+            files,
+            [
+                (1, "new name.txt".into(), "Renamed".into()),
+                (5, "gone.txt".into(), "Deleted".into()),
+                (11, "dir/hg.txt".into(), "Modified".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn long_lines_wrap_inside_their_column() {
+        let diff = "@@ -1 +1 @@\n-0123456789abc\n+x\n";
+        let output = strip_ansi(&side_by_side(diff, 25));
+        let lines: Vec<_> = output.lines().skip(1).collect();
+        assert_eq!(lines, ["0123456789 | x", "abc        | "]);
+    }
+}
@@ -0,0 +1,69 @@
+use std::{fs, io, process::Command};
+
+use crate::temp_file::TempFile;
+
+/// The editor to write long texts like commit messages in: `VISUAL`, then
+/// `EDITOR` and then the platform's default
+pub fn program() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|program| !program.trim().is_empty())
+        .unwrap_or_else(|| {
+            if cfg!(windows) {
+                "notepad".into()
+            } else {
+                "vi".into()
+            }
+        })
+}
+
+/// Waits for `program`, which may come with arguments like `code --wait`, to
+/// edit an empty temporary file and returns what was saved in it. Returns
+/// `None` when nothing was
+pub fn edit(program: &str) -> io::Result<Option<String>> {
+    let mut words = program.split_whitespace();
+    let name = match words.next() {
+        Some(name) => name,
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "no editor to open",
+            ))
+        }
+    };
+
+    let file = TempFile::create("message", "")?;
+    let status = Command::new(name).args(words).arg(file.path()).status();
+    let text = fs::read_to_string(file.path());
+    drop(file);
+
+    let status = status?;
+    if !status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("{} exited with {}", name, status),
+        ));
+    }
+    let text = text?;
+    let text = text.trim_end();
+    if text.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(text.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_saved_cancels() {
+        if cfg!(windows) {
+            return;
+        }
+        assert!(matches!(edit("true"), Ok(None)));
+        assert!(edit("false").is_err());
+    }
+}
@@ -1,7 +1,20 @@
+use std::{path::Path, process::Command};
+
 use crate::{
-    action::{parallel, serial, task_vec, ActionTask},
+    action::{
+        keeping, parallel, ready, serial, serial_while_ok, task_vec,
+        ActionResult, ActionTask,
+    },
     select::{Entry, State},
-    version_control_actions::{handle_command, task, VersionControlActions},
+    temp_file::TempFile,
+    theme,
+    vcs_error::VcsError,
+    version_control_actions::{
+        branch_entries, revision_error, task, CommitOptions, DateFormat,
+        DiffOptions, RemoteOperation, StashInclude, VersionControlActions,
+        KEEP_MESSAGE_EDITOR, MERGE_NOT_UNDONE, NO_COMMIT_TO_UNDO,
+        NO_OPERATION_TO_ABORT, NO_SUBMODULES_HERE,
+    },
 };
 
 fn str_to_state(s: &str) -> State {
@@ -17,25 +30,136 @@ fn str_to_state(s: &str) -> State {
     }
 }
 
+fn diff_args(options: &DiffOptions) -> Vec<String> {
+    let mut args = vec![format!("-U{}", options.context)];
+    if options.ignore_whitespace {
+        args.push("--ignore-all-space".into());
+    }
+    if let Some(algorithm) = options.algorithm {
+        args.push(format!("--diff-algorithm={}", algorithm.name()));
+    }
+    if options.detect_moves {
+        args.push(format!("--find-renames={}%", options.move_threshold));
+        args.push(format!("--find-copies={}%", options.move_threshold));
+        args.push("--color-moved=zebra".into());
+    }
+    args
+}
+
+fn commit_args(command: &mut Command, message: &str, options: &CommitOptions) {
+    command.arg("commit");
+    if options.sign_off {
+        command.arg("--signoff");
+    }
+    if options.gpg_sign {
+        command.arg("--gpg-sign");
+    }
+    command.arg("-m").arg(message);
+}
+
+/// Parses the output of `status -z`
+fn parse_status(output: &str) -> Vec<Entry> {
+    // every entry is "XY <filename>" where X is the staged and Y the
+    // unstaged state. renames and copies are followed by the old name
+    let mut files = Vec::new();
+    let mut entries = output.split('\0');
+    while let Some(entry) = entries.next() {
+        let mut chars = entry.chars();
+        let (staged, unstaged) = match (chars.next(), chars.next()) {
+            (Some(staged), Some(unstaged)) => (staged, unstaged),
+            _ => continue,
+        };
+        let filename = chars.as_str().trim();
+        if filename.is_empty() {
+            continue;
+        }
+        if matches!(staged, 'R' | 'C') || matches!(unstaged, 'R' | 'C') {
+            entries.next();
+        }
+
+        let state = if staged == ' ' { unstaged } else { staged };
+        files.push(Entry {
+            filename: filename.into(),
+            selected: false,
+            state: str_to_state(&state.to_string()),
+            columns: Some([staged, unstaged]),
+        });
+    }
+    files
+}
+
 pub struct GitActions {
     pub current_dir: String,
+    pub executable_path: String,
+    /// Passed as `--git-dir` so layouts with the repository outside of the
+    /// work tree keep working after changing directories
+    pub git_dir: Option<String>,
+    pub work_tree: Option<String>,
+    /// Leaves untracked files out of the status, mostly for work trees like
+    /// the home directory where nearly everything is untracked
+    pub hide_untracked: bool,
+    pub date_format: DateFormat,
+}
+
+impl GitActions {
+    /// Hides untracked files unless the repository configures
+    /// `status.showUntrackedFiles` itself
+    pub fn hide_untracked_by_default(&mut self) {
+        self.hide_untracked = self
+            .run(&["config", "--get", "status.showUntrackedFiles"])
+            .is_err();
+    }
 }
 
 impl VersionControlActions for GitActions {
-    fn executable_name(&self) -> &'static str {
+    fn name(&self) -> &'static str {
         "git"
     }
 
+    fn executable_path(&self) -> &str {
+        &self.executable_path[..]
+    }
+
     fn current_dir(&self) -> &str {
         &self.current_dir[..]
     }
 
-    fn set_root(&mut self) -> Result<(), String> {
-        let mut command = self.command();
-        let dir =
-            handle_command(command.args(&["rev-parse", "--show-toplevel"]))?;
+    fn global_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(dir) = &self.git_dir {
+            args.push(format!("--git-dir={}", dir));
+        }
+        if let Some(dir) = &self.work_tree {
+            args.push(format!("--work-tree={}", dir));
+        }
+        if self.hide_untracked {
+            args.push("-c".into());
+            args.push("status.showUntrackedFiles=no".into());
+        }
+
+        let theme = theme::current();
+        let colors = match &theme.vcs_colors {
+            Some(colors) => colors,
+            None => return args,
+        };
+        for (key, color) in &[
+            ("color.diff.new", colors.added),
+            ("color.diff.old", colors.removed),
+            ("color.status.added", colors.added),
+            ("color.status.changed", colors.removed),
+            ("color.status.untracked", colors.removed),
+        ] {
+            args.push("-c".into());
+            args.push(format!("{}={}", key, color));
+        }
+        args
+    }
+
+    fn set_root(&mut self) -> Result<(), VcsError> {
+        let output = self.run(&["rev-parse", "--show-toplevel"])?;
 
-        let dir = dir
+        let dir = output
+            .stdout
             .lines()
             .next()
             .expect("root directory is an empty string");
@@ -48,40 +172,115 @@ impl VersionControlActions for GitActions {
         &self.current_dir[..]
     }
 
-    fn get_current_changed_files(&self) -> Result<Vec<Entry>, String> {
-        let output = handle_command(self.command().args(&["status", "-z"]))?;
-
-        let files = output
-            .trim()
-            .split('\0')
-            .map(|e| e.trim())
-            .filter(|e| e.len() > 2)
-            .map(|e| {
-                let (state, filename) = e.split_at(2);
-                Entry {
-                    filename: String::from(filename.trim()),
-                    selected: false,
-                    state: str_to_state(&state[..1]),
-                }
+    fn current_branch(&self) -> Result<String, VcsError> {
+        let output = self.run(&["rev-parse", "--abbrev-ref", "HEAD"])?;
+        match output.stdout.trim() {
+            // detached
+            "HEAD" => Err(VcsError::NotFound("not on a branch".into())),
+            branch => Ok(branch.into()),
+        }
+    }
+
+    fn head_summary(&self) -> Option<String> {
+        let mut summary = match self.current_branch() {
+            Ok(branch) => branch,
+            Err(_) => {
+                let output =
+                    self.run(&["rev-parse", "--short", "HEAD"]).ok()?;
+                output.stdout.trim().into()
+            }
+        };
+        let counts = self.run(&[
+            "rev-list",
+            "--left-right",
+            "--count",
+            "@{upstream}...HEAD",
+        ]);
+        if let Ok(counts) = counts {
+            let mut counts = counts.stdout.split_whitespace();
+            let behind = counts.next().unwrap_or("0");
+            let ahead = counts.next().unwrap_or("0");
+            if ahead != "0" {
+                summary.push_str(" \u{2191}");
+                summary.push_str(ahead);
+            }
+            if behind != "0" {
+                summary.push_str(" \u{2193}");
+                summary.push_str(behind);
+            }
+        }
+        Some(summary)
+    }
+
+    fn upstream_branch(&self) -> Result<String, VcsError> {
+        let output = self.run(&[
+            "rev-parse",
+            "--abbrev-ref",
+            "--symbolic-full-name",
+            "@{upstream}",
+        ])?;
+        Ok(output.stdout.trim().into())
+    }
+
+    fn operation_in_progress(&self) -> Option<&'static str> {
+        let output = self.run(&["rev-parse", "--git-dir"]).ok()?;
+        let git_dir = Path::new(&self.current_dir).join(output.stdout.trim());
+        [
+            ("rebase-merge", "rebase"),
+            ("rebase-apply", "rebase"),
+            ("MERGE_HEAD", "merge"),
+            ("CHERRY_PICK_HEAD", "cherry-pick"),
+            ("REVERT_HEAD", "revert"),
+            ("BISECT_LOG", "bisect"),
+        ]
+        .iter()
+        .find(|(marker, _)| git_dir.join(marker).exists())
+        .map(|(_, operation)| *operation)
+    }
+
+    fn get_current_changed_files(&self) -> Result<Vec<Entry>, VcsError> {
+        let output = self.run(&["status", "-z"])?;
+        Ok(parse_status(&output.stdout))
+    }
+
+    fn get_revision_changed_files(
+        &self,
+        target: &str,
+    ) -> Result<Vec<Entry>, VcsError> {
+        let output = self.run(&[
+            "diff-tree",
+            "--no-commit-id",
+            "--name-status",
+            "-z",
+            "-r",
+            target,
+        ])?;
+
+        let output = &output.stdout[..];
+        let state_iter = output.split('\0').map(|e| e.trim()).step_by(2);
+        let filename_iter =
+            output.split('\0').map(|e| e.trim()).skip(1).step_by(2);
+
+        let files = state_iter
+            .zip(filename_iter)
+            .map(|(s, f)| Entry {
+                filename: String::from(f),
+                selected: false,
+                state: str_to_state(s),
+                columns: None,
             })
             .collect();
         Ok(files)
     }
 
-    fn get_revision_changed_files(
+    fn get_changed_files_since(
         &self,
         target: &str,
-    ) -> Result<Vec<Entry>, String> {
-        let output = handle_command(
-            self.command()
-                .arg("diff-tree")
-                .arg("--no-commit-id")
-                .arg("--name-status")
-                .arg("-z")
-                .arg("-r")
-                .arg(target),
-        )?;
+    ) -> Result<Vec<Entry>, VcsError> {
+        let output =
+            self.run(&["diff", "--name-status", "--no-renames", "-z", target])?;
 
+        let output = &output.stdout[..];
         let state_iter = output.split('\0').map(|e| e.trim()).step_by(2);
         let filename_iter =
             output.split('\0').map(|e| e.trim()).skip(1).step_by(2);
@@ -92,13 +291,26 @@ impl VersionControlActions for GitActions {
                 filename: String::from(f),
                 selected: false,
                 state: str_to_state(s),
+                columns: None,
             })
             .collect();
         Ok(files)
     }
 
-    fn version(&self) -> Result<String, String> {
-        handle_command(self.command().arg("--version"))
+    fn has_hunk_staging(&self) -> bool {
+        true
+    }
+
+    fn has_all_remotes(&self) -> bool {
+        true
+    }
+
+    fn has_remote_tags(&self) -> bool {
+        true
+    }
+
+    fn version(&self) -> Result<String, VcsError> {
+        self.run(&["--version"]).map(|o| o.stdout)
     }
 
     fn status(&self) -> Box<dyn ActionTask> {
@@ -107,63 +319,115 @@ impl VersionControlActions for GitActions {
         })
     }
 
-    fn current_export(&self) -> Box<dyn ActionTask> {
+    fn current_export(&self, options: &DiffOptions) -> Box<dyn ActionTask> {
         task(self, |command| {
-            command.args(&["show", "--color"]);
+            command.args(&["show", "--color"]).args(diff_args(options));
         })
     }
 
-    fn log(&self, count: usize) -> Box<dyn ActionTask> {
+    fn log(
+        &self,
+        skip: usize,
+        count: usize,
+        signatures: bool,
+        graph: bool,
+    ) -> Box<dyn ActionTask> {
         task(self, |command| {
-            let count_str = format!("-{}", count);
-            let template =
-                "--format=format:%x1e%h%x1e%as%x1e%<(10,trunc)%aN%x1e%D%x1e%s";
+            let template = if signatures {
+                "--format=format:%x1e%h%x1e%G?%x1e%ad%x1e%<(10,trunc)%aN%x1e%D%x1e%s"
+            } else {
+                "--format=format:%x1e%h%x1e%ad%x1e%<(10,trunc)%aN%x1e%D%x1e%s"
+            };
             command
                 .arg("log")
                 .arg("--all")
                 .arg("--decorate")
                 .arg("--oneline")
-                .arg("--graph")
-                .arg(&count_str)
-                .arg(template);
+                .arg(format!("--skip={}", skip))
+                .arg(format!("--max-count={}", count))
+                .arg(template)
+                .arg(self.date_format.git_arg());
+            if graph {
+                command.arg("--graph");
+            }
         })
     }
 
-    fn current_diff_all(&self) -> Box<dyn ActionTask> {
+    fn current_diff_all(&self, options: &DiffOptions) -> Box<dyn ActionTask> {
         task(self, |command| {
-            command.args(&["diff", "--color"]);
+            command.args(&["diff", "--color"]).args(diff_args(options));
         })
     }
 
     fn current_diff_selected(
         &self,
         entries: &Vec<Entry>,
+        options: &DiffOptions,
     ) -> Box<dyn ActionTask> {
         task(self, |command| {
-            command.arg("diff").arg("--color").arg("--");
+            command.arg("diff").arg("--color").args(diff_args(options));
+            command.arg("--");
             for e in entries.iter().filter(|e| e.selected) {
                 command.arg(&e.filename);
             }
         })
     }
 
-    fn revision_changes(&self, target: &str) -> Box<dyn ActionTask> {
+    fn diff_file(
+        &self,
+        file: &str,
+        options: &DiffOptions,
+    ) -> Result<String, VcsError> {
+        let diff_args = diff_args(options);
+        let mut args = vec!["diff", "--color"];
+        args.extend(diff_args.iter().map(String::as_str));
+        args.extend(["--", file]);
+        Ok(self.run(&args)?.stdout)
+    }
+
+    fn revision_details(&self, target: &str) -> Result<String, VcsError> {
+        let output = self
+            .run(&[
+                "show",
+                "--no-color",
+                "--name-status",
+                &self.date_format.git_arg(),
+                "--format=format:revision %H%nauthor %aN <%aE>%ndate %ad%n%n%B",
+                target,
+                "--",
+            ])
+            .map_err(|error| revision_error(target, error))?;
+        Ok(output.stdout)
+    }
+
+    /// `--name-status` lists files changed only in whitespace even with
+    /// `--ignore-all-space`, so a `--stat` is shown instead while ignoring it
+    fn revision_changes(
+        &self,
+        target: &str,
+        options: &DiffOptions,
+    ) -> Box<dyn ActionTask> {
         task(self, |command| {
-            command
-                .arg("diff-tree")
-                .arg("--no-commit-id")
-                .arg("--name-status")
-                .arg("-r")
-                .arg(target)
-                .arg("--color");
+            command.arg("diff-tree").arg("--no-commit-id");
+            if options.ignore_whitespace {
+                command.arg("--stat").arg("--ignore-all-space");
+            } else {
+                command.arg("--name-status");
+            }
+            command.arg("-r").arg(target).arg("--color");
         })
     }
 
-    fn revision_diff_all(&self, target: &str) -> Box<dyn ActionTask> {
+    fn revision_diff_all(
+        &self,
+        target: &str,
+        options: &DiffOptions,
+    ) -> Box<dyn ActionTask> {
         task(self, |command| {
             let mut parents = String::from(target);
             parents.push_str("^@");
             command.arg("diff").arg(parents).arg(target).arg("--color");
+            command.args(diff_args(options));
         })
     }
 
@@ -171,6 +435,7 @@ impl VersionControlActions for GitActions {
         &self,
         target: &str,
         entries: &Vec<Entry>,
+        options: &DiffOptions,
     ) -> Box<dyn ActionTask> {
         task(self, |command| {
             let mut parents = String::from(target);
@@ -179,6 +444,7 @@ impl VersionControlActions for GitActions {
             command
                 .arg("diff")
                 .arg("--color")
+                .args(diff_args(options))
                 .arg(parents)
                 .arg(target)
                 .arg("--");
@@ -189,13 +455,17 @@ impl VersionControlActions for GitActions {
         })
     }
 
-    fn commit_all(&self, message: &str) -> Box<dyn ActionTask> {
+    fn commit_all(
+        &self,
+        message: &str,
+        options: &CommitOptions,
+    ) -> Box<dyn ActionTask> {
         let mut tasks = task_vec();
         tasks.push(task(self, |command| {
             command.args(&["add", "--all"]);
         }));
         tasks.push(task(self, |command| {
-            command.arg("commit").arg("-m").arg(message);
+            commit_args(command, message, options);
         }));
         serial(tasks)
     }
@@ -204,6 +474,7 @@ impl VersionControlActions for GitActions {
         &self,
         message: &str,
         entries: &Vec<Entry>,
+        options: &CommitOptions,
     ) -> Box<dyn ActionTask> {
         let mut tasks = task_vec();
         for e in entries.iter().filter(|e| e.selected) {
@@ -213,11 +484,80 @@ impl VersionControlActions for GitActions {
         }
 
         tasks.push(task(self, |command| {
-            command.arg("commit").arg("-m").arg(message);
+            commit_args(command, message, options);
         }));
         serial(tasks)
     }
 
+    fn hunk_diff(&self, file: &str) -> Result<String, VcsError> {
+        let output =
+            self.run(&["diff", "--no-color", "--no-ext-diff", "--", file])?;
+        Ok(output.stdout)
+    }
+
+    /// Commits from an index of its own holding `HEAD` and the patch, so
+    /// what was staged before stays staged and out of the commit. The real
+    /// index then takes the patch too, to match the new `HEAD`
+    fn commit_patch(
+        &self,
+        message: &str,
+        patch: &str,
+        options: &CommitOptions,
+    ) -> Box<dyn ActionTask> {
+        let files = TempFile::create("index", "")
+            .and_then(|index| Ok((index, TempFile::create("patch", patch)?)));
+        let (index, patch) = match files {
+            Ok(files) => files,
+            Err(error) => {
+                return ready(ActionResult::from_err(error.to_string()))
+            }
+        };
+        let mut tasks = task_vec();
+        tasks.push(task(self, |command| {
+            command.env("GIT_INDEX_FILE", index.path());
+            command.args(["read-tree", "HEAD"]);
+        }));
+        tasks.push(task(self, |command| {
+            command.env("GIT_INDEX_FILE", index.path());
+            command.args(["apply", "--cached"]).arg(patch.path());
+        }));
+        tasks.push(task(self, |command| {
+            command.env("GIT_INDEX_FILE", index.path());
+            commit_args(command, message, options);
+        }));
+        tasks.push(task(self, |command| {
+            command.args(["apply", "--cached"]).arg(patch.path());
+        }));
+        keeping(index, keeping(patch, serial_while_ok(tasks)))
+    }
+
+    /// The first commit has no parent to reset to, so the branch is left
+    /// without commits instead
+    fn undo_last_commit(&self) -> Box<dyn ActionTask> {
+        let parents =
+            match self.run(&["rev-list", "--parents", "-n", "1", "HEAD"]) {
+                Ok(output) => output.stdout.split_whitespace().count() - 1,
+                // only a branch without commits has no `HEAD` to list
+                Err(VcsError::NotFound(_)) => {
+                    return ready(ActionResult::from_err(
+                        NO_COMMIT_TO_UNDO.into(),
+                    ))
+                }
+                Err(error) => {
+                    return ready(ActionResult::from_err(error.to_string()))
+                }
+            };
+        match parents {
+            0 => task(self, |command| {
+                command.args(["update-ref", "-d", "HEAD"]);
+            }),
+            1 => task(self, |command| {
+                command.args(["reset", "--soft", "HEAD~1"]);
+            }),
+            _ => ready(ActionResult::from_err(MERGE_NOT_UNDONE.into())),
+        }
+    }
+
     fn revert_all(&self) -> Box<dyn ActionTask> {
         let mut tasks = task_vec();
         tasks.push(task(self, |command| {
@@ -257,18 +597,129 @@ impl VersionControlActions for GitActions {
         parallel(tasks)
     }
 
+    fn reset_selected(
+        &self,
+        target: &str,
+        entries: &[Entry],
+        working_tree: bool,
+    ) -> Box<dyn ActionTask> {
+        let selected: Vec<_> = entries.iter().filter(|e| e.selected).collect();
+        let mut tasks = task_vec();
+        tasks.push(task(self, |command| {
+            command.args(["reset", "-q", target, "--"]);
+            command.args(selected.iter().map(|e| &e.filename));
+        }));
+        if working_tree {
+            for e in selected {
+                tasks.push(task(self, |command| {
+                    // files added since `target` are left untracked by the
+                    // reset and have nothing to check out
+                    if matches!(e.state, State::Added) {
+                        command.args(["clean", "-f", "--"]);
+                    } else {
+                        command.args(["checkout", "--"]);
+                    }
+                    command.arg(&e.filename);
+                }));
+            }
+        }
+        serial(tasks)
+    }
+
     fn update(&self, target: &str) -> Box<dyn ActionTask> {
         task(self, |command| {
             command.arg("checkout").arg(target);
         })
     }
 
+    fn update_submodules(
+        &self,
+        init: bool,
+        recursive: bool,
+    ) -> Box<dyn ActionTask> {
+        // lists the ones not cloned yet too, so empty means there are none
+        match self.run(&["submodule", "status"]) {
+            Ok(output) if output.stdout.trim().is_empty() => {
+                return ready(ActionResult::from_ok(NO_SUBMODULES_HERE.into()))
+            }
+            Ok(_) => (),
+            Err(error) => {
+                return ready(ActionResult::from_err(error.to_string()))
+            }
+        }
+        task(self, |command| {
+            command.args(["submodule", "update"]);
+            if init {
+                command.arg("--init");
+            }
+            if recursive {
+                command.arg("--recursive");
+            }
+        })
+    }
+
     fn merge(&self, target: &str) -> Box<dyn ActionTask> {
         task(self, |command| {
             command.arg("merge").arg(target);
         })
     }
 
+    fn cherry_pick(&self, target: &str) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.arg("cherry-pick").arg(target);
+        })
+    }
+
+    fn continue_cherry_pick(&self) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args(["cherry-pick", "--continue"]);
+            command.env("GIT_EDITOR", KEEP_MESSAGE_EDITOR);
+        })
+    }
+
+    fn abort_cherry_pick(&self) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args(["cherry-pick", "--abort"]);
+        })
+    }
+
+    fn rebase(&self, onto: &str) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.arg("rebase").arg(onto);
+        })
+    }
+
+    fn continue_rebase(&self) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args(["rebase", "--continue"]);
+            command.env("GIT_EDITOR", KEEP_MESSAGE_EDITOR);
+        })
+    }
+
+    fn abort_rebase(&self) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args(["rebase", "--abort"]);
+        })
+    }
+
+    fn abort_operation(&self) -> Box<dyn ActionTask> {
+        let args: &[&str] = match self.operation_in_progress() {
+            Some("rebase") => &["rebase", "--abort"],
+            Some("merge") => &["merge", "--abort"],
+            Some("cherry-pick") => &["cherry-pick", "--abort"],
+            Some("revert") => &["revert", "--abort"],
+            Some("bisect") => &["bisect", "reset"],
+            _ => {
+                return ready(ActionResult::from_err(
+                    NO_OPERATION_TO_ABORT.into(),
+                ))
+            }
+        };
+        task(self, |command| {
+            command.args(args);
+        })
+    }
+
     fn conflicts(&self) -> Box<dyn ActionTask> {
         task(self, |command| {
             command.args(&["diff", "--name-only", "--diff-filter=U"]);
@@ -287,21 +738,108 @@ impl VersionControlActions for GitActions {
         })
     }
 
-    fn fetch(&self) -> Box<dyn ActionTask> {
+    fn get_remotes(&self) -> Result<Vec<Entry>, VcsError> {
+        let output = self.run(&["remote"])?;
+        let upstream = self.upstream_branch().unwrap_or_default();
+        let tracked = upstream.split('/').next().unwrap_or("");
+        Ok(branch_entries(
+            output
+                .stdout
+                .lines()
+                .filter(|name| !name.is_empty())
+                .map(|name| (if name == tracked { "*" } else { " " }, name)),
+        ))
+    }
+
+    fn list_remotes(&self) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args(["remote", "--verbose"]);
+        })
+    }
+
+    fn add_remote(&self, name: &str, url: &str) -> Box<dyn ActionTask> {
         task(self, |command| {
-            command.args(&["fetch", "--all"]);
+            command.args(["remote", "add", name, url]);
         })
     }
 
-    fn pull(&self) -> Box<dyn ActionTask> {
+    fn remove_remote(&self, name: &str) -> Box<dyn ActionTask> {
         task(self, |command| {
-            command.args(&["pull", "--all"]);
+            command.args(["remote", "remove", name]);
         })
     }
 
-    fn push(&self) -> Box<dyn ActionTask> {
+    fn remote_args(
+        &self,
+        operation: RemoteOperation,
+        remote: Option<&str>,
+    ) -> Vec<String> {
+        let mut args = match operation {
+            RemoteOperation::Fetch => vec!["fetch".to_string()],
+            RemoteOperation::Pull => vec!["pull".to_string()],
+            RemoteOperation::Push => vec!["push".to_string()],
+        };
+        match (operation, remote) {
+            (RemoteOperation::Push, remote) => {
+                args.extend(remote.map(Into::into))
+            }
+            (_, Some(remote)) => {
+                args.push(remote.into());
+                // another remote has no upstream to merge, so its branch of
+                // the same name is
+                if operation == RemoteOperation::Pull {
+                    args.extend(self.current_branch().ok());
+                }
+            }
+            (_, None) => args.push("--all".into()),
+        }
+        args
+    }
+
+    fn fetch(&self, remote: Option<&str>) -> Box<dyn ActionTask> {
+        let args = self.remote_args(RemoteOperation::Fetch, remote);
         task(self, |command| {
-            command.arg("push");
+            command.args(args);
+        })
+    }
+
+    fn pull(&self, remote: Option<&str>) -> Box<dyn ActionTask> {
+        let args = self.remote_args(RemoteOperation::Pull, remote);
+        task(self, |command| {
+            command.args(args);
+        })
+    }
+
+    fn push(&self, remote: Option<&str>) -> Box<dyn ActionTask> {
+        let args = self.remote_args(RemoteOperation::Push, remote);
+        task(self, |command| {
+            command.args(args);
+        })
+    }
+
+    fn branch_without_upstream(&self) -> Option<String> {
+        let branch = self.current_branch().ok()?;
+        if self.upstream_branch().is_ok() {
+            return None;
+        }
+        // which pushes a new branch to a branch of the same name by itself
+        let auto_setup = self
+            .run(&["config", "--type=bool", "--get", "push.autoSetupRemote"])
+            .map_or(false, |output| output.stdout.trim() == "true");
+        if auto_setup {
+            None
+        } else {
+            Some(branch)
+        }
+    }
+
+    fn push_set_upstream(
+        &self,
+        remote: &str,
+        branch: &str,
+    ) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args(["push", "--set-upstream", remote, branch]);
         })
     }
 
@@ -322,6 +860,18 @@ impl VersionControlActions for GitActions {
         })
     }
 
+    fn get_branches_for_checkout(&self) -> Result<Vec<Entry>, VcsError> {
+        let output =
+            self.run(&["branch", "--format=%(HEAD)%(refname:short)"])?;
+        Ok(branch_entries(
+            output
+                .stdout
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(|line| line.split_at(1)),
+        ))
+    }
+
     fn create_branch(&self, name: &str) -> Box<dyn ActionTask> {
         let mut tasks = task_vec();
         tasks.push(task(self, |command| {
@@ -338,6 +888,40 @@ impl VersionControlActions for GitActions {
         serial(tasks)
     }
 
+    fn list_tags(&self) -> Result<Vec<Entry>, VcsError> {
+        let output = self.run(&[
+            "for-each-ref",
+            "refs/tags",
+            "--sort=-creatordate",
+            "--format=%(refname:short)",
+        ])?;
+        Ok(branch_entries(
+            output
+                .stdout
+                .lines()
+                .filter(|name| !name.is_empty())
+                .map(|name| (" ", name)),
+        ))
+    }
+
+    /// Like `create_tag`, the tag goes from origin as well
+    fn delete_tag(
+        &self,
+        name: &str,
+        remote: Option<&str>,
+    ) -> Box<dyn ActionTask> {
+        let mut tasks = task_vec();
+        tasks.push(task(self, |command| {
+            command.args(["tag", "-d", name]);
+        }));
+        if let Some(remote) = remote {
+            tasks.push(task(self, |command| {
+                command.args(["push", "-d", remote, name]);
+            }));
+        }
+        serial(tasks)
+    }
+
     fn close_branch(&self, name: &str) -> Box<dyn ActionTask> {
         let mut tasks = task_vec();
         tasks.push(task(self, |command| {
@@ -348,4 +932,1239 @@ impl VersionControlActions for GitActions {
         }));
         serial(tasks)
     }
+
+    fn delete_branch(
+        &self,
+        name: &str,
+        force: bool,
+    ) -> Result<String, VcsError> {
+        let flag = if force { "-D" } else { "-d" };
+        let output = self.run(&["branch", flag, name])?;
+        Ok(output.stdout)
+    }
+
+    fn list_merged_branches(&self, base: &str) -> Box<dyn ActionTask> {
+        let mut tasks = task_vec();
+        for (filter, status) in
+            &[("--merged", "merged"), ("--no-merged", "unmerged")]
+        {
+            tasks.push(task(self, |command| {
+                command.arg("branch").arg(filter).arg(base).arg(format!(
+                    "--format={}%1e%(refname:short)%1e%(HEAD)",
+                    status
+                ));
+            }));
+        }
+        parallel(tasks)
+    }
+
+    fn delete_branches(&self, names: &[String]) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args(["branch", "-d"]).args(names);
+        })
+    }
+
+    fn stash_files(
+        &self,
+        include: StashInclude,
+    ) -> Result<Vec<String>, VcsError> {
+        let mut args = vec!["status", "-z"];
+        match include {
+            StashInclude::Tracked => args.push("--untracked-files=no"),
+            StashInclude::Untracked => args.push("--untracked-files=all"),
+            StashInclude::All => {
+                args.extend(&["--untracked-files=all", "--ignored"])
+            }
+        }
+        let output = self.run(&args)?;
+        Ok(parse_status(&output.stdout)
+            .into_iter()
+            .map(|e| e.filename)
+            .collect())
+    }
+
+    fn stash(
+        &self,
+        message: &str,
+        include: StashInclude,
+    ) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args(["stash", "push"]);
+            match include {
+                StashInclude::Tracked => (),
+                StashInclude::Untracked => {
+                    command.arg("--include-untracked");
+                }
+                StashInclude::All => {
+                    command.arg("--all");
+                }
+            }
+            if !message.is_empty() {
+                command.arg("--message").arg(message);
+            }
+        })
+    }
+
+    fn blame_ignore_revs_file(&self) -> Option<String> {
+        let configured = self.run(&["config", "--get", "blame.ignoreRevsFile"]);
+        match configured {
+            Ok(output) if !output.stdout.trim().is_empty() => {
+                Some(output.stdout.trim().into())
+            }
+            _ => Some(".git-blame-ignore-revs".into()).filter(|file| {
+                Path::new(&self.current_dir).join(file).exists()
+            }),
+        }
+    }
+
+    fn blame(
+        &self,
+        target: Option<&str>,
+        file: &str,
+        lines: Option<(usize, usize)>,
+        ignore_revs: bool,
+    ) -> Box<dyn ActionTask> {
+        // an empty file name clears the configured one
+        let ignore_revs_file = if ignore_revs {
+            self.blame_ignore_revs_file().unwrap_or_default()
+        } else {
+            String::new()
+        };
+        task(self, |command| {
+            command.arg("blame").arg(self.date_format.git_arg());
+            command.arg(format!("--ignore-revs-file={}", ignore_revs_file));
+            if let Some((start, end)) = lines {
+                command.arg(format!("-L{},{}", start, end));
+            }
+            command.args(target).arg("--").arg(file);
+        })
+    }
+
+    fn list_stashes(&self) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args(["stash", "list"]);
+        })
+    }
+
+    fn stash_pop(&self, name: &str) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args(["stash", "pop"]);
+            if !name.is_empty() {
+                command.arg(name);
+            }
+        })
+    }
+
+    fn stash_show(
+        &self,
+        name: &str,
+        options: &DiffOptions,
+    ) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command
+                .args(["stash", "show", "--patch", "--color"])
+                .args(diff_args(options))
+                .arg(name);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        env, fs,
+        path::{Path, PathBuf},
+        process::Command,
+        sync::atomic::{AtomicUsize, Ordering},
+        task::Poll,
+        thread,
+        time::Duration,
+    };
+
+    use crate::{
+        action::{ActionKind, ActionResult, MergedBranch},
+        async_process::Executor,
+        diff::FilePatch,
+    };
+
+    /// A throwaway repository with a bare `origin` next to it, removed again
+    /// when dropped
+    struct TestRepo {
+        dir: PathBuf,
+        actions: GitActions,
+    }
+
+    impl TestRepo {
+        /// Returns `None` when git is not installed
+        fn new() -> Option<Self> {
+            static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+            if Command::new("git").arg("--version").output().is_err() {
+                eprintln!("git not found, skipping test");
+                return None;
+            }
+
+            let dir = env::temp_dir().join(format!(
+                "verco-test-{}-{}",
+                std::process::id(),
+                NEXT_ID.fetch_add(1, Ordering::Relaxed)
+            ));
+            let work_dir = dir.join("work");
+            let origin_dir = dir.join("origin");
+            fs::create_dir_all(&work_dir).unwrap();
+            fs::create_dir_all(&origin_dir).unwrap();
+
+            git(&origin_dir, &["init", "--bare", "-q"]);
+            git(&work_dir, &["init", "-q"]);
+            git(&work_dir, &["symbolic-ref", "HEAD", "refs/heads/master"]);
+            git(&work_dir, &["config", "user.name", "verco"]);
+            git(&work_dir, &["config", "user.email", "verco@example.com"]);
+            git(&work_dir, &["config", "commit.gpgsign", "false"]);
+            git(
+                &work_dir,
+                &["remote", "add", "origin", origin_dir.to_str().unwrap()],
+            );
+
+            let actions = GitActions {
+                current_dir: work_dir.to_string_lossy().into_owned(),
+                executable_path: "git".into(),
+                git_dir: None,
+                work_tree: None,
+                hide_untracked: false,
+                date_format: DateFormat::default(),
+            };
+            Some(Self { dir, actions })
+        }
+
+        fn work_dir(&self) -> PathBuf {
+            self.dir.join("work")
+        }
+
+        fn write(&self, filename: &str, contents: &str) {
+            fs::write(self.work_dir().join(filename), contents).unwrap();
+        }
+
+        fn commit(&self, message: &str) {
+            git(&self.work_dir(), &["add", "--all"]);
+            git(&self.work_dir(), &["commit", "-q", "-m", message]);
+        }
+    }
+
+    impl Drop for TestRepo {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn git(dir: &Path, args: &[&str]) {
+        let output = Command::new("git")
+            .current_dir(dir)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "git {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    fn wait(mut task: Box<dyn ActionTask>) -> ActionResult {
+        let mut executor = Executor::new(1);
+        loop {
+            match task.poll(&mut executor) {
+                Poll::Ready(result) => return result,
+                Poll::Pending => thread::sleep(Duration::from_millis(10)),
+            }
+        }
+    }
+
+    fn wait_ok(task: Box<dyn ActionTask>) -> String {
+        let result = wait(task);
+        assert!(result.success, "action failed: {}", result.output);
+        result.output
+    }
+
+    fn strip_colors(text: &str) -> String {
+        let mut stripped = String::with_capacity(text.len());
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                stripped.push(c);
+            }
+        }
+        stripped
+    }
+
+    fn states(entries: &[Entry]) -> Vec<(&str, String)> {
+        entries
+            .iter()
+            .map(|e| (&e.filename[..], format!("{:?}", e.state)))
+            .collect()
+    }
+
+    /// A bare `dotfiles.git` whose work tree is the `home` directory next to it
+    fn separated_layout(repo: &TestRepo) -> GitActions {
+        let git_dir = repo.dir.join("dotfiles.git");
+        let work_tree = repo.dir.join("home");
+        fs::create_dir_all(&work_tree).unwrap();
+        git(&repo.dir, &["init", "--bare", "-q", "dotfiles.git"]);
+        for (key, value) in &[
+            ("user.name", "verco"),
+            ("user.email", "verco@example.com"),
+            ("commit.gpgsign", "false"),
+        ] {
+            git(&git_dir, &["config", key, value]);
+        }
+        fs::write(work_tree.join(".vimrc"), "set number\n").unwrap();
+
+        let to_string = |path: &Path| path.to_string_lossy().into_owned();
+        let mut actions = GitActions {
+            // started somewhere else entirely
+            current_dir: to_string(&repo.dir),
+            executable_path: "git".into(),
+            git_dir: Some(to_string(&git_dir)),
+            work_tree: Some(to_string(&work_tree)),
+            hide_untracked: false,
+            date_format: DateFormat::default(),
+        };
+        actions.set_root().unwrap();
+        actions
+    }
+
+    #[test]
+    fn git_dir_can_live_outside_the_work_tree() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        let actions = separated_layout(&repo);
+        let work_tree = repo.dir.join("home");
+        assert_eq!(
+            fs::canonicalize(actions.get_root()).unwrap(),
+            fs::canonicalize(&work_tree).unwrap()
+        );
+
+        let entries = actions.get_current_changed_files().unwrap();
+        assert_eq!(states(&entries), [(".vimrc", "Untracked".into())]);
+
+        wait_ok(actions.commit_all("add vimrc", &CommitOptions::default()));
+        let output = wait_ok(actions.log(0, 1, false, true));
+        assert!(strip_colors(&output).contains("add vimrc"));
+        assert!(!work_tree.join(".git").exists());
+    }
+
+    #[test]
+    fn dotfiles_mode_hides_untracked_files() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        let mut actions = separated_layout(&repo);
+        let work_tree = repo.dir.join("home");
+        wait_ok(actions.commit_all("add vimrc", &CommitOptions::default()));
+        fs::write(work_tree.join(".vimrc"), "set nonumber\n").unwrap();
+        fs::write(work_tree.join(".bash_history"), "ls\n").unwrap();
+
+        actions.hide_untracked_by_default();
+        let entries = actions.get_current_changed_files().unwrap();
+        assert_eq!(states(&entries), [(".vimrc", "Modified".into())]);
+
+        git(
+            &repo.dir.join("dotfiles.git"),
+            &["config", "status.showUntrackedFiles", "normal"],
+        );
+        actions.hide_untracked_by_default();
+        let entries = actions.get_current_changed_files().unwrap();
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn files_reset_to_a_revision_keep_or_lose_their_changes() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "one\n");
+        repo.commit("initial");
+        repo.write("a.txt", "two\n");
+        repo.write("b.txt", "new\n");
+        repo.commit("second");
+
+        let mut entries =
+            repo.actions.get_changed_files_since("HEAD~1").unwrap();
+        let names: Vec<_> = entries.iter().map(|e| &e.filename[..]).collect();
+        assert_eq!(names, ["a.txt", "b.txt"]);
+        for e in &mut entries {
+            e.selected = true;
+        }
+
+        wait_ok(repo.actions.reset_selected("HEAD~1", &entries, false));
+        let staged = repo.actions.run(&["diff", "--cached", "--name-only"]);
+        assert_eq!(staged.unwrap().stdout, "a.txt\nb.txt\n");
+        let contents = fs::read_to_string(repo.work_dir().join("a.txt"));
+        assert_eq!(contents.unwrap(), "two\n");
+
+        wait_ok(repo.actions.reset_selected("HEAD~1", &entries, true));
+        let contents = fs::read_to_string(repo.work_dir().join("a.txt"));
+        assert_eq!(contents.unwrap(), "one\n");
+        assert!(!repo.work_dir().join("b.txt").exists());
+    }
+
+    #[test]
+    fn blame_at_a_revision_covers_only_the_range() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "one\ntwo\nthree\n");
+        repo.commit("initial");
+        let initial = repo.actions.run(&["rev-parse", "--short", "HEAD"]);
+        let initial = initial.unwrap().stdout;
+        repo.write("a.txt", "one\n2\nthree\n");
+        repo.commit("second");
+        repo.write("a.txt", "uncommitted\n2\nthree\n");
+
+        let blame = strip_colors(&wait_ok(repo.actions.blame(
+            Some("HEAD~1"),
+            "a.txt",
+            Some((2, 3)),
+            false,
+        )));
+        let lines: Vec<_> = blame.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(" two"));
+        let hash = ActionKind::Blame.parse_target(lines[0]).unwrap();
+        assert!(hash.starts_with(initial.trim()));
+
+        let blame = wait_ok(repo.actions.blame(None, "a.txt", None, false));
+        let first = blame.lines().next().unwrap();
+        assert!(first.ends_with(" uncommitted"));
+        assert_eq!(ActionKind::Blame.parse_target(first), None);
+    }
+
+    #[test]
+    fn blame_looks_past_ignored_revisions_only_when_asked() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        let head = || {
+            let output = repo.actions.run(&["rev-parse", "HEAD"]).unwrap();
+            output.stdout.trim().to_string()
+        };
+        repo.write("a.txt", "fn a(){}\n");
+        repo.commit("initial");
+        let initial = head();
+        repo.write("a.txt", "fn a() {}\n");
+        repo.commit("reformat");
+        assert_eq!(repo.actions.blame_ignore_revs_file(), None);
+
+        repo.write(".git-blame-ignore-revs", &format!("{}\n", head()));
+        let file = repo.actions.blame_ignore_revs_file();
+        assert_eq!(file.as_deref(), Some(".git-blame-ignore-revs"));
+        git(
+            &repo.work_dir(),
+            &["config", "blame.ignoreRevsFile", &file.unwrap()],
+        );
+
+        let blamed = |ignore_revs| {
+            let blame =
+                wait_ok(repo.actions.blame(None, "a.txt", None, ignore_revs));
+            let first = blame.lines().next().unwrap().to_string();
+            ActionKind::Blame.parse_target(&first).unwrap().to_string()
+        };
+        assert!(initial.starts_with(&blamed(true)));
+        assert!(!initial.starts_with(&blamed(false)));
+    }
+
+    #[test]
+    fn partially_staged_file_shows_both_columns() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("both.txt", "one\n");
+        repo.write("unstaged.txt", "one\n");
+        repo.write("old.txt", "moved\n");
+        repo.commit("initial");
+
+        repo.write("both.txt", "two\n");
+        git(&repo.work_dir(), &["add", "both.txt"]);
+        repo.write("both.txt", "three\n");
+        repo.write("unstaged.txt", "two\n");
+        git(&repo.work_dir(), &["mv", "old.txt", "new.txt"]);
+
+        let mut entries = repo.actions.get_current_changed_files().unwrap();
+        entries.sort_by(|a, b| a.filename.cmp(&b.filename));
+        let columns: Vec<_> = entries
+            .iter()
+            .map(|e| (&e.filename[..], e.columns.unwrap()))
+            .collect();
+        assert_eq!(
+            columns,
+            [
+                ("both.txt", ['M', 'M']),
+                ("new.txt", ['R', ' ']),
+                ("unstaged.txt", [' ', 'M']),
+            ]
+        );
+        assert_eq!(format!("{:?}", entries[2].state), "Modified");
+    }
+
+    #[test]
+    fn conflicted_merge_is_in_progress_until_aborted() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("file.txt", "base\n");
+        repo.commit("base");
+        git(&repo.work_dir(), &["checkout", "-q", "-b", "other"]);
+        repo.write("file.txt", "other\n");
+        repo.commit("other");
+        git(&repo.work_dir(), &["checkout", "-q", "master"]);
+        repo.write("file.txt", "master\n");
+        repo.commit("master");
+        assert_eq!(repo.actions.operation_in_progress(), None);
+
+        let merged = Command::new("git")
+            .current_dir(repo.work_dir())
+            .args(["merge", "-q", "other"])
+            .output()
+            .unwrap();
+        assert!(!merged.status.success());
+        assert_eq!(repo.actions.operation_in_progress(), Some("merge"));
+
+        wait_ok(repo.actions.abort_operation());
+        assert_eq!(repo.actions.operation_in_progress(), None);
+        let content = fs::read_to_string(repo.work_dir().join("file.txt"));
+        assert_eq!(content.unwrap(), "master\n");
+        let result = wait(repo.actions.abort_operation());
+        assert_eq!(result.output, NO_OPERATION_TO_ABORT);
+    }
+
+    #[test]
+    fn conflicted_cherry_pick_is_reported_and_aborted() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("file.txt", "base\n");
+        repo.commit("base");
+        git(&repo.work_dir(), &["checkout", "-q", "-b", "other"]);
+        repo.write("file.txt", "other\n");
+        repo.commit("other");
+        git(&repo.work_dir(), &["checkout", "-q", "master"]);
+        repo.write("file.txt", "master\n");
+        repo.commit("master");
+
+        let result = wait(repo.actions.cherry_pick("other"));
+        assert!(!result.success);
+        let error = VcsError::from_stderr(result.output, None);
+        assert!(matches!(error, VcsError::Conflict(_)));
+        assert_eq!(repo.actions.operation_in_progress(), Some("cherry-pick"));
+
+        wait_ok(repo.actions.abort_cherry_pick());
+        assert_eq!(repo.actions.operation_in_progress(), None);
+        let content = fs::read_to_string(repo.work_dir().join("file.txt"));
+        assert_eq!(content.unwrap(), "master\n");
+    }
+
+    #[test]
+    fn conflicted_rebase_is_reported_and_aborted() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("file.txt", "base\n");
+        repo.commit("base");
+        git(&repo.work_dir(), &["checkout", "-q", "-b", "other"]);
+        repo.write("file.txt", "other\n");
+        repo.commit("other");
+        git(&repo.work_dir(), &["checkout", "-q", "master"]);
+        repo.write("file.txt", "master\n");
+        repo.commit("master");
+
+        let result = wait(repo.actions.rebase("other"));
+        assert!(!result.success);
+        let error = VcsError::from_stderr(result.output, None);
+        assert!(matches!(error, VcsError::Conflict(_)));
+        assert_eq!(repo.actions.operation_in_progress(), Some("rebase"));
+
+        wait_ok(repo.actions.abort_rebase());
+        assert_eq!(repo.actions.operation_in_progress(), None);
+        let content = fs::read_to_string(repo.work_dir().join("file.txt"));
+        assert_eq!(content.unwrap(), "master\n");
+    }
+
+    #[test]
+    fn changed_files_are_parsed_from_status() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("modified.txt", "one\n");
+        repo.write("deleted.txt", "one\n");
+        repo.commit("initial");
+
+        repo.write("modified.txt", "two\n");
+        fs::remove_file(repo.work_dir().join("deleted.txt")).unwrap();
+        repo.write("added.txt", "new\n");
+        git(&repo.work_dir(), &["add", "added.txt"]);
+        repo.write("untracked.txt", "new\n");
+
+        let mut entries = repo.actions.get_current_changed_files().unwrap();
+        entries.sort_by(|a, b| a.filename.cmp(&b.filename));
+        assert_eq!(
+            states(&entries),
+            [
+                ("added.txt", "Added".into()),
+                ("deleted.txt", "Deleted".into()),
+                ("modified.txt", "Modified".into()),
+                ("untracked.txt", "Untracked".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn commit_all_leaves_a_clean_tree() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        // diff-tree lists nothing for a root commit
+        repo.write("README", "readme\n");
+        repo.commit("initial");
+        repo.write("a.txt", "a\n");
+        repo.write("b.txt", "b\n");
+
+        wait_ok(
+            repo.actions
+                .commit_all("add files", &CommitOptions::default()),
+        );
+
+        assert!(repo.actions.get_current_changed_files().unwrap().is_empty());
+        let changed = repo.actions.get_revision_changed_files("HEAD").unwrap();
+        let mut names: Vec<_> =
+            changed.iter().map(|e| &e.filename[..]).collect();
+        names.sort();
+        assert_eq!(names, ["a.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn signed_off_commit_gets_a_trailer() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "a\n");
+
+        let options = CommitOptions {
+            sign_off: true,
+            gpg_sign: false,
+        };
+        wait_ok(repo.actions.commit_all("add a", &options));
+
+        let message = repo.actions.run(&["log", "-1", "--format=%B"]).unwrap();
+        assert!(message.stdout.contains("\n\nSigned-off-by: "));
+    }
+
+    #[test]
+    fn commit_selected_only_commits_selected_files() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "a\n");
+        repo.write("b.txt", "b\n");
+
+        let mut entries = repo.actions.get_current_changed_files().unwrap();
+        for e in &mut entries {
+            e.selected = e.filename == "a.txt";
+        }
+        wait_ok(repo.actions.commit_selected(
+            "add a",
+            &entries,
+            &CommitOptions::default(),
+        ));
+
+        let entries = repo.actions.get_current_changed_files().unwrap();
+        assert_eq!(states(&entries), [("b.txt", "Untracked".into())]);
+    }
+
+    #[test]
+    fn commit_patch_leaves_the_hunks_not_picked() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        let lines: Vec<_> = (1..=12).map(|n| format!("{}\n", n)).collect();
+        repo.write("a.txt", &lines.concat());
+        repo.commit("initial");
+        let mut changed = lines.clone();
+        changed[0] = "one\nmore\n".into();
+        changed[11] = "twelve\n".into();
+        repo.write("a.txt", &changed.concat());
+
+        let diff = repo.actions.hunk_diff("a.txt").unwrap();
+        let patch = FilePatch::parse(&diff);
+        assert_eq!(patch.hunks.len(), 2);
+        wait_ok(repo.actions.commit_patch(
+            "last line",
+            &patch.with_hunks(&[false, true]),
+            &CommitOptions::default(),
+        ));
+
+        let left = repo.actions.hunk_diff("a.txt").unwrap();
+        assert!(left.contains("+one"));
+        assert!(!left.contains("+twelve"));
+        let committed = repo.actions.run(&["show", "HEAD"]).unwrap().stdout;
+        assert!(committed.contains("+twelve"));
+    }
+
+    #[test]
+    fn commit_patch_leaves_what_was_staged_before() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "a\n");
+        repo.write("b.txt", "b\n");
+        repo.commit("initial");
+        repo.write("a.txt", "changed a\n");
+        repo.write("b.txt", "staged b\n");
+        git(&repo.work_dir(), &["add", "b.txt"]);
+
+        let diff = repo.actions.hunk_diff("a.txt").unwrap();
+        let patch = FilePatch::parse(&diff);
+        wait_ok(repo.actions.commit_patch(
+            "only a",
+            &patch.with_hunks(&[true]),
+            &CommitOptions::default(),
+        ));
+
+        let committed = repo.actions.run(&["show", "HEAD"]).unwrap().stdout;
+        assert!(committed.contains("+changed a"));
+        assert!(!committed.contains("staged b"));
+        let staged = repo.actions.run(&["diff", "--cached"]).unwrap().stdout;
+        assert!(staged.contains("+staged b"));
+        assert!(!staged.contains("a.txt"));
+    }
+
+    #[test]
+    fn undone_commit_leaves_its_changes_staged() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        assert_eq!(
+            wait(repo.actions.undo_last_commit()).output,
+            NO_COMMIT_TO_UNDO
+        );
+        repo.write("a.txt", "a\n");
+        repo.commit("first");
+        repo.write("a.txt", "b\n");
+        repo.commit("premature");
+
+        wait_ok(repo.actions.undo_last_commit());
+
+        let log = wait_ok(repo.actions.log(0, 10, false, false));
+        assert!(!log.contains("premature"));
+        let entries = repo.actions.get_current_changed_files().unwrap();
+        assert_eq!(states(&entries), [("a.txt", "Modified".into())]);
+    }
+
+    #[test]
+    fn branches_are_listed_as_merged_or_not() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "a\n");
+        repo.commit("initial");
+        git(&repo.work_dir(), &["branch", "done"]);
+        git(&repo.work_dir(), &["checkout", "-q", "-b", "wip"]);
+        repo.write("b.txt", "b\n");
+        repo.commit("wip");
+        git(&repo.work_dir(), &["checkout", "-q", "master"]);
+
+        let output = wait_ok(repo.actions.list_merged_branches("master"));
+        let mut branches: Vec<_> = output
+            .lines()
+            .map(MergedBranch::parse)
+            .filter(|b| !b.name.is_empty())
+            .map(|b| (b.name, b.merged, b.current))
+            .collect();
+        branches.sort();
+        assert_eq!(
+            branches,
+            [
+                ("done", true, false),
+                ("master", true, true),
+                ("wip", false, false)
+            ]
+        );
+
+        wait_ok(repo.actions.delete_branches(&["done".into()]));
+        let output = wait_ok(repo.actions.list_merged_branches("master"));
+        assert!(!output.contains("done"));
+    }
+
+    #[test]
+    fn untracked_files_are_stashed_only_when_asked() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "one\n");
+        repo.write(".gitignore", "*.log\n");
+        repo.commit("initial");
+        repo.write("a.txt", "two\n");
+        repo.write("new.txt", "new\n");
+        repo.write("build.log", "log\n");
+
+        let files = |include| repo.actions.stash_files(include).unwrap();
+        assert_eq!(files(StashInclude::Tracked), ["a.txt"]);
+        assert_eq!(files(StashInclude::Untracked), ["a.txt", "new.txt"]);
+        assert_eq!(files(StashInclude::All), ["a.txt", "new.txt", "build.log"]);
+
+        wait_ok(repo.actions.stash("wip", StashInclude::Untracked));
+        assert!(!repo.work_dir().join("new.txt").exists());
+        assert!(repo.work_dir().join("build.log").exists());
+        let stashes = wait_ok(repo.actions.list_stashes());
+        assert!(stashes.contains("wip"));
+    }
+
+    #[test]
+    fn stash_diff_is_shown_without_applying_it() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "one\n");
+        repo.commit("initial");
+        repo.write("a.txt", "two\n");
+        git(&repo.work_dir(), &["stash", "-q"]);
+
+        let stashes = wait_ok(repo.actions.list_stashes());
+        let name = ActionKind::ListStashes
+            .parse_target(stashes.lines().next().unwrap())
+            .unwrap();
+        assert_eq!(name, "stash@{0}");
+
+        let diff = strip_colors(&wait_ok(
+            repo.actions.stash_show(name, &DiffOptions::default()),
+        ));
+        assert!(diff.contains("+two"));
+        let contents = fs::read_to_string(repo.work_dir().join("a.txt"));
+        assert_eq!(contents.unwrap(), "one\n");
+    }
+
+    #[test]
+    fn popping_a_stash_applies_and_drops_it() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "one\n");
+        repo.commit("initial");
+        repo.write("a.txt", "two\n");
+        git(&repo.work_dir(), &["stash", "-q"]);
+        repo.write("a.txt", "three\n");
+        git(&repo.work_dir(), &["stash", "-q"]);
+
+        wait_ok(repo.actions.stash_pop("stash@{1}"));
+
+        let contents = fs::read_to_string(repo.work_dir().join("a.txt"));
+        assert_eq!(contents.unwrap(), "two\n");
+        let stashes = wait_ok(repo.actions.list_stashes());
+        assert_eq!(stashes.lines().count(), 1);
+    }
+
+    #[test]
+    fn create_branch_checks_out_and_tracks_it() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "a\n");
+        repo.commit("initial");
+
+        wait_ok(repo.actions.create_branch("feature"));
+
+        let branches = wait_ok(repo.actions.list_branches());
+        let branches: Vec<_> = branches.lines().collect();
+        assert!(branches.contains(&"feature"));
+        assert!(branches.contains(&"origin/feature"));
+        assert_eq!(repo.actions.upstream_branch().unwrap(), "origin/feature");
+    }
+
+    #[test]
+    fn current_branch_is_offered_first_for_checkout() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "a\n");
+        repo.commit("initial");
+        git(&repo.work_dir(), &["branch", "-q", "another"]);
+        git(&repo.work_dir(), &["checkout", "-q", "-b", "feature"]);
+
+        let branches = repo.actions.get_branches_for_checkout().unwrap();
+        let names: Vec<_> = branches.iter().map(|e| &e.filename[..]).collect();
+        assert_eq!(names.len(), 3);
+        assert_eq!(names[0], "feature");
+        assert!(matches!(branches[0].state, State::Current));
+        assert!(matches!(branches[1].state, State::Branch));
+    }
+
+    #[test]
+    fn unmerged_branch_needs_force_to_be_deleted() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "a\n");
+        repo.commit("initial");
+        git(&repo.work_dir(), &["checkout", "-q", "-b", "feature"]);
+        repo.write("a.txt", "b\n");
+        repo.commit("unmerged");
+        git(&repo.work_dir(), &["checkout", "-q", "master"]);
+
+        assert!(matches!(
+            repo.actions.delete_branch("feature", false),
+            Err(VcsError::Unmerged(_))
+        ));
+        assert!(repo.actions.delete_branch("feature", true).is_ok());
+        let branches = repo.actions.get_branches_for_checkout().unwrap();
+        assert_eq!(branches.len(), 1);
+    }
+
+    #[test]
+    fn revision_details_name_unknown_revisions() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "a\n");
+        repo.commit("initial\n\nwith a body");
+
+        let details = repo.actions.revision_details("HEAD").unwrap();
+        assert!(details.contains("\nauthor "));
+        assert!(details.contains("initial\n\nwith a body"));
+        assert!(details.contains("A\ta.txt"));
+
+        let error = repo.actions.revision_details("missing").unwrap_err();
+        assert_eq!(error.to_string(), "there is no revision 'missing'");
+    }
+
+    #[test]
+    fn remotes_are_added_and_removed() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        let names = |repo: &TestRepo| {
+            let remotes = repo.actions.get_remotes().unwrap();
+            remotes.into_iter().map(|r| r.filename).collect::<Vec<_>>()
+        };
+
+        wait_ok(repo.actions.add_remote("upstream", "https://example.com/a"));
+        assert!(names(&repo).contains(&"upstream".into()));
+        let listed = wait_ok(repo.actions.list_remotes());
+        assert!(listed.contains("https://example.com/a"));
+
+        wait_ok(repo.actions.remove_remote("upstream"));
+        assert!(!names(&repo).contains(&"upstream".into()));
+    }
+
+    #[test]
+    fn tracked_remote_is_offered_first() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "a\n");
+        repo.commit("initial");
+        let dir = repo.work_dir();
+        git(&dir, &["checkout", "-q", "-b", "feature"]);
+        // next to the origin every test repository has
+        git(
+            &dir,
+            &["remote", "add", "upstream", "https://example.com/a"],
+        );
+        git(
+            &dir,
+            &["update-ref", "refs/remotes/upstream/feature", "HEAD"],
+        );
+        git(&dir, &["config", "branch.feature.remote", "upstream"]);
+        git(
+            &dir,
+            &["config", "branch.feature.merge", "refs/heads/feature"],
+        );
+
+        let remotes = repo.actions.get_remotes().unwrap();
+        let names: Vec<_> = remotes.iter().map(|e| &e.filename[..]).collect();
+        assert_eq!(names[0], "upstream");
+        assert!(names.contains(&"origin"));
+        assert!(matches!(remotes[0].state, State::Current));
+    }
+
+    #[test]
+    fn detached_head_is_not_a_current_branch() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "a\n");
+        repo.commit("initial");
+        git(&repo.work_dir(), &["checkout", "-q", "-b", "feature"]);
+        assert_eq!(repo.actions.current_branch().unwrap(), "feature");
+
+        git(&repo.work_dir(), &["checkout", "-q", "--detach"]);
+        assert!(repo.actions.current_branch().is_err());
+    }
+
+    #[test]
+    fn log_lists_commits_newest_first() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "a\n");
+        repo.commit("first commit");
+        repo.write("a.txt", "b\n");
+        repo.commit("second commit");
+
+        let log = wait_ok(repo.actions.log(0, 10, false, true));
+        let subjects: Vec<_> = log
+            .lines()
+            .filter_map(|l| l.rsplit('\x1e').next())
+            .collect();
+        assert_eq!(subjects, ["second commit", "first commit"]);
+
+        let older = wait_ok(repo.actions.log(1, 10, false, true));
+        assert!(older.ends_with("first commit"));
+        assert!(!older.contains("second commit"));
+
+        let flat = wait_ok(repo.actions.log(0, 10, false, false));
+        assert!(flat.lines().all(|l| l.starts_with('\x1e')));
+    }
+
+    #[test]
+    fn log_and_blame_dates_follow_the_format() {
+        let mut repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "a\n");
+        repo.commit("first commit");
+        repo.actions.date_format = DateFormat::parse("format:%Y!").unwrap();
+
+        let log = wait_ok(repo.actions.log(0, 10, false, true));
+        let date = log.lines().next().unwrap().split('\x1e').nth(2).unwrap();
+        assert_eq!(date.len(), 5);
+        assert!(date.ends_with('!'));
+
+        let blame = wait_ok(repo.actions.blame(None, "a.txt", None, false));
+        assert!(blame.contains(date));
+
+        repo.actions.date_format = DateFormat::Relative;
+        let log = wait_ok(repo.actions.log(0, 10, false, true));
+        assert!(log.contains(" ago"));
+    }
+
+    #[test]
+    fn log_marks_unsigned_commits() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "a\n");
+        repo.commit("unsigned");
+
+        let log = wait_ok(repo.actions.log(0, 1, true, true));
+        let line = log.lines().next().unwrap();
+        assert_eq!(line.split('\x1e').nth(2), Some("N"));
+        assert_eq!(ActionKind::Log.parse_target(line).map(str::len), Some(7));
+    }
+
+    #[test]
+    fn diff_context_follows_the_options() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "1\n2\n3\n4\n5\n");
+        repo.commit("initial");
+        repo.write("a.txt", "1\n2\nthree\n4\n5\n");
+
+        let diff = |context| {
+            let options = DiffOptions {
+                context,
+                ..Default::default()
+            };
+            strip_colors(&wait_ok(repo.actions.current_diff_all(&options)))
+        };
+        assert!(!diff(0).contains("\n 2\n"));
+        assert!(diff(1).contains("\n 2\n-3\n+three\n 4\n"));
+        assert!(!diff(1).contains("\n 1\n"));
+    }
+
+    #[test]
+    fn whitespace_changes_are_left_out_when_ignored() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "a\n");
+        repo.write("b.txt", "b\n");
+        repo.commit("initial");
+        repo.write("a.txt", "  a\n");
+        repo.write("b.txt", "c\n");
+        repo.commit("reindent");
+
+        let options = DiffOptions {
+            ignore_whitespace: true,
+            ..Default::default()
+        };
+        let diff = strip_colors(&wait_ok(
+            repo.actions.revision_diff_all("HEAD", &options),
+        ));
+        assert!(!diff.contains("a.txt"));
+        assert!(diff.contains("+c"));
+
+        let changes = strip_colors(&wait_ok(
+            repo.actions.revision_changes("HEAD", &options),
+        ));
+        assert!(!changes.contains("a.txt"));
+        assert!(changes.contains("b.txt"));
+        let changes = strip_colors(&wait_ok(
+            repo.actions
+                .revision_changes("HEAD", &DiffOptions::default()),
+        ));
+        assert!(changes.contains("a.txt"));
+    }
+
+    #[test]
+    fn renames_are_detected_above_the_threshold() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        let lines: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+        repo.write("a.txt", &lines);
+        repo.commit("initial");
+        git(&repo.work_dir(), &["mv", "a.txt", "b.txt"]);
+        repo.write("b.txt", &lines.replace("line 10", "changed"));
+        repo.commit("rename");
+
+        let diff = |move_threshold| {
+            let options = DiffOptions {
+                detect_moves: true,
+                move_threshold,
+                ..Default::default()
+            };
+            strip_colors(&wait_ok(
+                repo.actions.revision_diff_all("HEAD", &options),
+            ))
+        };
+        assert!(diff(50).contains("rename from a.txt"));
+        assert!(!diff(95).contains("rename from a.txt"));
+    }
+
+    #[test]
+    fn new_branch_gets_an_upstream_when_pushed() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "a\n");
+        repo.commit("initial");
+        git(&repo.work_dir(), &["checkout", "-q", "-b", "feature"]);
+
+        assert_eq!(
+            repo.actions.branch_without_upstream(),
+            Some("feature".into())
+        );
+        wait_ok(repo.actions.push_set_upstream("origin", "feature"));
+        assert_eq!(repo.actions.upstream_branch().unwrap(), "origin/feature");
+        assert_eq!(repo.actions.branch_without_upstream(), None);
+    }
+
+    #[test]
+    fn head_summary_counts_commits_ahead_of_the_upstream() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "a\n");
+        repo.commit("initial");
+        git(&repo.work_dir(), &["checkout", "-q", "-b", "feature"]);
+        assert_eq!(repo.actions.head_summary(), Some("feature".into()));
+
+        wait_ok(repo.actions.push_set_upstream("origin", "feature"));
+        repo.write("a.txt", "b\n");
+        repo.commit("second");
+        repo.write("a.txt", "c\n");
+        repo.commit("third");
+        assert_eq!(
+            repo.actions.head_summary(),
+            Some("feature \u{2191}2".into())
+        );
+
+        git(&repo.work_dir(), &["checkout", "-q", "--detach", "HEAD~2"]);
+        let summary = repo.actions.head_summary().unwrap();
+        assert!(!summary.is_empty());
+        assert!(summary.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn submodules_are_reported_missing() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "a\n");
+        repo.commit("initial");
+
+        let output = wait_ok(repo.actions.update_submodules(true, true));
+        assert_eq!(output, NO_SUBMODULES_HERE);
+    }
+
+    #[test]
+    fn diff_shows_working_tree_changes() {
+        let repo = match TestRepo::new() {
+            Some(repo) => repo,
+            None => return,
+        };
+        repo.write("a.txt", "old line\n");
+        repo.write("b.txt", "untouched\n");
+        repo.commit("initial");
+        repo.write("a.txt", "new line\n");
+        repo.write("b.txt", "changed\n");
+
+        let diff = strip_colors(&wait_ok(
+            repo.actions.current_diff_all(&DiffOptions::default()),
+        ));
+        assert!(diff.contains("-old line"));
+        assert!(diff.contains("+new line"));
+
+        let mut entries = repo.actions.get_current_changed_files().unwrap();
+        for e in &mut entries {
+            e.selected = e.filename == "b.txt";
+        }
+        let diff = strip_colors(&wait_ok(
+            repo.actions
+                .current_diff_selected(&entries, &DiffOptions::default()),
+        ));
+        assert!(diff.contains("+changed"));
+        assert!(!diff.contains("+new line"));
+    }
 }
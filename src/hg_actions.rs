@@ -1,7 +1,20 @@
+use std::{fs, io, path::Path};
+
 use crate::{
-    action::{parallel, serial, task_vec, ActionTask},
+    action::{
+        background, keeping, parallel, ready, serial, task_vec, then,
+        ActionResult, ActionTask, BOOKMARK_SUFFIX,
+    },
     select::{Entry, State},
-    version_control_actions::{handle_command, task, VersionControlActions},
+    temp_file::TempFile,
+    theme,
+    vcs_error::VcsError,
+    version_control_actions::{
+        branch_entries, revision_error, task, CommitOptions, DateFormat,
+        DiffOptions, HistoryStep, Phase, RemoteOperation, StashInclude,
+        VersionControlActions, KEEP_MESSAGE_EDITOR, MERGE_NOT_UNDONE,
+        NO_COMMIT_TO_UNDO, NO_OPERATION_TO_ABORT,
+    },
 };
 
 fn str_to_state(s: &str) -> State {
@@ -17,24 +30,202 @@ fn str_to_state(s: &str) -> State {
     }
 }
 
+const NO_GPG_SIGNING: &str = "hg can not gpg sign commits. turn off the gpg_sign option or toggle it with 'vg'";
+const NO_USERNAME: &str =
+    "signing off needs a name. set username in the [ui] section of the hgrc";
+
+const ABSORB_MISSING: &str =
+    "this hg has no absorb extension. it comes with mercurial 4.8 and later";
+
+fn diff_args(options: &DiffOptions) -> Vec<String> {
+    let mut args = vec!["--unified".into(), options.context.to_string()];
+    if options.ignore_whitespace {
+        args.push("--ignore-all-space".into());
+    }
+    // only shows the renames and copies hg recorded, so there is no threshold
+    if options.detect_moves {
+        args.push("--git".into());
+    }
+    args
+}
+
+/// `hgrc` with `name = url` in its `[paths]`, which is added when missing
+fn with_path(hgrc: &str, name: &str, url: &str) -> String {
+    let path = format!("{} = {}\n", name, url);
+    let mut edited = String::new();
+    let mut added = false;
+    for line in hgrc.split_inclusive('\n') {
+        edited.push_str(line);
+        if !added && line.trim() == "[paths]" {
+            if !line.ends_with('\n') {
+                edited.push('\n');
+            }
+            edited.push_str(&path);
+            added = true;
+        }
+    }
+    if !added {
+        if !edited.is_empty() && !edited.ends_with('\n') {
+            edited.push('\n');
+        }
+        edited.push_str("[paths]\n");
+        edited.push_str(&path);
+    }
+    edited
+}
+
+/// `hgrc` without the path `name` and its sub-options like `name:pushurl`
+fn without_path(hgrc: &str, name: &str) -> String {
+    let mut in_paths = false;
+    hgrc.split_inclusive('\n')
+        .filter(|line| {
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') {
+                in_paths = trimmed == "[paths]";
+                return true;
+            }
+            let key = trimmed.split('=').next().unwrap_or("").trim();
+            let key = key.split(':').next().unwrap_or("");
+            !(in_paths && key == name)
+        })
+        .collect()
+}
+
+#[derive(Clone)]
 pub struct HgActions {
     pub current_dir: String,
+    pub executable_path: String,
+    pub date_format: DateFormat,
+}
+
+impl HgActions {
+    /// Rewrites the repository's hgrc with `edit`, which errors to leave it
+    /// as it was
+    fn edit_hgrc<F>(&self, edit: F) -> Box<dyn ActionTask>
+    where
+        F: 'static + Send + FnOnce(&str) -> Result<String, String>,
+    {
+        let path = Path::new(&self.current_dir).join(".hg").join("hgrc");
+        background(move || {
+            let hgrc = match fs::read_to_string(&path) {
+                Ok(hgrc) => hgrc,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                    String::new()
+                }
+                Err(error) => {
+                    return ActionResult::from_err(format!(
+                        "{}: {}",
+                        path.display(),
+                        error
+                    ))
+                }
+            };
+            let edited = match edit(&hgrc) {
+                Ok(edited) => edited,
+                Err(error) => return ActionResult::from_err(error),
+            };
+            match fs::write(&path, edited) {
+                Ok(()) => {
+                    ActionResult::from_ok(format!("{} changed", path.display()))
+                }
+                Err(error) => ActionResult::from_err(format!(
+                    "{}: {}",
+                    path.display(),
+                    error
+                )),
+            }
+        })
+    }
+
+    /// Runs `commit` with `message`, given a `Signed-off-by` trailer when
+    /// signing off since hg has no option for it. Errors when asked to gpg
+    /// sign
+    fn commit_with_message<F>(
+        &self,
+        message: &str,
+        options: &CommitOptions,
+        commit: F,
+    ) -> Box<dyn ActionTask>
+    where
+        F: 'static + Send + FnOnce(&HgActions, String) -> Box<dyn ActionTask>,
+    {
+        if options.gpg_sign {
+            return ready(ActionResult::from_err(NO_GPG_SIGNING.into()));
+        }
+        if !options.sign_off {
+            return commit(self, message.into());
+        }
+        let actions = self.clone();
+        let message = message.to_string();
+        let username = task(self, |command| {
+            command.arg("config").arg("ui.username");
+        });
+        then(username, move |result| {
+            let username = if result.success {
+                result.output.trim()
+            } else {
+                ""
+            };
+            match signed_off(&message, username) {
+                Ok(message) => commit(&actions, message),
+                Err(error) => ready(ActionResult::from_err(error)),
+            }
+        })
+    }
+}
+
+/// `message` with a `Signed-off-by` trailer for `username` unless it has one
+fn signed_off(message: &str, username: &str) -> Result<String, String> {
+    if username.is_empty() {
+        return Err(NO_USERNAME.into());
+    }
+    let trailer = format!("Signed-off-by: {}", username);
+    if message.lines().any(|line| line == trailer) {
+        Ok(message.into())
+    } else {
+        Ok(format!("{}\n\n{}", message, trailer))
+    }
 }
 
 impl<'a> VersionControlActions for HgActions {
-    fn executable_name(&self) -> &'static str {
+    fn name(&self) -> &'static str {
         "hg"
     }
 
+    fn executable_path(&self) -> &str {
+        &self.executable_path[..]
+    }
+
     fn current_dir(&self) -> &str {
         &self.current_dir[..]
     }
 
-    fn set_root(&mut self) -> Result<(), String> {
-        let mut command = self.command();
-        let dir = handle_command(command.arg("root"))?;
+    fn global_args(&self) -> Vec<String> {
+        let theme = theme::current();
+        let colors = match &theme.vcs_colors {
+            Some(colors) => colors,
+            None => return Vec::new(),
+        };
+
+        let mut args = Vec::new();
+        for (key, color) in &[
+            ("color.diff.inserted", colors.added),
+            ("color.diff.deleted", colors.removed),
+            ("color.status.added", colors.added),
+            ("color.status.removed", colors.removed),
+            ("color.status.unknown", colors.removed),
+        ] {
+            args.push("--config".into());
+            args.push(format!("{}={}", key, color));
+        }
+        args
+    }
+
+    fn set_root(&mut self) -> Result<(), VcsError> {
+        let output = self.run(&["root"])?;
 
-        let dir = dir
+        let dir = output
+            .stdout
             .lines()
             .next()
             .expect("root directory is an empty string");
@@ -47,10 +238,36 @@ impl<'a> VersionControlActions for HgActions {
         &self.current_dir[..]
     }
 
-    fn get_current_changed_files(&self) -> Result<Vec<Entry>, String> {
-        let output = handle_command(self.command().arg("status"))?;
+    fn current_branch(&self) -> Result<String, VcsError> {
+        let output = self.run(&["branch"])?;
+        Ok(output.stdout.trim().into())
+    }
+
+    fn upstream_branch(&self) -> Result<String, VcsError> {
+        Err(VcsError::NotFound(
+            "mercurial branches do not track an upstream".into(),
+        ))
+    }
+
+    fn operation_in_progress(&self) -> Option<&'static str> {
+        let hg_dir = Path::new(&self.current_dir).join(".hg");
+        [
+            ("rebasestate", "rebase"),
+            ("histedit-state", "histedit"),
+            ("graftstate", "graft"),
+            ("merge/state", "merge"),
+            ("bisect.state", "bisect"),
+        ]
+        .iter()
+        .find(|(marker, _)| hg_dir.join(marker).exists())
+        .map(|(_, operation)| *operation)
+    }
+
+    fn get_current_changed_files(&self) -> Result<Vec<Entry>, VcsError> {
+        let output = self.run(&["status"])?;
 
         let files = output
+            .stdout
             .trim()
             .split('\n')
             .map(|e| e.trim())
@@ -61,6 +278,7 @@ impl<'a> VersionControlActions for HgActions {
                     filename: String::from(filename.trim()),
                     selected: false,
                     state: str_to_state(state),
+                    columns: None,
                 }
             })
             .collect();
@@ -70,12 +288,11 @@ impl<'a> VersionControlActions for HgActions {
     fn get_revision_changed_files(
         &self,
         target: &str,
-    ) -> Result<Vec<Entry>, String> {
-        let output = handle_command(
-            self.command().arg("status").arg("--change").arg(target),
-        )?;
+    ) -> Result<Vec<Entry>, VcsError> {
+        let output = self.run(&["status", "--change", target])?;
 
         let files = output
+            .stdout
             .trim()
             .split('\n')
             .map(|e| e.trim())
@@ -86,14 +303,48 @@ impl<'a> VersionControlActions for HgActions {
                     filename: String::from(filename.trim()),
                     selected: false,
                     state: str_to_state(state),
+                    columns: None,
                 }
             })
             .collect();
         Ok(files)
     }
 
-    fn version(&self) -> Result<String, String> {
-        handle_command(self.command().arg("--version"))
+    fn get_changed_files_since(
+        &self,
+        target: &str,
+    ) -> Result<Vec<Entry>, VcsError> {
+        let output = self.run(&["status", "--rev", target])?;
+
+        let files = output
+            .stdout
+            .trim()
+            .split('\n')
+            .map(|e| e.trim())
+            .filter(|e| e.len() > 1)
+            .map(|e| {
+                let (state, filename) = e.split_at(1);
+                Entry {
+                    filename: String::from(filename.trim()),
+                    selected: false,
+                    state: str_to_state(state),
+                    columns: None,
+                }
+            })
+            .collect();
+        Ok(files)
+    }
+
+    fn has_staging_area(&self) -> bool {
+        false
+    }
+
+    fn has_diff_algorithms(&self) -> bool {
+        false
+    }
+
+    fn version(&self) -> Result<String, VcsError> {
+        self.run(&["--version"]).map(|o| o.stdout)
     }
 
     fn status(&self) -> Box<dyn ActionTask> {
@@ -107,50 +358,105 @@ impl<'a> VersionControlActions for HgActions {
         parallel(tasks)
     }
 
-    fn current_export(&self) -> Box<dyn ActionTask> {
+    /// export has no diff options
+    fn current_export(&self, _options: &DiffOptions) -> Box<dyn ActionTask> {
         task(self, |command| {
             command.args(&["export", "--color", "always"]);
         })
     }
 
-    fn log(&self, count: usize) -> Box<dyn ActionTask> {
+    /// Signatures are left out as checking them needs the gpg extension
+    fn log(
+        &self,
+        skip: usize,
+        count: usize,
+        _signatures: bool,
+        graph: bool,
+    ) -> Box<dyn ActionTask> {
         task(self, |command| {
             let count_str = format!("{}", count);
-            let template = "\x1e{node|short}\x1e{date|shortdate}\x1e{author|person}\x1e{ifeq(phase,'secret','(secret) ','')}{ifeq(phase,'draft','(draft) ','')}{if(topics,'[{topics}] ')}{tags % '{tag} '}{branch}\x1e{desc|firstline|strip}";
+            let mut template = format!("\x1e{{node|short}}\x1e{}\x1e{{author|person}}\x1e{{ifeq(phase,'secret','(secret) ','')}}{{ifeq(phase,'draft','(draft) ','')}}{{if(topics,'[{{topics}}] ')}}{{tags % '{{tag}} '}}{{branch}}\x1e{{desc|firstline|strip}}", self.date_format.hg_template());
+            command.arg("log");
+            if graph {
+                command
+                    .arg("--config")
+                    .arg("experimental.graphshorten=True")
+                    .arg("--graph");
+            } else {
+                // the graph ends each revision's line by itself
+                template.push('\n');
+            }
             command
-                .arg("log")
-                .arg("--config")
-                .arg("experimental.graphshorten=True")
-                .arg("--graph")
                 .arg("--template")
-                .arg(template)
+                .arg(&template)
                 .arg("-l")
                 .arg(&count_str);
+            if skip > 0 {
+                command
+                    .arg("--rev")
+                    .arg(format!("limit(reverse(all()), {}, {})", count, skip));
+            }
         })
     }
 
-    fn current_diff_all(&self) -> Box<dyn ActionTask> {
+    fn current_diff_all(&self, options: &DiffOptions) -> Box<dyn ActionTask> {
         task(self, |command| {
             command.arg("diff").arg("--color").arg("always");
+            command.args(diff_args(options));
         })
     }
 
     fn current_diff_selected(
         &self,
         entries: &Vec<Entry>,
+        options: &DiffOptions,
     ) -> Box<dyn ActionTask> {
         task(self, |command| {
-            command.arg("diff").arg("--color").arg("always").arg("--");
+            command.arg("diff").arg("--color").arg("always");
+            command.args(diff_args(options)).arg("--");
             for e in entries.iter().filter(|e| e.selected) {
                 command.arg(&e.filename);
             }
         })
     }
 
-    fn revision_changes(&self, target: &str) -> Box<dyn ActionTask> {
+    fn diff_file(
+        &self,
+        file: &str,
+        options: &DiffOptions,
+    ) -> Result<String, VcsError> {
+        let diff_args = diff_args(options);
+        let mut args = vec!["diff", "--color", "always"];
+        args.extend(diff_args.iter().map(String::as_str));
+        args.extend(["--", file]);
+        Ok(self.run(&args)?.stdout)
+    }
+
+    fn revision_details(&self, target: &str) -> Result<String, VcsError> {
+        let template = format!(
+            "revision {{node}}\nauthor {{author}}\ndate {}\n\n{{desc}}\n\n{{file_mods % 'M\t{{file}}\n'}}{{file_adds % 'A\t{{file}}\n'}}{{file_dels % 'D\t{{file}}\n'}}",
+            self.date_format.hg_template()
+        );
+        let output = self
+            .run(&["log", "--rev", target, "--template", &template])
+            .map_err(|error| revision_error(target, error))?;
+        Ok(output.stdout)
+    }
+
+    /// status cannot ignore whitespace, so a diff stat is shown instead
+    /// while ignoring it
+    fn revision_changes(
+        &self,
+        target: &str,
+        options: &DiffOptions,
+    ) -> Box<dyn ActionTask> {
         task(self, |command| {
+            if options.ignore_whitespace {
+                command.arg("diff").arg("--stat").arg("--ignore-all-space");
+            } else {
+                command.arg("status");
+            }
             command
-                .arg("status")
                 .arg("--change")
                 .arg(target)
                 .arg("--color")
@@ -158,14 +464,19 @@ impl<'a> VersionControlActions for HgActions {
         })
     }
 
-    fn revision_diff_all(&self, target: &str) -> Box<dyn ActionTask> {
+    fn revision_diff_all(
+        &self,
+        target: &str,
+        options: &DiffOptions,
+    ) -> Box<dyn ActionTask> {
         task(self, |command| {
             command
                 .arg("diff")
                 .arg("--change")
                 .arg(target)
                 .arg("--color")
-                .arg("always");
+                .arg("always")
+                .args(diff_args(options));
         })
     }
 
@@ -173,6 +484,7 @@ impl<'a> VersionControlActions for HgActions {
         &self,
         target: &str,
         entries: &Vec<Entry>,
+        options: &DiffOptions,
     ) -> Box<dyn ActionTask> {
         task(self, |command| {
             command
@@ -181,6 +493,7 @@ impl<'a> VersionControlActions for HgActions {
                 .arg(target)
                 .arg("--color")
                 .arg("always")
+                .args(diff_args(options))
                 .arg("--");
 
             for e in entries.iter().filter(|e| e.selected) {
@@ -189,15 +502,21 @@ impl<'a> VersionControlActions for HgActions {
         })
     }
 
-    fn commit_all(&self, message: &str) -> Box<dyn ActionTask> {
-        task(self, |command| {
-            command
-                .arg("commit")
-                .arg("--addremove")
-                .arg("-m")
-                .arg(message)
-                .arg("--color")
-                .arg("always");
+    fn commit_all(
+        &self,
+        message: &str,
+        options: &CommitOptions,
+    ) -> Box<dyn ActionTask> {
+        self.commit_with_message(message, options, |actions, message| {
+            task(actions, |command| {
+                command
+                    .arg("commit")
+                    .arg("--addremove")
+                    .arg("-m")
+                    .arg(&message)
+                    .arg("--color")
+                    .arg("always");
+            })
         })
     }
 
@@ -205,35 +524,65 @@ impl<'a> VersionControlActions for HgActions {
         &self,
         message: &str,
         entries: &Vec<Entry>,
+        options: &CommitOptions,
     ) -> Box<dyn ActionTask> {
-        let mut tasks = task_vec();
-        let mut files_to_commit = Vec::new();
-        for e in entries.iter().filter(|e| e.selected) {
-            match e.state {
-                State::Missing | State::Deleted => {
-                    tasks.push(task(self, |command| {
-                        command.arg("remove").arg(&e.filename);
-                    }))
+        let entries: Vec<_> =
+            entries.iter().filter(|e| e.selected).cloned().collect();
+        self.commit_with_message(message, options, move |actions, message| {
+            let mut tasks = task_vec();
+            for e in &entries {
+                match e.state {
+                    State::Missing | State::Deleted => {
+                        tasks.push(task(actions, |command| {
+                            command.arg("remove").arg(&e.filename);
+                        }))
+                    }
+                    State::Untracked => tasks.push(task(actions, |command| {
+                        command.arg("add").arg(&e.filename);
+                    })),
+                    _ => (),
                 }
-                State::Untracked => tasks.push(task(self, |command| {
-                    command.arg("add").arg(&e.filename);
-                })),
-                _ => (),
             }
-            files_to_commit.push(&e.filename);
-        }
-        tasks.push(task(self, |command| {
-            command
-                .arg("commit")
-                .arg("-m")
-                .arg(message)
-                .arg("--color")
-                .arg("always");
-            for file in files_to_commit {
-                command.arg(file);
+            tasks.push(task(actions, |command| {
+                command
+                    .arg("commit")
+                    .arg("-m")
+                    .arg(&message)
+                    .arg("--color")
+                    .arg("always");
+                for e in &entries {
+                    command.arg(&e.filename);
+                }
+            }));
+            serial(tasks)
+        })
+    }
+
+    /// Needs the uncommit extension that comes with hg, which also keeps
+    /// public revisions from being undone
+    fn undo_last_commit(&self) -> Box<dyn ActionTask> {
+        let parents = match self.run(&[
+            "log",
+            "-r",
+            ".",
+            "--template",
+            "{rev} {p2rev}",
+        ]) {
+            Ok(output) => output.stdout,
+            Err(error) => {
+                return ready(ActionResult::from_err(error.to_string()))
             }
-        }));
-        serial(tasks)
+        };
+        let mut revs = parents.split_whitespace();
+        if revs.next() == Some("-1") {
+            return ready(ActionResult::from_err(NO_COMMIT_TO_UNDO.into()));
+        }
+        if revs.next() != Some("-1") {
+            return ready(ActionResult::from_err(MERGE_NOT_UNDONE.into()));
+        }
+        task(self, |command| {
+            command.args(["--config", "extensions.uncommit=", "uncommit"]);
+        })
     }
 
     fn revert_all(&self) -> Box<dyn ActionTask> {
@@ -269,6 +618,21 @@ impl<'a> VersionControlActions for HgActions {
         parallel(tasks)
     }
 
+    /// Without a staging area this always reverts the working copy
+    fn reset_selected(
+        &self,
+        target: &str,
+        entries: &[Entry],
+        _working_tree: bool,
+    ) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args(["revert", "-C", "--rev", target, "--"]);
+            command.args(
+                entries.iter().filter(|e| e.selected).map(|e| &e.filename),
+            );
+        })
+    }
+
     fn update(&self, target: &str) -> Box<dyn ActionTask> {
         task(self, |command| {
             command.arg("update").arg(target);
@@ -281,6 +645,75 @@ impl<'a> VersionControlActions for HgActions {
         })
     }
 
+    fn cherry_pick(&self, target: &str) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args(["graft", "--rev"]).arg(target);
+        })
+    }
+
+    fn continue_cherry_pick(&self) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args(["graft", "--continue"]);
+        })
+    }
+
+    fn abort_cherry_pick(&self) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args(["graft", "--abort"]);
+        })
+    }
+
+    fn rebase(&self, onto: &str) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args(["--config", "extensions.rebase=", "rebase", "-d"]);
+            command.arg(onto);
+        })
+    }
+
+    fn continue_rebase(&self) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args([
+                "--config",
+                "extensions.rebase=",
+                "rebase",
+                "--continue",
+            ]);
+        })
+    }
+
+    fn abort_rebase(&self) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args([
+                "--config",
+                "extensions.rebase=",
+                "rebase",
+                "--abort",
+            ]);
+        })
+    }
+
+    /// A merge has no abort in hg, so it is undone by updating back to the
+    /// first parent and throwing away the changes
+    fn abort_operation(&self) -> Box<dyn ActionTask> {
+        let args: &[&str] = match self.operation_in_progress() {
+            Some("rebase") => {
+                &["--config", "extensions.rebase=", "rebase", "--abort"]
+            }
+            Some("histedit") => return self.abort_history_edit(),
+            Some("graft") => &["graft", "--abort"],
+            Some("merge") => &["update", "--clean", "."],
+            Some("bisect") => &["bisect", "--reset"],
+            _ => {
+                return ready(ActionResult::from_err(
+                    NO_OPERATION_TO_ABORT.into(),
+                ))
+            }
+        };
+        task(self, |command| {
+            command.args(args);
+        })
+    }
+
     fn conflicts(&self) -> Box<dyn ActionTask> {
         task(self, |command| {
             command.args(&["resolve", "-l", "--color", "always"]);
@@ -299,19 +732,74 @@ impl<'a> VersionControlActions for HgActions {
         })
     }
 
-    fn fetch(&self) -> Box<dyn ActionTask> {
-        self.pull()
+    fn get_remotes(&self) -> Result<Vec<Entry>, VcsError> {
+        let output = self.run(&["paths", "--quiet"])?;
+        Ok(branch_entries(
+            output
+                .stdout
+                .lines()
+                .filter(|name| !name.is_empty())
+                .map(|name| (if name == "default" { "*" } else { " " }, name)),
+        ))
+    }
+
+    fn list_remotes(&self) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.arg("paths");
+        })
+    }
+
+    /// hg has no command to change its paths, so they are written to the
+    /// repository's hgrc
+    fn add_remote(&self, name: &str, url: &str) -> Box<dyn ActionTask> {
+        let (name, url) = (name.to_string(), url.to_string());
+        self.edit_hgrc(move |hgrc| Ok(with_path(hgrc, &name, &url)))
     }
 
-    fn pull(&self) -> Box<dyn ActionTask> {
+    fn remove_remote(&self, name: &str) -> Box<dyn ActionTask> {
+        let name = name.to_string();
+        self.edit_hgrc(move |hgrc| {
+            let edited = without_path(hgrc, &name);
+            if edited == hgrc {
+                Err(format!("there is no remote called '{}'", name))
+            } else {
+                Ok(edited)
+            }
+        })
+    }
+
+    fn remote_args(
+        &self,
+        operation: RemoteOperation,
+        remote: Option<&str>,
+    ) -> Vec<String> {
+        let mut args = match operation {
+            RemoteOperation::Fetch | RemoteOperation::Pull => {
+                vec!["pull".to_string()]
+            }
+            RemoteOperation::Push => {
+                vec!["push".to_string(), "--new-branch".into()]
+            }
+        };
+        args.extend(remote.map(Into::into));
+        args
+    }
+
+    fn fetch(&self, remote: Option<&str>) -> Box<dyn ActionTask> {
+        self.pull(remote)
+    }
+
+    fn pull(&self, remote: Option<&str>) -> Box<dyn ActionTask> {
+        let args = self.remote_args(RemoteOperation::Pull, remote);
         task(self, |command| {
-            command.arg("pull");
+            command.args(args);
         })
     }
 
-    fn push(&self) -> Box<dyn ActionTask> {
+    fn push(&self, remote: Option<&str>) -> Box<dyn ActionTask> {
+        let args = self.remote_args(RemoteOperation::Push, remote);
         task(self, |command| {
-            command.args(&["push", "--new-branch"]);
+            command.args(args);
         })
     }
 
@@ -321,9 +809,216 @@ impl<'a> VersionControlActions for HgActions {
         })
     }
 
-    fn list_branches(&self) -> Box<dyn ActionTask> {
+    fn list_tags(&self) -> Result<Vec<Entry>, VcsError> {
+        let output = self.run(&["tags", "--quiet"])?;
+        Ok(branch_entries(
+            output
+                .stdout
+                .lines()
+                .filter(|name| !name.is_empty() && *name != "tip")
+                .map(|name| (" ", name)),
+        ))
+    }
+
+    /// Removing a tag is committed like adding it
+    fn delete_tag(
+        &self,
+        name: &str,
+        _remote: Option<&str>,
+    ) -> Box<dyn ActionTask> {
         task(self, |command| {
+            command.args(["tag", "--remove", name]);
+        })
+    }
+
+    fn has_bookmarks(&self) -> bool {
+        true
+    }
+
+    fn list_branches(&self) -> Box<dyn ActionTask> {
+        let mut tasks = task_vec();
+        tasks.push(task(self, |command| {
             command.args(&["branches", "--template", "{branch}\n"]);
+        }));
+        tasks.push(task(self, |command| {
+            let template = format!("{{bookmark}}{}\n", BOOKMARK_SUFFIX);
+            command.args(["bookmarks", "--template", &template]);
+        }));
+        parallel(tasks)
+    }
+
+    /// Bookmarks come after the branches and updating to one activates it
+    fn get_branches_for_checkout(&self) -> Result<Vec<Entry>, VcsError> {
+        let current = self.run(&["branch"])?;
+        let current = current.stdout.trim();
+        let branches = self.run(&["branches", "--template", "{branch}\n"])?;
+        let bookmarks = self.run(&[
+            "bookmarks",
+            "--template",
+            "{if(active, '*', ' ')}{bookmark}\n",
+        ])?;
+        let bookmark_active =
+            bookmarks.stdout.lines().any(|line| line.starts_with('*'));
+        let branches = branches.stdout.lines().map(|name| {
+            // an active bookmark is current instead of its branch
+            let head = if name == current && !bookmark_active {
+                "*"
+            } else {
+                " "
+            };
+            (head, name)
+        });
+        let bookmarks = bookmarks
+            .stdout
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.split_at(1));
+        Ok(branch_entries(branches.chain(bookmarks)))
+    }
+
+    fn has_phases(&self) -> bool {
+        true
+    }
+
+    fn show_phase(&self, target: &str) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args(["phase", "-r", target]);
+        })
+    }
+
+    fn set_phase(&self, target: &str, phase: Phase) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.arg("phase").arg(format!("--{}", phase.name()));
+            if phase != Phase::Public {
+                command.arg("--force");
+            }
+            command.args(["-r", target]);
+        })
+    }
+
+    fn has_history_editing(&self) -> bool {
+        true
+    }
+
+    fn get_revisions_to_edit(
+        &self,
+        base: &str,
+    ) -> Result<Vec<(String, Entry)>, VcsError> {
+        let revisions = format!("{}::.", base);
+        let output = self.run(&[
+            "log",
+            "-r",
+            &revisions,
+            "--template",
+            "{node}\x1e{node|short} {desc|firstline}\n",
+        ])?;
+        Ok(output
+            .stdout
+            .lines()
+            .filter_map(|line| line.split_once('\x1e'))
+            .map(|(hash, name)| {
+                let entry = Entry {
+                    filename: name.into(),
+                    selected: false,
+                    state: State::Clean,
+                    columns: None,
+                };
+                (hash.into(), entry)
+            })
+            .collect())
+    }
+
+    fn edit_history(
+        &self,
+        base: &str,
+        steps: &[(HistoryStep, String)],
+    ) -> Box<dyn ActionTask> {
+        let rules: String = steps
+            .iter()
+            .map(|(step, hash)| format!("{} {}\n", step.name(), hash))
+            .collect();
+        let rules = match TempFile::create("histedit", &rules) {
+            Ok(rules) => rules,
+            Err(error) => {
+                return ready(ActionResult::from_err(error.to_string()))
+            }
+        };
+        let edit = task(self, |command| {
+            command
+                .args(["--config", "extensions.histedit="])
+                .arg("histedit")
+                .arg("--commands")
+                .arg(rules.path())
+                .arg(base);
+            command.env("HGEDITOR", KEEP_MESSAGE_EDITOR);
+        });
+        keeping(rules, edit)
+    }
+
+    fn continue_history_edit(&self) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args([
+                "--config",
+                "extensions.histedit=",
+                "histedit",
+                "--continue",
+            ]);
+            command.env("HGEDITOR", KEEP_MESSAGE_EDITOR);
+        })
+    }
+
+    fn abort_history_edit(&self) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args([
+                "--config",
+                "extensions.histedit=",
+                "histedit",
+                "--abort",
+            ]);
+        })
+    }
+
+    fn has_absorb(&self) -> bool {
+        true
+    }
+
+    fn absorb_preview(&self) -> Result<String, VcsError> {
+        let output = self
+            .run(&["--config", "extensions.absorb=", "absorb", "--dry-run"])
+            .map_err(|error| {
+                if error.to_string().contains("unknown command") {
+                    VcsError::CommandFailed {
+                        stderr: ABSORB_MISSING.into(),
+                        code: None,
+                    }
+                } else {
+                    error
+                }
+            })?;
+        Ok(output.stdout)
+    }
+
+    fn absorb(&self) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            // it would ask first otherwise, with no terminal to answer from
+            command.args([
+                "--config",
+                "extensions.absorb=",
+                "absorb",
+                "--apply-changes",
+            ]);
+        })
+    }
+
+    fn create_bookmark(&self, name: &str) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.arg("bookmark").arg(name);
+        })
+    }
+
+    fn delete_bookmark(&self, name: &str) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command.args(["bookmark", "--delete"]).arg(name);
         })
     }
 
@@ -334,8 +1029,7 @@ impl<'a> VersionControlActions for HgActions {
     }
 
     fn close_branch(&self, name: &str) -> Box<dyn ActionTask> {
-        let changeset =
-            handle_command(self.command().args(&["identify", "--num"])).ok();
+        let changeset = self.run(&["identify", "--num"]).ok();
 
         let mut tasks = task_vec();
         tasks.push(self.update(name));
@@ -348,8 +1042,223 @@ impl<'a> VersionControlActions for HgActions {
             ]);
         }));
         if let Some(changeset) = changeset {
-            tasks.push(self.update(changeset.trim()));
+            tasks.push(self.update(changeset.stdout.trim()));
         }
         serial(tasks)
     }
+
+    fn delete_branch(
+        &self,
+        _name: &str,
+        _force: bool,
+    ) -> Result<String, VcsError> {
+        Err(VcsError::CommandFailed {
+            stderr:
+                "mercurial branches can not be deleted, close them with 'bd'"
+                    .into(),
+            code: None,
+        })
+    }
+
+    fn list_merged_branches(&self, base: &str) -> Box<dyn ActionTask> {
+        let mut tasks = task_vec();
+        for (revset, status) in &[
+            ("head() and not closed() and ancestors({})", "merged"),
+            ("head() and not closed() and not ancestors({})", "unmerged"),
+        ] {
+            let template = format!(
+                "{}\x1e{{branch}}\x1e{{ifcontains(rev, revset('.'), '*')}}\n",
+                status
+            );
+            tasks.push(task(self, |command| {
+                command
+                    .arg("log")
+                    .arg("-r")
+                    .arg(revset.replace("{}", &format!("'{}'", base)))
+                    .arg("--template")
+                    .arg(&template);
+            }));
+        }
+        parallel(tasks)
+    }
+
+    fn delete_branches(&self, names: &[String]) -> Box<dyn ActionTask> {
+        let mut tasks = task_vec();
+        for name in names {
+            tasks.push(self.close_branch(name));
+        }
+        serial(tasks)
+    }
+
+    fn stash_files(
+        &self,
+        include: StashInclude,
+    ) -> Result<Vec<String>, VcsError> {
+        // shelve has no way of storing ignored files
+        let states = match include {
+            StashInclude::Tracked => "-mard",
+            StashInclude::Untracked | StashInclude::All => "-mardu",
+        };
+        let output = self.run(&["status", "--no-status", states])?;
+        Ok(output.stdout.lines().map(Into::into).collect())
+    }
+
+    fn stash(
+        &self,
+        message: &str,
+        include: StashInclude,
+    ) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command
+                .args(["--config", "extensions.shelve="])
+                .arg("shelve");
+            if include != StashInclude::Tracked {
+                command.arg("--unknown");
+            }
+            if !message.is_empty() {
+                command.arg("--message").arg(message);
+            }
+        })
+    }
+
+    /// annotate can not restrict itself to a range of lines, so `lines` is
+    /// ignored
+    fn blame(
+        &self,
+        target: Option<&str>,
+        file: &str,
+        _lines: Option<(usize, usize)>,
+        _ignore_revs: bool,
+    ) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            let template = format!(
+                "{{lines % '{{node|short}} ({{user|person}} {} {{lineno}}) {{line}}'}}",
+                self.date_format.hg_template()
+            );
+            command.args(["annotate", "--template", &template]);
+            if let Some(target) = target {
+                command.args(["--rev", target]);
+            }
+            command.arg("--").arg(file);
+        })
+    }
+
+    fn list_stashes(&self) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command
+                .args(["--config", "extensions.shelve="])
+                .args(["shelve", "--list"]);
+        })
+    }
+
+    fn stash_pop(&self, name: &str) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command
+                .args(["--config", "extensions.shelve="])
+                .arg("unshelve");
+            if !name.is_empty() {
+                command.arg(name);
+            }
+        })
+    }
+
+    /// shelve has no diff options
+    fn stash_show(
+        &self,
+        name: &str,
+        _options: &DiffOptions,
+    ) -> Box<dyn ActionTask> {
+        task(self, |command| {
+            command
+                .args(["--config", "extensions.shelve="])
+                .args(["shelve", "--patch", name])
+                .args(["--color", "always"]);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::Poll;
+
+    use super::*;
+    use crate::async_process::Executor;
+
+    #[test]
+    fn paths_are_edited_in_their_section() {
+        let hgrc = "[ui]\nusername = me\n[paths]\ndefault = https://a\n";
+        let added = with_path(hgrc, "upstream", "https://b");
+        assert_eq!(
+            added,
+            "[ui]\nusername = me\n[paths]\nupstream = https://b\ndefault = https://a\n"
+        );
+        assert_eq!(with_path("", "default", "x"), "[paths]\ndefault = x\n");
+
+        let hgrc =
+            "[paths]\nfork = x\nfork:pushurl = y\nforked = z\n[ui]\nfork = w\n";
+        assert_eq!(
+            without_path(hgrc, "fork"),
+            "[paths]\nforked = z\n[ui]\nfork = w\n"
+        );
+    }
+
+    #[test]
+    fn gpg_signing_is_refused() {
+        let actions = HgActions {
+            current_dir: ".".into(),
+            executable_path: "verco-missing-hg".into(),
+            date_format: DateFormat::default(),
+        };
+        let signed = CommitOptions {
+            sign_off: false,
+            gpg_sign: true,
+        };
+
+        let result = wait(actions.commit_all("fix", &signed));
+
+        assert!(!result.success);
+        assert_eq!(result.output, NO_GPG_SIGNING);
+    }
+
+    #[test]
+    fn sign_off_trailer_is_added_once() {
+        let signed = signed_off("fix", "me").unwrap();
+
+        assert_eq!(signed, "fix\n\nSigned-off-by: me");
+        assert_eq!(signed_off(&signed, "me").unwrap(), signed);
+        assert_eq!(signed_off("fix", "").unwrap_err(), NO_USERNAME);
+    }
+
+    #[test]
+    fn removing_a_missing_remote_leaves_the_hgrc_alone() {
+        let dir = std::env::temp_dir()
+            .join(format!("verco-hg-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join(".hg")).unwrap();
+        let hgrc = "[paths]\ndefault = https://a\n";
+        fs::write(dir.join(".hg").join("hgrc"), hgrc).unwrap();
+        let actions = HgActions {
+            current_dir: dir.to_string_lossy().into_owned(),
+            executable_path: "verco-missing-hg".into(),
+            date_format: DateFormat::default(),
+        };
+
+        let result = wait(actions.remove_remote("fork"));
+        let left = fs::read_to_string(dir.join(".hg").join("hgrc")).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        assert!(!result.success);
+        assert_eq!(result.output, "there is no remote called 'fork'");
+        assert_eq!(left, hgrc);
+    }
+
+    fn wait(mut task: Box<dyn ActionTask>) -> ActionResult {
+        let mut executor = Executor::new(1);
+        loop {
+            match task.poll(&mut executor) {
+                Poll::Ready(result) => return result,
+                Poll::Pending => {
+                    std::thread::sleep(std::time::Duration::from_millis(10))
+                }
+            }
+        }
+    }
 }
@@ -1,27 +1,570 @@
-use std::time::Duration;
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    fs,
+    io::{stdout, Write},
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
 
-use crossterm::event::{self, KeyCode, KeyEvent, KeyModifiers};
-use rustyline::{error::ReadlineError, Editor};
+use crossterm::{
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, KeyCode, KeyEvent,
+        KeyModifiers, MouseButton, MouseEvent,
+    },
+    execute,
+    style::style,
+    terminal,
+};
+use rustyline::{
+    completion::{Completer, Pair},
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    validate::Validator,
+    Cmd, Context, Editor, Helper, KeyPress,
+};
 
-use crate::tui_util::TerminalSize;
+use crate::{
+    history::History,
+    theme,
+    tui_util::{fit_color, TerminalSize},
+};
 
 pub enum Event {
     None,
     Resize(TerminalSize),
     Key(KeyEvent),
+    /// The left button went down on this row of the terminal
+    Click(u16),
+    /// Rows the mouse wheel scrolled, down when positive
+    Scroll(i32),
 }
 
-pub fn poll_event() -> Event {
-    if event::poll(Duration::from_millis(10)).unwrap() {
-        match event::read().unwrap() {
+static MOUSE_CAPTURE: AtomicBool = AtomicBool::new(false);
+
+/// Has the terminal report clicks and the mouse wheel as events, or leaves
+/// them to its own text selection and scrolling again
+pub fn capture_mouse(capture: bool) -> crossterm::Result<()> {
+    if MOUSE_CAPTURE.swap(capture, Ordering::Relaxed) == capture {
+        return Ok(());
+    }
+    report_mouse(capture)
+}
+
+/// Stops the reports while the line editor or another program has the
+/// terminal, and resumes them after, when the mouse is captured at all
+pub fn pause_mouse_capture(pause: bool) -> crossterm::Result<()> {
+    if MOUSE_CAPTURE.load(Ordering::Relaxed) {
+        report_mouse(!pause)
+    } else {
+        Ok(())
+    }
+}
+
+fn report_mouse(report: bool) -> crossterm::Result<()> {
+    if report {
+        execute!(stdout(), EnableMouseCapture)
+    } else {
+        execute!(stdout(), DisableMouseCapture)
+    }
+}
+
+/// What the line editor offers to complete when tab is pressed
+#[derive(Clone, Copy)]
+pub enum Completion<'a> {
+    /// Revisions, branch names and free text
+    None,
+    /// Files and directories relative to the given repository root
+    Path(&'a str),
+    /// Nothing, but alt+e on the empty line asks for the answer to be
+    /// written in an editor instead so it can span several lines
+    Message,
+}
+
+/// Where the ui reads its keys and lines from
+pub trait Input {
+    /// Returns `Event::None` if nothing happened for a short while
+    fn poll_event(&mut self) -> crossterm::Result<Event>;
+    /// Blocks until something happens
+    fn read_event(&mut self) -> crossterm::Result<Event>;
+    /// Returns `None` when editing was interrupted. With `Completion::Message`
+    /// `Err(ReadlineError::Eof)` asks for the answer to be written in an
+    /// editor
+    fn read_line(
+        &mut self,
+        prompt: &str,
+        initial: &str,
+        completion: Completion,
+    ) -> Result<Option<String>, ReadlineError>;
+
+    /// Whether the terminal has to be put in raw mode to read from this input
+    fn uses_terminal(&self) -> bool {
+        true
+    }
+
+    /// Blocks until a key is pressed
+    fn read_key(&mut self) -> crossterm::Result<KeyEvent> {
+        loop {
+            if let Event::Key(key) = self.read_event()? {
+                return Ok(key);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TerminalInput {
+    history: History,
+}
+
+impl TerminalInput {
+    /// Offers the lines typed before at a prompt when pressing up
+    pub fn with_history(history: History) -> Self {
+        Self { history }
+    }
+
+    fn convert_event(event: event::Event) -> Event {
+        match event {
             event::Event::Resize(width, height) => {
                 Event::Resize(TerminalSize { width, height })
             }
             event::Event::Key(key) => Event::Key(key),
+            event::Event::Mouse(MouseEvent::Down(
+                MouseButton::Left,
+                _,
+                row,
+                _,
+            )) => Event::Click(row),
+            event::Event::Mouse(MouseEvent::ScrollDown(..)) => Event::Scroll(1),
+            event::Event::Mouse(MouseEvent::ScrollUp(..)) => Event::Scroll(-1),
             _ => Event::None,
         }
+    }
+}
+
+impl Input for TerminalInput {
+    fn poll_event(&mut self) -> crossterm::Result<Event> {
+        if event::poll(Duration::from_millis(10))? {
+            Ok(Self::convert_event(event::read()?))
+        } else {
+            Ok(Event::None)
+        }
+    }
+
+    fn read_event(&mut self) -> crossterm::Result<Event> {
+        Ok(Self::convert_event(event::read()?))
+    }
+
+    fn read_line(
+        &mut self,
+        prompt: &str,
+        initial: &str,
+        completion: Completion,
+    ) -> Result<Option<String>, ReadlineError> {
+        // rustyline manages the terminal mode itself while editing
+        terminal::disable_raw_mode().map_err(to_readline_error)?;
+        pause_mouse_capture(true).map_err(to_readline_error)?;
+        let mut readline = Editor::<LineHelper>::new();
+        let root = match completion {
+            Completion::Path(root) => Some(root.into()),
+            _ => None,
+        };
+        readline.set_helper(Some(LineHelper { root }));
+        // ends the input on an empty line, which is the only way to tell the
+        // editor key apart, so ctrl+d cancels like ctrl+c here instead
+        if let Completion::Message = completion {
+            readline.bind_sequence(KeyPress::Meta('e'), Cmd::EndOfFile);
+            readline.bind_sequence(KeyPress::Meta('E'), Cmd::EndOfFile);
+            readline.bind_sequence(KeyPress::Ctrl('D'), Cmd::Interrupt);
+        }
+        for entry in self.history.entries(prompt) {
+            readline.add_history_entry(entry);
+        }
+        let result = match readline.readline_with_initial(prompt, (initial, ""))
+        {
+            Ok(line) => Ok(Some(line)),
+            Err(ReadlineError::Eof)
+                if matches!(completion, Completion::Message) =>
+            {
+                Err(ReadlineError::Eof)
+            }
+            Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => {
+                Ok(None)
+            }
+            Err(error) => Err(error),
+        };
+        pause_mouse_capture(false).map_err(to_readline_error)?;
+        terminal::enable_raw_mode().map_err(to_readline_error)?;
+        if let Ok(Some(line)) = &result {
+            // losing the history is not worth interrupting anything for
+            let _ = self.history.add(prompt, line);
+        }
+        result
+    }
+}
+
+/// Colors the prompt and completes paths inside of `root`, if any
+struct LineHelper {
+    root: Option<String>,
+}
+
+impl Completer for LineHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        match &self.root {
+            Some(root) => Ok(complete_path(Path::new(root), &line[..pos])),
+            None => Ok((pos, Vec::new())),
+        }
+    }
+}
+
+impl Hinter for LineHelper {}
+impl Highlighter for LineHelper {
+    /// In the color of entries, like the questions asked outside of the
+    /// line editor
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        _default: bool,
+    ) -> Cow<'b, str> {
+        let color = fit_color(theme::current().entry);
+        Cow::Owned(style(prompt).with(color).to_string())
+    }
+}
+impl Validator for LineHelper {}
+impl Helper for LineHelper {}
+
+/// Lists the entries of the directory `line` points into whose names start
+/// with what comes after its last separator. Directories end with '/' so
+/// completion can continue inside them. Hidden entries are only offered once
+/// a '.' is typed
+fn complete_path(root: &Path, line: &str) -> (usize, Vec<Pair>) {
+    let start = line.rfind(['/', '\\']).map_or(0, |i| i + 1);
+    let (dir, prefix) = line.split_at(start);
+
+    let read_dir = match fs::read_dir(root.join(dir)) {
+        Ok(read_dir) => read_dir,
+        Err(_) => return (start, Vec::new()),
+    };
+
+    let mut candidates: Vec<_> = read_dir
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let mut name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix)
+                || (name.starts_with('.') && !prefix.starts_with('.'))
+            {
+                return None;
+            }
+            if entry.file_type().ok()?.is_dir() {
+                name.push('/');
+            }
+            Some(Pair {
+                display: name.clone(),
+                replacement: name,
+            })
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.display.cmp(&b.display));
+
+    (start, candidates)
+}
+
+fn to_readline_error(error: crossterm::ErrorKind) -> ReadlineError {
+    ReadlineError::Io(std::io::Error::new(
+        std::io::ErrorKind::Other,
+        error.to_string(),
+    ))
+}
+
+pub enum ScriptedEvent {
+    Key(KeyEvent),
+    Line(String),
+    /// Pressing the key that writes a message in an editor
+    Editor,
+    Sleep(Duration),
+    Resize(TerminalSize),
+    Click(u16),
+    Scroll(i32),
+}
+
+/// Replays keys and prompt answers in order. Once exhausted it keeps pressing
+/// Ctrl+C and canceling prompts so the ui eventually quits
+#[derive(Default)]
+pub struct ScriptedInput {
+    events: VecDeque<ScriptedEvent>,
+    /// End of the current `sleep`, until which polling reports no events
+    sleeping_until: Option<Instant>,
+    /// Prints prompts and their answers since there is no line editor to do it
+    echo: bool,
+    /// Every prompt that was shown, in order
+    #[cfg(test)]
+    pub prompts: Vec<String>,
+}
+
+impl ScriptedInput {
+    /// Parses a script where each line is one of
+    /// `keys <chars>`, `key <name>`, `line <answer>`, `editor`,
+    /// `sleep <millis>`, `resize <width> <height>`, `click <row>` or
+    /// `scroll <rows>`.
+    /// Empty lines and lines starting with `#` are ignored
+    pub fn parse(script: &str) -> Result<Self, String> {
+        let mut input = Self {
+            echo: true,
+            ..Default::default()
+        };
+
+        for (i, line) in script.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (command, arg) = match line.find(' ') {
+                Some(index) => (&line[..index], &line[(index + 1)..]),
+                None => (line, ""),
+            };
+            input = match command {
+                "keys" => input.keys(arg),
+                "key" => match parse_key(arg.trim()) {
+                    Some(key) => input.key(key),
+                    None => {
+                        return Err(format!(
+                            "line {}: unknown key '{}'",
+                            i + 1,
+                            arg.trim()
+                        ))
+                    }
+                },
+                "line" => input.line(arg),
+                "editor" => input.editor(),
+                "sleep" => match arg.trim().parse() {
+                    Ok(millis) => input.sleep(Duration::from_millis(millis)),
+                    Err(_) => {
+                        return Err(format!(
+                            "line {}: could not parse milliseconds from '{}'",
+                            i + 1,
+                            arg.trim()
+                        ))
+                    }
+                },
+                "resize" => {
+                    let mut numbers = arg.split_whitespace().map(str::parse);
+                    match (numbers.next(), numbers.next()) {
+                        (Some(Ok(width)), Some(Ok(height))) => {
+                            input.resize(TerminalSize { width, height })
+                        }
+                        _ => {
+                            return Err(format!(
+                                "line {}: could not parse width and height from '{}'",
+                                i + 1,
+                                arg.trim()
+                            ))
+                        }
+                    }
+                }
+                "click" => match arg.trim().parse() {
+                    Ok(row) => input.click(row),
+                    Err(_) => {
+                        return Err(format!(
+                            "line {}: could not parse a row from '{}'",
+                            i + 1,
+                            arg.trim()
+                        ))
+                    }
+                },
+                "scroll" => match arg.trim().parse() {
+                    Ok(rows) => input.scroll(rows),
+                    Err(_) => {
+                        return Err(format!(
+                            "line {}: could not parse rows from '{}'",
+                            i + 1,
+                            arg.trim()
+                        ))
+                    }
+                },
+                _ => {
+                    return Err(format!(
+                        "line {}: unknown command '{}'",
+                        i + 1,
+                        command
+                    ))
+                }
+            };
+        }
+
+        Ok(input)
+    }
+
+    pub fn keys(mut self, keys: &str) -> Self {
+        for c in keys.chars() {
+            self.events.push_back(ScriptedEvent::Key(KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::NONE,
+            }));
+        }
+        self
+    }
+
+    pub fn key(mut self, key: KeyEvent) -> Self {
+        self.events.push_back(ScriptedEvent::Key(key));
+        self
+    }
+
+    pub fn line(mut self, line: &str) -> Self {
+        self.events.push_back(ScriptedEvent::Line(line.into()));
+        self
+    }
+
+    pub fn editor(mut self) -> Self {
+        self.events.push_back(ScriptedEvent::Editor);
+        self
+    }
+
+    pub fn sleep(mut self, duration: Duration) -> Self {
+        self.events.push_back(ScriptedEvent::Sleep(duration));
+        self
+    }
+
+    pub fn resize(mut self, size: TerminalSize) -> Self {
+        self.events.push_back(ScriptedEvent::Resize(size));
+        self
+    }
+
+    pub fn click(mut self, row: u16) -> Self {
+        self.events.push_back(ScriptedEvent::Click(row));
+        self
+    }
+
+    pub fn scroll(mut self, rows: i32) -> Self {
+        self.events.push_back(ScriptedEvent::Scroll(rows));
+        self
+    }
+
+    /// Blocks until the current and any directly following sleeps are over
+    fn wait_sleeps(&mut self) {
+        if let Some(until) = self.sleeping_until.take() {
+            thread::sleep(until.saturating_duration_since(Instant::now()));
+        }
+        while let Some(ScriptedEvent::Sleep(duration)) = self.events.front() {
+            thread::sleep(*duration);
+            self.events.pop_front();
+        }
+    }
+}
+
+fn parse_key(name: &str) -> Option<KeyEvent> {
+    let (modifiers, name) = if let Some(name) = name.strip_prefix("ctrl-") {
+        (KeyModifiers::CONTROL, name)
+    } else if let Some(name) = name.strip_prefix("alt-") {
+        (KeyModifiers::ALT, name)
     } else {
-        Event::None
+        (KeyModifiers::NONE, name)
+    };
+
+    let code = match name {
+        "esc" => KeyCode::Esc,
+        "enter" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "space" => KeyCode::Char(' '),
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return None,
+            }
+        }
+    };
+
+    Some(KeyEvent { code, modifiers })
+}
+
+impl Input for ScriptedInput {
+    fn poll_event(&mut self) -> crossterm::Result<Event> {
+        if let Some(until) = self.sleeping_until {
+            if Instant::now() < until {
+                thread::sleep(Duration::from_millis(10));
+                return Ok(Event::None);
+            }
+            self.sleeping_until = None;
+        }
+
+        match self.events.pop_front() {
+            Some(ScriptedEvent::Key(key)) => Ok(Event::Key(key)),
+            Some(ScriptedEvent::Resize(size)) => Ok(Event::Resize(size)),
+            Some(ScriptedEvent::Click(row)) => Ok(Event::Click(row)),
+            Some(ScriptedEvent::Scroll(rows)) => Ok(Event::Scroll(rows)),
+            Some(ScriptedEvent::Sleep(duration)) => {
+                self.sleeping_until = Some(Instant::now() + duration);
+                Ok(Event::None)
+            }
+            // an answer nobody prompted for
+            Some(_) => Ok(Event::None),
+            None => Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+            })),
+        }
+    }
+
+    fn read_event(&mut self) -> crossterm::Result<Event> {
+        self.wait_sleeps();
+        self.poll_event()
+    }
+
+    fn read_line(
+        &mut self,
+        prompt: &str,
+        _initial: &str,
+        completion: Completion,
+    ) -> Result<Option<String>, ReadlineError> {
+        #[cfg(test)]
+        self.prompts.push(prompt.into());
+
+        self.wait_sleeps();
+        let line = match self.events.pop_front() {
+            Some(ScriptedEvent::Line(line)) => Some(line),
+            Some(ScriptedEvent::Editor)
+                if matches!(completion, Completion::Message) =>
+            {
+                return Err(ReadlineError::Eof);
+            }
+            Some(event) => {
+                // a key where an answer was expected cancels the prompt
+                self.events.push_front(event);
+                None
+            }
+            None => None,
+        };
+
+        if self.echo {
+            let mut stdout = std::io::stdout();
+            write!(stdout, "{}{}", prompt, line.as_deref().unwrap_or(""))?;
+            stdout.flush()?;
+        }
+        Ok(line)
+    }
+
+    fn uses_terminal(&self) -> bool {
+        false
     }
 }
 
@@ -43,13 +586,130 @@ pub fn key_to_char(key: KeyEvent) -> Option<char> {
     }
 }
 
-pub fn read_line(initial: &str) -> Result<String, ReadlineError> {
-    let mut readline = Editor::<()>::new();
-    match readline.readline_with_initial("", (initial, "")) {
-        Ok(line) => Ok(line),
-        Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => {
-            Ok("".into())
+pub fn validate_revision(input: &str) -> Result<(), String> {
+    if input.trim().is_empty() {
+        Err("revision can not be empty".into())
+    } else {
+        Ok(())
+    }
+}
+
+pub fn validate_ref_name(input: &str) -> Result<(), String> {
+    let name = input.trim();
+    if name.is_empty() {
+        Err("name can not be empty".into())
+    } else if name.starts_with('-') {
+        Err("name can not start with '-'".into())
+    } else if name.contains(char::is_whitespace) {
+        Err("name can not contain whitespace".into())
+    } else {
+        Ok(())
+    }
+}
+
+pub fn validate_url(input: &str) -> Result<(), String> {
+    if input.trim().is_empty() {
+        Err("url can not be empty".into())
+    } else {
+        Ok(())
+    }
+}
+
+pub fn validate_path(input: &str) -> Result<(), String> {
+    if input.trim().is_empty() {
+        Err("path can not be empty".into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Parses an inclusive `start,end` range of 1 based line numbers, where empty
+/// input means every line
+pub fn parse_line_range(input: &str) -> Result<Option<(usize, usize)>, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+
+    let error = || format!("'{}' is not a line range like 10,20", input);
+    let mut bounds = input.splitn(2, ',').map(|n| n.trim().parse::<usize>());
+    match (bounds.next(), bounds.next()) {
+        (Some(Ok(start)), Some(Ok(end))) if 0 < start && start <= end => {
+            Ok(Some((start, end)))
+        }
+        _ => Err(error()),
+    }
+}
+
+pub fn validate_line_range(input: &str) -> Result<(), String> {
+    parse_line_range(input).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_is_parsed_in_order() {
+        let script =
+            "# commit everything\nkeys cc\nline fix typo\n\nkey ctrl-c\n";
+        let mut input = ScriptedInput::parse(script).unwrap();
+        input.echo = false;
+
+        for expected in &['c', 'c'] {
+            let key = input.read_key().unwrap();
+            assert_eq!(key_to_char(key), Some(*expected));
         }
-        Err(error) => Err(error),
+        assert_eq!(
+            input
+                .read_line("commit message: ", "", Completion::None)
+                .unwrap(),
+            Some("fix typo".into())
+        );
+        let key = input.read_key().unwrap();
+        assert_eq!(key.code, KeyCode::Char('c'));
+        assert_eq!(key.modifiers, KeyModifiers::CONTROL);
+    }
+
+    #[test]
+    fn paths_complete_relative_to_the_root() {
+        let root = std::env::temp_dir()
+            .join(format!("verco-complete-{}", std::process::id()));
+        fs::create_dir_all(root.join("src").join("bin")).unwrap();
+        fs::write(root.join("src").join("main.rs"), "").unwrap();
+        fs::write(root.join("src").join(".hidden"), "").unwrap();
+        fs::write(root.join("readme.md"), "").unwrap();
+
+        let names = |line| {
+            let (start, candidates) = complete_path(&root, line);
+            let names: Vec<_> =
+                candidates.into_iter().map(|c| c.replacement).collect();
+            (start, names)
+        };
+        assert_eq!(names("s"), (0, vec!["src/".to_string()]));
+        assert_eq!(names("src/"), (4, vec!["bin/".into(), "main.rs".into()]));
+        assert_eq!(names("src/."), (4, vec![".hidden".into()]));
+        assert_eq!(names("missing/"), (8, Vec::new()));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn line_ranges_need_both_ends_in_order() {
+        assert_eq!(parse_line_range(""), Ok(None));
+        assert_eq!(parse_line_range(" 3, 7 "), Ok(Some((3, 7))));
+        assert!(parse_line_range("7,3").is_err());
+        assert!(parse_line_range("0,3").is_err());
+        assert!(parse_line_range("3").is_err());
+        assert!(parse_line_range("a,b").is_err());
+    }
+
+    #[test]
+    fn script_errors_point_to_the_line() {
+        let error = ScriptedInput::parse("keys a\nkey hyper").err().unwrap();
+        assert_eq!(error, "line 2: unknown key 'hyper'");
+
+        let error = ScriptedInput::parse("sleep soon").err().unwrap();
+        assert_eq!(error, "line 1: could not parse milliseconds from 'soon'");
     }
 }
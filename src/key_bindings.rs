@@ -0,0 +1,246 @@
+use std::{fs, io};
+
+use crate::{action::ActionKind, config};
+
+/// Keys typed for actions instead of their built-in ones, read from
+/// `keys.txt` next to `config.txt`
+#[derive(Default)]
+pub struct KeyBindings {
+    rebound: Vec<Rebound>,
+}
+
+struct Rebound {
+    kind: ActionKind,
+    keys: Vec<char>,
+    /// The chord the action is handled under
+    default: Vec<char>,
+}
+
+/// What the keys typed so far do
+#[derive(Debug, PartialEq, Eq)]
+pub enum Resolved {
+    /// Runs the action handled under this chord
+    Chord(Vec<char>),
+    /// The start of a rebound chord, so more keys are needed
+    Pending,
+    /// Built-in keys of an action that was bound to others
+    Unbound,
+}
+
+impl KeyBindings {
+    /// Reads `keys.txt`. No bindings are changed when it does not exist
+    pub fn load(defaults: &[(&str, ActionKind)]) -> Result<Self, String> {
+        let path = match config::config_dir() {
+            Some(dir) => dir.join("keys.txt"),
+            None => return Ok(Self::default()),
+        };
+        match fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text, defaults)
+                .map_err(|error| format!("{}: {}", path.display(), error)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                Ok(Self::default())
+            }
+            Err(error) => Err(format!("{}: {}", path.display(), error)),
+        }
+    }
+
+    /// Parses lines with an action name, as the help lists it, followed by
+    /// the keys to type for it, like `commit all gc`. `defaults` are the
+    /// built-in keys of each action, alternatives separated by commas
+    pub fn parse(
+        text: &str,
+        defaults: &[(&str, ActionKind)],
+    ) -> Result<Self, String> {
+        let mut rebound = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error =
+                |message: String| format!("line {}: {}", index + 1, message);
+
+            let mut it = line.rsplitn(2, char::is_whitespace);
+            let keys = it.next().unwrap_or("");
+            let name = match it.next() {
+                Some(name) => name.trim(),
+                None => {
+                    return Err(error(format!(
+                        "'{}' needs the keys to type after it",
+                        line
+                    )))
+                }
+            };
+            let (kind, default) =
+                match defaults.iter().find(|(_, kind)| kind.name() == name) {
+                    Some(&(keys, kind)) => match chord(keys) {
+                        Some(default) => (kind, default),
+                        None => {
+                            return Err(error(format!(
+                                "'{}' can only be done with {}",
+                                name, keys
+                            )))
+                        }
+                    },
+                    None => {
+                        return Err(error(format!(
+                            "there is no action called '{}' to bind keys to",
+                            name
+                        )))
+                    }
+                };
+            rebound.retain(|r: &Rebound| r.kind != kind);
+            rebound.push(Rebound {
+                kind,
+                keys: keys.chars().collect(),
+                default,
+            });
+        }
+
+        let bindings = Self { rebound };
+        bindings.validate(defaults)?;
+        Ok(bindings)
+    }
+
+    /// Fails when typing the keys of one action would run another or would
+    /// never get to the end of them
+    fn validate(&self, defaults: &[(&str, ActionKind)]) -> Result<(), String> {
+        let bound: Vec<_> = defaults
+            .iter()
+            .filter_map(|&(keys, kind)| {
+                let keys = match self.rebound.iter().find(|r| r.kind == kind) {
+                    Some(rebound) => rebound.keys.clone(),
+                    None => chord(keys)?,
+                };
+                Some((keys, kind))
+            })
+            .collect();
+
+        for (i, (keys, kind)) in bound.iter().enumerate() {
+            for (other_keys, other_kind) in &bound[i + 1..] {
+                let (shorter, longer) = if keys.len() <= other_keys.len() {
+                    ((keys, kind), (other_keys, other_kind))
+                } else {
+                    ((other_keys, other_kind), (keys, kind))
+                };
+                if shorter.0 == longer.0 {
+                    return Err(format!(
+                        "'{}' is bound to both '{}' and '{}'",
+                        text(shorter.0),
+                        kind.name(),
+                        other_kind.name()
+                    ));
+                }
+                if longer.0.starts_with(shorter.0) {
+                    return Err(format!(
+                        "'{}' of '{}' is the start of '{}' of '{}'",
+                        text(shorter.0),
+                        shorter.1.name(),
+                        text(longer.0),
+                        longer.1.name()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The keys the help shows for `kind` in place of its built-in
+    /// `default` ones. Other alternatives like `esc` are kept
+    pub fn keys_for(&self, kind: ActionKind, default: &str) -> String {
+        match self.rebound.iter().find(|r| r.kind == kind) {
+            Some(rebound) => match default.find(", ") {
+                Some(i) => format!("{}{}", text(&rebound.keys), &default[i..]),
+                None => text(&rebound.keys),
+            },
+            None => default.into(),
+        }
+    }
+
+    pub fn resolve(&self, typed: &[char]) -> Resolved {
+        if let Some(rebound) = self.rebound.iter().find(|r| r.keys == typed) {
+            Resolved::Chord(rebound.default.clone())
+        } else if self.rebound.iter().any(|r| r.keys.starts_with(typed)) {
+            Resolved::Pending
+        } else if self.rebound.iter().any(|r| r.default == typed) {
+            Resolved::Unbound
+        } else {
+            Resolved::Chord(typed.into())
+        }
+    }
+}
+
+/// The chord of the first alternative in `keys`. `None` for keys with
+/// modifiers like `alt+left`, which can not be typed as characters
+fn chord(keys: &str) -> Option<Vec<char>> {
+    let first = keys.split(", ").next().unwrap_or("");
+    if first.is_empty() || (first.len() > 1 && first.contains('+')) {
+        None
+    } else {
+        Some(first.chars().collect())
+    }
+}
+
+fn text(chord: &[char]) -> String {
+    chord.iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULTS: &[(&str, ActionKind)] = &[
+        ("q, esc", ActionKind::Quit),
+        ("alt+left", ActionKind::GoBack),
+        ("s", ActionKind::Status),
+        ("cc", ActionKind::CommitAll),
+        ("cs", ActionKind::CommitSelected),
+        ("+", ActionKind::MoreContext),
+    ];
+
+    #[test]
+    fn rebound_keys_run_the_built_in_chord() {
+        let bindings = KeyBindings::parse(
+            "# mine\ncommit all gc\nquit Q\nmore diff context =\n",
+            DEFAULTS,
+        )
+        .unwrap();
+
+        assert_eq!(bindings.resolve(&['g']), Resolved::Pending);
+        assert_eq!(
+            bindings.resolve(&['g', 'c']),
+            Resolved::Chord(vec!['c', 'c'])
+        );
+        assert_eq!(bindings.resolve(&['c', 'c']), Resolved::Unbound);
+        assert_eq!(bindings.resolve(&['c']), Resolved::Chord(vec!['c']));
+        assert_eq!(bindings.resolve(&['=']), Resolved::Chord(vec!['+']));
+        assert_eq!(bindings.keys_for(ActionKind::Quit, "q, esc"), "Q, esc");
+        assert_eq!(bindings.keys_for(ActionKind::Status, "s"), "s");
+    }
+
+    #[test]
+    fn keys_bound_twice_are_reported() {
+        let error = KeyBindings::parse("commit all s", DEFAULTS).err().unwrap();
+        assert_eq!(error, "'s' is bound to both 'status' and 'commit all'");
+
+        let error = KeyBindings::parse("status c", DEFAULTS).err().unwrap();
+        assert_eq!(
+            error,
+            "'c' of 'status' is the start of 'cc' of 'commit all'"
+        );
+
+        let error =
+            KeyBindings::parse("\ncomit all x", DEFAULTS).err().unwrap();
+        assert_eq!(
+            error,
+            "line 2: there is no action called 'comit all' to bind keys to"
+        );
+
+        // swapping keys is fine
+        assert!(KeyBindings::parse(
+            "commit all cs\ncommit selected cc",
+            DEFAULTS
+        )
+        .is_ok());
+    }
+}
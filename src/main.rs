@@ -1,29 +1,171 @@
 mod action;
 mod application;
+mod args;
 mod async_process;
+mod cli;
+mod clipboard;
+mod command_log;
+mod config;
 mod custom_actions;
+mod diff;
+mod editor;
 mod git_actions;
 mod hg_actions;
+mod history;
 mod input;
+mod key_bindings;
+#[cfg(test)]
+mod mock_actions;
+mod notification;
+mod open;
+mod profile;
 mod repositories;
 mod scroll_view;
+mod scrollback;
 mod select;
+#[cfg(test)]
+mod snapshot;
+mod temp_file;
+mod theme;
 mod tui;
 mod tui_util;
+mod vcs_error;
 mod version_control_actions;
 
 fn main() {
-    if !crossterm::tty::IsTty::is_tty(&std::io::stdin()) {
-        eprintln!("not tty");
+    let args = match args::Args::parse() {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, args::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    // a script replaces the keyboard and its output may be captured
+    let script = match &args.script {
+        Some(path) => match std::fs::read_to_string(path) {
+            Ok(script) => match input::ScriptedInput::parse(&script) {
+                Ok(input) => Some(input),
+                Err(error) => {
+                    eprintln!("invalid script {}: {}", path, error);
+                    return;
+                }
+            },
+            Err(error) => {
+                eprintln!("could not read script {}: {}", path, error);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    if let Some(action) = args.action {
+        std::process::exit(run_action(&args, action));
+    }
+
+    use crossterm::tty::IsTty;
+    if script.is_none()
+        && (!std::io::stdin().is_tty() || !std::io::stdout().is_tty())
+    {
+        eprintln!(concat!(
+            env!("CARGO_PKG_NAME"),
+            " needs an interactive terminal to draw its ui.\n",
+            "it looks like its input or output is being piped or redirected."
+        ));
         return;
     }
 
+    if args.profile {
+        profile::enable();
+    }
+
+    tui_util::detect_color_support();
+
+    let config = config::Config::load(&args.options);
+    if let Err(error) =
+        config.validate().and_then(|()| open_command_log(&config))
+    {
+        eprintln!("{}", error);
+        return;
+    }
+    // the backend picker may show up before the application exists
+    theme::set(config.build_theme());
+    let key_bindings = match tui::load_key_bindings() {
+        Ok(key_bindings) => key_bindings,
+        Err(error) => {
+            eprintln!("{}", error);
+            return;
+        }
+    };
+
     ctrlc::set_handler(|| {}).unwrap();
-    if let Some(version_control) = repositories::get_current_version_control() {
-        let application = application::Application::new(
-            version_control,
-            custom_actions::CustomAction::load_custom_actions(),
+    if let Some(repository) =
+        repositories::get_current_repository(&config, args.directory.as_deref())
+    {
+        let custom_actions = custom_actions::CustomAction::load_custom_actions(
+            repository.version_control.get_root(),
+        );
+        let mut application =
+            application::Application::new(repository, custom_actions);
+        application.config_options = args.options.clone();
+        application.repository_config = config::Config {
+            git_path: config.git_path.clone(),
+            hg_path: config.hg_path.clone(),
+            date_format: config.date_format.clone(),
+            ..config::Config::default()
+        };
+        application.backend = version_control_actions::backend_name(
+            &*application.version_control,
         );
-        tui::show_tui(application);
+        application.apply_config(config);
+        application.key_bindings = key_bindings;
+        match script {
+            Some(input) => tui::show_tui(application, input),
+            None => {
+                let history = history::default_path()
+                    .map(history::History::load)
+                    .unwrap_or_default();
+                let input = input::TerminalInput::with_history(history);
+                tui::show_tui(application, input)
+            }
+        }
+        profile::print_report();
+    }
+}
+
+/// Prints the output of `action` in the repository found from `args`.
+/// Returns the exit code
+fn run_action(args: &args::Args, action: action::ActionKind) -> i32 {
+    let config = config::Config::load(&args.options);
+    if let Err(error) =
+        config.validate().and_then(|()| open_command_log(&config))
+    {
+        eprintln!("{}", error);
+        return 1;
+    }
+    theme::set(config.build_theme());
+    let repository = match repositories::get_current_repository(
+        &config,
+        args.directory.as_deref(),
+    ) {
+        Some(repository) => repository,
+        None => return 1,
+    };
+    let mut application = application::Application::new(repository, Vec::new());
+    application.apply_config(config);
+    cli::run(&application, action)
+}
+
+/// Starts appending the commands that run to the log `config` asks for
+fn open_command_log(config: &config::Config) -> Result<(), String> {
+    match &config.command_log {
+        Some(path) => command_log::enable(path).map_err(|error| {
+            format!(
+                "could not open the command log {}: {}",
+                path.display(),
+                error
+            )
+        }),
+        None => Ok(()),
     }
 }
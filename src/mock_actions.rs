@@ -0,0 +1,790 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    task::Poll,
+};
+
+use crate::{
+    action::{ActionResult, ActionTask},
+    async_process::Executor,
+    select::Entry,
+    vcs_error::VcsError,
+    version_control_actions::{
+        CommitOptions, DiffOptions, HistoryStep, Phase, RemoteOperation,
+        StashInclude, VersionControlActions,
+    },
+};
+
+/// Every call made to a `MockActions`, shared so it can still be inspected
+/// after the mock was moved into an `Application`
+pub type CallLog = Arc<Mutex<Vec<String>>>;
+
+/// A version control backend that never spawns processes. Each method records
+/// its call and answers with a canned result keyed by the method name
+#[derive(Default)]
+pub struct MockActions {
+    root: String,
+    calls: CallLog,
+    results: HashMap<&'static str, ActionResult>,
+    pending: HashSet<&'static str>,
+    changed_files: Vec<Entry>,
+    branches: Vec<Entry>,
+    remotes: Vec<Entry>,
+    tags: Vec<Entry>,
+    revisions: Vec<(String, Entry)>,
+    operation: Option<&'static str>,
+    ignore_revs_file: Option<String>,
+    /// Like hg, which commits whole files only
+    whole_files_only: bool,
+}
+
+impl MockActions {
+    pub fn new() -> Self {
+        Self {
+            root: "mock".into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_result(
+        mut self,
+        method: &'static str,
+        result: ActionResult,
+    ) -> Self {
+        self.results.insert(method, result);
+        self
+    }
+
+    /// Makes the task returned by `method` run until it gets canceled
+    pub fn with_pending(mut self, method: &'static str) -> Self {
+        self.pending.insert(method);
+        self
+    }
+
+    pub fn with_changed_files(mut self, entries: Vec<Entry>) -> Self {
+        self.changed_files = entries;
+        self
+    }
+
+    pub fn with_branches(mut self, entries: Vec<Entry>) -> Self {
+        self.branches = entries;
+        self
+    }
+
+    pub fn with_remotes(mut self, entries: Vec<Entry>) -> Self {
+        self.remotes = entries;
+        self
+    }
+
+    pub fn with_tags(mut self, entries: Vec<Entry>) -> Self {
+        self.tags = entries;
+        self
+    }
+
+    pub fn with_revisions(mut self, revisions: Vec<(String, Entry)>) -> Self {
+        self.revisions = revisions;
+        self
+    }
+
+    pub fn with_blame_ignore_revs_file(mut self, file: &str) -> Self {
+        self.ignore_revs_file = Some(file.into());
+        self
+    }
+
+    pub fn with_operation(mut self, operation: &'static str) -> Self {
+        self.operation = Some(operation);
+        self
+    }
+
+    pub fn without_hunk_staging(mut self) -> Self {
+        self.whole_files_only = true;
+        self
+    }
+
+    pub fn calls(&self) -> CallLog {
+        self.calls.clone()
+    }
+
+    fn record(&self, call: String) {
+        self.calls.lock().unwrap().push(call);
+    }
+
+    fn result(&self, method: &str) -> ActionResult {
+        self.results
+            .get(method)
+            .cloned()
+            .unwrap_or_else(|| ActionResult::from_ok(String::new()))
+    }
+
+    fn sync_result(&self, method: &'static str) -> Result<String, VcsError> {
+        self.sync_call_result(method.into(), method)
+    }
+
+    /// Like `sync_result` but recording `call`, which has the arguments
+    fn sync_call_result(
+        &self,
+        call: String,
+        method: &str,
+    ) -> Result<String, VcsError> {
+        self.record(call);
+        let result = self.result(method);
+        if result.success {
+            Ok(result.output)
+        } else {
+            Err(VcsError::from_stderr(result.output, None))
+        }
+    }
+
+    fn task(&self, method: &'static str, args: &[&str]) -> Box<dyn ActionTask> {
+        let call = if args.is_empty() {
+            String::from(method)
+        } else {
+            format!("{} {}", method, args.join(" "))
+        };
+        self.record(call);
+
+        if self.pending.contains(method) {
+            Box::new(MockTask::Pending {
+                method,
+                calls: self.calls.clone(),
+                output_so_far: self.result(method).output,
+            })
+        } else {
+            Box::new(MockTask::Ready(Some(self.result(method))))
+        }
+    }
+
+    /// Like `task` but also records the diff options that are not defaults
+    fn diff_task(
+        &self,
+        method: &'static str,
+        args: Vec<&str>,
+        options: &DiffOptions,
+    ) -> Box<dyn ActionTask> {
+        let options = diff_args(options);
+        let mut args = args;
+        args.extend(options.iter().map(String::as_str));
+        self.task(method, &args)
+    }
+}
+
+/// Only the options that are on, so plain commits record just the message
+fn commit_args(options: &CommitOptions) -> Vec<&'static str> {
+    let mut args = Vec::new();
+    if options.sign_off {
+        args.push("--signoff");
+    }
+    if options.gpg_sign {
+        args.push("--gpg-sign");
+    }
+    args
+}
+
+/// The options that differ from the defaults
+fn diff_args(options: &DiffOptions) -> Vec<String> {
+    let mut args = Vec::new();
+    let defaults = DiffOptions::default();
+    if options.context != defaults.context {
+        args.push(format!("-U{}", options.context));
+    }
+    if options.ignore_whitespace {
+        args.push("-w".into());
+    }
+    if let Some(algorithm) = options.algorithm {
+        args.push(algorithm.name().into());
+    }
+    if options.detect_moves {
+        args.push(format!("-M{}%", options.move_threshold));
+    }
+    args
+}
+
+fn selected_names(entries: &[Entry]) -> Vec<&str> {
+    entries
+        .iter()
+        .filter(|e| e.selected)
+        .map(|e| &e.filename[..])
+        .collect()
+}
+
+enum MockTask {
+    Ready(Option<ActionResult>),
+    Pending {
+        method: &'static str,
+        calls: CallLog,
+        /// The output of its result, shown as printed while it runs
+        output_so_far: String,
+    },
+    Canceled,
+}
+
+impl ActionTask for MockTask {
+    fn poll(&mut self, _executor: &mut Executor) -> Poll<ActionResult> {
+        match self {
+            MockTask::Ready(result) => match result.take() {
+                Some(result) => Poll::Ready(result),
+                None => panic!("mock task polled after completion"),
+            },
+            MockTask::Pending { .. } => Poll::Pending,
+            MockTask::Canceled => Poll::Ready(ActionResult::from_canceled()),
+        }
+    }
+
+    fn cancel(&mut self) {
+        if let MockTask::Pending { method, calls, .. } = self {
+            calls.lock().unwrap().push(format!("cancel {}", method));
+            *self = MockTask::Canceled;
+        }
+    }
+
+    fn output_so_far(&self) -> Option<String> {
+        match self {
+            MockTask::Pending { output_so_far, .. }
+                if !output_so_far.is_empty() =>
+            {
+                Some(output_so_far.clone())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl VersionControlActions for MockActions {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn executable_path(&self) -> &str {
+        "mock"
+    }
+
+    fn current_dir(&self) -> &str {
+        &self.root
+    }
+
+    fn set_root(&mut self) -> Result<(), VcsError> {
+        Ok(())
+    }
+
+    fn get_root(&self) -> &str {
+        &self.root
+    }
+
+    fn current_branch(&self) -> Result<String, VcsError> {
+        self.sync_result("current_branch")
+    }
+
+    fn upstream_branch(&self) -> Result<String, VcsError> {
+        self.sync_result("upstream_branch")
+    }
+
+    /// Not recorded since every refresh of the header asks for it
+    fn head_summary(&self) -> Option<String> {
+        let result = self.result("head_summary");
+        let success = result.success;
+        Some(result.output).filter(|head| success && !head.is_empty())
+    }
+
+    fn operation_in_progress(&self) -> Option<&'static str> {
+        self.operation
+    }
+
+    fn get_current_changed_files(&self) -> Result<Vec<Entry>, VcsError> {
+        self.record("get_current_changed_files".into());
+        let result = self.result("get_current_changed_files");
+        if !result.success {
+            return Err(VcsError::from_stderr(result.output, None));
+        }
+        Ok(self.changed_files.clone())
+    }
+
+    fn get_revision_changed_files(
+        &self,
+        target: &str,
+    ) -> Result<Vec<Entry>, VcsError> {
+        self.record(format!("get_revision_changed_files {}", target));
+        Ok(self.changed_files.clone())
+    }
+
+    fn diff_file(
+        &self,
+        file: &str,
+        _options: &DiffOptions,
+    ) -> Result<String, VcsError> {
+        self.sync_call_result(format!("diff_file {}", file), "diff_file")
+    }
+
+    fn revision_details(&self, target: &str) -> Result<String, VcsError> {
+        self.sync_call_result(
+            format!("revision_details {}", target),
+            "revision_details",
+        )
+    }
+
+    fn get_changed_files_since(
+        &self,
+        target: &str,
+    ) -> Result<Vec<Entry>, VcsError> {
+        self.record(format!("get_changed_files_since {}", target));
+        Ok(self.changed_files.clone())
+    }
+
+    fn version(&self) -> Result<String, VcsError> {
+        self.sync_result("version")
+    }
+
+    fn status(&self) -> Box<dyn ActionTask> {
+        self.task("status", &[])
+    }
+
+    fn current_export(&self, options: &DiffOptions) -> Box<dyn ActionTask> {
+        self.diff_task("current_export", Vec::new(), options)
+    }
+
+    fn log(
+        &self,
+        skip: usize,
+        count: usize,
+        signatures: bool,
+        graph: bool,
+    ) -> Box<dyn ActionTask> {
+        self.task(
+            "log",
+            &[
+                &skip.to_string(),
+                &count.to_string(),
+                &signatures.to_string(),
+                &graph.to_string(),
+            ],
+        )
+    }
+
+    fn current_diff_all(&self, options: &DiffOptions) -> Box<dyn ActionTask> {
+        self.diff_task("current_diff_all", Vec::new(), options)
+    }
+
+    fn current_diff_selected(
+        &self,
+        entries: &Vec<Entry>,
+        options: &DiffOptions,
+    ) -> Box<dyn ActionTask> {
+        self.diff_task(
+            "current_diff_selected",
+            selected_names(entries),
+            options,
+        )
+    }
+
+    fn revision_changes(
+        &self,
+        target: &str,
+        options: &DiffOptions,
+    ) -> Box<dyn ActionTask> {
+        self.diff_task("revision_changes", vec![target], options)
+    }
+
+    fn revision_diff_all(
+        &self,
+        target: &str,
+        options: &DiffOptions,
+    ) -> Box<dyn ActionTask> {
+        self.diff_task("revision_diff_all", vec![target], options)
+    }
+
+    fn revision_diff_selected(
+        &self,
+        target: &str,
+        entries: &Vec<Entry>,
+        options: &DiffOptions,
+    ) -> Box<dyn ActionTask> {
+        let mut args = vec![target];
+        args.extend(selected_names(entries));
+        self.diff_task("revision_diff_selected", args, options)
+    }
+
+    fn commit_all(
+        &self,
+        message: &str,
+        options: &CommitOptions,
+    ) -> Box<dyn ActionTask> {
+        let mut args = vec![message];
+        args.extend(commit_args(options));
+        self.task("commit_all", &args)
+    }
+
+    fn commit_selected(
+        &self,
+        message: &str,
+        entries: &Vec<Entry>,
+        options: &CommitOptions,
+    ) -> Box<dyn ActionTask> {
+        let mut args = vec![message];
+        args.extend(selected_names(entries));
+        args.extend(commit_args(options));
+        self.task("commit_selected", &args)
+    }
+
+    fn undo_last_commit(&self) -> Box<dyn ActionTask> {
+        self.task("undo_last_commit", &[])
+    }
+
+    fn revert_all(&self) -> Box<dyn ActionTask> {
+        self.task("revert_all", &[])
+    }
+
+    fn revert_selected(&self, entries: &Vec<Entry>) -> Box<dyn ActionTask> {
+        self.task("revert_selected", &selected_names(entries))
+    }
+
+    fn reset_selected(
+        &self,
+        target: &str,
+        entries: &[Entry],
+        working_tree: bool,
+    ) -> Box<dyn ActionTask> {
+        let mode = if working_tree {
+            "working_tree"
+        } else {
+            "index"
+        };
+        let mut args = vec![target, mode];
+        args.extend(selected_names(entries));
+        self.task("reset_selected", &args)
+    }
+
+    fn update(&self, target: &str) -> Box<dyn ActionTask> {
+        self.task("update", &[target])
+    }
+
+    fn update_submodules(
+        &self,
+        init: bool,
+        recursive: bool,
+    ) -> Box<dyn ActionTask> {
+        let init = init.to_string();
+        let recursive = recursive.to_string();
+        self.task("update_submodules", &[&init, &recursive])
+    }
+
+    fn merge(&self, target: &str) -> Box<dyn ActionTask> {
+        self.task("merge", &[target])
+    }
+
+    fn cherry_pick(&self, target: &str) -> Box<dyn ActionTask> {
+        self.task("cherry_pick", &[target])
+    }
+
+    fn continue_cherry_pick(&self) -> Box<dyn ActionTask> {
+        self.task("continue_cherry_pick", &[])
+    }
+
+    fn abort_cherry_pick(&self) -> Box<dyn ActionTask> {
+        self.task("abort_cherry_pick", &[])
+    }
+
+    fn rebase(&self, onto: &str) -> Box<dyn ActionTask> {
+        self.task("rebase", &[onto])
+    }
+
+    fn continue_rebase(&self) -> Box<dyn ActionTask> {
+        self.task("continue_rebase", &[])
+    }
+
+    fn abort_rebase(&self) -> Box<dyn ActionTask> {
+        self.task("abort_rebase", &[])
+    }
+
+    fn abort_operation(&self) -> Box<dyn ActionTask> {
+        self.task("abort_operation", &[])
+    }
+
+    fn conflicts(&self) -> Box<dyn ActionTask> {
+        self.task("conflicts", &[])
+    }
+
+    fn take_other(&self) -> Box<dyn ActionTask> {
+        self.task("take_other", &[])
+    }
+
+    fn take_local(&self) -> Box<dyn ActionTask> {
+        self.task("take_local", &[])
+    }
+
+    fn get_remotes(&self) -> Result<Vec<Entry>, VcsError> {
+        self.record("get_remotes".into());
+        Ok(self.remotes.clone())
+    }
+
+    fn list_remotes(&self) -> Box<dyn ActionTask> {
+        self.task("list_remotes", &[])
+    }
+
+    fn add_remote(&self, name: &str, url: &str) -> Box<dyn ActionTask> {
+        self.task("add_remote", &[name, url])
+    }
+
+    fn remove_remote(&self, name: &str) -> Box<dyn ActionTask> {
+        self.task("remove_remote", &[name])
+    }
+
+    fn remote_args(
+        &self,
+        operation: RemoteOperation,
+        remote: Option<&str>,
+    ) -> Vec<String> {
+        let name = match operation {
+            RemoteOperation::Fetch => "fetch",
+            RemoteOperation::Pull => "pull",
+            RemoteOperation::Push => "push",
+        };
+        let mut args = vec![name.to_string()];
+        args.extend(remote.map(Into::into));
+        args
+    }
+
+    fn run_in_terminal(&self, args: &[String]) -> Result<String, VcsError> {
+        let call = format!("run_in_terminal {}", args.join(" "));
+        self.sync_call_result(call, "run_in_terminal")
+    }
+
+    fn fetch(&self, remote: Option<&str>) -> Box<dyn ActionTask> {
+        let args: Vec<_> = remote.into_iter().collect();
+        self.task("fetch", &args)
+    }
+
+    fn pull(&self, remote: Option<&str>) -> Box<dyn ActionTask> {
+        let args: Vec<_> = remote.into_iter().collect();
+        self.task("pull", &args)
+    }
+
+    fn push(&self, remote: Option<&str>) -> Box<dyn ActionTask> {
+        let args: Vec<_> = remote.into_iter().collect();
+        self.task("push", &args)
+    }
+
+    fn branch_without_upstream(&self) -> Option<String> {
+        self.sync_result("branch_without_upstream")
+            .ok()
+            .filter(|branch| !branch.is_empty())
+    }
+
+    fn push_set_upstream(
+        &self,
+        remote: &str,
+        branch: &str,
+    ) -> Box<dyn ActionTask> {
+        self.task("push_set_upstream", &[remote, branch])
+    }
+
+    fn create_tag(&self, name: &str) -> Box<dyn ActionTask> {
+        self.task("create_tag", &[name])
+    }
+
+    fn list_tags(&self) -> Result<Vec<Entry>, VcsError> {
+        self.record("list_tags".into());
+        Ok(self.tags.clone())
+    }
+
+    fn delete_tag(
+        &self,
+        name: &str,
+        remote: Option<&str>,
+    ) -> Box<dyn ActionTask> {
+        let mut args = vec![name];
+        args.extend(remote);
+        self.task("delete_tag", &args)
+    }
+
+    fn list_branches(&self) -> Box<dyn ActionTask> {
+        self.task("list_branches", &[])
+    }
+
+    fn get_branches_for_checkout(&self) -> Result<Vec<Entry>, VcsError> {
+        self.record("get_branches_for_checkout".into());
+        Ok(self.branches.clone())
+    }
+
+    fn has_bookmarks(&self) -> bool {
+        true
+    }
+
+    fn has_phases(&self) -> bool {
+        true
+    }
+
+    fn show_phase(&self, target: &str) -> Box<dyn ActionTask> {
+        self.task("show_phase", &[target])
+    }
+
+    fn set_phase(&self, target: &str, phase: Phase) -> Box<dyn ActionTask> {
+        self.task("set_phase", &[target, phase.name()])
+    }
+
+    fn has_history_editing(&self) -> bool {
+        true
+    }
+
+    fn get_revisions_to_edit(
+        &self,
+        base: &str,
+    ) -> Result<Vec<(String, Entry)>, VcsError> {
+        self.record(format!("get_revisions_to_edit {}", base));
+        Ok(self.revisions.clone())
+    }
+
+    fn edit_history(
+        &self,
+        base: &str,
+        steps: &[(HistoryStep, String)],
+    ) -> Box<dyn ActionTask> {
+        let steps: Vec<_> = steps
+            .iter()
+            .map(|(step, hash)| format!("{}:{}", step.name(), hash))
+            .collect();
+        let mut args = vec![base];
+        args.extend(steps.iter().map(String::as_str));
+        self.task("edit_history", &args)
+    }
+
+    fn continue_history_edit(&self) -> Box<dyn ActionTask> {
+        self.task("continue_history_edit", &[])
+    }
+
+    fn abort_history_edit(&self) -> Box<dyn ActionTask> {
+        self.task("abort_history_edit", &[])
+    }
+
+    fn has_absorb(&self) -> bool {
+        true
+    }
+
+    fn has_hunk_staging(&self) -> bool {
+        !self.whole_files_only
+    }
+
+    fn has_all_remotes(&self) -> bool {
+        true
+    }
+
+    fn has_remote_tags(&self) -> bool {
+        true
+    }
+
+    fn hunk_diff(&self, file: &str) -> Result<String, VcsError> {
+        self.sync_call_result(format!("hunk_diff {}", file), "hunk_diff")
+    }
+
+    fn commit_patch(
+        &self,
+        message: &str,
+        patch: &str,
+        options: &CommitOptions,
+    ) -> Box<dyn ActionTask> {
+        let mut args = vec![message, patch];
+        args.extend(commit_args(options));
+        self.task("commit_patch", &args)
+    }
+
+    fn absorb_preview(&self) -> Result<String, VcsError> {
+        self.sync_result("absorb_preview")
+    }
+
+    fn absorb(&self) -> Box<dyn ActionTask> {
+        self.task("absorb", &[])
+    }
+
+    fn create_bookmark(&self, name: &str) -> Box<dyn ActionTask> {
+        self.task("create_bookmark", &[name])
+    }
+
+    fn delete_bookmark(&self, name: &str) -> Box<dyn ActionTask> {
+        self.task("delete_bookmark", &[name])
+    }
+
+    fn create_branch(&self, name: &str) -> Box<dyn ActionTask> {
+        self.task("create_branch", &[name])
+    }
+
+    fn close_branch(&self, name: &str) -> Box<dyn ActionTask> {
+        self.task("close_branch", &[name])
+    }
+
+    fn delete_branch(
+        &self,
+        name: &str,
+        force: bool,
+    ) -> Result<String, VcsError> {
+        // the result of each is configured apart to fail only without force
+        let method = if force {
+            "force_delete_branch"
+        } else {
+            "delete_branch"
+        };
+        self.sync_call_result(format!("{} {}", method, name), method)
+    }
+
+    fn list_merged_branches(&self, base: &str) -> Box<dyn ActionTask> {
+        self.task("list_merged_branches", &[base])
+    }
+
+    fn delete_branches(&self, names: &[String]) -> Box<dyn ActionTask> {
+        let names: Vec<_> = names.iter().map(String::as_str).collect();
+        self.task("delete_branches", &names)
+    }
+
+    fn stash_files(
+        &self,
+        include: StashInclude,
+    ) -> Result<Vec<String>, VcsError> {
+        self.record(format!("stash_files {:?}", include));
+        Ok(self
+            .changed_files
+            .iter()
+            .map(|e| e.filename.clone())
+            .collect())
+    }
+
+    fn stash(
+        &self,
+        message: &str,
+        include: StashInclude,
+    ) -> Box<dyn ActionTask> {
+        self.task("stash", &[&format!("{:?}", include), message])
+    }
+
+    fn blame_ignore_revs_file(&self) -> Option<String> {
+        self.ignore_revs_file.clone()
+    }
+
+    fn blame(
+        &self,
+        target: Option<&str>,
+        file: &str,
+        lines: Option<(usize, usize)>,
+        ignore_revs: bool,
+    ) -> Box<dyn ActionTask> {
+        let lines = lines.map(|(start, end)| format!("{},{}", start, end));
+        let mut args =
+            vec![target.unwrap_or("-"), file, lines.as_deref().unwrap_or("-")];
+        if ignore_revs {
+            args.push("ignore_revs");
+        }
+        self.task("blame", &args)
+    }
+
+    fn list_stashes(&self) -> Box<dyn ActionTask> {
+        self.task("list_stashes", &[])
+    }
+
+    fn stash_pop(&self, name: &str) -> Box<dyn ActionTask> {
+        self.task("stash_pop", &[name])
+    }
+
+    fn stash_show(
+        &self,
+        name: &str,
+        options: &DiffOptions,
+    ) -> Box<dyn ActionTask> {
+        self.diff_task("stash_show", vec![name], options)
+    }
+}
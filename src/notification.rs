@@ -0,0 +1,64 @@
+use std::{
+    process::{Command, Stdio},
+    thread,
+};
+
+/// Shows a desktop notification with `notify-send`, `osascript` or `toast`
+/// without waiting for it. Nothing happens when the program is missing
+pub fn notify(title: &str, body: &str) {
+    let mut command = match program(title, body) {
+        Some(command) => command,
+        None => return,
+    };
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    thread::spawn(move || {
+        let _ = command.status();
+    });
+}
+
+fn program(title: &str, body: &str) -> Option<Command> {
+    if cfg!(target_os = "macos") {
+        let script = format!(
+            "display notification {} with title {}",
+            applescript_string(body),
+            applescript_string(title)
+        );
+        let mut command = Command::new("osascript");
+        command.args(["-e", &script]);
+        return Some(command);
+    }
+    if cfg!(windows) {
+        let mut command = Command::new("toast");
+        command.args(["--title", title, "--message", body]);
+        return Some(command);
+    }
+    if std::env::var_os("DISPLAY").is_none()
+        && std::env::var_os("WAYLAND_DISPLAY").is_none()
+    {
+        return None;
+    }
+    let mut command = Command::new("notify-send");
+    command.args(["--app-name", env!("CARGO_PKG_NAME"), title, body]);
+    Some(command)
+}
+
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applescript_strings_escape_quotes() {
+        assert_eq!(applescript_string("push"), "\"push\"");
+        assert_eq!(
+            applescript_string("say \"hi\" \\o/"),
+            "\"say \\\"hi\\\" \\\\o/\""
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use std::{
+    io,
+    process::{Command, Stdio},
+    thread,
+};
+
+/// The platform's file explorer
+pub fn default_explorer() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "open {path}"
+    } else if cfg!(windows) {
+        "explorer {path}"
+    } else {
+        "xdg-open {path}"
+    }
+}
+
+/// `PAGER`, falling back to `less -R` or `more` on Windows
+pub fn default_pager() -> String {
+    match std::env::var("PAGER") {
+        Ok(pager) if !pager.trim().is_empty() => pager,
+        _ if cfg!(windows) => "more".into(),
+        _ => "less -R".into(),
+    }
+}
+
+/// The words of `template` with `{path}` replaced by `path`, which goes last
+/// when there is no placeholder
+pub fn command_line(template: &str, path: &str) -> Vec<String> {
+    let mut words: Vec<String> = template
+        .split_whitespace()
+        .map(|word| word.replace("{path}", path))
+        .collect();
+    if !template.contains("{path}") {
+        words.push(path.into());
+    }
+    words
+}
+
+/// Starts `template` on `path` without waiting for it, like a file explorer
+/// opening a window of its own
+pub fn spawn(template: &str, path: &str) -> io::Result<()> {
+    let mut command = command(template, path)?;
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    thread::spawn(move || {
+        let _ = child.wait();
+    });
+    Ok(())
+}
+
+/// Runs `template` on `path` until it exits, like an editor that works in
+/// the terminal it was started from
+pub fn run(template: &str, path: &str) -> io::Result<()> {
+    let mut command = command(template, path)?;
+    let status = command.status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "{} exited with {}",
+                command.get_program().to_string_lossy(),
+                status
+            ),
+        ))
+    }
+}
+
+fn command(template: &str, path: &str) -> io::Result<Command> {
+    let words = command_line(template, path);
+    let (name, args) = match words.split_first() {
+        Some(words) => words,
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "no command to open it with",
+            ))
+        }
+    };
+    let mut command = Command::new(name);
+    command.args(args);
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_replaces_the_placeholder_or_goes_last() {
+        assert_eq!(command_line("code {path}", "/repo"), ["code", "/repo"]);
+        assert_eq!(
+            command_line("subl --new-window {path}", "/repo"),
+            ["subl", "--new-window", "/repo"]
+        );
+        assert_eq!(command_line("nvim", "/repo"), ["nvim", "/repo"]);
+    }
+}
@@ -0,0 +1,75 @@
+use std::{
+    process::Command,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static RECORDS: Mutex<Vec<Record>> = Mutex::new(Vec::new());
+
+struct Record {
+    command: String,
+    duration: Duration,
+    success: bool,
+}
+
+/// Starts recording how long each command takes. Nothing is recorded unless
+/// this is called and the records never leave the process
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Describes a command for the report. Returns `None` when profiling is off
+/// so callers can skip recording altogether
+pub fn label(command: &Command) -> Option<String> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    let mut label = command.get_program().to_string_lossy().into_owned();
+    for arg in command.get_args() {
+        label.push(' ');
+        label.push_str(&arg.to_string_lossy());
+    }
+    Some(label)
+}
+
+pub fn record(command: String, duration: Duration, success: bool) {
+    if let Ok(mut records) = RECORDS.lock() {
+        records.push(Record {
+            command,
+            duration,
+            success,
+        });
+    }
+}
+
+/// Prints every recorded command with its wall clock time to stderr
+pub fn print_report() {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let records = match RECORDS.lock() {
+        Ok(records) => records,
+        Err(_) => return,
+    };
+    let total: Duration = records.iter().map(|r| r.duration).sum();
+    eprintln!(
+        "{} ran {} commands taking {:.3}s in total",
+        env!("CARGO_PKG_NAME"),
+        records.len(),
+        total.as_secs_f64()
+    );
+    for record in records.iter() {
+        eprintln!(
+            "{:>10.1}ms {} {}",
+            record.duration.as_secs_f64() * 1000.0,
+            if record.success { " " } else { "!" },
+            record.command
+        );
+    }
+}
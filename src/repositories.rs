@@ -1,14 +1,85 @@
-use std::{env, path::Path};
+use std::{
+    env, fs,
+    io::{stdin, stdout, Write},
+    path::{Path, PathBuf},
+};
+
+use crossterm::{
+    cursor, execute,
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+    tty::IsTty,
+};
 
 use crate::{
-    git_actions::GitActions, hg_actions::HgActions,
+    config::{self, Config},
+    git_actions::GitActions,
+    hg_actions::HgActions,
+    input::TerminalInput,
+    select::{select, Entry, State},
+    tui_util::{show_header, Header, HeaderKind, TerminalSize},
     version_control_actions::VersionControlActions,
 };
 
-pub fn get_current_version_control() -> Option<Box<dyn VersionControlActions>> {
-    let mut args = env::args();
-    if let Some(dir) = args.nth(1) {
-        let dir = Path::new(&dir);
+pub struct Repository {
+    pub version_control: Box<dyn VersionControlActions>,
+    /// Directory verco was launched from relative to the repository root
+    pub launch_subdir: String,
+}
+
+pub fn get_current_repository(
+    config: &Config,
+    directory: Option<&str>,
+) -> Option<Repository> {
+    let version_control = get_current_version_control(config, directory)?;
+
+    let launch_dir =
+        env::current_dir().expect("could not get current directory");
+    let root = Path::new(version_control.get_root());
+    let launch_subdir = match launch_dir.strip_prefix(root) {
+        Ok(subdir) => subdir.to_string_lossy().into_owned(),
+        Err(_) => String::new(),
+    };
+
+    // run everything from the repository root so relative paths line up
+    if let Err(error) = env::set_current_dir(root) {
+        eprintln!("could not set current directory to {:?}: {}", root, error);
+        return None;
+    }
+
+    Some(Repository {
+        version_control,
+        launch_subdir,
+    })
+}
+
+fn get_current_version_control(
+    config: &Config,
+    directory: Option<&str>,
+) -> Option<Box<dyn VersionControlActions>> {
+    // relative git dirs are relative to where verco was started
+    let launch_dir =
+        env::current_dir().expect("could not get current directory");
+    let absolute = |dir: &str| -> Option<String> {
+        let dir = launch_dir.join(dir);
+        match dir.to_str() {
+            Some(dir) => Some(dir.into()),
+            None => {
+                eprintln!("{:?} is not valid utf8", dir);
+                None
+            }
+        }
+    };
+    let git_dir = match &config.git_dir {
+        Some(dir) => Some(absolute(dir)?),
+        None => None,
+    };
+    let work_tree = match &config.git_work_tree {
+        Some(dir) => Some(absolute(dir)?),
+        None => None,
+    };
+
+    if let Some(dir) = directory {
+        let dir = Path::new(dir);
         if dir.canonicalize().is_err() {
             eprintln!("{:?} is not a valid directory", dir);
             return None;
@@ -27,22 +98,280 @@ pub fn get_current_version_control() -> Option<Box<dyn VersionControlActions>> {
         }
     };
 
+    if let Some(actions) =
+        detect_version_control(config, current_dir, git_dir, work_tree, true)
+    {
+        return Some(actions);
+    }
+
+    // outside of any other repository fall back to the dotfiles one, whose
+    // work tree is the home directory
+    if let (Some(dir), Some(home)) = (&config.dotfiles, config::home_dir()) {
+        let mut dotfiles_actions = Box::from(GitActions {
+            current_dir: current_dir.into(),
+            executable_path: config.git_path.clone(),
+            git_dir: Some(absolute(dir)?),
+            work_tree: Some(home),
+            hide_untracked: false,
+            date_format: config.date_format.clone(),
+        });
+        dotfiles_actions.hide_untracked_by_default();
+        if dotfiles_actions.set_root().is_ok() {
+            return Some(dotfiles_actions);
+        }
+    }
+
+    eprintln!(
+        concat!(
+            "no git or hg repository found in {} or any directory above it.\n",
+            "start ",
+            env!("CARGO_PKG_NAME"),
+            " inside a repository or pass the directory of one"
+        ),
+        current_dir
+    );
+    None
+}
+
+/// The git or hg repository `current_dir` is in. With `ask` the user picks
+/// one when it is in both and has not picked before
+fn detect_version_control(
+    config: &Config,
+    current_dir: &str,
+    git_dir: Option<String>,
+    work_tree: Option<String>,
+    ask: bool,
+) -> Option<Box<dyn VersionControlActions>> {
     // first try Git because it's the most common and also responds the fastest
     let mut git_actions = Box::from(GitActions {
         current_dir: current_dir.into(),
+        executable_path: config.git_path.clone(),
+        git_dir,
+        work_tree,
+        hide_untracked: false,
+        date_format: config.date_format.clone(),
+    });
+    let mut hg_actions = Box::from(HgActions {
+        current_dir: current_dir.into(),
+        executable_path: config.hg_path.clone(),
+        date_format: config.date_format.clone(),
     });
     if git_actions.set_root().is_ok() {
+        // hg is only asked when there may be one of its repositories too
+        // since it takes much longer to start
+        let hg_found = Path::new(current_dir)
+            .ancestors()
+            .any(|dir| dir.join(".hg").is_dir())
+            && hg_actions.set_root().is_ok();
+        if hg_found
+            && prefers_hg(
+                &git_actions.current_dir,
+                &hg_actions.current_dir,
+                ask,
+            )
+        {
+            return Some(hg_actions);
+        }
         return Some(git_actions);
     }
 
     // otherwise try Mercurial
-    let mut hg_actions = Box::from(HgActions {
-        current_dir: current_dir.into(),
-    });
     if hg_actions.set_root().is_ok() {
         return Some(hg_actions);
     }
-
-    eprintln!("no repository found");
     None
 }
+
+/// Opens the repository at `root` to switch to it from the one in use. The
+/// git dir and work tree options only apply to the repository opened at
+/// launch. The working directory only changes once one was found there
+pub fn open_repository(
+    config: &Config,
+    root: &str,
+) -> Result<Repository, String> {
+    fs::metadata(root)
+        .map_err(|error| format!("could not open {}: {}", root, error))?;
+    let version_control = detect_version_control(
+        config, root, None, None, false,
+    )
+    .ok_or_else(|| format!("no git or hg repository found in {}", root))?;
+    let root = version_control.get_root();
+    env::set_current_dir(root)
+        .map_err(|error| format!("could not open {}: {}", root, error))?;
+    Ok(Repository {
+        version_control,
+        launch_subdir: String::new(),
+    })
+}
+
+/// Directories next to `root` that have a git or hg repository of their
+/// own, by name
+pub fn sibling_repositories(root: &str) -> Vec<PathBuf> {
+    let root = Path::new(root);
+    let parent = match root.parent() {
+        Some(parent) => parent,
+        None => return Vec::new(),
+    };
+    let mut siblings: Vec<_> = match fs::read_dir(parent) {
+        Ok(entries) => entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| {
+                path != root
+                    && (path.join(".git").exists() || path.join(".hg").is_dir())
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    siblings.sort();
+    siblings
+}
+
+/// Whether to use hg when there are both git and hg repositories, like with
+/// hg-git or one nested in the other. The choice is remembered for the
+/// innermost of the two and, with `ask`, the user makes it the first time
+fn prefers_hg(git_root: &str, hg_root: &str, ask: bool) -> bool {
+    let key = if hg_root.len() > git_root.len() {
+        hg_root
+    } else {
+        git_root
+    };
+    if let Some(name) = remembered_backend(key) {
+        return name == "hg";
+    }
+    // there is no one to ask when the input is scripted or piped
+    if !ask || !stdin().is_tty() || !stdout().is_tty() {
+        return false;
+    }
+
+    let mut entries = vec![
+        Entry {
+            filename: format!("git {}", git_root),
+            selected: false,
+            state: State::Clean,
+            columns: None,
+        },
+        Entry {
+            filename: format!("hg {}", hg_root),
+            selected: false,
+            state: State::Clean,
+            columns: None,
+        },
+    ];
+    let hg = match pick_backend(key, &mut entries) {
+        Ok(true) => entries[1].selected && !entries[0].selected,
+        _ => return false,
+    };
+    remember_backend(key, if hg { "hg" } else { "git" });
+    hg
+}
+
+fn pick_backend(
+    root: &str,
+    entries: &mut Vec<Entry>,
+) -> crossterm::Result<bool> {
+    let mut stdout = stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, EnterAlternateScreen, cursor::Hide)?;
+    let mut terminal_size = TerminalSize::get();
+    let header = Header {
+        action_name: "pick the version control to use",
+        directory_name: root,
+        backend: "",
+        branch: "",
+        progress: "",
+        status_counts: None,
+    };
+    let picked =
+        show_header(&mut stdout, header, HeaderKind::Waiting, terminal_size)
+            .and_then(|_| {
+                select(
+                    &mut stdout,
+                    &mut TerminalInput::default(),
+                    &mut terminal_size,
+                    header,
+                    root,
+                    entries,
+                    None,
+                )
+            });
+    execute!(stdout, cursor::Show, LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    picked
+}
+
+/// Holds a line with the picked version control and the repository root for
+/// each repository it was asked for
+fn backends_path() -> Option<PathBuf> {
+    let mut path = config::config_dir()?;
+    path.push("backends.txt");
+    Some(path)
+}
+
+fn remembered_backend(root: &str) -> Option<String> {
+    let backends = fs::read_to_string(backends_path()?).ok()?;
+    backends.lines().rev().find_map(|line| {
+        let (name, line_root) = line.split_once(' ')?;
+        if line_root == root {
+            Some(name.into())
+        } else {
+            None
+        }
+    })
+}
+
+fn remember_backend(root: &str, name: &str) {
+    let path = match backends_path() {
+        Some(path) => path,
+        None => return,
+    };
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let file = fs::OpenOptions::new().create(true).append(true).open(path);
+    if let Ok(mut file) = file {
+        let _ = writeln!(file, "{} {}", name, root);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn siblings_with_a_repository_are_found() {
+        let parent = env::temp_dir()
+            .join(format!("verco-siblings-{}", std::process::id()));
+        for (name, marker) in [
+            ("current", ".git"),
+            ("hg", ".hg"),
+            ("git", ".git"),
+            ("plain", "src"),
+        ] {
+            fs::create_dir_all(parent.join(name).join(marker)).unwrap();
+        }
+
+        let root = parent.join("current");
+        let siblings = sibling_repositories(root.to_str().unwrap());
+        assert_eq!(siblings, [parent.join("git"), parent.join("hg")]);
+
+        fs::remove_dir_all(parent).unwrap();
+    }
+
+    #[test]
+    fn opening_a_directory_without_a_repository_stays_where_it_was() {
+        let dir = env::temp_dir()
+            .join(format!("verco-no-repository-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let config = Config {
+            hg_path: "verco-missing-hg".into(),
+            ..Config::default()
+        };
+        let before = env::current_dir().unwrap();
+
+        let opened = open_repository(&config, dir.to_str().unwrap());
+
+        assert!(opened.is_err());
+        assert_eq!(env::current_dir().unwrap(), before);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
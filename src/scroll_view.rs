@@ -2,19 +2,19 @@ use crossterm::{
     cursor,
     event::{KeyCode, KeyEvent, KeyModifiers},
     handle_command,
-    style::{ResetColor, SetBackgroundColor},
+    style::{Print, ResetColor},
     terminal::{Clear, ClearType},
     Result,
 };
 
-use std::io::Write;
+use std::{io::Write, ops::Range};
 
 use crate::{
     action::ActionKind,
-    input,
+    input, theme,
     tui_util::{
-        draw_filter_bar, fuzzy_matches, move_cursor, AvailableSize,
-        TerminalSize, SELECTED_BG_COLOR,
+        bg, draw_filter_bar, fuzzy_matches, move_cursor, wrap_line,
+        AvailableSize, TerminalSize,
     },
 };
 
@@ -22,9 +22,16 @@ pub struct ScrollView {
     action_kind: ActionKind,
     content: String,
     scroll: usize,
+    /// Rows of the top line that are scrolled past when lines wrap
+    scroll_row: usize,
     cursor: Option<usize>,
+    /// Lines drawn with the selection background while not filtering
+    highlight: Option<Range<usize>>,
     is_filtering: bool,
     filter: Vec<char>,
+    /// Whether diffs wrap like other output instead of being cut off at the
+    /// terminal width
+    pub wrap_diffs: bool,
 }
 
 impl Default for ScrollView {
@@ -33,9 +40,12 @@ impl Default for ScrollView {
             action_kind: ActionKind::Quit,
             content: String::with_capacity(1024 * 4),
             scroll: 0,
+            scroll_row: 0,
             cursor: None,
+            highlight: None,
             is_filtering: false,
             filter: Vec::new(),
+            wrap_diffs: true,
         }
     }
 }
@@ -45,6 +55,29 @@ impl ScrollView {
         self.cursor
     }
 
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// Index of the first line shown
+    pub fn top_line(&self) -> usize {
+        self.scroll
+    }
+
+    /// Scrolls to show `line` at the top, highlighting nothing
+    pub fn scroll_to(&mut self, line: usize) {
+        self.scroll = line;
+        self.scroll_row = 0;
+        self.highlight = None;
+    }
+
+    /// Highlights `lines` and scrolls to show them from the top
+    pub fn highlight(&mut self, lines: Range<usize>) {
+        self.scroll = lines.start;
+        self.scroll_row = 0;
+        self.highlight = Some(lines);
+    }
+
     pub fn set_content(
         &mut self,
         content: &str,
@@ -54,11 +87,13 @@ impl ScrollView {
         self.content.clear();
         self.content.push_str(content);
 
+        self.highlight = None;
         self.is_filtering = false;
         self.filter.clear();
 
         if self.action_kind != action_kind {
             self.scroll = 0;
+            self.scroll_row = 0;
             self.cursor = if action_kind.can_select_output() {
                 Some(0)
             } else {
@@ -85,29 +120,49 @@ impl ScrollView {
         handle_command!(write, cursor::MoveTo(0, 1))?;
         handle_command!(write, ResetColor)?;
 
-        for (i, line) in self
-            .filtered_lines()
-            .enumerate()
-            .skip(self.scroll)
-            .take(available_size.height)
-        {
+        let mut rows_left = available_size.height;
+        for (i, line) in self.filtered_lines().enumerate().skip(self.scroll) {
+            if rows_left == 0 {
+                break;
+            }
+
             if let Some(cursor) = self.cursor {
                 if cursor == i {
-                    handle_command!(
-                        write,
-                        SetBackgroundColor(SELECTED_BG_COLOR)
-                    )?;
+                    handle_command!(write, bg(theme::current().selected_bg))?;
                 }
 
                 line_formatter(write, line, available_size)?;
                 handle_command!(write, Clear(ClearType::UntilNewLine))?;
                 handle_command!(write, cursor::MoveToNextLine(1))?;
                 handle_command!(write, ResetColor)?;
+                rows_left -= 1;
+                continue;
+            }
+
+            let rows = wrap_line(line, available_size.width);
+            let (skipped, shown) = if !self.wraps() {
+                (0, 1)
+            } else if i == self.scroll {
+                let skipped = self.scroll_row.min(rows.len() - 1);
+                (skipped, rows.len() - skipped)
             } else {
-                handle_command!(write, Clear(ClearType::CurrentLine))?;
-                line_formatter(write, line, available_size)?;
-                handle_command!(write, cursor::MoveToNextLine(1))?;
+                (0, rows.len())
+            };
+            let shown = shown.min(rows_left);
+            for row in &rows[skipped..skipped + shown] {
+                if self.is_highlighted(i) {
+                    draw_highlighted(write, row)?;
+                } else {
+                    handle_command!(write, Clear(ClearType::CurrentLine))?;
+                    line_formatter(write, row, available_size)?;
+                    handle_command!(write, cursor::MoveToNextLine(1))?;
+                }
+            }
+            // the colors of the part cut off would carry over otherwise
+            if skipped + shown < rows.len() {
+                handle_command!(write, ResetColor)?;
             }
+            rows_left -= shown;
         }
 
         handle_command!(write, Clear(ClearType::FromCursorDown))?;
@@ -196,6 +251,7 @@ impl ScrollView {
             } => {
                 self.is_filtering = false;
                 self.scroll = 0;
+                self.scroll_row = 0;
                 if let Some(ref mut cursor) = self.cursor {
                     *cursor = 0;
                 }
@@ -209,11 +265,9 @@ impl ScrollView {
                 code: KeyCode::End, ..
             } => {
                 self.is_filtering = false;
-                let content_height = self.content_height(available_size);
-                self.scroll = 0
-                    .max(content_height as i32 - available_size.height as i32)
-                    as usize;
+                self.scroll(available_size, i32::MAX);
 
+                let content_height = self.content_height();
                 if let Some(ref mut cursor) = self.cursor {
                     *cursor = content_height - 1;
                 }
@@ -284,25 +338,32 @@ impl ScrollView {
         Ok(true)
     }
 
+    fn is_highlighted(&self, line: usize) -> bool {
+        self.filter.is_empty()
+            && self.highlight.as_ref().map_or(false, |h| h.contains(&line))
+    }
+
     fn filtered_lines(&self) -> impl Iterator<Item = &str> {
         self.content
             .lines()
             .filter(move |line| fuzzy_matches(line, &self.filter[..]))
     }
 
-    fn content_height(&self, available_size: AvailableSize) -> usize {
-        if self.cursor.is_some() {
-            self.filtered_lines().count()
-        } else {
-            let width = available_size.width;
-            self.filtered_lines()
-                .map(|l| (l.len() + width - 1) / width)
-                .sum()
-        }
+    fn content_height(&self) -> usize {
+        self.filtered_lines().count()
+    }
+
+    /// Whether lines wider than the terminal continue on the rows below.
+    /// Selectable lines and, unless `wrap_diffs` is set, diffs are cut off
+    fn wraps(&self) -> bool {
+        self.cursor.is_none()
+            && (self.wrap_diffs || !self.action_kind.is_diff())
     }
 
+    /// Moves by `delta` rows, which are whole lines unless they wrap, and
+    /// stops once the last line ends at the bottom
     fn scroll(&mut self, available_size: AvailableSize, delta: i32) {
-        let content_height = self.content_height(available_size);
+        let content_height = self.content_height();
         if let Some(ref mut cursor) = self.cursor {
             move_cursor(
                 &mut self.scroll,
@@ -311,11 +372,33 @@ impl ScrollView {
                 content_height,
                 delta,
             );
+            return;
+        }
+
+        let line_rows: Vec<_> = if self.wraps() {
+            self.filtered_lines()
+                .map(|line| wrap_line(line, available_size.width).len())
+                .collect()
         } else {
-            self.scroll = (self.scroll as i32 + delta)
-                .min(content_height as i32 - available_size.height as i32)
-                .max(0) as usize;
+            vec![1; content_height]
+        };
+        let scroll = self.scroll.min(line_rows.len());
+        let top: usize =
+            line_rows[..scroll].iter().sum::<usize>() + self.scroll_row;
+        let total: usize = line_rows.iter().sum();
+        let max_top = total.saturating_sub(available_size.height);
+        let mut top =
+            (top as i64 + delta as i64).clamp(0, max_top as i64) as usize;
+
+        self.scroll = 0;
+        for rows in line_rows {
+            if top < rows {
+                break;
+            }
+            top -= rows;
+            self.scroll += 1;
         }
+        self.scroll_row = top;
     }
 
     fn on_filter_changed<W>(
@@ -327,7 +410,29 @@ impl ScrollView {
         W: Write,
     {
         self.scroll = 0;
+        self.scroll_row = 0;
         self.cursor = self.cursor.map(|_| 0);
         self.draw_content(writer, terminal_size)
     }
 }
+
+/// Draws `row` on the selection background. Colored output resets the
+/// background along with the text color, so it is set again after every reset
+fn draw_highlighted<W>(write: &mut W, row: &str) -> Result<()>
+where
+    W: Write,
+{
+    let background = bg(theme::current().selected_bg);
+    handle_command!(write, background)?;
+    let row = row.replace("\x1b[0m", "\x1b[m");
+    for (j, part) in row.split("\x1b[m").enumerate() {
+        if j > 0 {
+            handle_command!(write, ResetColor)?;
+            handle_command!(write, background)?;
+        }
+        handle_command!(write, Print(part))?;
+    }
+    handle_command!(write, Clear(ClearType::UntilNewLine))?;
+    handle_command!(write, cursor::MoveToNextLine(1))?;
+    handle_command!(write, ResetColor)
+}
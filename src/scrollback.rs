@@ -0,0 +1,143 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crossterm::style::style;
+
+use crate::{action::ActionKind, theme, tui_util::fit_color};
+
+/// Lines kept before the oldest outputs are dropped
+pub const DEFAULT_MAX_LINES: usize = 10_000;
+
+/// Whether the output of each action replaces the previous one or goes below
+/// it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OutputMode {
+    Clear,
+    Scrollback,
+}
+
+impl OutputMode {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "clear" => Some(Self::Clear),
+            "scrollback" => Some(Self::Scrollback),
+            _ => None,
+        }
+    }
+}
+
+struct ScrollbackEntry {
+    kind: ActionKind,
+    output: String,
+    line_count: usize,
+    /// Since the session started
+    finished: Duration,
+}
+
+/// The outputs of the actions that finished this session, oldest first
+pub struct Scrollback {
+    entries: VecDeque<ScrollbackEntry>,
+    line_count: usize,
+    pub max_lines: usize,
+    started: Instant,
+}
+
+impl Default for Scrollback {
+    fn default() -> Self {
+        Self {
+            entries: VecDeque::new(),
+            line_count: 0,
+            max_lines: DEFAULT_MAX_LINES,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Scrollback {
+    pub fn push(&mut self, kind: ActionKind, output: &str) {
+        // plus the title
+        let line_count = output.lines().count() + 1;
+        self.entries.push_back(ScrollbackEntry {
+            kind,
+            output: output.into(),
+            line_count,
+            finished: self.started.elapsed(),
+        });
+        self.line_count += line_count;
+
+        while self.line_count > self.max_lines && self.entries.len() > 1 {
+            if let Some(entry) = self.entries.pop_front() {
+                self.line_count -= entry.line_count;
+            }
+        }
+    }
+
+    /// Whether `output` of `kind` is the newest entry
+    pub fn ends_with(&self, kind: ActionKind, output: &str) -> bool {
+        match self.entries.back() {
+            Some(entry) => entry.kind == kind && entry.output == output,
+            None => false,
+        }
+    }
+
+    /// All the entries, each below its action name and when it finished, and
+    /// the line the newest one starts at
+    pub fn text(&self) -> (String, usize) {
+        let mut text = String::new();
+        let mut last_start = 0;
+        let mut line = 0;
+        for entry in &self.entries {
+            last_start = line;
+            let seconds = entry.finished.as_secs();
+            let title = format!(
+                "> {:02}:{:02}:{:02} {}",
+                seconds / 3600,
+                seconds / 60 % 60,
+                seconds % 60,
+                entry.kind.name()
+            );
+            let title = style(title).with(fit_color(theme::current().entry));
+            text.push_str(&title.to_string());
+            text.push('\n');
+            text.push_str(&entry.output);
+            if !entry.output.is_empty() && !entry.output.ends_with('\n') {
+                text.push('\n');
+            }
+            line += entry.line_count;
+        }
+        (text, last_start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::diff;
+
+    #[test]
+    fn oldest_entries_are_dropped_past_the_line_limit() {
+        let mut scrollback = Scrollback::default();
+        // half the limit with the title
+        let long_output = "line\n".repeat(DEFAULT_MAX_LINES / 2 - 1);
+        scrollback.push(ActionKind::Status, "clean\n");
+        scrollback.push(ActionKind::Log, &long_output);
+        scrollback.push(ActionKind::CurrentDiffAll, &long_output);
+
+        let (text, last_start) = scrollback.text();
+        assert!(!text.contains("clean"));
+        assert_eq!(last_start, DEFAULT_MAX_LINES / 2);
+        assert!(scrollback.ends_with(ActionKind::CurrentDiffAll, &long_output));
+        assert!(!scrollback.ends_with(ActionKind::Log, &long_output));
+    }
+
+    #[test]
+    fn entries_are_titled_with_when_they_finished() {
+        let mut scrollback = Scrollback::default();
+        scrollback.push(ActionKind::Status, "clean");
+        let (text, _) = scrollback.text();
+        assert_eq!(diff::strip_ansi(&text), "> 00:00:00 status\nclean\n");
+    }
+}
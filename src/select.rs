@@ -1,8 +1,8 @@
 use crossterm::{
     cursor,
-    event::{self, KeyCode, KeyEvent, KeyModifiers},
-    handle_command,
-    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    event::{KeyCode, KeyEvent, KeyModifiers},
+    handle_command, queue,
+    style::{Color, Print, ResetColor},
     terminal::{Clear, ClearType},
     QueueableCommand, Result,
 };
@@ -10,56 +10,16 @@ use crossterm::{
 use std::io::Write;
 
 use crate::{
-    input,
+    action::ActionKind,
+    input::{self, Completion, Event, Input},
+    scroll_view::ScrollView,
+    theme,
     tui_util::{
-        draw_filter_bar, fuzzy_matches, move_cursor, AvailableSize,
-        TerminalSize, SELECTED_BG_COLOR,
+        bg, draw_filter_bar, fg, fuzzy_matches, move_cursor, show_header,
+        AvailableSize, Header, HeaderKind, Region, TerminalSize,
     },
 };
 
-const UNTRACKED_COLOR: Color = Color::Rgb {
-    r: 100,
-    g: 180,
-    b: 255,
-};
-const UNMODIFIED_COLOR: Color = Color::Rgb {
-    r: 255,
-    g: 255,
-    b: 255,
-};
-const MODIFIED_COLOR: Color = Color::Rgb {
-    r: 255,
-    g: 200,
-    b: 0,
-};
-const ADDED_COLOR: Color = Color::Rgb { r: 0, g: 255, b: 0 };
-const DELETED_COLOR: Color = Color::Rgb { r: 255, g: 0, b: 0 };
-const RENAMED_COLOR: Color = Color::Rgb {
-    r: 100,
-    g: 100,
-    b: 255,
-};
-const COPIED_COLOR: Color = Color::Rgb {
-    r: 255,
-    g: 0,
-    b: 255,
-};
-const UNMERGED_COLOR: Color = Color::Rgb {
-    r: 255,
-    g: 180,
-    b: 100,
-};
-const MISSING_COLOR: Color = Color::Rgb { r: 255, g: 0, b: 0 };
-const IGNORED_COLOR: Color = Color::Rgb {
-    r: 255,
-    g: 180,
-    b: 0,
-};
-const CLEAN_COLOR: Color = Color::Rgb {
-    r: 100,
-    g: 180,
-    b: 255,
-};
 const ITEM_NAME_COLUMN: usize = 16;
 
 #[derive(Clone, Debug)]
@@ -75,23 +35,96 @@ pub enum State {
     Missing,
     Ignored,
     Clean,
+    /// A branch to pick from
+    Branch,
+    /// The branch checked out
+    Current,
 }
 
 impl State {
     fn color(&self) -> Color {
+        let theme = theme::current();
         match self {
-            State::Untracked => UNTRACKED_COLOR,
-            State::Unmodified => UNMODIFIED_COLOR,
-            State::Modified => MODIFIED_COLOR,
-            State::Added => ADDED_COLOR,
-            State::Deleted => DELETED_COLOR,
-            State::Renamed => RENAMED_COLOR,
-            State::Copied => COPIED_COLOR,
-            State::Unmerged => UNMERGED_COLOR,
-            State::Missing => MISSING_COLOR,
-            State::Ignored => IGNORED_COLOR,
-            State::Clean => CLEAN_COLOR,
+            State::Untracked => theme.untracked,
+            State::Unmodified => theme.unmodified,
+            State::Modified => theme.modified,
+            State::Added => theme.added,
+            State::Deleted => theme.deleted,
+            State::Renamed => theme.renamed,
+            State::Copied => theme.copied,
+            State::Unmerged => theme.unmerged,
+            State::Missing => theme.missing,
+            State::Ignored => theme.ignored,
+            State::Clean => theme.clean,
+            State::Branch => theme.unmodified,
+            State::Current => theme.entry,
+        }
+    }
+}
+
+/// How many files of the working tree have each kind of change
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct StatusCounts {
+    /// `None` without a staging area
+    pub staged: Option<usize>,
+    pub modified: usize,
+    pub untracked: usize,
+    pub conflicted: usize,
+}
+
+impl StatusCounts {
+    pub fn from_entries(entries: &[Entry], staging_area: bool) -> Self {
+        let mut counts = Self {
+            staged: Some(0).filter(|_| staging_area),
+            ..Default::default()
+        };
+        for entry in entries {
+            match entry.columns {
+                _ if entry.is_conflicted() => counts.conflicted += 1,
+                Some(['?', '?']) => counts.untracked += 1,
+                Some(['!', '!']) => (),
+                Some([staged, unstaged]) => {
+                    if let (Some(count), false) =
+                        (counts.staged.as_mut(), staged == ' ')
+                    {
+                        *count += 1;
+                    }
+                    if unstaged != ' ' {
+                        counts.modified += 1;
+                    }
+                }
+                None => match entry.state {
+                    State::Untracked => counts.untracked += 1,
+                    State::Unmodified | State::Ignored | State::Clean => (),
+                    _ => counts.modified += 1,
+                },
+            }
         }
+        counts
+    }
+
+    /// Each count after its symbol, in the color of its kind of change. Only
+    /// `clean` when there are no changes at all
+    pub fn segments(&self) -> Vec<(String, Color)> {
+        let theme = theme::current();
+        if self.is_clean() {
+            return vec![("clean".into(), theme.added)];
+        }
+        let mut segments = Vec::new();
+        if let Some(staged) = self.staged {
+            segments.push((format!("\u{25cf}{}", staged), theme.added));
+        }
+        segments.push((format!("+{}", self.modified), theme.modified));
+        segments.push((format!("?{}", self.untracked), theme.untracked));
+        segments.push((format!("\u{2717}{}", self.conflicted), theme.unmerged));
+        segments
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.staged.unwrap_or(0) == 0
+            && self.modified == 0
+            && self.untracked == 0
+            && self.conflicted == 0
     }
 }
 
@@ -100,10 +133,28 @@ pub struct Entry {
     pub filename: String,
     pub selected: bool,
     pub state: State,
+    /// The staged and unstaged columns of git's short status, so a file with
+    /// both kinds of changes shows up as `MM`. `None` without a staging area
+    pub columns: Option<[char; 2]>,
+}
+
+impl Entry {
+    pub fn is_conflicted(&self) -> bool {
+        match self.columns {
+            // both sides added or deleted the file or one of them changed
+            // what the other deleted
+            Some([staged, unstaged]) => {
+                staged == 'U'
+                    || unstaged == 'U'
+                    || (staged == unstaged && matches!(staged, 'A' | 'D'))
+            }
+            None => matches!(self.state, State::Unmerged),
+        }
+    }
 }
 
 struct Select<'a> {
-    entries: &'a mut [Entry],
+    entries: &'a mut Vec<Entry>,
     scroll: usize,
     cursor: usize,
     filter: Vec<char>,
@@ -144,6 +195,37 @@ impl<'a> Select<'a> {
         self.draw_all_entries(write, available_size)
     }
 
+    /// A click on the entry under the cursor toggles it and one on another
+    /// entry moves the cursor there. Clicks below the entries do nothing
+    fn click(&mut self, available_size: AvailableSize, row: u16) {
+        let region = Region::from(available_size);
+        let offset = match row.checked_sub(region.y) {
+            Some(offset) if offset < region.height => offset as usize,
+            _ => return,
+        };
+        let index = self.scroll + offset;
+        if index != self.cursor {
+            if index < self.filtered_entries().count() {
+                self.cursor = index;
+            }
+        } else if let Some(e) = self.filtered_entries_mut().nth(index) {
+            e.selected = !e.selected;
+        }
+    }
+
+    /// Moves the entries in view by `rows`, taking the cursor along when it
+    /// would leave the view
+    fn scroll_by(&mut self, available_size: AvailableSize, rows: i32) {
+        let entry_count = self.filtered_entries().count();
+        let max_scroll = entry_count.saturating_sub(available_size.height);
+        self.scroll =
+            (self.scroll as i32 + rows).max(0).min(max_scroll as i32) as usize;
+        let last_in_view = (self.scroll + available_size.height)
+            .min(entry_count)
+            .saturating_sub(1);
+        self.cursor = self.cursor.max(self.scroll).min(last_in_view);
+    }
+
     fn draw_all_entries<W>(
         &self,
         write: &mut W,
@@ -152,17 +234,18 @@ impl<'a> Select<'a> {
     where
         W: Write,
     {
-        handle_command!(write, cursor::MoveTo(0, 1))?;
+        let region = Region::from(available_size);
+        handle_command!(write, cursor::MoveTo(region.x, region.y))?;
         handle_command!(write, ResetColor)?;
 
         for (i, entry) in self
             .filtered_entries()
             .enumerate()
             .skip(self.scroll)
-            .take(available_size.height)
+            .take(region.height as usize)
         {
             if i == self.cursor {
-                handle_command!(write, SetBackgroundColor(SELECTED_BG_COLOR))?;
+                handle_command!(write, bg(theme::current().selected_bg))?;
             } else {
                 handle_command!(write, ResetColor)?;
             }
@@ -172,17 +255,28 @@ impl<'a> Select<'a> {
 
             handle_command!(write, Print(select_char))?;
             handle_command!(write, Print(' '))?;
-            handle_command!(write, SetForegroundColor(entry.state.color()))?;
+            let mut cursor_x = 2 + state_name.len();
+            if let Some(columns) = entry.columns {
+                for &(c, color) in &[
+                    (columns[0], theme::current().added),
+                    (columns[1], theme::current().modified),
+                ] {
+                    handle_command!(write, fg(color))?;
+                    handle_command!(write, Print(c))?;
+                }
+                handle_command!(write, Print(' '))?;
+                cursor_x += 3;
+            }
+            handle_command!(write, fg(entry.state.color()))?;
             handle_command!(write, Print(&state_name))?;
             handle_command!(write, ResetColor)?;
 
             if i == self.cursor {
-                handle_command!(write, SetBackgroundColor(SELECTED_BG_COLOR))?;
+                handle_command!(write, bg(theme::current().selected_bg))?;
             } else {
                 handle_command!(write, ResetColor)?;
             }
 
-            let cursor_x = 2 + state_name.len();
             for _ in cursor_x..ITEM_NAME_COLUMN {
                 handle_command!(write, Print(' '))?;
             }
@@ -190,7 +284,7 @@ impl<'a> Select<'a> {
                 .filename
                 .char_indices()
                 .rev()
-                .take(available_size.width - ITEM_NAME_COLUMN)
+                .take((region.width as usize).saturating_sub(ITEM_NAME_COLUMN))
                 .last()
                 .map(|(i, _)| i)
                 .unwrap_or(0);
@@ -207,6 +301,26 @@ impl<'a> Select<'a> {
         Ok(())
     }
 
+    /// Selects the entry for `filename` and moves the cursor to it, adding it
+    /// as unmodified if it is not in the list
+    fn add_path(&mut self, filename: String) {
+        self.filter.clear();
+        self.cursor =
+            match self.entries.iter().position(|e| e.filename == filename) {
+                Some(index) => index,
+                None => {
+                    self.entries.push(Entry {
+                        filename,
+                        selected: false,
+                        state: State::Unmodified,
+                        columns: None,
+                    });
+                    self.entries.len() - 1
+                }
+            };
+        self.entries[self.cursor].selected = true;
+    }
+
     fn on_filter_changed<W>(
         &mut self,
         write: &mut W,
@@ -222,9 +336,23 @@ impl<'a> Select<'a> {
     }
 }
 
-pub fn select<W>(write: &mut W, entries: &mut [Entry]) -> Result<bool>
+/// Lets the user select some of `entries` below the header already shown,
+/// which is drawn again with the entries when the terminal gets resized.
+/// Returns false when canceled. `terminal_size` ends up with the size after
+/// the last resize. With a `preview`, the right arrow shows what it gives for
+/// the entry under the cursor until the left arrow goes back to the list
+pub fn select<W, I>(
+    write: &mut W,
+    input: &mut I,
+    terminal_size: &mut TerminalSize,
+    header: Header,
+    root: &str,
+    entries: &mut Vec<Entry>,
+    preview: Option<&dyn Fn(&Entry) -> String>,
+) -> Result<bool>
 where
     W: Write,
+    I: Input,
 {
     if entries.len() == 0 {
         return Ok(false);
@@ -237,22 +365,34 @@ where
         filter: Vec::new(),
     };
 
-    let mut available_size =
-        AvailableSize::from_temrinal_size(TerminalSize::get()?);
+    let mut available_size = AvailableSize::from_temrinal_size(*terminal_size);
     select.draw_all_entries(write, available_size)?;
 
     loop {
         write.queue(cursor::MoveTo(0, 2))?;
         write.flush()?;
-        match event::read()? {
-            event::Event::Resize(width, height) => {
-                available_size =
-                    AvailableSize::from_temrinal_size(TerminalSize {
-                        width,
-                        height,
-                    });
+        match input.read_event()? {
+            Event::Resize(size) => {
+                *terminal_size = size;
+                available_size = AvailableSize::from_temrinal_size(size);
+                show_header(write, header, HeaderKind::Waiting, size)?;
+                // fills the rows a taller terminal adds and keeps the cursor
+                // in view of a shorter one
+                let entry_count = select.filtered_entries().count();
+                select.scroll = select
+                    .scroll
+                    .min(entry_count.saturating_sub(available_size.height));
+                select.move_cursor(write, available_size, 0)?;
+            }
+            Event::Click(row) => {
+                select.click(available_size, row);
+                select.draw_all_entries(write, available_size)?;
             }
-            event::Event::Key(key_event) => match key_event {
+            Event::Scroll(rows) => {
+                select.scroll_by(available_size, rows);
+                select.draw_all_entries(write, available_size)?;
+            }
+            Event::Key(key_event) => match key_event {
                 KeyEvent {
                     code: KeyCode::Esc, ..
                 }
@@ -386,6 +526,29 @@ where
                     select.cursor = entries_len - 1;
                     select.draw_all_entries(write, available_size)?;
                 }
+                KeyEvent {
+                    code: KeyCode::Right,
+                    ..
+                } => {
+                    let cursor = select.cursor;
+                    let text = match (
+                        preview,
+                        select.filtered_entries().nth(cursor),
+                    ) {
+                        (Some(preview), Some(entry)) => preview(entry),
+                        _ => continue,
+                    };
+                    show_preview(write, input, terminal_size, header, &text)?;
+                    available_size =
+                        AvailableSize::from_temrinal_size(*terminal_size);
+                    show_header(
+                        write,
+                        header,
+                        HeaderKind::Waiting,
+                        *terminal_size,
+                    )?;
+                    select.draw_all_entries(write, available_size)?;
+                }
                 KeyEvent {
                     code: KeyCode::Char(' '),
                     ..
@@ -407,6 +570,30 @@ where
                     }
                     select.draw_all_entries(write, available_size)?;
                 }
+                KeyEvent {
+                    code: KeyCode::Char('o'),
+                    modifiers: KeyModifiers::CONTROL,
+                } => {
+                    queue!(
+                        write,
+                        cursor::MoveTo(0, 1),
+                        ResetColor,
+                        Clear(ClearType::FromCursorDown),
+                        cursor::Show,
+                    )?;
+                    write.flush()?;
+                    let line =
+                        input.read_line("path: ", "", Completion::Path(root));
+                    write.queue(cursor::Hide)?;
+
+                    if let Ok(Some(path)) = line {
+                        let path = path.trim();
+                        if !path.is_empty() {
+                            select.add_path(path.into());
+                        }
+                    }
+                    select.move_cursor(write, available_size, 0)?;
+                }
                 KeyEvent {
                     code: KeyCode::Char('h'),
                     modifiers: KeyModifiers::CONTROL,
@@ -438,3 +625,240 @@ where
         }
     }
 }
+
+/// Shows `text` scrollable in place of the entries until the left arrow or
+/// esc goes back to them
+fn show_preview<W, I>(
+    write: &mut W,
+    input: &mut I,
+    terminal_size: &mut TerminalSize,
+    header: Header,
+    text: &str,
+) -> Result<()>
+where
+    W: Write,
+    I: Input,
+{
+    let mut view = ScrollView::default();
+    view.set_content(text, ActionKind::CurrentDiffSelected, *terminal_size);
+    show_header(write, header, HeaderKind::Ok, *terminal_size)?;
+    view.draw_content(write, *terminal_size)?;
+
+    loop {
+        write.flush()?;
+        match input.read_event()? {
+            Event::Resize(size) => {
+                *terminal_size = size;
+                show_header(write, header, HeaderKind::Ok, size)?;
+                view.draw_content(write, size)?;
+            }
+            Event::Key(key_event) => {
+                if view.update(write, key_event, *terminal_size)? {
+                    continue;
+                }
+                match key_event {
+                    KeyEvent {
+                        code: KeyCode::Left,
+                        ..
+                    }
+                    | KeyEvent {
+                        code: KeyCode::Esc, ..
+                    }
+                    | KeyEvent {
+                        code: KeyCode::Char('c'),
+                        modifiers: KeyModifiers::CONTROL,
+                    } => return Ok(()),
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{input::ScriptedInput, snapshot::assert_snapshot};
+
+    #[test]
+    fn select_snapshot() {
+        let entry = |filename: &str, selected, state| Entry {
+            filename: filename.into(),
+            selected,
+            state,
+            columns: None,
+        };
+        let mut entries = vec![
+            entry("src/main.rs", false, State::Modified),
+            entry("src/new.rs", true, State::Added),
+            entry("old.txt", false, State::Deleted),
+            entry("notes.txt", false, State::Untracked),
+        ];
+        entries[0].columns = Some(['M', 'M']);
+        let mut input = ScriptedInput::default().key(KeyEvent {
+            code: KeyCode::Down,
+            modifiers: KeyModifiers::NONE,
+        });
+        let mut terminal_size = TerminalSize {
+            width: 60,
+            height: 10,
+        };
+
+        let mut write = Vec::new();
+        let selected = select(
+            &mut write,
+            &mut input,
+            &mut terminal_size,
+            test_header(),
+            "",
+            &mut entries,
+            None,
+        )
+        .unwrap();
+
+        assert!(!selected);
+        assert_snapshot("select", &write);
+    }
+
+    #[test]
+    fn resizing_redraws_the_header_and_entries() {
+        let mut entries: Vec<_> = (0..20)
+            .map(|i| Entry {
+                filename: format!("file{}.txt", i),
+                selected: false,
+                state: State::Modified,
+                columns: None,
+            })
+            .collect();
+        let mut input = ScriptedInput::default()
+            .key(KeyEvent {
+                code: KeyCode::End,
+                modifiers: KeyModifiers::NONE,
+            })
+            .resize(TerminalSize {
+                width: 40,
+                height: 30,
+            });
+        let mut terminal_size = TerminalSize {
+            width: 60,
+            height: 8,
+        };
+
+        let mut write = Vec::new();
+        select(
+            &mut write,
+            &mut input,
+            &mut terminal_size,
+            test_header(),
+            "",
+            &mut entries,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(terminal_size.width, 40);
+        assert_eq!(terminal_size.height, 30);
+        let output = String::from_utf8(write).unwrap();
+        let after_resize = &output[output.rfind("select").unwrap()..];
+        assert!(after_resize.contains("file0.txt"));
+        assert!(after_resize.contains("file19.txt"));
+    }
+
+    #[test]
+    fn clicks_move_the_cursor_then_toggle_and_the_wheel_scrolls() {
+        let mut entries: Vec<_> = (0..20)
+            .map(|i| Entry {
+                filename: format!("file{}.txt", i),
+                selected: false,
+                state: State::Modified,
+                columns: None,
+            })
+            .collect();
+        // the first entry is on the row below the header
+        let mut input = ScriptedInput::default()
+            .click(3)
+            .click(3)
+            .scroll(5)
+            .click(2)
+            .click(2)
+            .click(40)
+            .key(KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::NONE,
+            });
+        let mut terminal_size = TerminalSize {
+            width: 60,
+            height: 8,
+        };
+
+        let mut write = Vec::new();
+        let selected = select(
+            &mut write,
+            &mut input,
+            &mut terminal_size,
+            test_header(),
+            "",
+            &mut entries,
+            None,
+        )
+        .unwrap();
+
+        assert!(selected);
+        let selected: Vec<_> = entries
+            .iter()
+            .filter(|e| e.selected)
+            .map(|e| &e.filename[..])
+            .collect();
+        assert_eq!(selected, ["file2.txt", "file6.txt"]);
+    }
+
+    fn test_header() -> Header<'static> {
+        Header {
+            action_name: "select",
+            directory_name: "repo",
+            backend: "",
+            branch: "",
+            progress: "",
+            status_counts: None,
+        }
+    }
+
+    #[test]
+    fn status_counts_follow_the_status_columns() {
+        let entry = |columns| Entry {
+            filename: "a".into(),
+            selected: false,
+            state: State::Modified,
+            columns: Some(columns),
+        };
+        let entries = [
+            entry(['M', 'M']),
+            entry(['A', ' ']),
+            entry([' ', 'D']),
+            entry(['?', '?']),
+            entry(['U', 'U']),
+            entry(['A', 'A']),
+        ];
+        let counts = StatusCounts::from_entries(&entries, true);
+        assert_eq!(
+            counts,
+            StatusCounts {
+                staged: Some(2),
+                modified: 2,
+                untracked: 1,
+                conflicted: 2,
+            }
+        );
+        assert_eq!(counts.segments().len(), 4);
+        let counts = StatusCounts::from_entries(&[], false);
+        assert_eq!(counts.staged, None);
+        let segments: Vec<_> = counts
+            .segments()
+            .into_iter()
+            .map(|(text, _)| text)
+            .collect();
+        assert_eq!(segments, ["clean"]);
+    }
+}
@@ -0,0 +1,52 @@
+use std::{env, fs, path::PathBuf};
+
+/// Compares rendered terminal output against `src/snapshots/<name>.snap`.
+/// Run the tests with `UPDATE_SNAPSHOTS=1` to write the current output instead
+pub fn assert_snapshot(name: &str, rendered: &[u8]) {
+    let actual = escape(&String::from_utf8_lossy(rendered));
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src")
+        .join("snapshots")
+        .join(format!("{}.snap", name));
+
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    let expected = match fs::read_to_string(&path) {
+        Ok(expected) => expected.replace("\r\n", "\n"),
+        Err(_) => panic!(
+            "missing snapshot {:?}. run with UPDATE_SNAPSHOTS=1 to create it",
+            path
+        ),
+    };
+    if expected != actual {
+        panic!(
+            concat!(
+                "snapshot {:?} does not match.",
+                " run with UPDATE_SNAPSHOTS=1 to accept the new output",
+                "\n\n--- expected\n{}\n\n+++ actual\n{}",
+            ),
+            path, expected, actual
+        );
+    }
+}
+
+/// Puts every escape sequence on its own line so snapshot diffs stay readable
+fn escape(rendered: &str) -> String {
+    let version = env!("CARGO_PKG_VERSION");
+    let mut escaped = String::with_capacity(rendered.len() * 2);
+    for c in rendered.replace(version, "<version>").chars() {
+        match c {
+            '\x1b' => escaped.push_str("\n\\e"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('\n');
+    escaped
+}
@@ -0,0 +1,93 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+/// A file in the temporary directory that this process created itself and
+/// only the user can read. It is deleted when dropped
+pub struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// Creates a file named like `verco-<name>-<unique>.txt` holding
+    /// `contents`. A file that is already there is never opened instead, so
+    /// no one else can have it read or written
+    pub fn create(name: &str, contents: &str) -> io::Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.subsec_nanos())
+            .unwrap_or(0);
+        let mut attempts = 0;
+        loop {
+            let path = std::env::temp_dir().join(format!(
+                "{}-{}-{}-{}-{}.txt",
+                env!("CARGO_PKG_NAME"),
+                name,
+                std::process::id(),
+                CREATED.fetch_add(1, Ordering::Relaxed),
+                nanos
+            ));
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.mode(0o600);
+            }
+            match options.open(&path) {
+                Ok(mut handle) => {
+                    // dropped on a failed write, which deletes the file again
+                    let file = Self { path };
+                    handle.write_all(contents.as_bytes())?;
+                    return Ok(file);
+                }
+                Err(error)
+                    if error.kind() == io::ErrorKind::AlreadyExists
+                        && attempts < 100 =>
+                {
+                    attempts += 1;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_are_new_each_time_and_deleted_when_dropped() {
+        let first = TempFile::create("test", "first").unwrap();
+        let second = TempFile::create("test", "second").unwrap();
+        assert_ne!(first.path(), second.path());
+        assert_eq!(fs::read_to_string(first.path()).unwrap(), "first");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(first.path()).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let path = first.path().to_owned();
+        drop(first);
+        assert!(!path.exists());
+    }
+}
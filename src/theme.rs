@@ -0,0 +1,558 @@
+use std::{
+    convert::TryFrom,
+    env,
+    io::{self, Write},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyModifiers},
+    style::Color,
+    terminal,
+    tty::IsTty,
+};
+
+const fn rgb(r: u8, g: u8, b: u8) -> Color {
+    Color::Rgb { r, g, b }
+}
+
+pub struct Theme {
+    pub selected_bg: Color,
+    pub entry: Color,
+    pub error: Color,
+    pub warning: Color,
+    /// One color for each column of the log
+    pub log: [Color; 6],
+
+    pub header: Color,
+    pub header_action: Color,
+    pub header_bg_waiting: Color,
+    pub header_bg_waiting_dark: Color,
+    pub header_bg_ok: Color,
+    pub header_bg_ok_dark: Color,
+    pub header_bg_error: Color,
+    pub header_bg_error_dark: Color,
+
+    pub filter: Color,
+    pub filter_active_bg: Color,
+    pub filter_inactive_bg: Color,
+
+    pub untracked: Color,
+    pub unmodified: Color,
+    pub modified: Color,
+    pub added: Color,
+    pub deleted: Color,
+    pub renamed: Color,
+    pub copied: Color,
+    pub unmerged: Color,
+    pub missing: Color,
+    pub ignored: Color,
+    pub clean: Color,
+
+    /// Colors git and hg should use for their own diff and status output
+    /// instead of their red and green defaults
+    pub vcs_colors: Option<VcsColors>,
+}
+
+impl Theme {
+    /// This theme with the colors of `colors`, pairs of role and value, in
+    /// place of its own. Ones that do not parse are skipped
+    pub fn with_colors(mut self, colors: &[(String, String)]) -> Self {
+        for (role, value) in colors {
+            if let (Some(slot), Some(color)) =
+                (self.role_mut(role), parse_color(value))
+            {
+                *slot = color;
+            }
+        }
+        self
+    }
+
+    /// The color a `color_<role>` option sets, named like its field
+    fn role_mut(&mut self, role: &str) -> Option<&mut Color> {
+        let color = match role {
+            "selected_bg" => &mut self.selected_bg,
+            "entry" => &mut self.entry,
+            "error" => &mut self.error,
+            "warning" => &mut self.warning,
+            "header" => &mut self.header,
+            "header_action" => &mut self.header_action,
+            "header_bg_waiting" => &mut self.header_bg_waiting,
+            "header_bg_waiting_dark" => &mut self.header_bg_waiting_dark,
+            "header_bg_ok" => &mut self.header_bg_ok,
+            "header_bg_ok_dark" => &mut self.header_bg_ok_dark,
+            "header_bg_error" => &mut self.header_bg_error,
+            "header_bg_error_dark" => &mut self.header_bg_error_dark,
+            "filter" => &mut self.filter,
+            "filter_active_bg" => &mut self.filter_active_bg,
+            "filter_inactive_bg" => &mut self.filter_inactive_bg,
+            "untracked" => &mut self.untracked,
+            "unmodified" => &mut self.unmodified,
+            "modified" => &mut self.modified,
+            "added" => &mut self.added,
+            "deleted" => &mut self.deleted,
+            "renamed" => &mut self.renamed,
+            "copied" => &mut self.copied,
+            "unmerged" => &mut self.unmerged,
+            "missing" => &mut self.missing,
+            "ignored" => &mut self.ignored,
+            "clean" => &mut self.clean,
+            _ => return None,
+        };
+        Some(color)
+    }
+}
+
+/// Checks that every pair of role and value names a known role and a color
+/// `parse_color` understands
+pub fn validate_colors(colors: &[(String, String)]) -> Result<(), String> {
+    let mut theme = DARK;
+    for (role, value) in colors {
+        if theme.role_mut(role).is_none() {
+            return Err(format!("there is no color called 'color_{}'", role));
+        }
+        if parse_color(value).is_none() {
+            return Err(format!(
+                "'{}' is not a color for 'color_{}'. use a name like \
+                 'dark_blue' or a hex value like '#ff8800'",
+                value, role
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Parses one of the 16 basic colors by name, like `magenta` or
+/// `dark_blue`, or a `#rrggbb` hex value
+pub fn parse_color(value: &str) -> Option<Color> {
+    let hex = match value.strip_prefix('#') {
+        Some(hex) => hex,
+        None => return Color::try_from(value).ok(),
+    };
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..(i + 2)], 16).ok();
+    Some(rgb(channel(0)?, channel(2)?, channel(4)?))
+}
+
+pub struct VcsColors {
+    pub added: &'static str,
+    pub removed: &'static str,
+}
+
+pub const DARK: Theme = Theme {
+    selected_bg: rgb(80, 80, 80),
+    entry: rgb(255, 180, 100),
+    error: rgb(255, 80, 80),
+    warning: rgb(230, 200, 60),
+    log: [
+        Color::White,
+        rgb(211, 153, 33),
+        rgb(52, 113, 134),
+        rgb(137, 151, 29),
+        rgb(251, 73, 47),
+        Color::White,
+    ],
+
+    header: Color::Black,
+    header_action: Color::White,
+    header_bg_waiting: Color::Magenta,
+    header_bg_waiting_dark: Color::DarkMagenta,
+    header_bg_ok: Color::Green,
+    header_bg_ok_dark: Color::DarkGreen,
+    header_bg_error: Color::Red,
+    header_bg_error_dark: Color::DarkRed,
+
+    filter: Color::Black,
+    filter_active_bg: rgb(255, 180, 100),
+    filter_inactive_bg: rgb(180, 90, 50),
+
+    untracked: rgb(100, 180, 255),
+    unmodified: rgb(255, 255, 255),
+    modified: rgb(255, 200, 0),
+    added: rgb(0, 255, 0),
+    deleted: rgb(255, 0, 0),
+    renamed: rgb(100, 100, 255),
+    copied: rgb(255, 0, 255),
+    unmerged: rgb(255, 180, 100),
+    missing: rgb(255, 0, 0),
+    ignored: rgb(255, 180, 0),
+    clean: rgb(100, 180, 255),
+
+    vcs_colors: None,
+};
+
+/// Darker and more saturated colors that stay readable on a white background
+pub const LIGHT: Theme = Theme {
+    selected_bg: rgb(210, 210, 210),
+    entry: rgb(190, 90, 0),
+    error: rgb(200, 0, 0),
+    warning: rgb(150, 110, 0),
+    log: [
+        Color::Black,
+        rgb(150, 100, 0),
+        rgb(20, 90, 130),
+        rgb(70, 110, 0),
+        rgb(200, 40, 20),
+        Color::Black,
+    ],
+
+    header: Color::Black,
+    header_action: Color::White,
+    header_bg_waiting: Color::Magenta,
+    header_bg_waiting_dark: Color::DarkMagenta,
+    header_bg_ok: Color::Green,
+    header_bg_ok_dark: Color::DarkGreen,
+    header_bg_error: Color::Red,
+    header_bg_error_dark: Color::DarkRed,
+
+    filter: Color::Black,
+    filter_active_bg: rgb(255, 180, 100),
+    filter_inactive_bg: rgb(230, 150, 100),
+
+    untracked: rgb(0, 90, 200),
+    unmodified: rgb(0, 0, 0),
+    modified: rgb(170, 120, 0),
+    added: rgb(0, 140, 0),
+    deleted: rgb(200, 0, 0),
+    renamed: rgb(60, 60, 200),
+    copied: rgb(170, 0, 170),
+    unmerged: rgb(190, 90, 0),
+    missing: rgb(200, 0, 0),
+    ignored: rgb(150, 110, 0),
+    clean: rgb(0, 90, 200),
+
+    vcs_colors: None,
+};
+
+/// Saturated colors at the extremes of brightness and a solid selection
+pub const HIGH_CONTRAST_DARK: Theme = Theme {
+    selected_bg: rgb(0, 0, 160),
+    entry: rgb(255, 255, 0),
+    error: rgb(255, 64, 64),
+    warning: rgb(255, 200, 0),
+    log: [
+        Color::White,
+        rgb(255, 255, 0),
+        rgb(0, 255, 255),
+        rgb(0, 255, 0),
+        rgb(255, 80, 80),
+        Color::White,
+    ],
+
+    header: Color::Black,
+    header_action: Color::White,
+    header_bg_waiting: Color::Magenta,
+    header_bg_waiting_dark: Color::Black,
+    header_bg_ok: Color::Green,
+    header_bg_ok_dark: Color::Black,
+    header_bg_error: Color::Red,
+    header_bg_error_dark: Color::Black,
+
+    filter: Color::Black,
+    filter_active_bg: rgb(255, 255, 0),
+    filter_inactive_bg: rgb(200, 200, 0),
+
+    untracked: rgb(0, 255, 255),
+    unmodified: rgb(255, 255, 255),
+    modified: rgb(255, 255, 0),
+    added: rgb(0, 255, 0),
+    deleted: rgb(255, 64, 64),
+    renamed: rgb(128, 160, 255),
+    copied: rgb(255, 0, 255),
+    unmerged: rgb(255, 160, 0),
+    missing: rgb(255, 64, 64),
+    ignored: rgb(160, 160, 160),
+    clean: rgb(0, 255, 255),
+
+    vcs_colors: None,
+};
+
+pub const HIGH_CONTRAST_LIGHT: Theme = Theme {
+    selected_bg: rgb(255, 255, 0),
+    entry: rgb(0, 0, 160),
+    error: rgb(180, 0, 0),
+    warning: rgb(120, 70, 0),
+    log: [
+        Color::Black,
+        rgb(120, 60, 0),
+        rgb(0, 0, 160),
+        rgb(0, 100, 0),
+        rgb(180, 0, 0),
+        Color::Black,
+    ],
+
+    header: Color::Black,
+    header_action: Color::White,
+    header_bg_waiting: Color::Magenta,
+    header_bg_waiting_dark: Color::Black,
+    header_bg_ok: Color::Green,
+    header_bg_ok_dark: Color::Black,
+    header_bg_error: Color::Red,
+    header_bg_error_dark: Color::Black,
+
+    filter: Color::Black,
+    filter_active_bg: rgb(255, 255, 0),
+    filter_inactive_bg: rgb(220, 220, 120),
+
+    untracked: rgb(0, 0, 200),
+    unmodified: rgb(0, 0, 0),
+    modified: rgb(120, 70, 0),
+    added: rgb(0, 110, 0),
+    deleted: rgb(180, 0, 0),
+    renamed: rgb(0, 0, 160),
+    copied: rgb(130, 0, 130),
+    unmerged: rgb(150, 60, 0),
+    missing: rgb(180, 0, 0),
+    ignored: rgb(90, 90, 90),
+    clean: rgb(0, 0, 200),
+
+    vcs_colors: None,
+};
+
+/// Swaps red and green for blue and orange so additions and removals stay
+/// apart for people with red-green color blindness
+fn deuteranopia(base: Theme, light: bool) -> Theme {
+    let (blue, orange) = if light {
+        (rgb(0, 90, 200), rgb(200, 110, 0))
+    } else {
+        (rgb(80, 160, 255), rgb(255, 150, 0))
+    };
+
+    Theme {
+        error: orange,
+        header_bg_ok: Color::Cyan,
+        header_bg_ok_dark: Color::DarkCyan,
+        header_bg_error: Color::Yellow,
+        header_bg_error_dark: Color::DarkYellow,
+        untracked: orange,
+        added: blue,
+        deleted: orange,
+        missing: orange,
+        vcs_colors: Some(VcsColors {
+            added: "blue",
+            removed: "yellow",
+        }),
+        ..base
+    }
+}
+
+static CURRENT: RwLock<Option<Arc<Theme>>> = RwLock::new(None);
+
+/// Sets the theme from now on. The one set before is dropped once nothing
+/// uses it anymore
+pub fn set(theme: Theme) {
+    if let Ok(mut current) = CURRENT.write() {
+        *current = Some(Arc::new(theme));
+    }
+}
+
+pub fn current() -> Arc<Theme> {
+    match CURRENT.read() {
+        Ok(current) => match &*current {
+            Some(theme) => Arc::clone(theme),
+            None => Arc::new(DARK),
+        },
+        Err(_) => Arc::new(DARK),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Background {
+    Dark,
+    Light,
+    /// Asks the terminal and falls back to dark
+    Auto,
+}
+
+impl Background {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::Dark),
+            "light" => Some(Self::Light),
+            "auto" => Some(Self::Auto),
+            _ => None,
+        }
+    }
+
+    fn is_light(self) -> bool {
+        match self {
+            Self::Dark => false,
+            Self::Light => true,
+            Self::Auto => detect_background() == Some(Self::Light),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    Default,
+    HighContrast,
+    Deuteranopia,
+}
+
+impl Preset {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::Default),
+            "high-contrast" => Some(Self::HighContrast),
+            "deuteranopia" => Some(Self::Deuteranopia),
+            _ => None,
+        }
+    }
+
+    /// Builds the palette of this preset that suits `background`
+    pub fn theme(self, background: Background) -> Theme {
+        let light = background.is_light();
+        match (self, light) {
+            (Self::Default, false) => DARK,
+            (Self::Default, true) => LIGHT,
+            (Self::HighContrast, false) => HIGH_CONTRAST_DARK,
+            (Self::HighContrast, true) => HIGH_CONTRAST_LIGHT,
+            (Self::Deuteranopia, false) => deuteranopia(DARK, false),
+            (Self::Deuteranopia, true) => deuteranopia(LIGHT, true),
+        }
+    }
+}
+
+/// Looks at `COLORFGBG` first and then asks the terminal for its background
+/// color with OSC 11
+fn detect_background() -> Option<Background> {
+    if let Ok(colors) = env::var("COLORFGBG") {
+        // "foreground;background" using the basic color indices
+        if let Some(Ok(index)) = colors.rsplit(';').next().map(str::parse::<u8>)
+        {
+            let light = index == 7 || index == 15;
+            return Some(if light {
+                Background::Light
+            } else {
+                Background::Dark
+            });
+        }
+    }
+
+    if !io::stdin().is_tty() || !io::stdout().is_tty() {
+        return None;
+    }
+
+    terminal::enable_raw_mode().ok()?;
+    let response = query_background_color();
+    let _ = terminal::disable_raw_mode();
+
+    let (r, g, b) = parse_osc_color(&response?)?;
+    let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    Some(if luminance > 0.5 {
+        Background::Light
+    } else {
+        Background::Dark
+    })
+}
+
+/// Terminals answer with `ESC ] 11 ; rgb:RRRR/GGGG/BBBB` followed by BEL or
+/// `ESC \`. Terminals that do not understand the query stay silent
+fn query_background_color() -> Option<String> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]11;?\x07").ok()?;
+    stdout.flush().ok()?;
+
+    let deadline = Instant::now() + Duration::from_millis(100);
+    let mut response = String::new();
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        if timeout == Duration::from_millis(0)
+            || !event::poll(timeout).unwrap_or(false)
+        {
+            return None;
+        }
+
+        // the response arrives parsed as key presses
+        match event::read() {
+            Ok(Event::Key(key)) => match (key.code, key.modifiers) {
+                // BEL
+                (KeyCode::Char('g'), KeyModifiers::CONTROL) => break,
+                // ST
+                (KeyCode::Char('\\'), KeyModifiers::ALT) => break,
+                (KeyCode::Char(c), _) => response.push(c),
+                _ => (),
+            },
+            Ok(_) => (),
+            Err(_) => return None,
+        }
+    }
+
+    Some(response)
+}
+
+/// Parses the `rgb:RRRR/GGGG/BBBB` part of an OSC color response into values
+/// between 0 and 1
+fn parse_osc_color(response: &str) -> Option<(f32, f32, f32)> {
+    let rgb = &response[(response.find("rgb:")? + 4)..];
+    let mut channels = rgb.split('/').map(|channel| {
+        let digits: String = channel
+            .chars()
+            .take_while(char::is_ascii_hexdigit)
+            .collect();
+        let max = 16u32.checked_pow(digits.len() as u32)? - 1;
+        let value = u32::from_str_radix(&digits, 16).ok()?;
+        Some(value as f32 / max as f32)
+    });
+    Some((channels.next()??, channels.next()??, channels.next()??))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn osc_colors_are_parsed() {
+        let white = parse_osc_color("]11;rgb:ffff/ffff/ffff").unwrap();
+        assert_eq!(white, (1.0, 1.0, 1.0));
+
+        let (r, g, b) = parse_osc_color("]11;rgb:00/80/ff").unwrap();
+        assert_eq!((r, b), (0.0, 1.0));
+        assert!((g - 0.5).abs() < 0.01);
+
+        assert!(parse_osc_color("]11;rgb:zz/00").is_none());
+    }
+
+    #[test]
+    fn configured_colors_replace_their_roles() {
+        let colors = [
+            ("header".to_string(), "White".to_string()),
+            ("header_bg_waiting".into(), "#1a2B3c".into()),
+            ("entry".into(), "pink".into()),
+        ];
+        let theme = DARK.with_colors(&colors);
+        assert_eq!(theme.header, Color::White);
+        assert_eq!(theme.header_bg_waiting, rgb(0x1a, 0x2b, 0x3c));
+        assert_eq!(theme.entry, DARK.entry);
+
+        assert!(validate_colors(&colors[..2]).is_ok());
+        assert!(validate_colors(&colors).is_err());
+        let unknown = [("cursor".to_string(), "red".to_string())];
+        assert!(validate_colors(&unknown).is_err());
+        assert!(parse_color("#12345").is_none());
+    }
+
+    #[test]
+    fn deuteranopia_does_not_use_red_or_green_for_changes() {
+        let is_red_or_green = |color| match color {
+            Color::Rgb { r, g, b } => {
+                (r > 150 && g < 100 && b < 100)
+                    || (g > 150 && r < 100 && b < 100)
+            }
+            _ => true,
+        };
+
+        for &light in &[false, true] {
+            let base = if light { LIGHT } else { DARK };
+            let theme = deuteranopia(base, light);
+            for &color in &[theme.added, theme.deleted, theme.missing] {
+                assert!(!is_red_or_green(color));
+            }
+            assert!(theme.vcs_colors.is_some());
+        }
+    }
+}
@@ -2,37 +2,161 @@ use crossterm::{
     cursor,
     event::{KeyCode, KeyEvent, KeyModifiers},
     execute, queue,
-    style::{Print, ResetColor, SetForegroundColor},
+    style::{style, Print, ResetColor},
     terminal::{
         self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen,
         SetTitle,
     },
     ExecutableCommand, QueueableCommand, Result,
 };
+use rustyline::error::ReadlineError;
 
 use std::{
+    collections::HashMap,
     io::{stdout, Write},
-    iter, thread,
+    iter, panic, thread,
     time::Duration,
 };
 
 use crate::{
-    action::{ActionKind, ActionResult, ActionTask},
+    action::{
+        appended, ready, serial, serial_while_ok, ActionKind, ActionResult,
+        ActionTask, MergedBranch,
+    },
     application::{ActionFuture, Application},
-    input::{self, Event},
+    clipboard::Clipboard,
+    config::Config,
+    custom_actions::CustomAction,
+    diff, editor,
+    input::{
+        self, parse_line_range, validate_line_range, validate_path,
+        validate_ref_name, validate_revision, validate_url, Completion, Event,
+        Input,
+    },
+    key_bindings::{KeyBindings, Resolved},
+    open, repositories,
     scroll_view::ScrollView,
-    select::{select, Entry},
-    tui_util::{show_header, Header, HeaderKind, TerminalSize, ENTRY_COLOR},
+    scrollback::OutputMode,
+    select::{select, Entry, State},
+    temp_file::TempFile,
+    theme,
+    tui_util::{
+        draw_header, draw_text_in, fg, fit_color, show_header, AvailableSize,
+        Header, HeaderKind, Region, TerminalSize,
+    },
+    vcs_error::VcsError,
+    version_control_actions::{
+        DiffAlgorithm, DiffOptions, HistoryStep, Phase, RemoteOperation,
+        StashInclude, VersionControlActions, NO_ABSORB, NO_BOOKMARKS,
+        NO_HISTORY_EDITING, NO_OPERATION_TO_ABORT, NO_PHASES,
+    },
 };
 
+/// What a diff was run with so it can be run again with other options
+enum DiffView {
+    CurrentExport,
+    CurrentAll,
+    CurrentSelected(Vec<Entry>),
+    RevisionChanges(String),
+    RevisionAll(String),
+    RevisionSelected(String, Vec<Entry>),
+    Stash(String),
+}
+
+impl DiffView {
+    fn kind(&self) -> ActionKind {
+        match self {
+            Self::CurrentExport => ActionKind::CurrentFullRevision,
+            Self::CurrentAll => ActionKind::CurrentDiffAll,
+            Self::CurrentSelected(_) => ActionKind::CurrentDiffSelected,
+            Self::RevisionChanges(_) => ActionKind::RevisionChanges,
+            Self::RevisionAll(_) => ActionKind::RevisionDiffAll,
+            Self::RevisionSelected(..) => ActionKind::RevisionDiffSelected,
+            Self::Stash(_) => ActionKind::StashShow,
+        }
+    }
+
+    fn action(
+        &self,
+        version_control: &dyn VersionControlActions,
+        options: &DiffOptions,
+    ) -> Box<dyn ActionTask> {
+        match self {
+            Self::CurrentExport => version_control.current_export(options),
+            Self::CurrentAll => version_control.current_diff_all(options),
+            Self::CurrentSelected(entries) => {
+                version_control.current_diff_selected(entries, options)
+            }
+            Self::RevisionChanges(target) => {
+                version_control.revision_changes(target, options)
+            }
+            Self::RevisionAll(target) => {
+                version_control.revision_diff_all(target, options)
+            }
+            Self::RevisionSelected(target, entries) => {
+                version_control.revision_diff_selected(target, entries, options)
+            }
+            Self::Stash(name) => version_control.stash_show(name, options),
+        }
+    }
+}
+
+/// What a blame was run with so it can be shown again
+struct BlameView {
+    target: Option<String>,
+    file: String,
+    lines: Option<(usize, usize)>,
+    ignore_revs: bool,
+}
+
 const BIN_NAME: &'static str = env!("CARGO_PKG_NAME");
 const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
-pub fn show_tui(mut app: Application) {
+pub fn show_tui<I>(mut app: Application, input: I)
+where
+    I: Input,
+{
+    // make sure a panic message is printed to the normal screen
+    let default_panic_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        TerminalGuard::restore();
+        default_panic_hook(info);
+    }));
+
     let stdout = stdout();
     let stdout = stdout.lock();
-    let mut tui = Tui::new(stdout);
-    tui.show(&mut app).unwrap();
+    let mut tui = Tui::new(stdout, input);
+    // drawing already stops at the first write error, which happens when
+    // the terminal went away, and the guard restored the terminal by now
+    if let Err(error) = tui.show(&mut app) {
+        eprintln!("{}", error);
+    }
+}
+
+/// Shows the cursor and leaves the alternate screen and raw mode when dropped,
+/// even when unwinding
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter(raw_mode: bool) -> Result<Self> {
+        if raw_mode {
+            terminal::enable_raw_mode()?;
+        }
+        Ok(Self)
+    }
+
+    fn restore() {
+        let _ = input::capture_mouse(false);
+        let _ = terminal::disable_raw_mode();
+        let _ =
+            execute!(stdout(), ResetColor, cursor::Show, LeaveAlternateScreen);
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        Self::restore();
+    }
 }
 
 enum HandleChordResult {
@@ -41,29 +165,93 @@ enum HandleChordResult {
     Quit,
 }
 
-struct Tui<W>
+/// A view left for another one, to go back or forward to
+#[derive(Clone, Copy)]
+struct VisitedView {
+    kind: ActionKind,
+    /// The line at the top of the screen
+    scroll: usize,
+}
+
+struct Tui<W, I>
 where
     W: Write,
+    I: Input,
 {
     previous_action_kind: ActionKind,
     current_action_kind: ActionKind,
     current_key_chord: Vec<char>,
+    last_drawn_progress: String,
+    /// Of the running action, to draw again only once it printed more
+    last_drawn_output: String,
+    /// Base of the last merged branches listing
+    merged_base: String,
+    /// Blames walked through so far, the one being shown last
+    blame_stack: Vec<BlameView>,
+    /// Whether diffs show the old and new lines in two columns
+    side_by_side: bool,
+    last_diff: Option<DiffView>,
+    /// Index of the highlighted hunk and how many the diff has
+    current_hunk: Option<(usize, usize)>,
+    hunk_copied: bool,
+    /// What `yr`, `yp` or `yb` copied last, shown in the header until the next
+    /// output
+    copied: Option<String>,
+    /// Action whose prompt was left empty or escaped, shown in the header of
+    /// the view gone back to until the next action
+    canceled: Option<&'static str>,
+    /// Index of the file scrolled to and how many the diff has
+    current_file: Option<(usize, usize)>,
+    clipboard: Clipboard,
+    /// Views left behind, the last left last
+    back_views: Vec<VisitedView>,
+    /// Views gone back from, the last gone back from last
+    forward_views: Vec<VisitedView>,
+    /// Whether actions that change the working tree also show the status
+    /// once they finish
+    status_after_changes: bool,
+    /// Files picked for a commit whose message was canceled, by repository
+    /// root, so picking them again starts from there
+    canceled_commit_selections: HashMap<String, Vec<Entry>>,
+    /// The last fetch, pull or push with the remote it was picked with, to
+    /// run again in the terminal when it needs credentials
+    last_remote_operation: Option<(RemoteOperation, Option<String>)>,
 
     write: W,
+    input: I,
     terminal_size: TerminalSize,
     scroll_view: ScrollView,
 }
 
-impl<W> Tui<W>
+impl<W, I> Tui<W, I>
 where
     W: Write,
+    I: Input,
 {
-    fn new(write: W) -> Self {
+    fn new(write: W, input: I) -> Self {
         Tui {
             previous_action_kind: ActionKind::Quit,
             current_action_kind: ActionKind::Quit,
             current_key_chord: Vec::new(),
+            last_drawn_progress: String::new(),
+            last_drawn_output: String::new(),
+            merged_base: String::new(),
+            blame_stack: Vec::new(),
+            side_by_side: false,
+            last_diff: None,
+            current_hunk: None,
+            hunk_copied: false,
+            copied: None,
+            canceled: None,
+            current_file: None,
+            clipboard: Clipboard::detect(),
+            back_views: Vec::new(),
+            forward_views: Vec::new(),
+            status_after_changes: true,
+            canceled_commit_selections: HashMap::new(),
+            last_remote_operation: None,
             write,
+            input,
             terminal_size: Default::default(),
             scroll_view: Default::default(),
         }
@@ -74,20 +262,208 @@ where
         app: &Application,
         kind: HeaderKind,
     ) -> Result<()> {
-        let header = Header {
-            action_name: self.current_action_kind.name(),
-            directory_name: app.version_control.get_root(),
+        let progress = self.header_progress(app);
+        let action_name = self.header_action_name(app);
+        let header = app_header(app, &action_name, &progress);
+        show_header(&mut self.write, header, kind, self.terminal_size)?;
+        self.last_drawn_progress = progress;
+        Ok(())
+    }
+
+    /// Name of the current action along with the options it runs with
+    fn header_action_name(&self, app: &Application) -> String {
+        let name = self.action_name_with_options(app);
+        let name = match &self.copied {
+            Some(copied) => format!("{} (copied {})", name, copied),
+            None => name,
+        };
+        match self.canceled {
+            Some(canceled) => format!("{} ({} canceled)", name, canceled),
+            None => name,
+        }
+    }
+
+    fn action_name_with_options(&self, app: &Application) -> String {
+        let name = match self.current_action_kind {
+            ActionKind::BlameIgnoreRevs => ActionKind::Blame.name(),
+            kind => kind.name(),
+        };
+        match self.blame_stack.last() {
+            Some(view) if self.current_action_kind.is_blame() => {
+                if view.ignore_revs {
+                    format!("{} (ignoring revs)", name)
+                } else {
+                    name.into()
+                }
+            }
+            _ if self.current_action_kind.is_diff() => {
+                let mut options = app.diff_options.describe();
+                if let Some((index, count)) = self.current_hunk {
+                    options.push_str(&format!(
+                        ", hunk {} of {}",
+                        index + 1,
+                        count
+                    ));
+                    if self.hunk_copied {
+                        options.push_str(" copied");
+                    }
+                } else if let Some((index, count)) = self.current_file {
+                    options.push_str(&format!(
+                        ", file {} of {}",
+                        index + 1,
+                        count
+                    ));
+                }
+                format!("{} ({})", name, options)
+            }
+            _ if self.current_action_kind == ActionKind::RevisionChanges
+                && app.diff_options.ignore_whitespace =>
+            {
+                format!("{} (ignoring whitespace)", name)
+            }
+            _ => name.into(),
+        }
+    }
+
+    fn header_progress(&self, app: &Application) -> String {
+        const SPINNER: &[char] = &['|', '/', '-', '\\'];
+        match app.pending_action_elapsed(self.current_action_kind) {
+            Some(_) if app.quiet => "running".into(),
+            Some(elapsed) => {
+                let frame =
+                    (elapsed.as_millis() / 100) as usize % SPINNER.len();
+                format!("{} {}s", SPINNER[frame], elapsed.as_secs())
+            }
+            None => app
+                .action_duration(self.current_action_kind)
+                .map(finished_progress)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Redraws the header spinner while the current action is running
+    fn update_spinner(&mut self, app: &Application) -> Result<()> {
+        let progress = self.header_progress(app);
+        if progress.is_empty() || progress == self.last_drawn_progress {
+            return Ok(());
+        }
+
+        let action_name = self.header_action_name(app);
+        let header = app_header(app, &action_name, &progress);
+        draw_header(
+            &mut self.write,
+            header,
+            HeaderKind::Waiting,
+            self.terminal_size,
+        )?;
+        self.write.flush()?;
+        self.last_drawn_progress = progress;
+        Ok(())
+    }
+
+    /// Shows what the current action printed so far while it is running.
+    /// Outputs that are selected from wait until they are complete
+    fn update_output_so_far(&mut self, app: &Application) -> Result<()> {
+        if self.current_action_kind.can_select_output() {
+            return Ok(());
+        }
+        let output = match app.pending_action_output(self.current_action_kind) {
+            Some(output) => output,
+            None => return Ok(()),
         };
-        show_header(&mut self.write, header, kind, self.terminal_size)
+        if output == self.last_drawn_output {
+            return Ok(());
+        }
+
+        self.scroll_view.set_content(
+            &output[..],
+            self.current_action_kind,
+            self.terminal_size,
+        );
+        self.scroll_view
+            .draw_content(&mut self.write, self.terminal_size)?;
+        self.write.flush()?;
+        self.last_drawn_output = output;
+        Ok(())
     }
 
     fn show_select_ui(
         &mut self,
         app: &Application,
-        entries: &mut [Entry],
+        entries: &mut Vec<Entry>,
     ) -> Result<bool> {
-        self.show_header(app, HeaderKind::Waiting)?;
-        select(&mut self.write, entries)
+        self.show_select_ui_with_preview(app, entries, None)
+    }
+
+    /// Like `show_select_ui` for files changed in the working tree, whose
+    /// diff the right arrow shows
+    fn show_changed_files_select_ui(
+        &mut self,
+        app: &Application,
+        entries: &mut Vec<Entry>,
+    ) -> Result<bool> {
+        let preview = |entry: &Entry| match app
+            .version_control
+            .diff_file(&entry.filename, &app.diff_options)
+        {
+            Ok(diff) if diff.trim().is_empty() => format!(
+                "no diff to show for '{}'. it may be untracked",
+                entry.filename
+            ),
+            Ok(diff) => diff,
+            Err(error) => error.to_string(),
+        };
+        self.show_select_ui_with_preview(app, entries, Some(&preview))
+    }
+
+    fn show_select_ui_with_preview(
+        &mut self,
+        app: &Application,
+        entries: &mut Vec<Entry>,
+        preview: Option<&dyn Fn(&Entry) -> String>,
+    ) -> Result<bool> {
+        let progress = self.header_progress(app);
+        let action_name = self.header_action_name(app);
+        let header = app_header(app, &action_name, &progress);
+        show_header(
+            &mut self.write,
+            header,
+            HeaderKind::Waiting,
+            self.terminal_size,
+        )?;
+        select(
+            &mut self.write,
+            &mut self.input,
+            &mut self.terminal_size,
+            header,
+            app.version_control.get_root(),
+            entries,
+            preview,
+        )
+    }
+
+    /// Lets the user pick what happens to `revision` when editing history
+    fn pick_history_step(
+        &mut self,
+        app: &Application,
+        revision: &str,
+    ) -> Result<Option<HistoryStep>> {
+        let mut entries: Vec<_> = HistoryStep::ALL
+            .iter()
+            .map(|step| Entry {
+                filename: format!("{} {}", step.name(), revision),
+                selected: false,
+                state: State::Clean,
+                columns: None,
+            })
+            .collect();
+        if !self.show_select_ui(app, &mut entries)? {
+            return Ok(None);
+        }
+        Ok(entries
+            .iter()
+            .position(|e| e.selected)
+            .map(|i| HistoryStep::ALL[i]))
     }
 
     fn show_action(
@@ -95,158 +471,773 @@ where
         app: &mut Application,
         task: Box<dyn ActionTask>,
     ) -> Result<()> {
-        app.run_action(ActionFuture {
-            kind: self.current_action_kind,
-            task,
-        });
+        let kind = self.current_action_kind;
+        let task = if self.status_after_changes && kind.changes_working_tree() {
+            let title = format!("> {}", ActionKind::Status.name());
+            let title = style(title).with(fit_color(theme::current().entry));
+            serial(vec![
+                task,
+                ready(ActionResult::from_ok(title.to_string())),
+                app.version_control.status(),
+            ])
+        } else {
+            task
+        };
+        app.run_action(ActionFuture { kind, task });
         let result = app.get_cached_action_result(self.current_action_kind);
         self.show_result(app, result)
     }
 
-    fn show_empty_entries(&mut self, app: &Application) -> Result<()> {
-        self.show_header(app, HeaderKind::Error)?;
-        self.write.queue(Print("nothing to select"))?;
-        Ok(())
+    fn show_diff(
+        &mut self,
+        app: &mut Application,
+        view: DiffView,
+    ) -> Result<()> {
+        let action = view.action(&*app.version_control, &app.diff_options);
+        self.last_diff = Some(view);
+        self.show_action(app, action)
     }
 
-    fn show_previous_action_result(&mut self, app: &Application) -> Result<()> {
-        self.current_action_kind = self.previous_action_kind;
-        let result = app.get_cached_action_result(self.current_action_kind);
-        self.show_result(app, result)
+    /// Runs the last diff again with the current options when it is the one
+    /// being shown
+    fn rerun_diff(&mut self, app: &mut Application) -> Result<()> {
+        match &self.last_diff {
+            Some(view) if view.kind() == self.previous_action_kind => {
+                self.current_action_kind = self.previous_action_kind;
+                let action =
+                    view.action(&*app.version_control, &app.diff_options);
+                self.show_action(app, action)
+            }
+            _ => self.show_previous_action_result(app),
+        }
     }
 
-    fn action_context<F>(
-        &mut self,
-        action: ActionKind,
-        callback: F,
-    ) -> Result<HandleChordResult>
-    where
-        F: FnOnce(&mut Self) -> Result<()>,
-    {
-        self.previous_action_kind = self.current_action_kind;
-        self.current_action_kind = action;
-        callback(self).map(|_| HandleChordResult::Handled)
+    /// Highlights the next or previous hunk of the diff being shown
+    fn move_to_hunk(&mut self, app: &Application, forward: bool) -> Result<()> {
+        let hunks = diff::hunks(self.scroll_view.content());
+        if hunks.is_empty() {
+            return Ok(());
+        }
+        let last = hunks.len() - 1;
+        let top = self.scroll_view.top_line();
+        let index = match (self.current_hunk, forward) {
+            (None, true) => hunks.iter().position(|h| h.start >= top),
+            (None, false) => hunks.iter().rposition(|h| h.start < top),
+            (Some((index, _)), true) => Some((index + 1).min(last)),
+            (Some((index, _)), false) => Some(index.saturating_sub(1)),
+        };
+        let index = index.unwrap_or(if forward { last } else { 0 });
+        self.current_hunk = Some((index, hunks.len()));
+        self.hunk_copied = false;
+        self.current_file = None;
+        self.scroll_view.highlight(hunks[index].clone());
+        self.show_header(app, HeaderKind::Ok)?;
+        self.scroll_view
+            .draw_content(&mut self.write, self.terminal_size)
     }
 
-    fn previous_target<'a>(&self, app: &'a Application) -> Option<&'a str> {
-        let previous_result =
-            app.get_cached_action_result(self.previous_action_kind);
-        if !previous_result.success {
-            return None;
+    /// Scrolls to the next or previous file of the diff being shown
+    fn move_to_file(&mut self, app: &Application, forward: bool) -> Result<()> {
+        let files = diff::files(self.scroll_view.content());
+        let top = self.scroll_view.top_line();
+        let index = if forward {
+            files.iter().position(|f| f.start > top)
+        } else {
+            files.iter().rposition(|f| f.start < top)
+        };
+        match index {
+            Some(index) => self.scroll_to_file(app, &files, index),
+            None => Ok(()),
         }
+    }
 
+    /// Lets the user pick which file of the diff being shown to scroll to
+    fn jump_to_file(&mut self, app: &Application) -> Result<()> {
+        let files = diff::files(self.scroll_view.content());
+        let mut entries: Vec<_> = files
+            .iter()
+            .map(|f| Entry {
+                filename: f.name.clone(),
+                selected: false,
+                state: f.state.clone(),
+                columns: None,
+            })
+            .collect();
+        if self.show_select_ui(app, &mut entries)? {
+            if let Some(index) = entries.iter().position(|e| e.selected) {
+                return self.scroll_to_file(app, &files, index);
+            }
+        }
+        self.show_header(app, HeaderKind::Ok)?;
         self.scroll_view
-            .cursor()
-            .and_then(|c| previous_result.output.lines().nth(c))
-            .and_then(|l| self.previous_action_kind.parse_target(l))
+            .draw_content(&mut self.write, self.terminal_size)
     }
 
-    fn show(&mut self, app: &mut Application) -> Result<()> {
-        execute!(
-            self.write,
-            SetTitle(app.version_control.get_root()),
-            EnterAlternateScreen,
-            cursor::Hide
-        )?;
-        terminal::enable_raw_mode()?;
-
-        self.write.flush()?;
-        self.terminal_size = TerminalSize::get()?;
-
-        {
-            self.current_action_kind = ActionKind::Help;
-            let help = self.show_help(app)?;
-            self.show_result(app, &help)?;
-            self.show_current_key_chord()?;
-            self.write.flush()?;
+    fn scroll_to_file(
+        &mut self,
+        app: &Application,
+        files: &[diff::FileSection],
+        index: usize,
+    ) -> Result<()> {
+        self.current_file = Some((index, files.len()));
+        self.current_hunk = None;
+        self.scroll_view.scroll_to(files[index].start);
+        self.show_header(app, HeaderKind::Ok)?;
+        self.scroll_view
+            .draw_content(&mut self.write, self.terminal_size)
+    }
 
-            app.set_cached_action_result(ActionKind::Help, help);
+    /// Copies the highlighted hunk, highlighting the first one if there is
+    /// none yet
+    fn copy_hunk(&mut self, app: &Application) -> Result<()> {
+        if self.current_hunk.is_none() {
+            self.move_to_hunk(app, true)?;
         }
+        let index = match self.current_hunk {
+            Some((index, _)) => index,
+            None => return Ok(()),
+        };
 
-        loop {
-            if app.poll_and_check_action(self.current_action_kind) {
-                let result =
-                    app.get_cached_action_result(self.current_action_kind);
-                self.show_result(app, result)?;
-                self.write.flush()?;
-            }
-
-            match input::poll_event() {
-                Event::Resize(terminal_size) => {
-                    self.terminal_size = terminal_size;
-                    let result =
-                        app.get_cached_action_result(self.current_action_kind);
-                    self.show_result(app, result)?;
-                }
-                Event::Key(KeyEvent {
-                    code: KeyCode::Esc, ..
-                })
-                | Event::Key(KeyEvent {
-                    code: KeyCode::Char('c'),
-                    modifiers: KeyModifiers::CONTROL,
-                }) => {
-                    let esc_key_event = KeyEvent {
-                        code: KeyCode::Esc,
-                        modifiers: KeyModifiers::NONE,
-                    };
+        // side by side rows do not have the diff lines themselves
+        let result = app.get_cached_action_result(self.current_action_kind);
+        let diff = diff::strip_ansi(&result.output);
+        let lines: Vec<_> = diff.lines().collect();
+        let text = match diff::hunks(&diff).get(index) {
+            Some(hunk) => lines[hunk.clone()].join("\n") + "\n",
+            None => return Ok(()),
+        };
+        self.clipboard.copy(&text, &mut self.write)?;
+        self.hunk_copied = true;
+        self.show_header(app, HeaderKind::Ok)
+    }
 
-                    if self.scroll_view.update(
-                        &mut self.write,
-                        esc_key_event,
-                        self.terminal_size,
-                    )? {
-                        self.write.flush()?;
-                        continue;
-                    }
+    /// Copies `text`, described as `what` in the header, keeping the output
+    /// that is shown
+    fn copy_text(
+        &mut self,
+        app: &Application,
+        what: String,
+        text: &str,
+    ) -> Result<()> {
+        self.clipboard.copy(text, &mut self.write)?;
+        self.copied = Some(what);
+        self.show_header(app, HeaderKind::Ok)
+    }
 
-                    if self.current_key_chord.len() == 0 {
-                        break;
-                    }
+    /// Lets the user pick a remote when there is more than one, giving
+    /// `Some(None)` for the default one. With `all` the list starts on an
+    /// entry for all of them, which also gives `Some(None)`. On `None` the
+    /// pick was canceled or failed and something else is shown already
+    fn pick_remote(
+        &mut self,
+        app: &Application,
+        all: bool,
+    ) -> Result<Option<Option<String>>> {
+        let mut entries = match app.version_control.get_remotes() {
+            Ok(entries) => entries,
+            Err(error) => {
+                let result = ActionResult::from_err(error.to_string());
+                self.show_result(app, &result)?;
+                return Ok(None);
+            }
+        };
+        if entries.len() <= 1 {
+            return Ok(Some(None));
+        }
+        let all = all && app.version_control.has_all_remotes();
+        if all {
+            let entry = Entry {
+                filename: ALL_REMOTES.into(),
+                selected: false,
+                state: State::Branch,
+                columns: None,
+            };
+            entries.insert(0, entry);
+        }
+        if !self.show_select_ui(app, &mut entries)? {
+            self.show_previous_action_result(app)?;
+            return Ok(None);
+        }
+        match entries.iter().position(|e| e.selected) {
+            Some(0) if all => Ok(Some(None)),
+            Some(i) => Ok(Some(Some(entries.swap_remove(i).filename))),
+            None => {
+                self.show_previous_action_result(app)?;
+                Ok(None)
+            }
+        }
+    }
 
-                    self.current_key_chord.clear();
-                    self.show_current_key_chord()?;
-                    self.write.flush()?;
+    /// Offers to push a branch that has no upstream yet to `remote`, or the
+    /// first one when `None`, so it tracks the branch there. `None` when
+    /// there is nothing to offer or the user rather pushes as usual
+    fn push_new_branch(
+        &mut self,
+        app: &Application,
+        remote: Option<&str>,
+    ) -> Result<Option<Box<dyn ActionTask>>> {
+        let branch = match app.version_control.branch_without_upstream() {
+            Some(branch) => branch,
+            None => return Ok(None),
+        };
+        let remote = match remote {
+            Some(remote) => remote.to_string(),
+            None => match app.version_control.get_remotes() {
+                Ok(remotes) if !remotes.is_empty() => {
+                    remotes[0].filename.clone()
                 }
-                Event::Key(key_event) => {
-                    if self.scroll_view.update(
-                        &mut self.write,
-                        key_event,
-                        self.terminal_size,
-                    )? {
-                        self.write.flush()?;
-                        continue;
-                    }
+                _ => return Ok(None),
+            },
+        };
 
-                    if let Some(c) = input::key_to_char(key_event) {
-                        self.current_key_chord.push(c);
-                    }
+        self.show_header(app, HeaderKind::Waiting)?;
+        let question = format!(
+            "'{}' has no upstream. push it to '{}' and track it there?",
+            branch, remote
+        );
+        if !self.confirm(app, &question, true)? {
+            return Ok(None);
+        }
+        Ok(Some(
+            app.version_control.push_set_upstream(&remote, &branch),
+        ))
+    }
 
-                    match self.handle_key_chord(app)? {
-                        HandleChordResult::Handled => {
-                            self.current_key_chord.clear()
-                        }
-                        HandleChordResult::Unhandled => (),
-                        HandleChordResult::Quit => break,
-                    }
+    /// Lets the user pick one of the repositories next to the current one and
+    /// continues in it with the version control it uses
+    fn switch_repository(&mut self, app: &mut Application) -> Result<()> {
+        let running = app.running_action_names();
+        if !running.is_empty() {
+            let result = ActionResult::from_err(format!(
+                "wait for {} to finish before switching repositories",
+                running.join(", ")
+            ));
+            return self.show_action(app, ready(result));
+        }
+        let root = app.version_control.get_root();
+        let siblings = repositories::sibling_repositories(root);
+        if siblings.is_empty() {
+            let result = ActionResult::from_err(format!(
+                "there are no other git or hg repositories next to {}",
+                root
+            ));
+            return self.show_action(app, ready(result));
+        }
 
-                    self.show_current_key_chord()?;
-                    self.write.flush()?;
-                }
-                _ => (),
+        let mut entries: Vec<_> = siblings
+            .iter()
+            .map(|path| Entry {
+                filename: path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+                selected: false,
+                state: State::Clean,
+                columns: None,
+            })
+            .collect();
+        if !self.show_select_ui(app, &mut entries)? {
+            return self.show_previous_action_result(app);
+        }
+        let path = match entries.iter().position(|e| e.selected) {
+            Some(index) => &siblings[index],
+            None => return self.show_previous_action_result(app),
+        };
+        let path = path.to_string_lossy();
+        let repository = match repositories::open_repository(
+            &app.repository_config,
+            &path,
+        ) {
+            Ok(repository) => repository,
+            Err(error) => {
+                return self
+                    .show_action(app, ready(ActionResult::from_err(error)))
             }
+        };
 
-            thread::sleep(Duration::from_millis(20));
-        }
+        let custom_actions = CustomAction::load_custom_actions(
+            repository.version_control.get_root(),
+        );
+        app.switch_repository(repository, custom_actions);
+        self.back_views.clear();
+        self.forward_views.clear();
+        self.blame_stack.clear();
+        self.last_remote_operation = None;
+        let help = self.show_help(app)?;
+        app.set_cached_action_result(ActionKind::Help, help);
 
-        execute!(self.write, ResetColor, cursor::Show)?;
-        terminal::disable_raw_mode()?;
-        self.write.execute(LeaveAlternateScreen)?;
-        Ok(())
+        let root = app.version_control.get_root();
+        self.write.queue(SetTitle(root))?;
+        let result = ActionResult::from_ok(format!("switched to {}", root));
+        self.show_action(app, ready(result))
     }
 
-    fn handle_key_chord(
+    /// Asks whether to open the repository with `command` when the
+    /// `confirm_open` option is on
+    fn confirm_open(
         &mut self,
-        app: &mut Application,
+        app: &Application,
+        command: &str,
+    ) -> Result<bool> {
+        if !app.confirm_open {
+            return Ok(true);
+        }
+        self.show_header(app, HeaderKind::Waiting)?;
+        let question = format!(
+            "open {} with '{}'?",
+            app.version_control.get_root(),
+            command
+        );
+        self.confirm(app, &question, true)
+    }
+
+    /// Runs the blame on top of `blame_stack`
+    fn show_blame(&mut self, app: &mut Application) -> Result<()> {
+        let action = match self.blame_stack.last() {
+            Some(view) => app.version_control.blame(
+                view.target.as_deref(),
+                &view.file,
+                view.lines,
+                view.ignore_revs,
+            ),
+            None => return Ok(()),
+        };
+        self.show_action(app, action)
+    }
+
+    fn show_empty_entries(&mut self, app: &Application) -> Result<()> {
+        self.show_header(app, HeaderKind::Error)?;
+        self.write.queue(Print("nothing to select"))?;
+        Ok(())
+    }
+
+    /// Picks files, then the hunks of them to commit, showing each hunk's
+    /// lines with the right arrow
+    fn commit_hunks(&mut self, app: &mut Application) -> Result<()> {
+        let mut files = match app.current_changed_files() {
+            Ok(files) => files,
+            Err(error) => {
+                let result = ActionResult::from_err(error.to_string());
+                return self.show_result(app, &result);
+            }
+        };
+        if files.is_empty() {
+            return self.show_empty_entries(app);
+        }
+        if !self.show_changed_files_select_ui(app, &mut files)? {
+            return self.show_previous_action_result(app);
+        }
+        if !app.version_control.has_hunk_staging() {
+            self.show_header(app, HeaderKind::Waiting)?;
+            return match self.read_commit_message(app)? {
+                Some(message) => {
+                    let action = serial_while_ok(vec![
+                        ready(ActionResult::from_ok(
+                            WHOLE_FILES_COMMITTED.into(),
+                        )),
+                        app.version_control.commit_selected(
+                            message.trim(),
+                            &files,
+                            &app.commit_options,
+                        ),
+                    ]);
+                    self.show_action(app, action)
+                }
+                None => self.show_previous_action_result(app),
+            };
+        }
+
+        let mut patches = Vec::new();
+        let mut hunks = Vec::new();
+        for file in files.iter().filter(|f| f.selected) {
+            let patch = match app.version_control.hunk_diff(&file.filename) {
+                Ok(diff) => diff::FilePatch::parse(&diff),
+                Err(error) => {
+                    let result = ActionResult::from_err(error.to_string());
+                    return self.show_result(app, &result);
+                }
+            };
+            for hunk in &patch.hunks {
+                let header = hunk.lines().next().unwrap_or("");
+                hunks.push((
+                    Entry {
+                        filename: format!("{} {}", file.filename, header),
+                        selected: false,
+                        state: file.state.clone(),
+                        columns: None,
+                    },
+                    hunk.clone(),
+                ));
+            }
+            patches.push(patch);
+        }
+        if hunks.is_empty() {
+            let result = ActionResult::from_err(
+                "no hunks to pick. untracked files can be committed with 'cs'"
+                    .into(),
+            );
+            return self.show_result(app, &result);
+        }
+
+        let lines: Vec<_> =
+            hunks.iter().map(|(_, lines)| lines.clone()).collect();
+        let mut entries: Vec<_> = hunks.into_iter().map(|(e, _)| e).collect();
+        let labels: Vec<_> =
+            entries.iter().map(|e| e.filename.clone()).collect();
+        let preview = |entry: &Entry| {
+            labels
+                .iter()
+                .position(|label| *label == entry.filename)
+                .map(|i| lines[i].clone())
+                .unwrap_or_default()
+        };
+        if !self.show_select_ui_with_preview(
+            app,
+            &mut entries,
+            Some(&preview),
+        )? {
+            return self.show_previous_action_result(app);
+        }
+
+        let mut picked = entries.iter().map(|e| e.selected);
+        let patch: String = patches
+            .iter()
+            .map(|patch| {
+                let picked: Vec<_> =
+                    picked.by_ref().take(patch.hunks.len()).collect();
+                patch.with_hunks(&picked)
+            })
+            .collect();
+        if patch.is_empty() {
+            return self.show_previous_action_result(app);
+        }
+
+        self.show_header(app, HeaderKind::Waiting)?;
+        match self.read_commit_message(app)? {
+            Some(message) => {
+                let action = app.version_control.commit_patch(
+                    message.trim(),
+                    &patch,
+                    &app.commit_options,
+                );
+                self.show_action(app, action)
+            }
+            None => self.show_previous_action_result(app),
+        }
+    }
+
+    /// Has the terminal report the mouse when the configuration asks for it
+    fn capture_mouse(&self, app: &Application) -> Result<()> {
+        if self.input.uses_terminal() {
+            input::capture_mouse(app.mouse)?;
+        }
+        Ok(())
+    }
+
+    /// Leaves the screen to a program running in the terminal until `run`
+    /// returns
+    fn hand_over_terminal<T>(
+        &mut self,
+        run: impl FnOnce(&mut W) -> T,
+    ) -> Result<T> {
+        let uses_terminal = self.input.uses_terminal();
+        if uses_terminal {
+            input::pause_mouse_capture(true)?;
+            execute!(self.write, LeaveAlternateScreen, cursor::Show)?;
+            terminal::disable_raw_mode()?;
+        }
+        let result = run(&mut self.write);
+        if uses_terminal {
+            terminal::enable_raw_mode()?;
+            execute!(self.write, EnterAlternateScreen, cursor::Hide)?;
+            input::pause_mouse_capture(false)?;
+        }
+        Ok(result)
+    }
+
+    /// Lets the user pick a tag. `None` when there was nothing to pick or it
+    /// was canceled, with what to show instead already shown
+    fn pick_tag(&mut self, app: &Application) -> Result<Option<String>> {
+        let mut entries = match app.version_control.list_tags() {
+            Ok(entries) => entries,
+            Err(error) => {
+                let result = ActionResult::from_err(error.to_string());
+                self.show_result(app, &result)?;
+                return Ok(None);
+            }
+        };
+        if entries.is_empty() {
+            self.show_result(app, &ActionResult::from_ok("no tags".into()))?;
+            return Ok(None);
+        }
+        if !self.show_select_ui(app, &mut entries)? {
+            self.show_previous_action_result(app)?;
+            return Ok(None);
+        }
+        match entries.into_iter().find(|e| e.selected) {
+            Some(entry) => Ok(Some(entry.filename)),
+            None => {
+                self.show_previous_action_result(app)?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn show_previous_action_result(&mut self, app: &Application) -> Result<()> {
+        self.current_action_kind = self.previous_action_kind;
+        let result = app.get_cached_action_result(self.current_action_kind);
+        self.show_result(app, result)
+    }
+
+    fn visited_view(&self) -> VisitedView {
+        VisitedView {
+            kind: self.current_action_kind,
+            scroll: self.scroll_view.top_line(),
+        }
+    }
+
+    /// Remembers `left` to go back to it when another view is shown now.
+    /// Toggles and canceled prompts end up on the view they started from
+    fn remember_view(&mut self, app: &Application, left: VisitedView) {
+        if left.kind != self.current_action_kind
+            && app.has_action_result(left.kind)
+        {
+            self.back_views.push(left);
+            self.forward_views.clear();
+        }
+    }
+
+    /// Shows the view before the current one, or the one gone back from when
+    /// going `forward`, scrolled to where it was left
+    fn go_through_history(
+        &mut self,
+        app: &Application,
+        forward: bool,
+    ) -> Result<()> {
+        let current = self.visited_view();
+        let (from, to) = if forward {
+            (&mut self.forward_views, &mut self.back_views)
+        } else {
+            (&mut self.back_views, &mut self.forward_views)
+        };
+        let view = match from.pop() {
+            Some(view) => view,
+            None => return Ok(()),
+        };
+        to.push(current);
+
+        self.canceled = None;
+        self.previous_action_kind = self.current_action_kind;
+        self.current_action_kind = view.kind;
+        let result = app.get_cached_action_result(view.kind);
+        self.show_result(app, result)?;
+        self.scroll_view.scroll_to(view.scroll);
+        self.scroll_view
+            .draw_content(&mut self.write, self.terminal_size)
+    }
+
+    /// Shows the help screen verco starts with
+    fn show_home(&mut self, app: &Application) -> Result<()> {
+        self.previous_action_kind = self.current_action_kind;
+        self.current_action_kind = ActionKind::Help;
+        let help = app.get_cached_action_result(ActionKind::Help);
+        self.show_result(app, help)
+    }
+
+    fn action_context<F>(
+        &mut self,
+        action: ActionKind,
+        callback: F,
+    ) -> Result<HandleChordResult>
+    where
+        F: FnOnce(&mut Self) -> Result<()>,
+    {
+        self.previous_action_kind = self.current_action_kind;
+        self.current_action_kind = action;
+        self.canceled = None;
+        callback(self).map(|_| HandleChordResult::Handled)
+    }
+
+    fn previous_target<'a>(&self, app: &'a Application) -> Option<&'a str> {
+        self.cursor_target(app, self.previous_action_kind)
+    }
+
+    /// The revision or branch on the line under the cursor of `kind`'s output
+    fn cursor_target<'a>(
+        &self,
+        app: &'a Application,
+        kind: ActionKind,
+    ) -> Option<&'a str> {
+        let result = app.get_cached_action_result(kind);
+        if !result.success {
+            return None;
+        }
+
+        self.scroll_view
+            .cursor()
+            .and_then(|c| result.output.lines().nth(c))
+            .and_then(|l| kind.parse_target(l))
+    }
+
+    fn show(&mut self, app: &mut Application) -> Result<()> {
+        execute!(
+            self.write,
+            SetTitle(app.version_control.get_root()),
+            EnterAlternateScreen,
+            cursor::Hide
+        )?;
+        let _guard = TerminalGuard::enter(self.input.uses_terminal())?;
+        self.capture_mouse(app)?;
+
+        self.write.flush()?;
+        self.terminal_size = TerminalSize::get();
+        self.run(app)
+    }
+
+    /// The main loop, separate from the terminal setup so it can be driven
+    /// by any input
+    fn run(&mut self, app: &mut Application) -> Result<()> {
+        app.refresh_status();
+        self.scroll_view.wrap_diffs = app.wrap_diffs;
+        {
+            self.current_action_kind = ActionKind::Help;
+            let help = self.show_help(app)?;
+            self.show_result(app, &help)?;
+            self.show_current_key_chord()?;
+            self.write.flush()?;
+
+            app.set_cached_action_result(ActionKind::Help, help);
+        }
+        self.offer_resume(app)?;
+
+        loop {
+            if app.poll_and_check_action(self.current_action_kind) {
+                let result =
+                    app.get_cached_action_result(self.current_action_kind);
+                self.show_result(app, result)?;
+                self.write.flush()?;
+            }
+
+            self.update_spinner(app)?;
+            self.update_output_so_far(app)?;
+
+            match self.input.poll_event()? {
+                Event::Resize(terminal_size) => {
+                    self.terminal_size = terminal_size;
+                    let result =
+                        app.get_cached_action_result(self.current_action_kind);
+                    self.show_result(app, result)?;
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Esc, ..
+                })
+                | Event::Key(KeyEvent {
+                    code: KeyCode::Char('c'),
+                    modifiers: KeyModifiers::CONTROL,
+                }) => {
+                    let esc_key_event = KeyEvent {
+                        code: KeyCode::Esc,
+                        modifiers: KeyModifiers::NONE,
+                    };
+
+                    if self.scroll_view.update(
+                        &mut self.write,
+                        esc_key_event,
+                        self.terminal_size,
+                    )? {
+                        self.write.flush()?;
+                        continue;
+                    }
+
+                    if self.current_key_chord.is_empty() {
+                        if app.cancel_action(self.current_action_kind) {
+                            continue;
+                        }
+                        // results go back to the help screen, which quits
+                        if self.current_action_kind != ActionKind::Help {
+                            let left = self.visited_view();
+                            self.show_home(app)?;
+                            self.remember_view(app, left);
+                            self.write.flush()?;
+                            continue;
+                        }
+                        if self.confirm_quit(app)? {
+                            break;
+                        }
+                        continue;
+                    }
+
+                    self.current_key_chord.clear();
+                    self.show_current_key_chord()?;
+                    self.write.flush()?;
+                }
+                Event::Key(key_event) => {
+                    if self.scroll_view.update(
+                        &mut self.write,
+                        key_event,
+                        self.terminal_size,
+                    )? {
+                        self.write.flush()?;
+                        continue;
+                    }
+
+                    if let KeyEvent {
+                        code: code @ (KeyCode::Left | KeyCode::Right),
+                        modifiers: KeyModifiers::ALT,
+                    } = key_event
+                    {
+                        self.current_key_chord.clear();
+                        self.go_through_history(app, code == KeyCode::Right)?;
+                        self.show_current_key_chord()?;
+                        self.write.flush()?;
+                        continue;
+                    }
+
+                    if let Some(c) = input::key_to_char(key_event) {
+                        self.current_key_chord.push(c);
+                    }
+
+                    let left = self.visited_view();
+                    let handled =
+                        match app.key_bindings.resolve(&self.current_key_chord)
+                        {
+                            Resolved::Chord(chord) => {
+                                self.current_key_chord = chord;
+                                self.handle_key_chord(app)?
+                            }
+                            Resolved::Pending => HandleChordResult::Unhandled,
+                            Resolved::Unbound => HandleChordResult::Handled,
+                        };
+                    match handled {
+                        HandleChordResult::Handled => {
+                            self.current_key_chord.clear();
+                            self.remember_view(app, left);
+                        }
+                        HandleChordResult::Unhandled => (),
+                        HandleChordResult::Quit => {
+                            self.current_key_chord.clear();
+                            if self.confirm_quit(app)? {
+                                break;
+                            }
+                        }
+                    }
+
+                    self.show_current_key_chord()?;
+                    self.write.flush()?;
+                }
+                _ => (),
+            }
+
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        Ok(())
+    }
+
+    fn handle_key_chord(
+        &mut self,
+        app: &mut Application,
     ) -> Result<HandleChordResult> {
         match &self.current_key_chord[..] {
             ['q'] => Ok(HandleChordResult::Quit),
@@ -260,18 +1251,58 @@ where
                 let action = app.version_control.status();
                 s.show_action(app, action)
             }),
+            // shows like the status it runs again
+            ['S'] => self.action_context(ActionKind::Status, |s| {
+                app.refresh_status();
+                let action = app.version_control.status();
+                s.show_action(app, action)
+            }),
             ['l'] => self.action_context(ActionKind::Log, |s| {
-                let action =
-                    app.version_control.log(s.terminal_size.height as usize);
+                let action = app.log(0, app.log_count);
                 s.show_action(app, action)
             }),
             ['L'] => Ok(HandleChordResult::Unhandled),
+            ['L', 'S'] => self.action_context(ActionKind::LogSignatures, |s| {
+                app.log_signatures = !app.log_signatures;
+                let action = app.log(0, app.log_count);
+                s.show_action(app, action)
+            }),
+            ['L', 'G'] => self.action_context(ActionKind::LogGraph, |s| {
+                app.log_graph = !app.log_graph;
+                let action = app.log(0, app.log_count);
+                s.show_action(app, action)
+            }),
+            // older revisions go below the log being shown, keeping its cursor
+            ['L', 'M'] => {
+                let kind = match self.current_action_kind {
+                    kind @ (ActionKind::Log
+                    | ActionKind::LogCount
+                    | ActionKind::LogSignatures
+                    | ActionKind::LogGraph) => kind,
+                    _ => ActionKind::Log,
+                };
+                self.action_context(kind, |s| {
+                    let shown = app.get_cached_action_result(kind);
+                    let shown = if shown.success {
+                        shown.output.clone()
+                    } else {
+                        String::new()
+                    };
+                    let skip = shown
+                        .lines()
+                        .filter(|line| kind.parse_target(line).is_some())
+                        .count();
+                    let older = app.log(skip, app.log_count);
+                    s.show_action(app, appended(shown, older))
+                })
+            }
             ['L', 'C'] => self.action_context(ActionKind::LogCount, |s| {
+                let default = app.log_count.to_string();
                 if let Some(input) =
-                    s.handle_input(app, "logs to show", None)?
+                    s.handle_input(app, "logs to show", Some(&default))?
                 {
                     if let Ok(count) = input.trim().parse() {
-                        let action = app.version_control.log(count);
+                        let action = app.log(0, count);
                         s.show_action(app, action)
                     } else {
                         s.show_header(app, HeaderKind::Error)?;
@@ -286,51 +1317,70 @@ where
                 }
             }),
             ['e'] => Ok(HandleChordResult::Unhandled),
-            ['e', 'e'] => {
-                self.action_context(ActionKind::CurrentFullRevision, |s| {
-                    let action = app.version_control.current_export();
-                    s.show_action(app, action)
-                })
-            }
+            ['e', 'e'] => self
+                .action_context(ActionKind::CurrentFullRevision, |s| {
+                    s.show_diff(app, DiffView::CurrentExport)
+                }),
             ['d'] => Ok(HandleChordResult::Unhandled),
-            ['d', 'd'] => {
-                self.action_context(ActionKind::CurrentDiffAll, |s| {
-                    let action = app.version_control.current_diff_all();
-                    s.show_action(app, action)
-                })
-            }
+            ['d', 'd'] => self
+                .action_context(ActionKind::CurrentDiffAll, |s| {
+                    s.show_diff(app, DiffView::CurrentAll)
+                }),
             ['d', 's'] => {
                 self.action_context(ActionKind::CurrentDiffSelected, |s| {
-                    match app.version_control.get_current_changed_files() {
+                    match app.current_changed_files() {
                         Ok(mut entries) => {
                             if entries.len() == 0 {
                                 s.show_empty_entries(app)
-                            } else if s.show_select_ui(app, &mut entries[..])? {
-                                let action = app
-                                    .version_control
-                                    .current_diff_selected(&entries);
-                                s.show_action(app, action)
+                            } else if s.show_changed_files_select_ui(app, &mut entries)? {
+                                let view = DiffView::CurrentSelected(entries);
+                                s.show_diff(app, view)
                             } else {
                                 s.show_previous_action_result(app)
                             }
                         }
-                        Err(error) => {
-                            s.show_result(app, &ActionResult::from_err(error))
-                        }
+                        Err(error) => s.show_result(
+                            app,
+                            &ActionResult::from_err(error.to_string()),
+                        ),
                     }
                 })
             }
             ['D'] => Ok(HandleChordResult::Unhandled),
+            ['D', 'I'] => {
+                self.action_context(ActionKind::RevisionDetails, |s| {
+                    if let Some(input) = s.handle_input_validated(
+                        app,
+                        "show details of",
+                        s.previous_target(app),
+                        validate_revision,
+                    )? {
+                        let result = match app
+                            .version_control
+                            .revision_details(input.trim())
+                        {
+                            Ok(details) => ActionResult::from_ok(details),
+                            Err(error) => {
+                                ActionResult::from_err(error.to_string())
+                            }
+                        };
+                        s.show_action(app, ready(result))
+                    } else {
+                        s.show_previous_action_result(app)
+                    }
+                })
+            }
             ['D', 'C'] => {
                 self.action_context(ActionKind::RevisionChanges, |s| {
-                    if let Some(input) = s.handle_input(
+                    if let Some(input) = s.handle_input_validated(
                         app,
                         "show changes from",
                         s.previous_target(app),
+                        validate_revision,
                     )? {
-                        let action =
-                            app.version_control.revision_changes(input.trim());
-                        s.show_action(app, action)
+                        let view =
+                            DiffView::RevisionChanges(input.trim().into());
+                        s.show_diff(app, view)
                     } else {
                         s.show_previous_action_result(app)
                     }
@@ -338,14 +1388,14 @@ where
             }
             ['D', 'D'] => {
                 self.action_context(ActionKind::RevisionDiffAll, |s| {
-                    if let Some(input) = s.handle_input(
+                    if let Some(input) = s.handle_input_validated(
                         app,
                         "show diff from",
                         s.previous_target(app),
+                        validate_revision,
                     )? {
-                        let action =
-                            app.version_control.revision_diff_all(input.trim());
-                        s.show_action(app, action)
+                        let view = DiffView::RevisionAll(input.trim().into());
+                        s.show_diff(app, view)
                     } else {
                         s.show_previous_action_result(app)
                     }
@@ -353,10 +1403,11 @@ where
             }
             ['D', 'S'] => {
                 self.action_context(ActionKind::RevisionDiffSelected, |s| {
-                    if let Some(input) = s.handle_input(
+                    if let Some(input) = s.handle_input_validated(
                         app,
                         "show diff from",
                         s.previous_target(app),
+                        validate_revision,
                     )? {
                         match app
                             .version_control
@@ -365,23 +1416,19 @@ where
                             Ok(mut entries) => {
                                 if entries.len() == 0 {
                                     s.show_empty_entries(app)
-                                } else if s
-                                    .show_select_ui(app, &mut entries[..])?
-                                {
-                                    let action = app
-                                        .version_control
-                                        .revision_diff_selected(
-                                            input.trim(),
-                                            &entries,
-                                        );
-                                    s.show_action(app, action)
+                                } else if s.show_select_ui(app, &mut entries)? {
+                                    let view = DiffView::RevisionSelected(
+                                        input.trim().into(),
+                                        entries,
+                                    );
+                                    s.show_diff(app, view)
                                 } else {
                                     s.show_previous_action_result(app)
                                 }
                             }
                             Err(error) => s.show_result(
                                 app,
-                                &ActionResult::from_err(error),
+                                &ActionResult::from_err(error.to_string()),
                             ),
                         }
                     } else {
@@ -391,10 +1438,30 @@ where
             }
             ['c'] => Ok(HandleChordResult::Unhandled),
             ['c', 'c'] => self.action_context(ActionKind::CommitAll, |s| {
-                if let Some(input) =
-                    s.handle_input(app, "commit message", None)?
-                {
-                    let action = app.version_control.commit_all(input.trim());
+                if let Some(input) = s.read_commit_message(app)? {
+                    let action = app
+                        .version_control
+                        .commit_all(input.trim(), &app.commit_options);
+                    s.show_action(app, action)
+                } else {
+                    s.show_previous_action_result(app)
+                }
+            }),
+            ['c', 'p'] => self.action_context(ActionKind::CommitAndPush, |s| {
+                if let Some(input) = s.read_commit_message(app)? {
+                    let remote = match s.pick_remote(app, false)? {
+                        Some(remote) => remote,
+                        None => return Ok(()),
+                    };
+                    let title = format!("> {}", ActionKind::Push.name());
+                    let title =
+                        style(title).with(fit_color(theme::current().entry));
+                    let action = serial_while_ok(vec![
+                        app.version_control
+                            .commit_all(input.trim(), &app.commit_options),
+                        ready(ActionResult::from_ok(title.to_string())),
+                        app.version_control.push(remote.as_deref()),
+                    ]);
                     s.show_action(app, action)
                 } else {
                     s.show_previous_action_result(app)
@@ -402,67 +1469,199 @@ where
             }),
             ['c', 's'] => {
                 self.action_context(ActionKind::CommitSelected, |s| {
-                    match app.version_control.get_current_changed_files() {
+                    match app.current_changed_files() {
                         Ok(mut entries) => {
+                            let root = app.version_control.get_root();
+                            if let Some(previous) =
+                                s.canceled_commit_selections.remove(root)
+                            {
+                                restore_selection(&mut entries, &previous);
+                            }
                             if entries.len() == 0 {
                                 s.show_empty_entries(app)
-                            } else if s.show_select_ui(app, &mut entries[..])? {
+                            } else if s.show_changed_files_select_ui(app, &mut entries)? {
                                 s.show_header(app, HeaderKind::Waiting)?;
                                 if let Some(input) =
-                                    s.handle_input(app, "commit message", None)?
+                                    s.read_commit_message(app)?
                                 {
                                     let action =
                                         app.version_control.commit_selected(
                                             input.trim(),
                                             &entries,
+                                            &app.commit_options,
                                         );
                                     s.show_action(app, action)
                                 } else {
+                                    s.canceled_commit_selections
+                                        .insert(root.into(), entries);
                                     s.show_previous_action_result(app)
                                 }
                             } else {
                                 s.show_previous_action_result(app)
                             }
                         }
-                        Err(error) => {
-                            s.show_result(app, &ActionResult::from_err(error))
-                        }
+                        Err(error) => s.show_result(
+                            app,
+                            &ActionResult::from_err(error.to_string()),
+                        ),
                     }
                 })
             }
+            ['c', 'h'] => self.action_context(ActionKind::CommitHunks, |s| {
+                s.commit_hunks(app)
+            }),
+            ['c', 'u'] => self.action_context(ActionKind::UndoLastCommit, |s| {
+                let question =
+                    "undo the last commit? its changes stay in the working tree";
+                s.show_header(app, HeaderKind::Waiting)?;
+                if !s.confirm(app, question, false)? {
+                    return s.show_previous_action_result(app);
+                }
+                let action = app.version_control.undo_last_commit();
+                s.show_action(app, action)
+            }),
+            ['c', 'a'] => self.action_context(ActionKind::Absorb, |s| {
+                if !app.version_control.has_absorb() {
+                    let result = ActionResult::from_err(NO_ABSORB.into());
+                    return s.show_result(app, &result);
+                }
+                let preview = match app.version_control.absorb_preview() {
+                    Ok(preview) => preview,
+                    Err(error) => {
+                        let result = ActionResult::from_err(error.to_string());
+                        return s.show_result(app, &result);
+                    }
+                };
+                let lines: Vec<_> = preview.lines().map(String::from).collect();
+                if s.confirm_list(
+                    app,
+                    "changes will be amended into these revisions",
+                    &lines,
+                    false,
+                )? {
+                    let action = app.version_control.absorb();
+                    s.show_action(app, action)
+                } else {
+                    s.show_previous_action_result(app)
+                }
+            }),
             ['u'] => self.action_context(ActionKind::Update, |s| {
-                if let Some(input) =
-                    s.handle_input(app, "update to", s.previous_target(app))?
-                {
+                let upstream = app.version_control.upstream_branch().ok();
+                let default = s.previous_target(app).or(upstream.as_deref());
+                if let Some(input) = s.handle_input_validated(
+                    app,
+                    "update to",
+                    default,
+                    validate_revision,
+                )? {
                     let action = app.version_control.update(input.trim());
                     s.show_action(app, action)
                 } else {
                     s.show_previous_action_result(app)
                 }
             }),
+            ['U'] => self.action_context(ActionKind::UpdateSubmodules, |s| {
+                let action = app.version_control.update_submodules(true, true);
+                s.show_action(app, action)
+            }),
             ['m'] => self.action_context(ActionKind::Merge, |s| {
-                if let Some(input) =
-                    s.handle_input(app, "merge with", s.previous_target(app))?
-                {
+                let upstream = app.version_control.upstream_branch().ok();
+                let default = s.previous_target(app).or(upstream.as_deref());
+                if let Some(input) = s.handle_input_validated(
+                    app,
+                    "merge with",
+                    default,
+                    validate_revision,
+                )? {
                     let action = app.version_control.merge(input.trim());
                     s.show_action(app, action)
                 } else {
                     s.show_previous_action_result(app)
                 }
             }),
+            ['k'] => Ok(HandleChordResult::Unhandled),
+            ['k', 'k'] => self.action_context(ActionKind::CherryPick, |s| {
+                if let Some(input) = s.handle_input_validated(
+                    app,
+                    "cherry-pick revision",
+                    s.previous_target(app),
+                    validate_revision,
+                )? {
+                    let action = app.version_control.cherry_pick(input.trim());
+                    s.show_action(app, action)
+                } else {
+                    s.show_previous_action_result(app)
+                }
+            }),
+            ['k', 'c'] => {
+                self.action_context(ActionKind::ContinueCherryPick, |s| {
+                    let action = app.version_control.continue_cherry_pick();
+                    s.show_action(app, action)
+                })
+            }
+            ['k', 'a'] => {
+                self.action_context(ActionKind::AbortCherryPick, |s| {
+                    let action = app.version_control.abort_cherry_pick();
+                    s.show_action(app, action)
+                })
+            }
+            ['B'] => Ok(HandleChordResult::Unhandled),
+            ['B', 'B'] => self.action_context(ActionKind::Rebase, |s| {
+                let upstream = app.version_control.upstream_branch().ok();
+                let default = s.previous_target(app).or(upstream.as_deref());
+                if let Some(input) = s.handle_input_validated(
+                    app,
+                    "rebase onto",
+                    default,
+                    validate_revision,
+                )? {
+                    let action = app.version_control.rebase(input.trim());
+                    s.show_action(app, action)
+                } else {
+                    s.show_previous_action_result(app)
+                }
+            }),
+            ['B', 'c'] => self.action_context(ActionKind::ContinueRebase, |s| {
+                let action = app.version_control.continue_rebase();
+                s.show_action(app, action)
+            }),
+            ['B', 'a'] => self.action_context(ActionKind::AbortRebase, |s| {
+                let action = app.version_control.abort_rebase();
+                s.show_action(app, action)
+            }),
             ['R'] => Ok(HandleChordResult::Unhandled),
             ['R', 'A'] => self.action_context(ActionKind::RevertAll, |s| {
-                let action = app.version_control.revert_all();
-                s.show_action(app, action)
+                let filenames: Vec<_> = app
+                    .current_changed_files()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|e| e.filename)
+                    .collect();
+                if s.confirm_list_losing_work(
+                    app,
+                    "all changes will be reverted",
+                    &filenames,
+                )? {
+                    let action = app.version_control.revert_all();
+                    s.show_action(app, action)
+                } else {
+                    s.show_previous_action_result(app)
+                }
             }),
             ['r'] => Ok(HandleChordResult::Unhandled),
             ['r', 's'] => {
                 self.action_context(ActionKind::RevertSelected, |s| {
-                    match app.version_control.get_current_changed_files() {
+                    match app.current_changed_files() {
                         Ok(mut entries) => {
                             if entries.len() == 0 {
                                 s.show_empty_entries(app)
-                            } else if s.show_select_ui(app, &mut entries[..])? {
+                            } else if s.show_changed_files_select_ui(app, &mut entries)?
+                                && s.confirm_list_losing_work(
+                                    app,
+                                    "these files will be reverted",
+                                    &selected_filenames(&entries),
+                                )?
+                            {
                                 let action = app
                                     .version_control
                                     .revert_selected(&entries);
@@ -471,12 +1670,74 @@ where
                                 s.show_previous_action_result(app)
                             }
                         }
-                        Err(error) => {
-                            s.show_result(app, &ActionResult::from_err(error))
-                        }
+                        Err(error) => s.show_result(
+                            app,
+                            &ActionResult::from_err(error.to_string()),
+                        ),
                     }
                 })
             }
+            ['r', 'v'] => self.action_context(ActionKind::ResetSelected, |s| {
+                let target = match s.handle_input_validated(
+                    app,
+                    "reset to revision",
+                    s.previous_target(app),
+                    validate_revision,
+                )? {
+                    Some(target) => target,
+                    None => return s.show_previous_action_result(app),
+                };
+                let target = target.trim();
+                let mut entries =
+                    match app.version_control.get_changed_files_since(target) {
+                        Ok(entries) => entries,
+                        Err(error) => {
+                            return s.show_result(
+                                app,
+                                &ActionResult::from_err(error.to_string()),
+                            )
+                        }
+                    };
+                if entries.is_empty() {
+                    return s.show_empty_entries(app);
+                }
+                if !s.show_select_ui(app, &mut entries)? {
+                    return s.show_previous_action_result(app);
+                }
+
+                let working_tree = if app.version_control.has_staging_area() {
+                    match s.choose(
+                        app,
+                        "what should be reset?",
+                        &[
+                            ('i', "only the index, keeping the files"),
+                            ('w', "the index and the working tree"),
+                        ],
+                    )? {
+                        Some('i') => false,
+                        Some('w') => true,
+                        _ => return s.show_previous_action_result(app),
+                    }
+                } else {
+                    true
+                };
+                if working_tree
+                    && !s.confirm_list_losing_work(
+                        app,
+                        "these files will be overwritten",
+                        &selected_filenames(&entries),
+                    )?
+                {
+                    return s.show_previous_action_result(app);
+                }
+
+                let action = app.version_control.reset_selected(
+                    target,
+                    &entries,
+                    working_tree,
+                );
+                s.show_action(app, action)
+            }),
             ['r', 'r'] => {
                 self.action_context(ActionKind::UnresolvedConflicts, |s| {
                     let action = app.version_control.conflicts();
@@ -485,48 +1746,264 @@ where
             }
             ['r', 'o'] => {
                 self.action_context(ActionKind::MergeTakingOther, |s| {
-                    let action = app.version_control.take_other();
-                    s.show_action(app, action)
+                    let conflicts = conflicted_filenames(app);
+                    if s.confirm_list_losing_work(
+                        app,
+                        "conflicts will be resolved taking the other side",
+                        &conflicts,
+                    )? {
+                        let action = app.version_control.take_other();
+                        s.show_action(app, action)
+                    } else {
+                        s.show_previous_action_result(app)
+                    }
                 })
             }
             ['r', 'l'] => {
                 self.action_context(ActionKind::MergeTakingLocal, |s| {
-                    let action = app.version_control.take_local();
-                    s.show_action(app, action)
+                    let conflicts = conflicted_filenames(app);
+                    if s.confirm_list_losing_work(
+                        app,
+                        "conflicts will be resolved taking the local side",
+                        &conflicts,
+                    )? {
+                        let action = app.version_control.take_local();
+                        s.show_action(app, action)
+                    } else {
+                        s.show_previous_action_result(app)
+                    }
                 })
             }
+            ['r', 'a'] => self.action_context(ActionKind::AbortOperation, |s| {
+                let operation = match app.version_control.operation_in_progress()
+                {
+                    Some(operation) => operation,
+                    None => {
+                        let result =
+                            ActionResult::from_err(NO_OPERATION_TO_ABORT.into());
+                        return s.show_result(app, &result);
+                    }
+                };
+                let question = format!(
+                    "abort the {}? the changes and resolutions it has so far are lost",
+                    operation
+                );
+                s.show_header(app, HeaderKind::Waiting)?;
+                if !s.confirm_losing_work(&question)? {
+                    return s.show_previous_action_result(app);
+                }
+                let action = app.version_control.abort_operation();
+                s.show_action(app, action)
+            }),
             ['f'] => self.action_context(ActionKind::Fetch, |s| {
-                let action = app.version_control.fetch();
+                let remote = match s.pick_remote(app, true)? {
+                    Some(remote) => remote,
+                    None => return Ok(()),
+                };
+                let action = app.version_control.fetch(remote.as_deref());
+                s.last_remote_operation = Some((RemoteOperation::Fetch, remote));
                 s.show_action(app, action)
             }),
             ['p'] => self.action_context(ActionKind::Pull, |s| {
-                let action = app.version_control.pull();
+                let remote = match s.pick_remote(app, true)? {
+                    Some(remote) => remote,
+                    None => return Ok(()),
+                };
+                let action = app.version_control.pull(remote.as_deref());
+                s.last_remote_operation = Some((RemoteOperation::Pull, remote));
                 s.show_action(app, action)
             }),
             ['P'] => self.action_context(ActionKind::Push, |s| {
-                let action = app.version_control.push();
+                let remote = match s.pick_remote(app, false)? {
+                    Some(remote) => remote,
+                    None => return Ok(()),
+                };
+                let action = match s.push_new_branch(app, remote.as_deref())? {
+                    Some(action) => action,
+                    None => app.version_control.push(remote.as_deref()),
+                };
+                s.last_remote_operation = Some((RemoteOperation::Push, remote));
+                s.show_action(app, action)
+            }),
+            ['T'] => self.action_context(ActionKind::RunInTerminal, |s| {
+                let (operation, remote) = match &s.last_remote_operation {
+                    Some((operation, remote)) => (*operation, remote.clone()),
+                    None => {
+                        let result = ActionResult::from_err(
+                            "there is no fetch, pull or push to run again. \
+                             use 'f', 'p' or 'P' first"
+                                .into(),
+                        );
+                        return s.show_result(app, &result);
+                    }
+                };
+                let args = app
+                    .version_control
+                    .remote_args(operation, remote.as_deref());
+                let output = s.hand_over_terminal(|write| {
+                    let name = app.version_control.name();
+                    let _ = writeln!(write, "> {} {}", name, args.join(" "));
+                    let _ = write.flush();
+                    app.version_control.run_in_terminal(&args)
+                })?;
+                let result = match output {
+                    Ok(output) => ActionResult::from_ok(output),
+                    Err(error) => ActionResult::from_err(error.to_string()),
+                };
+                s.show_action(app, ready(result))
+            }),
+            ['R', 'l'] => self.action_context(ActionKind::ListRemotes, |s| {
+                let action = app.version_control.list_remotes();
+                s.show_action(app, action)
+            }),
+            ['R', 'n'] => self.action_context(ActionKind::NewRemote, |s| {
+                let name = match s.handle_input_validated(
+                    app,
+                    "new remote name",
+                    None,
+                    validate_ref_name,
+                )? {
+                    Some(name) => name.trim().to_string(),
+                    None => return s.show_previous_action_result(app),
+                };
+                let remotes =
+                    app.version_control.get_remotes().unwrap_or_default();
+                if remotes.iter().any(|remote| remote.filename == name) {
+                    let result = ActionResult::from_err(format!(
+                        "there is already a remote called '{}'",
+                        name
+                    ));
+                    return s.show_result(app, &result);
+                }
+                let prompt = format!("url of '{}'", name);
+                match s.handle_input_validated(app, &prompt, None, validate_url)?
+                {
+                    Some(url) => {
+                        let action =
+                            app.version_control.add_remote(&name, url.trim());
+                        s.show_action(app, action)
+                    }
+                    None => s.show_previous_action_result(app),
+                }
+            }),
+            ['R', 'd'] => self.action_context(ActionKind::DeleteRemote, |s| {
+                let mut entries = match app.version_control.get_remotes() {
+                    Ok(entries) => entries,
+                    Err(error) => {
+                        let result = ActionResult::from_err(error.to_string());
+                        return s.show_result(app, &result);
+                    }
+                };
+                if entries.is_empty() {
+                    let result = ActionResult::from_ok("no remotes".into());
+                    return s.show_result(app, &result);
+                }
+                if !s.show_select_ui(app, &mut entries)? {
+                    return s.show_previous_action_result(app);
+                }
+                let name = match entries.into_iter().find(|e| e.selected) {
+                    Some(entry) => entry.filename,
+                    None => return s.show_previous_action_result(app),
+                };
+                s.show_header(app, HeaderKind::Waiting)?;
+                if !s.confirm(app, &format!("delete remote '{}'?", name), false)? {
+                    return s.show_previous_action_result(app);
+                }
+                let action = app.version_control.remove_remote(&name);
                 s.show_action(app, action)
             }),
             ['t'] => Ok(HandleChordResult::Unhandled),
             ['t', 'n'] => self.action_context(ActionKind::NewTag, |s| {
-                if let Some(input) =
-                    s.handle_input(app, "new tag name", None)?
-                {
+                if let Some(input) = s.handle_input_validated(
+                    app,
+                    "new tag name",
+                    None,
+                    validate_ref_name,
+                )? {
                     let action = app.version_control.create_tag(input.trim());
                     s.show_action(app, action)
                 } else {
                     s.show_previous_action_result(app)
                 }
             }),
+            ['t', 'u'] => self.action_context(ActionKind::CheckoutTag, |s| {
+                let name = match s.pick_tag(app)? {
+                    Some(name) => name,
+                    None => return Ok(()),
+                };
+                let action = app.version_control.update(&name);
+                s.show_action(app, action)
+            }),
+            ['t', 'd'] => self.action_context(ActionKind::DeleteTag, |s| {
+                let name = match s.pick_tag(app)? {
+                    Some(name) => name,
+                    None => return Ok(()),
+                };
+                // only git keeps tags on remotes, and only origin gets told
+                let has_origin = app.version_control.has_remote_tags()
+                    && app
+                        .version_control
+                        .get_remotes()
+                        .unwrap_or_default()
+                        .iter()
+                        .any(|remote| remote.filename == "origin");
+                let remote = if has_origin { Some("origin") } else { None };
+                let question = match remote {
+                    Some(remote) => format!(
+                        "delete tag '{}'? this also runs '{} push -d {} {}'",
+                        name,
+                        app.version_control.name(),
+                        remote,
+                        name
+                    ),
+                    None => format!("delete tag '{}'?", name),
+                };
+                s.show_header(app, HeaderKind::Waiting)?;
+                if !s.confirm(app, &question, false)? {
+                    return s.show_previous_action_result(app);
+                }
+                let action = app.version_control.delete_tag(&name, remote);
+                s.show_action(app, action)
+            }),
             ['b'] => Ok(HandleChordResult::Unhandled),
             ['b', 'b'] => self.action_context(ActionKind::ListBranches, |s| {
                 let action = app.version_control.list_branches();
                 s.show_action(app, action)
             }),
+            ['b', 'u'] => {
+                self.action_context(ActionKind::CheckoutBranch, |s| {
+                    let mut entries =
+                        match app.version_control.get_branches_for_checkout() {
+                            Ok(entries) => entries,
+                            Err(error) => {
+                                let result =
+                                    ActionResult::from_err(error.to_string());
+                                return s.show_result(app, &result);
+                            }
+                        };
+                    if entries.is_empty() {
+                        return s.show_empty_entries(app);
+                    }
+                    if !s.show_select_ui(app, &mut entries)? {
+                        return s.show_previous_action_result(app);
+                    }
+                    match entries.iter().find(|e| e.selected) {
+                        Some(entry) => {
+                            let action =
+                                app.version_control.update(&entry.filename);
+                            s.show_action(app, action)
+                        }
+                        None => s.show_previous_action_result(app),
+                    }
+                })
+            }
             ['b', 'n'] => self.action_context(ActionKind::NewBranch, |s| {
-                if let Some(input) =
-                    s.handle_input(app, "new branch name", None)?
-                {
+                if let Some(input) = s.handle_input_validated(
+                    app,
+                    "new branch name",
+                    None,
+                    validate_ref_name,
+                )? {
                     let action =
                         app.version_control.create_branch(input.trim());
                     s.show_action(app, action)
@@ -535,10 +2012,11 @@ where
                 }
             }),
             ['b', 'd'] => self.action_context(ActionKind::DeleteBranch, |s| {
-                if let Some(input) = s.handle_input(
+                if let Some(input) = s.handle_input_validated(
                     app,
                     "branch to delete",
                     s.previous_target(app),
+                    validate_ref_name,
                 )? {
                     let action = app.version_control.close_branch(input.trim());
                     s.show_action(app, action)
@@ -546,302 +2024,3620 @@ where
                     s.show_previous_action_result(app)
                 }
             }),
-            ['x'] => self.action_context(ActionKind::CustomAction, |s| {
-                if app.custom_actions.len() > 0 {
-                    s.show_header(app, HeaderKind::Ok)?;
-                    for c in &app.custom_actions {
-                        s.write
-                            .queue(SetForegroundColor(ENTRY_COLOR))?
-                            .queue(Print(&c.shortcut))?
-                            .queue(ResetColor)?
-                            .queue(Print('\t'))?
-                            .queue(Print(&c.command))?;
-                        for a in &c.args {
-                            s.write.queue(Print(' '))?.queue(Print(a))?;
-                        }
-                        s.write.queue(cursor::MoveToNextLine(1))?;
+            ['b', 'D'] => {
+                self.action_context(ActionKind::DeletePickedBranch, |s| {
+                    let mut entries =
+                        match app.version_control.get_branches_for_checkout() {
+                            Ok(entries) => entries,
+                            Err(error) => {
+                                let result =
+                                    ActionResult::from_err(error.to_string());
+                                return s.show_result(app, &result);
+                            }
+                        };
+                    if entries.is_empty() {
+                        return s.show_empty_entries(app);
                     }
-                    s.handle_custom_action(app)?;
-                    s.current_key_chord.clear();
+                    if !s.show_select_ui(app, &mut entries)? {
+                        return s.show_previous_action_result(app);
+                    }
+                    let entry = match entries.iter().find(|e| e.selected) {
+                        Some(entry) => entry,
+                        None => return s.show_previous_action_result(app),
+                    };
+                    if let State::Current = entry.state {
+                        let result = ActionResult::from_err(
+                            "the current branch can not be deleted".into(),
+                        );
+                        return s.show_result(app, &result);
+                    }
+
+                    let name = &entry.filename[..];
+                    let deleted =
+                        match app.version_control.delete_branch(name, false) {
+                            Err(VcsError::Unmerged(error)) => {
+                                s.show_header(app, HeaderKind::Waiting)?;
+                                queue!(
+                                    s.write,
+                                    Print(error.trim()),
+                                    cursor::MoveToNextLine(2),
+                                )?;
+                                let question = format!(
+                                    "force deleting '{}' loses those commits. continue?",
+                                    name
+                                );
+                                if !s.confirm_losing_work(&question)? {
+                                    return s.show_previous_action_result(app);
+                                }
+                                app.version_control.delete_branch(name, true)
+                            }
+                            deleted => deleted,
+                        };
+                    let result = match deleted {
+                        Ok(output) => ActionResult::from_ok(output),
+                        Err(error) => ActionResult::from_err(error.to_string()),
+                    };
+                    s.show_action(app, ready(result))
+                })
+            }
+            ['b', 'k'] => self.action_context(ActionKind::NewBookmark, |s| {
+                if !app.version_control.has_bookmarks() {
+                    let result = ActionResult::from_err(NO_BOOKMARKS.into());
+                    return s.show_result(app, &result);
+                }
+                if let Some(input) = s.handle_input_validated(
+                    app,
+                    "new bookmark name",
+                    None,
+                    validate_ref_name,
+                )? {
+                    let action =
+                        app.version_control.create_bookmark(input.trim());
+                    s.show_action(app, action)
                 } else {
-                    s.show_header(app, HeaderKind::Error)?;
-                    queue!(
-                        s.write,
-                        ResetColor,
-                        Print("no commands available"),
-                        cursor::MoveToNextLine(2),
-                        Print(concat!(
-                            "create custom actions by placing them inside '.",
-                            env!("CARGO_PKG_NAME"),
-                            "/custom_actions.txt'"
-                        )),
-                    )?;
+                    s.show_previous_action_result(app)
                 }
-                Ok(())
             }),
-            _ => Ok(HandleChordResult::Handled),
-        }
-    }
-
-    fn handle_custom_action(&mut self, app: &mut Application) -> Result<()> {
-        self.current_key_chord.clear();
-        self.write.queue(cursor::SavePosition)?;
-
-        'outer: loop {
-            self.write.flush()?;
-            match input::poll_event() {
-                Event::Resize(terminal_size) => {
-                    self.terminal_size = terminal_size;
-                }
-                Event::Key(KeyEvent {
-                    code: KeyCode::Esc, ..
+            ['b', 'K'] => {
+                self.action_context(ActionKind::DeleteBookmark, |s| {
+                    if !app.version_control.has_bookmarks() {
+                        let result =
+                            ActionResult::from_err(NO_BOOKMARKS.into());
+                        return s.show_result(app, &result);
+                    }
+                    if let Some(input) = s.handle_input_validated(
+                        app,
+                        "bookmark to delete",
+                        s.previous_target(app),
+                        validate_ref_name,
+                    )? {
+                        let action =
+                            app.version_control.delete_bookmark(input.trim());
+                        s.show_action(app, action)
+                    } else {
+                        s.show_previous_action_result(app)
+                    }
                 })
-                | Event::Key(KeyEvent {
-                    code: KeyCode::Char('c'),
-                    modifiers: KeyModifiers::CONTROL,
-                }) => {
-                    return self.show_previous_action_result(app);
+            }
+            ['g'] => Ok(HandleChordResult::Unhandled),
+            ['g', 'p'] => self.action_context(ActionKind::ShowPhase, |s| {
+                if !app.version_control.has_phases() {
+                    let result = ActionResult::from_err(NO_PHASES.into());
+                    return s.show_result(app, &result);
                 }
-                Event::Key(key_event) => {
-                    if let Some(c) = input::key_to_char(key_event) {
-                        self.current_key_chord.push(c);
-                    }
-                    for action in &app.custom_actions {
-                        if action
-                            .shortcut
-                            .chars()
-                            .zip(
-                                self.current_key_chord
-                                    .iter()
-                                    .map(|c| *c)
-                                    .chain(iter::repeat('\0')),
-                            )
-                            .all(|(a, b)| a == b)
-                        {
-                            self.write
-                                .queue(cursor::RestorePosition)?
-                                .queue(cursor::MoveToNextLine(2))?
-                                .queue(SetForegroundColor(ENTRY_COLOR))?
-                                .queue(Print(&action.command))?
-                                .queue(ResetColor)?;
-                            for arg in &action.args {
-                                self.write
-                                    .queue(Print(' '))?
-                                    .queue(Print(arg))?;
-                            }
-                            self.write.queue(cursor::MoveToNextLine(2))?;
-
+                if let Some(input) = s.handle_input_validated(
+                    app,
+                    "phase of revision",
+                    s.previous_target(app),
+                    validate_revision,
+                )? {
+                    let action = app.version_control.show_phase(input.trim());
+                    s.show_action(app, action)
+                } else {
+                    s.show_previous_action_result(app)
+                }
+            }),
+            ['g', 'P'] => self.action_context(ActionKind::SetPhase, |s| {
+                if !app.version_control.has_phases() {
+                    let result = ActionResult::from_err(NO_PHASES.into());
+                    return s.show_result(app, &result);
+                }
+                let target = match s.handle_input_validated(
+                    app,
+                    "set phase of revision",
+                    s.previous_target(app),
+                    validate_revision,
+                )? {
+                    Some(input) => input.trim().to_string(),
+                    None => return s.show_previous_action_result(app),
+                };
+                let mut entries: Vec<_> = Phase::ALL
+                    .iter()
+                    .map(|phase| Entry {
+                        filename: phase.name().into(),
+                        selected: false,
+                        state: State::Clean,
+                        columns: None,
+                    })
+                    .collect();
+                if !s.show_select_ui(app, &mut entries)? {
+                    return s.show_previous_action_result(app);
+                }
+                let phase = entries
+                    .iter()
+                    .find(|e| e.selected)
+                    .and_then(|e| Phase::parse(&e.filename));
+                let phase = match phase {
+                    Some(phase) => phase,
+                    None => return s.show_previous_action_result(app),
+                };
+                if phase == Phase::Public
+                    && !s.confirm_list(
+                        app,
+                        "public revisions can no longer be amended or rebased",
+                        std::slice::from_ref(&target),
+                        false,
+                    )?
+                {
+                    return s.show_previous_action_result(app);
+                }
+                let action = app.version_control.set_phase(&target, phase);
+                s.show_action(app, action)
+            }),
+            ['H'] => Ok(HandleChordResult::Unhandled),
+            ['H', 'e'] => self.action_context(ActionKind::EditHistory, |s| {
+                if !app.version_control.has_history_editing() {
+                    let result =
+                        ActionResult::from_err(NO_HISTORY_EDITING.into());
+                    return s.show_result(app, &result);
+                }
+                let base = match s.handle_input_validated(
+                    app,
+                    "edit history since",
+                    s.previous_target(app),
+                    validate_revision,
+                )? {
+                    Some(input) => input.trim().to_string(),
+                    None => return s.show_previous_action_result(app),
+                };
+                let (hashes, mut revisions): (Vec<_>, Vec<_>) =
+                    match app.version_control.get_revisions_to_edit(&base) {
+                        Ok(revisions) => revisions.into_iter().unzip(),
+                        Err(error) => {
                             let result =
-                                action.execute(app.version_control.get_root());
-                            self.show_result(app, &result)?;
-                            return Ok(());
+                                ActionResult::from_err(error.to_string());
+                            return s.show_result(app, &result);
+                        }
+                    };
+                if revisions.is_empty() {
+                    return s.show_empty_entries(app);
+                }
+                // the ones left unselected are picked as they are
+                if !s.show_select_ui(app, &mut revisions)? {
+                    return s.show_previous_action_result(app);
+                }
+
+                let mut steps = Vec::with_capacity(revisions.len());
+                let mut plan = Vec::with_capacity(revisions.len());
+                for (hash, revision) in hashes.into_iter().zip(&revisions) {
+                    let step = if revision.selected {
+                        match s.pick_history_step(app, &revision.filename)? {
+                            Some(step) => step,
+                            None => return s.show_previous_action_result(app),
                         }
+                    } else {
+                        HistoryStep::Pick
+                    };
+                    steps.push((step, hash));
+                    plan.push(format!("{} {}", step.name(), revision.filename));
+                }
+                if !s.confirm_list(
+                    app,
+                    "history will be rewritten",
+                    &plan,
+                    false,
+                )? {
+                    return s.show_previous_action_result(app);
+                }
+                let action = app.version_control.edit_history(&base, &steps);
+                s.show_action(app, action)
+            }),
+            ['H', 'c'] => {
+                self.action_context(ActionKind::ContinueHistoryEdit, |s| {
+                    let action = app.version_control.continue_history_edit();
+                    s.show_action(app, action)
+                })
+            }
+            ['H', 'a'] => {
+                self.action_context(ActionKind::AbortHistoryEdit, |s| {
+                    let action = app.version_control.abort_history_edit();
+                    s.show_action(app, action)
+                })
+            }
+            ['b', 'm'] => {
+                self.action_context(ActionKind::ListMergedBranches, |s| {
+                    if let Some(input) = s.handle_input_validated(
+                        app,
+                        "merged into",
+                        s.previous_target(app),
+                        validate_revision,
+                    )? {
+                        s.merged_base = input.trim().into();
+                        let action = app
+                            .version_control
+                            .list_merged_branches(&s.merged_base);
+                        s.show_action(app, action)
+                    } else {
+                        s.show_previous_action_result(app)
                     }
-                    self.show_current_key_chord()?;
+                })
+            }
+            ['b', 'c'] => {
+                self.action_context(ActionKind::DeleteMergedBranches, |s| {
+                    let listing = app.get_cached_action_result(
+                        ActionKind::ListMergedBranches,
+                    );
+                    if s.previous_action_kind != ActionKind::ListMergedBranches
+                        || !listing.success
+                    {
+                        let error = "list merged branches with 'bm' first";
+                        let result = ActionResult::from_err(error.into());
+                        return s.show_result(app, &result);
+                    }
+
+                    let names: Vec<String> = listing
+                        .output
+                        .lines()
+                        .map(MergedBranch::parse)
+                        .filter(|b| {
+                            b.merged && !b.current && !b.name.is_empty()
+                        })
+                        .map(|b| b.name)
+                        .filter(|&name| {
+                            name != s.merged_base
+                                && !app
+                                    .protected_branches
+                                    .iter()
+                                    .any(|p| p == name)
+                        })
+                        .map(Into::into)
+                        .collect();
+                    if names.is_empty() {
+                        let result = ActionResult::from_ok(
+                            "no merged branches to delete".into(),
+                        );
+                        s.show_result(app, &result)
+                    } else if s.confirm_list(
+                        app,
+                        "these merged branches will be deleted",
+                        &names,
+                        false,
+                    )? {
+                        let action =
+                            app.version_control.delete_branches(&names);
+                        s.show_action(app, action)
+                    } else {
+                        s.show_previous_action_result(app)
+                    }
+                })
+            }
+            ['a'] => Ok(HandleChordResult::Unhandled),
+            ['a', 'a'] => self.action_context(ActionKind::Blame, |s| {
+                let root = app.version_control.get_root();
+                let file = match s.read_input(
+                    app,
+                    "blame file",
+                    None,
+                    Completion::Path(root),
+                    false,
+                    validate_path,
+                )? {
+                    Some(file) => file,
+                    None => return s.show_previous_action_result(app),
+                };
+                let target = match s.read_input(
+                    app,
+                    "at revision (empty for the working tree)",
+                    None,
+                    Completion::None,
+                    true,
+                    |_| Ok(()),
+                )? {
+                    Some(target) => target,
+                    None => return s.show_previous_action_result(app),
+                };
+                let lines = match s.read_input(
+                    app,
+                    "lines (start,end or empty for all)",
+                    None,
+                    Completion::None,
+                    true,
+                    validate_line_range,
+                )? {
+                    Some(lines) => parse_line_range(&lines).unwrap_or(None),
+                    None => return s.show_previous_action_result(app),
+                };
+
+                s.blame_stack = vec![BlameView {
+                    target: Some(target.trim())
+                        .filter(|t| !t.is_empty())
+                        .map(Into::into),
+                    file: file.trim().into(),
+                    lines,
+                    ignore_revs: app
+                        .version_control
+                        .blame_ignore_revs_file()
+                        .is_some(),
+                }];
+                s.show_blame(app)
+            }),
+            ['a', 'p'] => self.action_context(ActionKind::BlameParent, |s| {
+                let view = match s.blame_stack.last() {
+                    Some(view) if s.previous_action_kind.is_blame() => view,
+                    _ => {
+                        let error = "blame a file with 'aa' first";
+                        let result = ActionResult::from_err(error.into());
+                        return s.show_result(app, &result);
+                    }
+                };
+                let target = match s.previous_target(app) {
+                    Some(target) => format!("{}^", target),
+                    None => {
+                        let error =
+                            "the line under the cursor is not committed";
+                        let result = ActionResult::from_err(error.into());
+                        return s.show_result(app, &result);
+                    }
+                };
+                let view = BlameView {
+                    target: Some(target),
+                    file: view.file.clone(),
+                    lines: view.lines,
+                    ignore_revs: view.ignore_revs,
+                };
+                s.blame_stack.push(view);
+                s.show_blame(app)
+            }),
+            ['a', 'i'] => {
+                self.action_context(ActionKind::BlameIgnoreRevs, |s| {
+                    let has_file =
+                        app.version_control.blame_ignore_revs_file().is_some();
+                    let view = match s.blame_stack.last_mut() {
+                        Some(view) if s.previous_action_kind.is_blame() => view,
+                        _ => {
+                            let error = "blame a file with 'aa' first";
+                            let result = ActionResult::from_err(error.into());
+                            return s.show_result(app, &result);
+                        }
+                    };
+                    if !view.ignore_revs && !has_file {
+                        let error = "there is no .git-blame-ignore-revs file";
+                        let result = ActionResult::from_err(error.into());
+                        return s.show_result(app, &result);
+                    }
+                    view.ignore_revs = !view.ignore_revs;
+                    s.show_blame(app)
+                })
+            }
+            ['a', 'b'] => self.action_context(ActionKind::BlameBack, |s| {
+                if s.previous_action_kind.is_blame() && s.blame_stack.len() > 1
+                {
+                    s.blame_stack.pop();
+                    s.show_blame(app)
+                } else {
+                    let error = "there is no previous blame to go back to";
+                    s.show_result(app, &ActionResult::from_err(error.into()))
+                }
+            }),
+            ['z'] => Ok(HandleChordResult::Unhandled),
+            ['z', 'z'] => self.action_context(ActionKind::Stash, |s| {
+                let include = match s.choose(
+                    app,
+                    "which files should be stashed?",
+                    &[
+                        ('t', "tracked changes only"),
+                        ('u', "also untracked files"),
+                        ('a', "also untracked and ignored files"),
+                    ],
+                )? {
+                    Some('t') => StashInclude::Tracked,
+                    Some('u') => StashInclude::Untracked,
+                    Some('a') => StashInclude::All,
+                    _ => return s.show_previous_action_result(app),
+                };
+                let filenames = match app.version_control.stash_files(include) {
+                    Ok(filenames) => filenames,
+                    Err(error) => {
+                        return s.show_result(
+                            app,
+                            &ActionResult::from_err(error.to_string()),
+                        )
+                    }
+                };
+                if filenames.is_empty() {
+                    s.show_header(app, HeaderKind::Error)?;
+                    s.write.queue(Print("nothing to stash"))?;
+                    return Ok(());
+                }
+
+                if !s.confirm_list(
+                    app,
+                    "these files will be stashed",
+                    &filenames,
+                    true,
+                )? {
+                    return s.show_previous_action_result(app);
+                }
+                if let Some(message) =
+                    s.handle_input(app, "stash message", None)?
+                {
+                    let action =
+                        app.version_control.stash(message.trim(), include);
+                    s.show_action(app, action)
+                } else {
+                    s.show_previous_action_result(app)
+                }
+            }),
+            ['z', 'l'] => self.action_context(ActionKind::ListStashes, |s| {
+                let action = app.version_control.list_stashes();
+                s.show_action(app, action)
+            }),
+            ['z', 's'] => self.action_context(ActionKind::StashShow, |s| {
+                if let Some(input) = s.handle_input_validated(
+                    app,
+                    "show stash",
+                    s.previous_target(app),
+                    validate_revision,
+                )? {
+                    s.show_diff(app, DiffView::Stash(input.trim().into()))
+                } else {
+                    s.show_previous_action_result(app)
+                }
+            }),
+            ['z', 'p'] => self.action_context(ActionKind::StashPop, |s| {
+                let name = match s.read_input(
+                    app,
+                    "pop stash (empty for the newest)",
+                    s.previous_target(app),
+                    Completion::None,
+                    true,
+                    |_| Ok(()),
+                )? {
+                    Some(name) => name,
+                    None => return s.show_previous_action_result(app),
+                };
+                let action = app.version_control.stash_pop(name.trim());
+                s.show_action(app, action)
+            }),
+            ['v'] => Ok(HandleChordResult::Unhandled),
+            ['v', 's'] => {
+                self.action_context(ActionKind::ToggleSideBySide, |s| {
+                    s.side_by_side = !s.side_by_side;
+                    s.show_previous_action_result(app)
+                })
+            }
+            ['v', 'S'] => self.action_context(
+                ActionKind::ToggleStatusAfterChanges,
+                |s| {
+                    s.status_after_changes = !s.status_after_changes;
+                    let message = if s.status_after_changes {
+                        "changes to the working tree are followed by the status"
+                    } else {
+                        "changes to the working tree are no longer followed by the status"
+                    };
+                    s.show_result(app, &ActionResult::from_ok(message.into()))
+                },
+            ),
+            ['v', 'o'] => self.action_context(ActionKind::ToggleSignOff, |s| {
+                app.commit_options.sign_off = !app.commit_options.sign_off;
+                let message = if app.commit_options.sign_off {
+                    "commits are signed off"
+                } else {
+                    "commits are no longer signed off"
+                };
+                s.show_result(app, &ActionResult::from_ok(message.into()))
+            }),
+            ['v', 'g'] => self.action_context(ActionKind::ToggleGpgSign, |s| {
+                app.commit_options.gpg_sign = !app.commit_options.gpg_sign;
+                let message = if app.commit_options.gpg_sign {
+                    "commits are signed with gpg"
+                } else {
+                    "commits are no longer signed with gpg"
+                };
+                s.show_result(app, &ActionResult::from_ok(message.into()))
+            }),
+            ['+'] => self.action_context(ActionKind::MoreContext, |s| {
+                app.diff_options.context += 1;
+                s.rerun_diff(app)
+            }),
+            ['-'] => self.action_context(ActionKind::LessContext, |s| {
+                app.diff_options.context =
+                    app.diff_options.context.saturating_sub(1);
+                s.rerun_diff(app)
+            }),
+            ['v', 'w'] => {
+                self.action_context(ActionKind::ToggleIgnoreWhitespace, |s| {
+                    app.diff_options.ignore_whitespace =
+                        !app.diff_options.ignore_whitespace;
+                    s.rerun_diff(app)
+                })
+            }
+            ['v', 'a'] => {
+                self.action_context(ActionKind::NextDiffAlgorithm, |s| {
+                    if !app.version_control.has_diff_algorithms() {
+                        let error = "hg has only one diff algorithm";
+                        let result = ActionResult::from_err(error.into());
+                        return s.show_result(app, &result);
+                    }
+                    app.diff_options.algorithm =
+                        DiffAlgorithm::next(app.diff_options.algorithm);
+                    s.rerun_diff(app)
+                })
+            }
+            ['v', 'm'] => {
+                self.action_context(ActionKind::ToggleDetectMoves, |s| {
+                    app.diff_options.detect_moves =
+                        !app.diff_options.detect_moves;
+                    s.rerun_diff(app)
+                })
+            }
+            [']'] | ['['] if self.current_action_kind.is_diff() => {
+                let forward = self.current_key_chord[0] == ']';
+                self.move_to_hunk(app, forward)?;
+                Ok(HandleChordResult::Handled)
+            }
+            ['}'] | ['{'] if self.current_action_kind.is_diff() => {
+                let forward = self.current_key_chord[0] == '}';
+                self.move_to_file(app, forward)?;
+                Ok(HandleChordResult::Handled)
+            }
+            ['v', 'f'] => {
+                if self.current_action_kind.is_diff() {
+                    self.jump_to_file(app)?;
+                }
+                Ok(HandleChordResult::Handled)
+            }
+            ['v', 'l'] => {
+                if self.current_action_kind == ActionKind::SessionLog {
+                    self.show_previous_action_result(app)?;
+                    return Ok(HandleChordResult::Handled);
+                }
+                self.action_context(ActionKind::SessionLog, |s| {
+                    let (text, _) = app.scrollback.text();
+                    s.show_result(app, &ActionResult::from_ok(text))
+                })
+            }
+            ['v', 'p'] => {
+                let output = self.scroll_view.content().to_string();
+                self.action_context(ActionKind::PageOutput, |s| {
+                    let paged = match TempFile::create("output", &output) {
+                        Ok(file) => {
+                            let path = file.path().display().to_string();
+                            s.hand_over_terminal(|_| open::run(&app.pager, &path))?
+                        }
+                        Err(error) => Err(error),
+                    };
+                    match paged {
+                        Ok(()) => s.show_previous_action_result(app),
+                        Err(error) => {
+                            let result = ActionResult::from_err(format!(
+                                "could not run '{}': {}",
+                                app.pager, error
+                            ));
+                            s.show_action(app, ready(result))
+                        }
+                    }
+                })
+            }
+            ['v', 'r'] => self.action_context(ActionKind::ReloadConfig, |s| {
+                let reloaded = load_key_bindings().and_then(|key_bindings| {
+                    app.reload_config(Config::load(&app.config_options))?;
+                    app.key_bindings = key_bindings;
+                    Ok(())
+                });
+                s.capture_mouse(app)?;
+                s.scroll_view.wrap_diffs = app.wrap_diffs;
+                let result = match reloaded {
+                    Ok(()) => ActionResult::from_ok("configuration reloaded".into()),
+                    Err(error) => ActionResult::from_err(format!(
+                        "{}\n\nthe previous configuration is still in use",
+                        error
+                    )),
+                };
+                s.show_result(app, &result)
+            }),
+            ['y'] => Ok(HandleChordResult::Unhandled),
+            ['y', 'h'] => {
+                if self.current_action_kind.is_diff() {
+                    self.copy_hunk(app)?;
+                }
+                Ok(HandleChordResult::Handled)
+            }
+            ['y', 'p'] => {
+                let root = app.version_control.get_root().to_owned();
+                self.copy_text(app, format!("'{}'", root), &root)?;
+                Ok(HandleChordResult::Handled)
+            }
+            ['y', 'r'] => {
+                if let Some(target) =
+                    self.cursor_target(app, self.current_action_kind)
+                {
+                    let target = target.to_owned();
+                    self.copy_text(app, format!("'{}'", target), &target)?;
+                }
+                Ok(HandleChordResult::Handled)
+            }
+            ['y', 'b'] => {
+                match app.version_control.current_branch() {
+                    Ok(branch) => {
+                        self.copy_text(app, format!("'{}'", branch), &branch)?
+                    }
+                    Err(error) => {
+                        let result = ActionResult::from_err(error.to_string());
+                        self.show_result(app, &result)?;
+                    }
+                }
+                Ok(HandleChordResult::Handled)
+            }
+            ['o'] => Ok(HandleChordResult::Unhandled),
+            ['o', 'f'] => self.action_context(ActionKind::OpenExplorer, |s| {
+                let command = match &app.open_command {
+                    Some(command) => command,
+                    None => {
+                        let result = ActionResult::from_err(OPEN_TURNED_OFF.into());
+                        return s.show_action(app, ready(result));
+                    }
+                };
+                if !s.confirm_open(app, command)? {
+                    return s.show_previous_action_result(app);
+                }
+                let root = app.version_control.get_root();
+                let result = match open::spawn(command, root) {
+                    Ok(()) => ActionResult::from_ok(format!("opened {}", root)),
+                    Err(error) => ActionResult::from_err(format!(
+                        "could not run '{}': {}",
+                        command, error
+                    )),
+                };
+                s.show_action(app, ready(result))
+            }),
+            ['o', 'r'] => {
+                self.action_context(ActionKind::SwitchRepository, |s| {
+                    s.switch_repository(app)
+                })
+            }
+            ['o', 'e'] => self.action_context(ActionKind::OpenEditor, |s| {
+                if !s.confirm_open(app, &app.editor_command)? {
+                    return s.show_previous_action_result(app);
+                }
+                let root = app.version_control.get_root().to_string();
+                let opened = s.hand_over_terminal(|_| {
+                    open::run(&app.editor_command, &root)
+                })?;
+                let result = match opened {
+                    Ok(()) => ActionResult::from_ok(format!("edited {}", root)),
+                    Err(error) => ActionResult::from_err(format!(
+                        "could not run '{}': {}",
+                        app.editor_command, error
+                    )),
+                };
+                s.show_action(app, ready(result))
+            }),
+            ['x'] => self.action_context(ActionKind::CustomAction, |s| {
+                if app.custom_actions.len() > 0 {
+                    s.show_header(app, HeaderKind::Ok)?;
+                    for c in &app.custom_actions {
+                        s.write
+                            .queue(fg(theme::current().entry))?
+                            .queue(Print(&c.shortcut))?
+                            .queue(ResetColor)?
+                            .queue(Print('\t'))?
+                            .queue(Print(&c.command))?;
+                        for a in &c.args {
+                            s.write.queue(Print(' '))?.queue(Print(a))?;
+                        }
+                        s.write.queue(cursor::MoveToNextLine(1))?;
+                    }
+                    s.handle_custom_action(app)?;
+                    s.current_key_chord.clear();
+                } else {
+                    s.show_header(app, HeaderKind::Error)?;
+                    queue!(
+                        s.write,
+                        ResetColor,
+                        Print("no commands available"),
+                        cursor::MoveToNextLine(2),
+                        Print(concat!(
+                            "create custom actions by placing them inside '.",
+                            env!("CARGO_PKG_NAME"),
+                            "/custom_actions.txt'"
+                        )),
+                    )?;
+                }
+                Ok(())
+            }),
+            _ => Ok(HandleChordResult::Handled),
+        }
+    }
+
+    fn handle_custom_action(&mut self, app: &mut Application) -> Result<()> {
+        self.current_key_chord.clear();
+        self.write.queue(cursor::SavePosition)?;
 
+        'outer: loop {
+            self.write.flush()?;
+            match self.input.poll_event()? {
+                Event::Resize(terminal_size) => {
+                    self.terminal_size = terminal_size;
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Esc, ..
+                })
+                | Event::Key(KeyEvent {
+                    code: KeyCode::Char('c'),
+                    modifiers: KeyModifiers::CONTROL,
+                }) => {
+                    return self.show_previous_action_result(app);
+                }
+                Event::Key(key_event) => {
+                    if let Some(c) = input::key_to_char(key_event) {
+                        self.current_key_chord.push(c);
+                    }
                     for action in &app.custom_actions {
                         if action
                             .shortcut
                             .chars()
-                            .zip(&self.current_key_chord)
-                            .all(|(a, b)| a == *b)
+                            .zip(
+                                self.current_key_chord
+                                    .iter()
+                                    .map(|c| *c)
+                                    .chain(iter::repeat('\0')),
+                            )
+                            .all(|(a, b)| a == b)
                         {
-                            continue 'outer;
-                        }
-                    }
+                            self.write
+                                .queue(cursor::RestorePosition)?
+                                .queue(cursor::MoveToNextLine(2))?
+                                .queue(fg(theme::current().entry))?
+                                .queue(Print(&action.command))?
+                                .queue(ResetColor)?;
+                            for arg in &action.args {
+                                self.write
+                                    .queue(Print(' '))?
+                                    .queue(Print(arg))?;
+                            }
+                            self.write.queue(cursor::MoveToNextLine(2))?;
+
+                            let result =
+                                action.execute(app.version_control.get_root());
+                            self.show_result(app, &result)?;
+                            return Ok(());
+                        }
+                    }
+                    self.show_current_key_chord()?;
+
+                    for action in &app.custom_actions {
+                        if action
+                            .shortcut
+                            .chars()
+                            .zip(&self.current_key_chord)
+                            .all(|(a, b)| a == *b)
+                        {
+                            continue 'outer;
+                        }
+                    }
+
+                    self.show_header(app, HeaderKind::Error)?;
+                    self.write.queue(Print("no match found"))?;
+                    return Ok(());
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Lists every item an action is about to affect and waits for a y/n key
+    fn confirm_list(
+        &mut self,
+        app: &Application,
+        title: &str,
+        items: &[String],
+        default: bool,
+    ) -> Result<bool> {
+        self.show_affected(app, title, items)?;
+        self.confirm(app, "continue?", default)
+    }
+
+    /// Like `confirm_list` but asked even with `no_confirm`
+    fn confirm_list_losing_work(
+        &mut self,
+        app: &Application,
+        title: &str,
+        items: &[String],
+    ) -> Result<bool> {
+        self.show_affected(app, title, items)?;
+        self.confirm_losing_work("continue?")
+    }
+
+    fn show_affected(
+        &mut self,
+        app: &Application,
+        title: &str,
+        items: &[String],
+    ) -> Result<()> {
+        self.show_header(app, HeaderKind::Waiting)?;
+        queue!(
+            self.write,
+            fg(theme::current().entry),
+            Print(title),
+            ResetColor,
+            cursor::MoveToNextLine(2),
+        )?;
+
+        // below the title, leaving room for the "and more" line and the
+        // question
+        let region = Region::body(self.terminal_size).inset(2, 2);
+        let region = Region {
+            height: region.height.saturating_sub(2),
+            ..region
+        };
+        let max_items = region.height as usize;
+        draw_text_in(
+            &mut self.write,
+            region,
+            items.iter().take(max_items).map(String::as_str),
+        )?;
+        if items.len() > max_items {
+            queue!(
+                self.write,
+                Print(format!("... and {} more", items.len() - max_items)),
+                cursor::MoveToNextLine(1),
+            )?;
+        }
+
+        self.write.queue(cursor::MoveToNextLine(1))?;
+        Ok(())
+    }
+
+    /// Asks a yes/no question where Enter picks `default`, which is shown
+    /// capitalized. Any other key answers no. With `no_confirm` it is yes
+    /// without asking, so questions losing work use `confirm_losing_work`
+    fn confirm(
+        &mut self,
+        app: &Application,
+        question: &str,
+        default: bool,
+    ) -> Result<bool> {
+        if app.no_confirm {
+            return Ok(true);
+        }
+        let key = self.ask(question, default)?;
+        Ok(is_confirmed(key, default))
+    }
+
+    /// Like `confirm` with no as the default, but asked even with
+    /// `no_confirm` since a yes throws away work there is no getting back
+    fn confirm_losing_work(&mut self, question: &str) -> Result<bool> {
+        let key = self.ask(question, false)?;
+        Ok(is_confirmed(key, false))
+    }
+
+    /// Lets the user pick one of `options` by pressing its key. Enter picks
+    /// the first one and any other key cancels
+    fn choose(
+        &mut self,
+        app: &Application,
+        title: &str,
+        options: &[(char, &str)],
+    ) -> Result<Option<char>> {
+        self.show_header(app, HeaderKind::Waiting)?;
+        queue!(
+            self.write,
+            fg(theme::current().entry),
+            Print(title),
+            ResetColor,
+            cursor::MoveToNextLine(2),
+        )?;
+        for (key, description) in options {
+            queue!(
+                self.write,
+                Print("  "),
+                fg(theme::current().entry),
+                Print(key),
+                ResetColor,
+                Print("  "),
+                Print(description),
+                cursor::MoveToNextLine(1),
+            )?;
+        }
+        self.write.flush()?;
+
+        let key = self.input.read_key()?;
+        let chosen = match key.code {
+            KeyCode::Enter => options.first().map(|(key, _)| *key),
+            KeyCode::Char(c) => {
+                options.iter().map(|(key, _)| *key).find(|key| *key == c)
+            }
+            _ => None,
+        };
+        Ok(chosen)
+    }
+
+    fn ask(&mut self, question: &str, default: bool) -> Result<KeyEvent> {
+        queue!(
+            self.write,
+            fg(theme::current().entry),
+            Print(question),
+            Print(if default { " (Y/n)" } else { " (y/N)" }),
+            ResetColor,
+        )?;
+        self.write.flush()?;
+        self.input.read_key()
+    }
+
+    /// Offers to pick up an operation left unfinished by a previous session
+    /// by showing what still needs to be resolved
+    fn offer_resume(&mut self, app: &mut Application) -> Result<()> {
+        let operation = match app.version_control.operation_in_progress() {
+            // bisecting has no flow to resume in here
+            Some("bisect") | None => return Ok(()),
+            Some(operation) => operation,
+        };
+
+        self.show_header(app, HeaderKind::Waiting)?;
+        queue!(
+            self.write,
+            fg(theme::current().entry),
+            Print(format!("a {} is in progress", operation)),
+            ResetColor,
+            cursor::MoveToNextLine(2),
+        )?;
+
+        if self.confirm(app, "show its unresolved conflicts?", true)? {
+            self.current_key_chord = vec!['r', 'r'];
+            self.handle_key_chord(app)?;
+            self.current_key_chord.clear();
+        } else {
+            let help = app.get_cached_action_result(ActionKind::Help);
+            self.show_result(app, help)?;
+        }
+        self.show_current_key_chord()?;
+        self.write.flush()?;
+        Ok(())
+    }
+
+    /// Asks before quitting while actions are still running or the repository
+    /// is in the middle of a merge, rebase or similar. Pressing Ctrl+C again
+    /// also quits
+    fn confirm_quit(&mut self, app: &Application) -> Result<bool> {
+        let mut reasons: Vec<_> = app
+            .running_action_names()
+            .into_iter()
+            .map(|name| format!("'{}' is still running", name))
+            .collect();
+        if let Some(operation) = app.version_control.operation_in_progress() {
+            reasons.push(format!("a {} is in progress", operation));
+        }
+        if reasons.is_empty() {
+            return Ok(true);
+        }
+
+        self.show_header(app, HeaderKind::Waiting)?;
+        queue!(
+            self.write,
+            fg(theme::current().entry),
+            Print("operation in progress, really quit?"),
+            ResetColor,
+            cursor::MoveToNextLine(2),
+        )?;
+        for reason in &reasons {
+            queue!(
+                self.write,
+                Print("  "),
+                Print(reason),
+                cursor::MoveToNextLine(1)
+            )?;
+        }
+        self.write.queue(cursor::MoveToNextLine(1))?;
+
+        let key = self.ask("quit?", false)?;
+        let quit = is_confirmed(key, false)
+            || key
+                == KeyEvent {
+                    code: KeyCode::Char('c'),
+                    modifiers: KeyModifiers::CONTROL,
+                };
+        if !quit {
+            let result = app.get_cached_action_result(self.current_action_kind);
+            self.show_result(app, result)?;
+            self.show_current_key_chord()?;
+            self.write.flush()?;
+        }
+        Ok(quit)
+    }
+
+    fn handle_input(
+        &mut self,
+        app: &Application,
+        prompt: &str,
+        default: Option<&str>,
+    ) -> Result<Option<String>> {
+        self.handle_input_validated(app, prompt, default, |_| Ok(()))
+    }
+
+    /// Like `handle_input` but alt+e opens an editor to write the input in
+    fn read_message(
+        &mut self,
+        app: &Application,
+        prompt: &str,
+    ) -> Result<Option<String>> {
+        self.read_input(app, prompt, None, Completion::Message, false, |_| {
+            Ok(())
+        })
+    }
+
+    /// Tells in the prompt whether the commit gets signed off or signed
+    fn read_commit_message(
+        &mut self,
+        app: &Application,
+    ) -> Result<Option<String>> {
+        let options = app.commit_options.describe();
+        if options.is_empty() {
+            self.read_message(app, "commit message")
+        } else {
+            self.read_message(app, &format!("commit message ({})", options))
+        }
+    }
+
+    /// Keeps prompting while `validator` rejects the input, showing its error
+    /// above the prompt. `default` is pre-filled and also used when the input
+    /// is left empty
+    fn handle_input_validated<V>(
+        &mut self,
+        app: &Application,
+        prompt: &str,
+        default: Option<&str>,
+        validator: V,
+    ) -> Result<Option<String>>
+    where
+        V: Fn(&str) -> std::result::Result<(), String>,
+    {
+        self.read_input(
+            app,
+            prompt,
+            default,
+            Completion::None,
+            false,
+            validator,
+        )
+    }
+
+    /// Like `handle_input_validated` but completing with `completion`. Unless
+    /// `allow_empty` is set, input that is empty or only spaces without a
+    /// default cancels, which the header of the view gone back to tells
+    fn read_input<V>(
+        &mut self,
+        app: &Application,
+        prompt: &str,
+        default: Option<&str>,
+        completion: Completion,
+        allow_empty: bool,
+        validator: V,
+    ) -> Result<Option<String>>
+    where
+        V: Fn(&str) -> std::result::Result<(), String>,
+    {
+        let prompt = match default {
+            Some(default) => format!("{} ({}): ", prompt, default),
+            None => format!("{}: ", prompt),
+        };
+        let mut initial = String::from(default.unwrap_or(""));
+        let mut error = None;
+
+        loop {
+            self.show_header(app, HeaderKind::Waiting)?;
+            if let Some(error) = &error {
+                queue!(
+                    self.write,
+                    fg(theme::current().error),
+                    Print(error),
+                    ResetColor,
+                    cursor::MoveToNextLine(1),
+                )?;
+            }
+            execute!(self.write, cursor::Show)?;
+
+            let line = self.input.read_line(&prompt, &initial, completion);
+            self.write.execute(cursor::Hide)?;
+            let line = match line {
+                Err(ReadlineError::Eof) => {
+                    let editor = &app.message_editor;
+                    match self.hand_over_terminal(|_| editor::edit(editor))? {
+                        Ok(text) => Ok(text),
+                        Err(edit_error) => {
+                            error = Some(format!(
+                                "could not run '{}': {}",
+                                editor, edit_error
+                            ));
+                            continue;
+                        }
+                    }
+                }
+                line => line,
+            };
+
+            let line = match (line, default) {
+                (Ok(Some(line)), _) if !line.trim().is_empty() => line,
+                (Ok(Some(_)), Some(default)) => default.into(),
+                (Ok(Some(line)), None) if allow_empty => line,
+                _ => {
+                    self.canceled = Some(self.current_action_kind.name());
+                    return Ok(None);
+                }
+            };
+            match validator(&line) {
+                Ok(()) => return Ok(Some(line)),
+                Err(message) => {
+                    error = Some(message);
+                    initial = line;
+                }
+            }
+        }
+    }
+
+    fn show_result(
+        &mut self,
+        app: &Application,
+        result: &ActionResult,
+    ) -> Result<()> {
+        self.current_hunk = None;
+        self.hunk_copied = false;
+        self.copied = None;
+        self.current_file = None;
+        self.last_drawn_output.clear();
+
+        let mut hint = None;
+        if app.has_pending_action_of_type(self.current_action_kind) {
+            self.show_header(app, HeaderKind::Waiting)?;
+        } else if result.success {
+            self.show_header(app, HeaderKind::Ok)?;
+        } else {
+            self.show_header(app, HeaderKind::Error)?;
+            let error = VcsError::from_stderr(result.output.clone(), None);
+            hint = match error {
+                VcsError::Conflict(_)
+                    if self.current_action_kind == ActionKind::CherryPick =>
+                {
+                    Some(CHERRY_PICK_CONFLICT_HINT)
+                }
+                VcsError::Conflict(_)
+                    if matches!(
+                        self.current_action_kind,
+                        ActionKind::Rebase | ActionKind::ContinueRebase
+                    ) =>
+                {
+                    Some(REBASE_CONFLICT_HINT)
+                }
+                _ => error.hint(),
+            };
+        }
+
+        let has_warnings = result.success
+            && !result.warnings.trim().is_empty()
+            && !self.current_action_kind.can_select_output();
+
+        if hint.is_some() || has_warnings {
+            let mut content = result.output.clone();
+            if let Some(hint) = hint {
+                content.push_str("\n\nhint: ");
+                content.push_str(hint);
+            }
+            if has_warnings {
+                content.push('\n');
+                for line in result.warnings.lines() {
+                    content.push('\n');
+                    content.push_str(
+                        &style(line)
+                            .with(fit_color(theme::current().warning))
+                            .to_string()[..],
+                    );
+                }
+            }
+            self.scroll_view.set_content(
+                &content[..],
+                self.current_action_kind,
+                self.terminal_size,
+            );
+        } else {
+            let kind = self.current_action_kind;
+            let side_by_side;
+            let scrollback;
+            let mut scrollback_start = None;
+            let output = match kind.empty_output_message() {
+                Some(message)
+                    if result.success && result.output.trim().is_empty() =>
+                {
+                    message
+                }
+                // an empty diff would otherwise look like there are no
+                // changes at all
+                _ if app.diff_options.ignore_whitespace
+                    && (kind.is_diff()
+                        || kind == ActionKind::RevisionChanges)
+                    && result.success
+                    && result.output.trim().is_empty() =>
+                {
+                    "no changes besides whitespace"
+                }
+                // selectable outputs need their lines where they are
+                _ if app.output_mode == OutputMode::Scrollback
+                    && !kind.can_select_output()
+                    && app.scrollback.ends_with(kind, &result.output) =>
+                {
+                    // newest at the bottom unless it is taller than the screen
+                    let (text, last_start) = app.scrollback.text();
+                    let height =
+                        AvailableSize::from_temrinal_size(self.terminal_size)
+                            .height;
+                    let bottom = text.lines().count().saturating_sub(height);
+                    scrollback = text;
+                    scrollback_start = Some(last_start.min(bottom));
+                    &scrollback[..]
+                }
+                _ if kind == ActionKind::SessionLog => {
+                    let height =
+                        AvailableSize::from_temrinal_size(self.terminal_size)
+                            .height;
+                    let bottom =
+                        result.output.lines().count().saturating_sub(height);
+                    scrollback_start = Some(bottom);
+                    &result.output[..]
+                }
+                _ if self.side_by_side && kind.is_diff() && result.success => {
+                    let width = self.terminal_size.width as usize;
+                    side_by_side = diff::side_by_side(&result.output, width);
+                    &side_by_side[..]
+                }
+                _ => &result.output[..],
+            };
+            self.scroll_view.set_content(
+                output,
+                self.current_action_kind,
+                self.terminal_size,
+            );
+            if let Some(start) = scrollback_start {
+                self.scroll_view.scroll_to(start);
+            }
+        }
+        self.scroll_view
+            .draw_content(&mut self.write, self.terminal_size)
+    }
+
+    fn show_current_key_chord(&mut self) -> Result<()> {
+        let TerminalSize { width, height } = self.terminal_size;
+        queue!(
+            self.write,
+            cursor::MoveTo(
+                width - self.current_key_chord.len() as u16,
+                height - 1
+            ),
+            Clear(ClearType::CurrentLine),
+            fg(theme::current().entry),
+        )?;
+        for c in &self.current_key_chord {
+            self.write.queue(Print(c))?;
+        }
+        self.write.queue(ResetColor)?;
+        Ok(())
+    }
+
+    fn show_help(&mut self, app: &Application) -> Result<ActionResult> {
+        let mut write = Vec::with_capacity(1024);
+
+        queue!(
+            &mut write,
+            Print(BIN_NAME),
+            Print(' '),
+            Print(VERSION),
+            cursor::MoveToNextLine(2),
+        )?;
+
+        if let Ok(version) = app.version_control.version() {
+            queue!(&mut write, Print(version), cursor::MoveToNextLine(2))?;
+        }
+
+        if !app.launch_subdir.is_empty() {
+            queue!(
+                &mut write,
+                Print("launched from "),
+                Print(&app.launch_subdir),
+                cursor::MoveToNextLine(2),
+            )?;
+        }
+
+        write
+            .queue(Print("press a key and peform an action"))?
+            .queue(cursor::MoveToNextLine(2))?;
+
+        if !app.quick_actions.is_empty() {
+            write
+                .queue(Print("quick actions"))?
+                .queue(cursor::MoveToNextLine(1))?;
+            for name in &app.quick_actions {
+                let quick = HELP_ACTIONS
+                    .iter()
+                    .flatten()
+                    .find(|(_, kind)| kind.name() == name);
+                if let Some(&(keys, kind)) = quick {
+                    let keys = app.key_bindings.keys_for(kind, keys);
+                    Self::show_help_action(&mut write, &keys, kind)?;
+                }
+            }
+            write.queue(cursor::MoveToNextLine(1))?;
+        }
+
+        for action in HELP_ACTIONS {
+            match action {
+                Some((keys, kind)) => {
+                    let keys = app.key_bindings.keys_for(*kind, keys);
+                    Self::show_help_action(&mut write, &keys, *kind)?
+                }
+                None => {
+                    write.queue(cursor::MoveToNextLine(1))?;
+                }
+            }
+        }
+
+        write.flush()?;
+        Ok(ActionResult::from_ok(String::from_utf8(write)?))
+    }
+
+    fn show_help_action<HW>(
+        write: &mut HW,
+        shortcut: &str,
+        action: ActionKind,
+    ) -> Result<()>
+    where
+        HW: Write,
+    {
+        queue!(
+            write,
+            fg(theme::current().entry),
+            Print('\t'),
+            Print(shortcut),
+            ResetColor,
+            Print('\t'),
+            Print('\t'),
+            Print(action.name()),
+            cursor::MoveToNextLine(1),
+        )
+    }
+}
+
+/// Reads the keys bound to actions instead of the ones the help lists
+pub fn load_key_bindings() -> std::result::Result<KeyBindings, String> {
+    let defaults: Vec<_> = HELP_ACTIONS.iter().flatten().copied().collect();
+    KeyBindings::load(&defaults)
+}
+
+/// Every action the help lists with its keys, `None` between groups
+const HELP_ACTIONS: &[Option<(&str, ActionKind)>] = &[
+    Some(("h", ActionKind::Help)),
+    Some(("q, esc", ActionKind::Quit)),
+    Some(("alt+left", ActionKind::GoBack)),
+    Some(("alt+right", ActionKind::GoForward)),
+    None,
+    Some(("s", ActionKind::Status)),
+    Some(("S", ActionKind::RefreshStatus)),
+    Some(("l", ActionKind::Log)),
+    Some(("LC", ActionKind::LogCount)),
+    Some(("LS", ActionKind::LogSignatures)),
+    Some(("LG", ActionKind::LogGraph)),
+    Some(("LM", ActionKind::LogMore)),
+    Some(("ee", ActionKind::CurrentFullRevision)),
+    Some(("dd", ActionKind::CurrentDiffAll)),
+    Some(("ds", ActionKind::CurrentDiffSelected)),
+    Some(("DI", ActionKind::RevisionDetails)),
+    Some(("DC", ActionKind::RevisionChanges)),
+    Some(("DD", ActionKind::RevisionDiffAll)),
+    Some(("DS", ActionKind::RevisionDiffSelected)),
+    None,
+    Some(("cc", ActionKind::CommitAll)),
+    Some(("cp", ActionKind::CommitAndPush)),
+    Some(("cs", ActionKind::CommitSelected)),
+    Some(("ch", ActionKind::CommitHunks)),
+    Some(("cu", ActionKind::UndoLastCommit)),
+    Some(("ca", ActionKind::Absorb)),
+    Some(("u", ActionKind::Update)),
+    Some(("U", ActionKind::UpdateSubmodules)),
+    Some(("m", ActionKind::Merge)),
+    Some(("kk", ActionKind::CherryPick)),
+    Some(("kc", ActionKind::ContinueCherryPick)),
+    Some(("ka", ActionKind::AbortCherryPick)),
+    Some(("BB", ActionKind::Rebase)),
+    Some(("Bc", ActionKind::ContinueRebase)),
+    Some(("Ba", ActionKind::AbortRebase)),
+    Some(("RA", ActionKind::RevertAll)),
+    Some(("rs", ActionKind::RevertSelected)),
+    Some(("rv", ActionKind::ResetSelected)),
+    None,
+    Some(("rr", ActionKind::UnresolvedConflicts)),
+    Some(("ro", ActionKind::MergeTakingOther)),
+    Some(("rl", ActionKind::MergeTakingLocal)),
+    Some(("ra", ActionKind::AbortOperation)),
+    None,
+    Some(("f", ActionKind::Fetch)),
+    Some(("p", ActionKind::Pull)),
+    Some(("P", ActionKind::Push)),
+    Some(("T", ActionKind::RunInTerminal)),
+    Some(("Rl", ActionKind::ListRemotes)),
+    Some(("Rn", ActionKind::NewRemote)),
+    Some(("Rd", ActionKind::DeleteRemote)),
+    None,
+    Some(("tn", ActionKind::NewTag)),
+    Some(("tu", ActionKind::CheckoutTag)),
+    Some(("td", ActionKind::DeleteTag)),
+    None,
+    Some(("bb", ActionKind::ListBranches)),
+    Some(("bu", ActionKind::CheckoutBranch)),
+    Some(("bn", ActionKind::NewBranch)),
+    Some(("bd", ActionKind::DeleteBranch)),
+    Some(("bD", ActionKind::DeletePickedBranch)),
+    Some(("bk", ActionKind::NewBookmark)),
+    Some(("bK", ActionKind::DeleteBookmark)),
+    Some(("bm", ActionKind::ListMergedBranches)),
+    Some(("bc", ActionKind::DeleteMergedBranches)),
+    None,
+    Some(("gp", ActionKind::ShowPhase)),
+    Some(("gP", ActionKind::SetPhase)),
+    Some(("He", ActionKind::EditHistory)),
+    Some(("Hc", ActionKind::ContinueHistoryEdit)),
+    Some(("Ha", ActionKind::AbortHistoryEdit)),
+    None,
+    Some(("aa", ActionKind::Blame)),
+    Some(("ap", ActionKind::BlameParent)),
+    Some(("ab", ActionKind::BlameBack)),
+    Some(("ai", ActionKind::BlameIgnoreRevs)),
+    None,
+    Some(("zz", ActionKind::Stash)),
+    Some(("zl", ActionKind::ListStashes)),
+    Some(("zs", ActionKind::StashShow)),
+    Some(("zp", ActionKind::StashPop)),
+    None,
+    Some(("vs", ActionKind::ToggleSideBySide)),
+    Some(("vS", ActionKind::ToggleStatusAfterChanges)),
+    Some(("vo", ActionKind::ToggleSignOff)),
+    Some(("vg", ActionKind::ToggleGpgSign)),
+    Some(("+", ActionKind::MoreContext)),
+    Some(("-", ActionKind::LessContext)),
+    Some(("vw", ActionKind::ToggleIgnoreWhitespace)),
+    Some(("va", ActionKind::NextDiffAlgorithm)),
+    Some(("vm", ActionKind::ToggleDetectMoves)),
+    Some(("[", ActionKind::PreviousHunk)),
+    Some(("]", ActionKind::NextHunk)),
+    Some(("yh", ActionKind::CopyHunk)),
+    Some(("yr", ActionKind::CopyTarget)),
+    Some(("yp", ActionKind::CopyRoot)),
+    Some(("yb", ActionKind::CopyBranch)),
+    Some(("{", ActionKind::PreviousFile)),
+    Some(("}", ActionKind::NextFile)),
+    Some(("vf", ActionKind::JumpToFile)),
+    Some(("vl", ActionKind::SessionLog)),
+    Some(("vp", ActionKind::PageOutput)),
+    Some(("vr", ActionKind::ReloadConfig)),
+    None,
+    Some(("x", ActionKind::CustomAction)),
+    Some(("of", ActionKind::OpenExplorer)),
+    Some(("oe", ActionKind::OpenEditor)),
+    Some(("or", ActionKind::SwitchRepository)),
+];
+
+const ALL_REMOTES: &str = "all remotes";
+const CHERRY_PICK_CONFLICT_HINT: &str = "the cherry-pick stopped halfway. resolve the conflicts with 'rr', 'ro' and 'rl', then 'kc' commits it or 'ka' gives it up";
+const OPEN_TURNED_OFF: &str =
+    "opening the repository is turned off by 'open_command false'";
+const REBASE_CONFLICT_HINT: &str = "the rebase stopped at a commit that conflicts. resolve the conflicts with 'rr', 'ro' and 'rl', then 'Bc' goes on with the rest or 'Ba' gives it up";
+const WHOLE_FILES_COMMITTED: &str =
+    "only git can commit parts of a file, so the picked files are committed whole";
+
+/// Selects the `entries` that were selected in `previous`, leaving the ones
+/// that were not listed then as they are
+fn restore_selection(entries: &mut [Entry], previous: &[Entry]) {
+    for entry in entries {
+        if let Some(previous) =
+            previous.iter().find(|p| p.filename == entry.filename)
+        {
+            entry.selected = previous.selected;
+        }
+    }
+}
+
+fn app_header<'a>(
+    app: &'a Application,
+    action_name: &'a str,
+    progress: &'a str,
+) -> Header<'a> {
+    Header {
+        action_name,
+        directory_name: app.version_control.get_root(),
+        backend: &app.backend,
+        branch: app.head.as_deref().unwrap_or(""),
+        progress,
+        status_counts: app.status_counts,
+    }
+}
+
+/// How long an action took, next to its ok or error. Actions done before
+/// the spinner counted a second show nothing
+fn finished_progress(elapsed: Duration) -> String {
+    if elapsed < Duration::from_secs(1) {
+        String::new()
+    } else {
+        format!("{:.1}s", elapsed.as_secs_f64())
+    }
+}
+
+fn selected_filenames(entries: &[Entry]) -> Vec<String> {
+    entries
+        .iter()
+        .filter(|e| e.selected)
+        .map(|e| e.filename.clone())
+        .collect()
+}
+
+/// The files still in conflict, listed when resolving them all at once
+fn conflicted_filenames(app: &mut Application) -> Vec<String> {
+    app.current_changed_files()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(Entry::is_conflicted)
+        .map(|e| e.filename)
+        .collect()
+}
+
+fn is_confirmed(key: KeyEvent, default: bool) -> bool {
+    match key.code {
+        KeyCode::Enter => default,
+        _ => matches!(input::key_to_char(key), Some('y') | Some('Y')),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        input::ScriptedInput, mock_actions::MockActions,
+        repositories::Repository, select::State, snapshot::assert_snapshot,
+    };
+
+    const TEST_TERMINAL_SIZE: TerminalSize = TerminalSize {
+        width: 80,
+        height: 24,
+    };
+
+    struct TestRun {
+        calls: Vec<String>,
+        prompts: Vec<String>,
+        output: String,
+    }
+
+    fn test_app(mock: MockActions) -> Application {
+        Application::new(
+            Repository {
+                version_control: Box::new(mock),
+                launch_subdir: String::new(),
+            },
+            Vec::new(),
+        )
+    }
+
+    fn test_tui(input: ScriptedInput) -> Tui<Vec<u8>, ScriptedInput> {
+        let mut tui = Tui::new(Vec::new(), input);
+        tui.terminal_size = TEST_TERMINAL_SIZE;
+        tui.clipboard = Clipboard::Terminal;
+        tui
+    }
+
+    fn run(mock: MockActions, input: ScriptedInput) -> TestRun {
+        let calls = mock.calls();
+        let mut app = test_app(mock);
+
+        let mut tui = test_tui(input);
+        tui.run(&mut app).unwrap();
+
+        let calls = calls.lock().unwrap().clone();
+        TestRun {
+            calls,
+            prompts: tui.input.prompts,
+            output: String::from_utf8_lossy(&tui.write).into_owned(),
+        }
+    }
+
+    #[test]
+    fn commit_all_uses_typed_message() {
+        let mock = MockActions::new().with_result(
+            "commit_all",
+            ActionResult::from_ok("1 file changed".into()),
+        );
+        let input = ScriptedInput::default().keys("cc").line("fix typo");
+
+        let run = run(mock, input);
+
+        assert!(run.calls.contains(&"commit_all fix typo".into()));
+        assert_eq!(run.prompts, ["commit message: "]);
+        assert!(run.output.contains("1 file changed"));
+    }
+
+    #[test]
+    fn sign_off_is_toggled_and_shown_at_the_commit_prompt() {
+        let input = ScriptedInput::default().keys("vocc").line("fix typo");
+
+        let run = run(MockActions::new(), input);
+
+        assert!(run.calls.contains(&"commit_all fix typo --signoff".into()));
+        assert_eq!(run.prompts, ["commit message (signed off): "]);
+    }
+
+    #[test]
+    fn push_follows_a_successful_commit() {
+        let mock = MockActions::new()
+            .with_result(
+                "commit_all",
+                ActionResult::from_ok("committed".into()),
+            )
+            .with_result("push", ActionResult::from_ok("pushed".into()));
+        let input = ScriptedInput::default().keys("cp").line("fix typo");
+
+        let run = run(mock, input);
+
+        assert!(run.calls.contains(&"commit_all fix typo".into()));
+        assert!(run.output.contains("committed"));
+        assert!(run.output.contains("pushed"));
+    }
+
+    #[test]
+    fn failed_commit_is_not_pushed() {
+        let mock = MockActions::new()
+            .with_result(
+                "commit_all",
+                ActionResult::from_err("nothing to commit".into()),
+            )
+            .with_result("push", ActionResult::from_ok("pushed".into()));
+        let input = ScriptedInput::default().keys("cp").line("fix typo");
+
+        let run = run(mock, input);
+
+        assert!(run.output.contains("nothing to commit"));
+        assert!(!run.output.contains("pushed"));
+    }
+
+    #[test]
+    fn single_remote_is_used_without_asking() {
+        let mock = MockActions::new().with_remotes(vec![remote("origin")]);
+
+        let run = run(mock, ScriptedInput::default().keys("P"));
+
+        assert!(run.calls.contains(&"push".into()));
+    }
+
+    #[test]
+    fn remote_is_picked_when_there_are_several() {
+        let mock = MockActions::new()
+            .with_remotes(vec![remote("origin"), remote("upstream")]);
+        let input = ScriptedInput::default()
+            .keys("P")
+            .key(KeyEvent {
+                code: KeyCode::Down,
+                modifiers: KeyModifiers::NONE,
+            })
+            .key(KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::NONE,
+            });
+
+        let run = run(mock, input);
+
+        assert!(run.calls.contains(&"push upstream".into()));
+    }
+
+    fn remote(name: &str) -> Entry {
+        Entry {
+            filename: name.into(),
+            selected: false,
+            state: State::Branch,
+            columns: None,
+        }
+    }
+
+    #[test]
+    fn fetch_starts_on_all_remotes() {
+        let remote = |name: &str| Entry {
+            filename: name.into(),
+            selected: false,
+            state: State::Branch,
+            columns: None,
+        };
+        let mock = MockActions::new()
+            .with_remotes(vec![remote("origin"), remote("upstream")]);
+        let enter = KeyEvent {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::NONE,
+        };
+
+        let fetched = run(mock, ScriptedInput::default().keys("f").key(enter));
+
+        assert!(fetched.calls.contains(&"fetch".into()));
+    }
+
+    #[test]
+    fn fetch_can_pick_a_single_remote() {
+        let remote = |name: &str| Entry {
+            filename: name.into(),
+            selected: false,
+            state: State::Branch,
+            columns: None,
+        };
+        let mock = MockActions::new()
+            .with_remotes(vec![remote("origin"), remote("upstream")]);
+        let key = |code| KeyEvent {
+            code,
+            modifiers: KeyModifiers::NONE,
+        };
+        let input = ScriptedInput::default()
+            .keys("f")
+            .key(key(KeyCode::Down))
+            .key(key(KeyCode::Enter));
+
+        let fetched = run(mock, input);
+
+        assert!(fetched.calls.contains(&"fetch origin".into()));
+    }
+
+    #[test]
+    fn new_branch_is_pushed_with_an_upstream_once_confirmed() {
+        let run = run(new_branch_mock(), ScriptedInput::default().keys("Py"));
+
+        assert!(run.output.contains("'feature' has no upstream"));
+        assert!(run
+            .calls
+            .contains(&"push_set_upstream origin feature".into()));
+    }
+
+    #[test]
+    fn declined_upstream_pushes_as_usual() {
+        let run = run(new_branch_mock(), ScriptedInput::default().keys("Pn"));
+
+        assert!(run.calls.contains(&"push".into()));
+        assert!(!run.calls.iter().any(|c| c.starts_with("push_set")));
+    }
+
+    #[test]
+    fn push_with_an_upstream_can_run_again_in_the_terminal() {
+        let run = run(new_branch_mock(), ScriptedInput::default().keys("PyT"));
+
+        assert!(run.calls.contains(&"run_in_terminal push".into()));
+    }
+
+    fn new_branch_mock() -> MockActions {
+        MockActions::new()
+            .with_result(
+                "branch_without_upstream",
+                ActionResult::from_ok("feature".into()),
+            )
+            .with_remotes(vec![Entry {
+                filename: "origin".into(),
+                selected: false,
+                state: State::Branch,
+                columns: None,
+            }])
+    }
+
+    #[test]
+    fn failed_push_runs_again_in_the_terminal() {
+        let mock = MockActions::new()
+            .with_result(
+                "push",
+                ActionResult::from_err(
+                    "fatal: could not read Username: terminal prompts disabled"
+                        .into(),
+                ),
+            )
+            .with_result(
+                "run_in_terminal",
+                ActionResult::from_ok("pushed".into()),
+            );
+
+        let run = run(mock, ScriptedInput::default().keys("PT"));
+
+        assert!(run.output.contains("use 'T' to run"));
+        assert!(run.output.contains("> mock push"));
+        assert!(run.calls.contains(&"run_in_terminal push".into()));
+        assert!(run.output.contains("pushed"));
+    }
+
+    #[test]
+    fn nothing_runs_in_the_terminal_before_a_remote_operation() {
+        let run = run(MockActions::new(), ScriptedInput::default().keys("T"));
+
+        assert!(run.output.contains("there is no fetch, pull or push"));
+        assert!(!run.calls.iter().any(|call| call.starts_with("run_in_")));
+    }
+
+    #[test]
+    fn conflicted_cherry_pick_points_to_continue_and_abort() {
+        let mock = MockActions::new().with_result(
+            "cherry_pick",
+            ActionResult::from_err("CONFLICT (content): Merge conflict".into()),
+        );
+        let input = ScriptedInput::default().keys("kk").line("abc123");
+
+        let run = run(mock, input);
+
+        assert!(run.calls.contains(&"cherry_pick abc123".into()));
+        assert!(run.output.contains("'kc' commits it or 'ka' gives it up"));
+    }
+
+    #[test]
+    fn conflicted_rebase_points_to_continue_and_abort() {
+        let mock = MockActions::new().with_result(
+            "rebase",
+            ActionResult::from_err("CONFLICT (content): Merge conflict".into()),
+        );
+        let input = ScriptedInput::default().keys("BB").line("main").keys("Bc");
+
+        let run = run(mock, input);
+
+        assert!(run.calls.contains(&"rebase main".into()));
+        assert!(run.output.contains("'Bc' goes on with the rest"));
+        assert!(run.calls.contains(&"continue_rebase".into()));
+    }
+
+    #[test]
+    fn merge_defaults_to_upstream_branch() {
+        let mock = MockActions::new()
+            .with_result(
+                "upstream_branch",
+                ActionResult::from_ok("origin/master".into()),
+            )
+            .with_result("merge", ActionResult::from_err("CONFLICT".into()));
+        let input = ScriptedInput::default().keys("m").line("");
+
+        let run = run(mock, input);
+
+        assert!(run.calls.contains(&"upstream_branch".into()));
+        assert!(run.calls.contains(&"merge origin/master".into()));
+        assert_eq!(run.prompts, ["merge with (origin/master): "]);
+        assert!(run.output.contains("CONFLICT"));
+    }
+
+    #[test]
+    fn commit_message_keeps_its_body() {
+        let input = ScriptedInput::default()
+            .keys("cc")
+            .line("subject\n\nbody\n");
+
+        let run = run(MockActions::new(), input);
+
+        assert!(run.calls.contains(&"commit_all subject\n\nbody".into()));
+    }
+
+    #[test]
+    fn commit_message_can_be_written_in_an_editor() {
+        if cfg!(windows) {
+            return;
+        }
+        let script = TempFile::create(
+            "editor",
+            "#!/bin/sh\nprintf 'subject\\n\\nbody\\n' > \"$1\"\n",
+        )
+        .unwrap();
+        let mock = MockActions::new();
+        let calls = mock.calls();
+        let mut app = test_app(mock);
+        app.message_editor = format!("sh {}", script.path().display());
+        let mut tui = test_tui(ScriptedInput::default().keys("cc").editor());
+
+        tui.run(&mut app).unwrap();
+
+        let calls = calls.lock().unwrap();
+        assert!(calls.contains(&"commit_all subject\n\nbody".into()));
+    }
+
+    #[test]
+    fn editor_failures_are_shown_above_the_prompt() {
+        let mut app = test_app(MockActions::new());
+        app.message_editor = "verco-missing-editor".into();
+        let input = ScriptedInput::default().keys("cc").editor();
+        let mut tui = test_tui(input);
+
+        tui.run(&mut app).unwrap();
+
+        let output = String::from_utf8_lossy(&tui.write);
+        assert!(output.contains("could not run 'verco-missing-editor'"));
+        assert_eq!(tui.input.prompts.len(), 2);
+        assert!(!app.has_action_result(ActionKind::CommitAll));
+    }
+
+    #[test]
+    fn commit_selected_commits_entry_under_cursor() {
+        let entry = |filename: &str| Entry {
+            filename: filename.into(),
+            selected: false,
+            state: State::Modified,
+            columns: None,
+        };
+        let mock = MockActions::new()
+            .with_changed_files(vec![entry("a.txt"), entry("b.txt")]);
+        let input = ScriptedInput::default()
+            .keys("cs")
+            .key(KeyEvent {
+                code: KeyCode::Down,
+                modifiers: KeyModifiers::NONE,
+            })
+            .key(KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::NONE,
+            })
+            .line("only b");
+
+        let run = run(mock, input);
+
+        assert!(run.calls.contains(&"commit_selected only b b.txt".into()));
+    }
+
+    #[test]
+    fn commit_hunks_commits_only_the_picked_ones() {
+        let diff = "diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n\
+            @@ -1 +1 @@\n-a\n+b\n@@ -5 +5 @@\n-e\n+f\n";
+        let mock = MockActions::new()
+            .with_changed_files(vec![Entry {
+                filename: "a.txt".into(),
+                selected: false,
+                state: State::Modified,
+                columns: None,
+            }])
+            .with_result("hunk_diff", ActionResult::from_ok(diff.into()));
+        let enter = KeyEvent {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::NONE,
+        };
+        let input = ScriptedInput::default()
+            .keys("ch")
+            .key(enter)
+            .key(KeyEvent {
+                code: KeyCode::Down,
+                modifiers: KeyModifiers::NONE,
+            })
+            .key(enter)
+            .line("only f");
+
+        let run = run(mock, input);
+
+        let patch = "diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n\
+            @@ -5 +5 @@\n-e\n+f\n";
+        assert!(run.calls.contains(&"hunk_diff a.txt".into()));
+        assert!(run
+            .calls
+            .contains(&format!("commit_patch only f {}", patch)));
+    }
+
+    #[test]
+    fn last_commit_is_undone_once_confirmed() {
+        let run = run(MockActions::new(), ScriptedInput::default().keys("cuy"));
+
+        assert!(run.calls.contains(&"undo_last_commit".into()));
+    }
+
+    #[test]
+    fn last_commit_is_kept_when_declined() {
+        let run = run(MockActions::new(), ScriptedInput::default().keys("cun"));
+
+        assert!(!run.calls.contains(&"undo_last_commit".into()));
+    }
+
+    #[test]
+    fn commit_hunks_commits_whole_files_without_hunk_staging() {
+        let mock = MockActions::new()
+            .with_changed_files(vec![Entry {
+                filename: "a.txt".into(),
+                selected: false,
+                state: State::Modified,
+                columns: None,
+            }])
+            .without_hunk_staging();
+        let enter = KeyEvent {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::NONE,
+        };
+        let input = ScriptedInput::default().keys("ch").key(enter).line("all");
+
+        let run = run(mock, input);
+
+        assert!(run.calls.contains(&"commit_selected all a.txt".into()));
+        assert!(!run.calls.iter().any(|c| c.starts_with("hunk_diff")));
+        assert!(run.output.contains("committed whole"));
+    }
+
+    #[test]
+    fn canceled_commit_keeps_the_selection() {
+        let entry = |filename: &str| Entry {
+            filename: filename.into(),
+            selected: false,
+            state: State::Modified,
+            columns: None,
+        };
+        let mock = MockActions::new().with_changed_files(vec![
+            entry("a.txt"),
+            entry("b.txt"),
+            entry("c.txt"),
+        ]);
+        let key = |code| KeyEvent {
+            code,
+            modifiers: KeyModifiers::NONE,
+        };
+        let input = ScriptedInput::default()
+            .keys("cs ")
+            .key(key(KeyCode::Down))
+            .key(key(KeyCode::Down))
+            .keys(" ")
+            .key(key(KeyCode::Enter))
+            // keys instead of a message cancel it
+            .keys("cs")
+            .key(key(KeyCode::Enter))
+            .line("a and c");
+
+        let run = run(mock, input);
+
+        assert!(run
+            .calls
+            .contains(&"commit_selected a and c a.txt c.txt".into()));
+    }
+
+    #[test]
+    fn file_diff_is_previewed_without_losing_the_selection() {
+        let entry = |filename: &str| Entry {
+            filename: filename.into(),
+            selected: false,
+            state: State::Modified,
+            columns: None,
+        };
+        let mock = MockActions::new()
+            .with_changed_files(vec![entry("a.txt"), entry("b.txt")])
+            .with_result(
+                "diff_file",
+                ActionResult::from_ok("+added to b".into()),
+            );
+        let key = |code| KeyEvent {
+            code,
+            modifiers: KeyModifiers::NONE,
+        };
+        let input = ScriptedInput::default()
+            .keys("cs ")
+            .key(key(KeyCode::Down))
+            .key(key(KeyCode::Right))
+            .key(key(KeyCode::Left))
+            .key(key(KeyCode::Enter))
+            .line("only a");
+
+        let run = run(mock, input);
+
+        assert!(run.calls.contains(&"diff_file b.txt".into()));
+        assert!(run.output.contains("+added to b"));
+        assert!(run.calls.contains(&"commit_selected only a a.txt".into()));
+    }
+
+    #[test]
+    fn typed_path_is_added_to_the_selection() {
+        let mock = MockActions::new().with_changed_files(vec![Entry {
+            filename: "a.txt".into(),
+            selected: false,
+            state: State::Modified,
+            columns: None,
+        }]);
+        let input = ScriptedInput::default()
+            .keys("cs")
+            .key(KeyEvent {
+                code: KeyCode::Char('o'),
+                modifiers: KeyModifiers::CONTROL,
+            })
+            .line("docs/notes.md")
+            .key(KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::NONE,
+            })
+            .line("add notes");
+
+        let run = run(mock, input);
+
+        assert!(run.prompts.contains(&"path: ".into()));
+        assert!(run
+            .calls
+            .contains(&"commit_selected add notes docs/notes.md".into()));
+    }
+
+    #[test]
+    fn unfinished_merge_offers_to_show_conflicts() {
+        let mock = MockActions::new().with_operation("merge");
+
+        let run = run(mock, ScriptedInput::default().keys("y"));
+
+        assert!(run.output.contains("a merge is in progress"));
+        assert!(run.calls.contains(&"conflicts".into()));
+    }
+
+    #[test]
+    fn declined_unfinished_merge_shows_no_conflicts() {
+        let mock = MockActions::new().with_operation("merge");
+
+        let run = run(mock, ScriptedInput::default().keys("n"));
+
+        assert!(!run.calls.contains(&"conflicts".into()));
+    }
+
+    #[test]
+    fn enter_picks_the_confirmation_default() {
+        let mock = MockActions::new().with_operation("merge");
+        let input = ScriptedInput::default().key(KeyEvent {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::NONE,
+        });
+
+        let run = run(mock, input);
+
+        assert!(run.calls.contains(&"conflicts".into()));
+    }
+
+    #[test]
+    fn enter_keeps_the_changes_when_no_is_the_default() {
+        let input = ScriptedInput::default().keys("RA").key(KeyEvent {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::NONE,
+        });
+
+        let run = run(MockActions::new(), input);
+
+        assert!(run.output.contains("all changes will be reverted"));
+        assert!(!run.calls.contains(&"revert_all".into()));
+    }
+
+    #[test]
+    fn changes_are_reverted_once_confirmed() {
+        let run = run(MockActions::new(), ScriptedInput::default().keys("RAy"));
+
+        assert!(run.calls.contains(&"revert_all".into()));
+    }
+
+    #[test]
+    fn quit_asks_while_merge_is_in_progress() {
+        let mock = MockActions::new().with_operation("merge");
+        let input = ScriptedInput::default().keys("nqns").keys("qy");
+
+        let run = run(mock, input);
+
+        assert!(run.output.contains("a merge is in progress"));
+        assert!(run.calls.contains(&"status".into()));
+    }
+
+    #[test]
+    fn only_unprotected_merged_branches_are_deleted() {
+        let listing = [
+            "merged\x1emain\x1e",
+            "merged\x1eold-fix\x1e",
+            "merged\x1ecurrent\x1e*",
+            "merged\x1erelease\x1e",
+            "unmerged\x1ewip\x1e",
+        ]
+        .join("\n");
+        let mock = MockActions::new().with_result(
+            "list_merged_branches",
+            ActionResult::from_ok(listing),
+        );
+        let input =
+            ScriptedInput::default().keys("bm").line("main").keys("bcy");
+        let calls = mock.calls();
+        let mut app = test_app(mock);
+        app.protected_branches = vec!["release".into()];
+
+        let mut tui = test_tui(input);
+        tui.run(&mut app).unwrap();
+
+        let calls = calls.lock().unwrap();
+        assert!(calls.contains(&"list_merged_branches main".into()));
+        assert!(calls.contains(&"delete_branches old-fix".into()));
+    }
+
+    #[test]
+    fn untracked_files_are_previewed_before_stashing() {
+        let mock = MockActions::new().with_changed_files(vec![Entry {
+            filename: "new.txt".into(),
+            selected: false,
+            state: State::Untracked,
+            columns: None,
+        }]);
+        let input = ScriptedInput::default().keys("zzuy").line("wip");
+
+        let run = run(mock, input);
+
+        assert!(run.output.contains("also untracked and ignored files"));
+        assert!(run.output.contains("new.txt"));
+        assert!(run.calls.contains(&"stash_files Untracked".into()));
+        assert!(run.calls.contains(&"stash Untracked wip".into()));
+    }
+
+    #[test]
+    fn index_is_reset_without_asking() {
+        let run = run(modified_file_mock(), reset_input("i"));
+
+        assert!(run
+            .calls
+            .contains(&"reset_selected HEAD~1 index a.txt".into()));
+    }
+
+    #[test]
+    fn resetting_the_working_tree_asks_first() {
+        let input = reset_input("w").key(KeyEvent {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::NONE,
+        });
+
+        let run = run(modified_file_mock(), input);
+
+        assert!(run.output.contains("these files will be overwritten"));
+        assert!(!run.calls.iter().any(|c| c.starts_with("reset_selected")));
+    }
+
+    #[test]
+    fn working_tree_is_reset_once_confirmed() {
+        let run = run(modified_file_mock(), reset_input("wy"));
+
+        assert!(run
+            .calls
+            .contains(&"reset_selected HEAD~1 working_tree a.txt".into()));
+    }
+
+    fn modified_file_mock() -> MockActions {
+        MockActions::new().with_changed_files(vec![Entry {
+            filename: "a.txt".into(),
+            selected: false,
+            state: State::Modified,
+            columns: None,
+        }])
+    }
+
+    /// Picks the file to reset to `HEAD~1`, then types `mode`
+    fn reset_input(mode: &str) -> ScriptedInput {
+        ScriptedInput::default()
+            .keys("rv")
+            .line("HEAD~1")
+            .keys(" ")
+            .key(KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::NONE,
+            })
+            .keys(mode)
+    }
+
+    #[test]
+    fn older_revisions_are_appended_to_the_log() {
+        let log = "* \x1e1234\x1edate\x1eauthor\x1e\x1ecommit";
+        let mock = MockActions::new()
+            .with_result("log", ActionResult::from_ok(log.into()));
+        let calls = mock.calls();
+        let mut app = test_app(mock);
+        let mut tui = test_tui(ScriptedInput::default().keys("lLM"));
+
+        tui.run(&mut app).unwrap();
+
+        let calls = calls.lock().unwrap().clone();
+        assert!(calls.contains(&"log 0 50 false true".into()));
+        assert!(calls.contains(&"log 1 50 false true".into()));
+        let shown = &app.get_cached_action_result(ActionKind::Log).output;
+        assert_eq!(shown.matches("commit").count(), 2);
+    }
+
+    #[test]
+    fn log_signatures_stay_toggled() {
+        let run = run(MockActions::new(), ScriptedInput::default().keys("LSl"));
+
+        let logs: Vec<_> =
+            run.calls.iter().filter(|c| c.starts_with("log ")).collect();
+        assert_eq!(logs, ["log 0 50 true true", "log 0 50 true true"]);
+    }
+
+    #[test]
+    fn log_graph_stays_toggled() {
+        let run = run(MockActions::new(), ScriptedInput::default().keys("LGl"));
+
+        let logs: Vec<_> =
+            run.calls.iter().filter(|c| c.starts_with("log ")).collect();
+        assert_eq!(logs, ["log 0 50 false false", "log 0 50 false false"]);
+    }
+
+    #[test]
+    fn blame_asks_for_revision_and_lines() {
+        let input = ScriptedInput::default()
+            .keys("aa")
+            .line("src/main.rs")
+            .line("v1.0")
+            .line("10,20");
+
+        let run = run(MockActions::new(), input);
+
+        assert!(run.calls.contains(&"blame v1.0 src/main.rs 10,20".into()));
+    }
+
+    #[test]
+    fn blame_defaults_to_the_whole_working_tree_file() {
+        let input = ScriptedInput::default()
+            .keys("aa")
+            .line("src/main.rs")
+            .line("")
+            .line("");
+
+        let run = run(MockActions::new(), input);
+
+        assert!(run.calls.contains(&"blame - src/main.rs -".into()));
+    }
+
+    #[test]
+    fn reblaming_walks_back_and_forth() {
+        let blame = "1234abcd (someone 2026-01-01 1) fn main() {}\n";
+        let mock = MockActions::new()
+            .with_result("blame", ActionResult::from_ok(blame.into()));
+        let input = ScriptedInput::default()
+            .keys("aa")
+            .line("src/main.rs")
+            .line("")
+            .line("")
+            .keys("apab");
+
+        let run = run(mock, input);
+
+        let blames: Vec<_> = run
+            .calls
+            .iter()
+            .filter(|c| c.starts_with("blame "))
+            .collect();
+        assert_eq!(
+            blames,
+            [
+                "blame - src/main.rs -",
+                "blame 1234abcd^ src/main.rs -",
+                "blame - src/main.rs -",
+            ]
+        );
+    }
+
+    #[test]
+    fn ignore_revs_are_used_until_toggled_off() {
+        let mock = MockActions::new()
+            .with_blame_ignore_revs_file(".git-blame-ignore-revs");
+        let input = ScriptedInput::default()
+            .keys("aa")
+            .line("src/main.rs")
+            .line("")
+            .line("")
+            .keys("ai");
+
+        let run = run(mock, input);
+
+        assert!(run
+            .calls
+            .contains(&"blame - src/main.rs - ignore_revs".into()));
+        assert!(run.calls.contains(&"blame - src/main.rs -".into()));
+        assert!(run.output.contains("blame (ignoring revs)"));
+    }
+
+    #[test]
+    fn diffs_can_be_shown_side_by_side() {
+        let diff = "@@ -1 +1 @@\n-old line\n+new line\n";
+        let mock = MockActions::new().with_result(
+            "current_diff_all",
+            ActionResult::from_ok(diff.into()),
+        );
+
+        let run = run(mock, ScriptedInput::default().keys("ddvs"));
+
+        let output = diff::strip_ansi(&run.output);
+        assert!(output.contains("-old line"));
+        assert!(output.contains(&format!("{:<38} | new line", "old line")));
+    }
+
+    #[test]
+    fn context_keys_run_the_diff_again() {
+        let input = ScriptedInput::default().keys("dd++-");
+
+        let run = run(MockActions::new(), input);
+
+        let diffs: Vec<_> = run
+            .calls
+            .iter()
+            .filter(|c| c.starts_with("current_diff_all"))
+            .collect();
+        assert_eq!(
+            diffs,
+            [
+                "current_diff_all",
+                "current_diff_all -U4",
+                "current_diff_all -U5",
+                "current_diff_all -U4",
+            ]
+        );
+        assert!(run.output.contains("current diff all (4 lines of context)"));
+    }
+
+    #[test]
+    fn ignoring_whitespace_reruns_the_changes() {
+        let input = ScriptedInput::default().keys("DC").line("abc").keys("vw");
+
+        let run = run(MockActions::new(), input);
+
+        assert!(run.calls.iter().any(|c| c == "revision_changes abc"));
+        assert!(run.calls.iter().any(|c| c == "revision_changes abc -w"));
+        assert!(run
+            .output
+            .contains("revision changes (ignoring whitespace)"));
+        assert!(run.output.contains("no changes besides whitespace"));
+    }
+
+    #[test]
+    fn hunks_are_stepped_through_and_copied() {
+        let diff = "diff --git a/a.txt b/a.txt\n@@ -1 +1 @@\n-a\n+b\n@@ -5 +5 @@\n-c\n+d\n";
+        let mock = MockActions::new().with_result(
+            "current_diff_all",
+            ActionResult::from_ok(diff.into()),
+        );
+        let input = ScriptedInput::default().keys("dd]]]");
+
+        let run = run(mock, input.keys("yh"));
+
+        assert!(run.output.contains("hunk 1 of 2)"));
+        assert!(run.output.contains("hunk 2 of 2 copied)"));
+        // "@@ -5 +5 @@\n-c\n+d\n"
+        assert!(run
+            .output
+            .contains("\x1b]52;c;QEAgLTUgKzUgQEAKLWMKK2QK\x07"));
+    }
+
+    #[test]
+    fn branch_and_root_are_copied() {
+        let mock = MockActions::new().with_result(
+            "current_branch",
+            ActionResult::from_ok("main".into()),
+        );
+
+        let run = run(mock, ScriptedInput::default().keys("ybyp"));
+
+        assert!(run.output.contains("(copied 'main')"));
+        assert!(run.output.contains("(copied 'mock')"));
+        // "main"
+        assert!(run.output.contains("\x1b]52;c;bWFpbg==\x07"));
+    }
+
+    #[test]
+    fn revision_under_the_cursor_is_copied() {
+        let log = "*\x1eaaaa111\x1efirst\n*\x1ebbbb222\x1esecond\n";
+        let mock = MockActions::new()
+            .with_result("log", ActionResult::from_ok(log.into()));
+        let input = ScriptedInput::default().keys("l").key(KeyEvent {
+            code: KeyCode::Down,
+            modifiers: KeyModifiers::NONE,
+        });
+
+        let run = run(mock, input.keys("yr"));
+
+        assert!(run.output.contains("(copied 'bbbb222')"));
+        // "bbbb222"
+        assert!(run.output.contains("\x1b]52;c;YmJiYjIyMg==\x07"));
+    }
+
+    #[test]
+    fn diffs_are_walked_file_by_file() {
+        let diff: String = ["a", "b", "c"]
+            .iter()
+            .map(|f| format!("diff --git a/{0} b/{0}\n@@ -1 +1 @@\n-{0}\n", f))
+            .collect();
+        let mock = MockActions::new()
+            .with_result("current_diff_all", ActionResult::from_ok(diff));
+        let down = KeyEvent {
+            code: KeyCode::Down,
+            modifiers: KeyModifiers::NONE,
+        };
+        let input = ScriptedInput::default()
+            .keys("ddvf")
+            .key(down)
+            .key(down)
+            .key(KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::NONE,
+            })
+            .keys("{]");
+
+        let run = run(mock, input);
+
+        let file_3 = run.output.find("file 3 of 3)").unwrap();
+        let file_2 = run.output.find("file 2 of 3)").unwrap();
+        let hunk_2 = run.output.find("hunk 2 of 3)").unwrap();
+        assert!(file_3 < file_2 && file_2 < hunk_2);
+    }
+
+    #[test]
+    fn diff_algorithms_cycle_back_to_the_configured_one() {
+        let input = ScriptedInput::default().keys("ddvavavavava");
+
+        let run = run(MockActions::new(), input);
+
+        let diffs: Vec<_> = run
+            .calls
+            .iter()
+            .filter(|c| c.starts_with("current_diff_all"))
+            .collect();
+        assert_eq!(
+            diffs,
+            [
+                "current_diff_all",
+                "current_diff_all myers",
+                "current_diff_all minimal",
+                "current_diff_all patience",
+                "current_diff_all histogram",
+                "current_diff_all",
+            ]
+        );
+        assert!(run
+            .output
+            .contains("current diff all (3 lines of context, histogram)"));
+    }
+
+    #[test]
+    fn detecting_moves_reruns_the_diff() {
+        let input = ScriptedInput::default().keys("DD").line("abc").keys("vm");
+
+        let run = run(MockActions::new(), input);
+
+        assert!(run.calls.iter().any(|c| c == "revision_diff_all abc -M50%"));
+        assert!(run.output.contains("(3 lines of context, detecting moves)"));
+    }
+
+    #[test]
+    fn session_log_shows_every_output_so_far() {
+        let mock = MockActions::new()
+            .with_result("status", ActionResult::from_ok("clean\n".into()))
+            .with_result("fetch", ActionResult::from_ok("fetched\n".into()));
+        let mut app = test_app(mock);
+        let mut tui = test_tui(ScriptedInput::default().keys("sfvlvl"));
+
+        tui.run(&mut app).unwrap();
+
+        let output = String::from_utf8_lossy(&tui.write);
+        let log = output.find("session log").unwrap();
+        let status = output[log..].find("00 status").unwrap();
+        let fetch = output[log + status..].find("00 fetch").unwrap();
+        assert!(output[log + status..log + status + fetch].contains("clean"));
+        // pressing it again goes back to the fetch output
+        assert!(output[log..].rfind("fetched").unwrap() > status + fetch);
+    }
+
+    #[test]
+    fn refreshing_runs_the_status_again() {
+        let input = ScriptedInput::default().keys("S");
+        let run = run(MockActions::new(), input);
+        assert_eq!(
+            run.calls,
+            [
+                "get_current_changed_files",
+                "version",
+                "get_current_changed_files",
+                "status"
+            ]
+        );
+    }
+
+    #[test]
+    fn changed_files_are_listed_again_after_a_commit() {
+        let input = ScriptedInput::default()
+            .keys("RA")
+            .key(KeyEvent {
+                code: KeyCode::Char('n'),
+                modifiers: KeyModifiers::NONE,
+            })
+            .keys("cc")
+            .line("message")
+            .keys("RA");
+        let run = run(MockActions::new(), input);
+        let listed = run
+            .calls
+            .iter()
+            .filter(|c| *c == "get_current_changed_files")
+            .count();
+        assert_eq!(listed, 2);
+    }
+
+    #[test]
+    fn unmerged_branch_is_deleted_when_forced() {
+        let input = ScriptedInput::default()
+            .keys("bD")
+            .key(KeyEvent {
+                code: KeyCode::Down,
+                modifiers: KeyModifiers::NONE,
+            })
+            .key(KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::NONE,
+            })
+            .keys("y");
+
+        let run = run(unmerged_branch_mock(), input);
+
+        assert!(run.output.contains("not fully merged"));
+        assert!(run.calls.contains(&"delete_branch feature".into()));
+        assert!(run.calls.contains(&"force_delete_branch feature".into()));
+    }
+
+    #[test]
+    fn unmerged_branch_is_kept_unless_forced() {
+        let input = ScriptedInput::default()
+            .keys("bD")
+            .key(KeyEvent {
+                code: KeyCode::Down,
+                modifiers: KeyModifiers::NONE,
+            })
+            .key(KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::NONE,
+            })
+            .keys("n");
+
+        let run = run(unmerged_branch_mock(), input);
+
+        assert!(!run.calls.iter().any(|c| c.starts_with("force")));
+    }
+
+    #[test]
+    fn current_branch_is_not_deleted() {
+        let input = ScriptedInput::default().keys("bD").key(KeyEvent {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::NONE,
+        });
+
+        let run = run(unmerged_branch_mock(), input);
+
+        assert!(run.output.contains("current branch can not be deleted"));
+        assert!(!run.calls.iter().any(|c| c.contains("delete_branch")));
+    }
+
+    fn unmerged_branch_mock() -> MockActions {
+        let branch = |name: &str, state| Entry {
+            filename: name.into(),
+            selected: false,
+            state,
+            columns: None,
+        };
+        MockActions::new()
+            .with_branches(vec![
+                branch("main", State::Current),
+                branch("feature", State::Branch),
+            ])
+            .with_result(
+                "delete_branch",
+                ActionResult::from_err(
+                    "error: the branch 'feature' is not fully merged".into(),
+                ),
+            )
+    }
+
+    #[test]
+    fn picked_branch_is_checked_out() {
+        let branch = |name: &str, state| Entry {
+            filename: name.into(),
+            selected: false,
+            state,
+            columns: None,
+        };
+        let mock = MockActions::new().with_branches(vec![
+            branch("main", State::Current),
+            branch("feature", State::Branch),
+        ]);
+        let input = ScriptedInput::default()
+            .keys("bu")
+            .key(KeyEvent {
+                code: KeyCode::Down,
+                modifiers: KeyModifiers::NONE,
+            })
+            .key(KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::NONE,
+            });
+
+        let run = run(mock, input);
+
+        assert!(run.calls.contains(&"update feature".into()));
+    }
+
+    #[test]
+    fn remote_is_added_with_its_url() {
+        let input = ScriptedInput::default()
+            .keys("Rn")
+            .line("upstream")
+            .line("https://example.com/repo.git");
+
+        let run = run(origin_mock(), input);
+
+        assert!(run.calls.contains(
+            &"add_remote upstream https://example.com/repo.git".into()
+        ));
+    }
+
+    #[test]
+    fn remote_names_are_not_added_twice() {
+        let input = ScriptedInput::default().keys("Rn").line("origin");
+
+        let run = run(origin_mock(), input);
+
+        assert!(run.output.contains("there is already a remote called"));
+        assert!(!run.calls.iter().any(|c| c.starts_with("add_remote")));
+    }
+
+    fn origin_mock() -> MockActions {
+        MockActions::new().with_remotes(vec![Entry {
+            filename: "origin".into(),
+            selected: false,
+            state: State::Current,
+            columns: None,
+        }])
+    }
+
+    #[test]
+    fn picked_tag_is_deleted_after_confirming() {
+        let input = ScriptedInput::default()
+            .keys("td")
+            .key(KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::NONE,
+            })
+            .keys("y");
+
+        let run = run(tags_mock(), input);
+
+        assert!(run.calls.contains(&"delete_tag v2".into()));
+    }
+
+    #[test]
+    fn picked_tag_is_kept_when_declined() {
+        let input = ScriptedInput::default()
+            .keys("td")
+            .key(KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::NONE,
+            })
+            .keys("n");
+
+        let run = run(tags_mock(), input);
+
+        assert!(!run.calls.iter().any(|c| c.starts_with("delete_tag")));
+    }
+
+    #[test]
+    fn no_tags_are_told_instead_of_an_empty_list() {
+        let run = run(MockActions::new(), ScriptedInput::default().keys("tu"));
+
+        assert!(run.output.contains("no tags"));
+        assert!(!run.output.contains("nothing to select"));
+    }
+
+    fn tags_mock() -> MockActions {
+        let tag = |name: &str| Entry {
+            filename: name.into(),
+            selected: false,
+            state: State::Branch,
+            columns: None,
+        };
+        MockActions::new().with_tags(vec![tag("v2"), tag("v1")])
+    }
+
+    #[test]
+    fn deleting_a_tag_says_it_is_pushed_to_origin() {
+        let entry = |name: &str| Entry {
+            filename: name.into(),
+            selected: false,
+            state: State::Branch,
+            columns: None,
+        };
+        let enter = KeyEvent {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::NONE,
+        };
+        let mock = MockActions::new()
+            .with_tags(vec![entry("v1")])
+            .with_remotes(vec![entry("origin")]);
+
+        let input = ScriptedInput::default().keys("td").key(enter).keys("y");
+        let deleted = run(mock, input);
+
+        assert!(deleted.output.contains("this also runs"));
+        assert!(deleted.calls.contains(&"delete_tag v1 origin".into()));
+    }
+
+    #[test]
+    fn no_confirm_deletes_without_asking() {
+        let tag = Entry {
+            filename: "v1".into(),
+            selected: false,
+            state: State::Branch,
+            columns: None,
+        };
+        let enter = KeyEvent {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::NONE,
+        };
+        let mock = MockActions::new().with_tags(vec![tag]);
+        let calls = mock.calls();
+        let mut app = test_app(mock);
+        app.no_confirm = true;
+        let mut tui = test_tui(ScriptedInput::default().keys("td").key(enter));
+
+        tui.run(&mut app).unwrap();
+
+        let output = String::from_utf8_lossy(&tui.write);
+        assert!(!output.contains("delete tag 'v1'?"));
+        assert!(calls.lock().unwrap().contains(&"delete_tag v1".into()));
+    }
+
+    #[test]
+    fn no_confirm_still_asks_before_force_deleting() {
+        let mock = unmerged_branch_mock();
+        let calls = mock.calls();
+        let mut app = test_app(mock);
+        app.no_confirm = true;
+        let input = ScriptedInput::default()
+            .keys("bD")
+            .key(KeyEvent {
+                code: KeyCode::Down,
+                modifiers: KeyModifiers::NONE,
+            })
+            .key(KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::NONE,
+            })
+            .keys("n");
+        let mut tui = test_tui(input);
+
+        tui.run(&mut app).unwrap();
+
+        let output = String::from_utf8_lossy(&tui.write);
+        assert!(output.contains("force deleting 'feature' loses those commits"));
+        let calls = calls.lock().unwrap();
+        assert!(!calls.iter().any(|c| c.starts_with("force")));
+    }
+
+    #[test]
+    fn shown_output_is_paged_from_a_file() {
+        if cfg!(windows) {
+            return;
+        }
+        let paged = TempFile::create("paged", "").unwrap();
+        let mut app = test_app(MockActions::new());
+        app.pager = format!("cp {{path}} {}", paged.path().display());
+        let mut tui = test_tui(ScriptedInput::default().keys("vp"));
+
+        tui.run(&mut app).unwrap();
+
+        let paged = std::fs::read_to_string(paged.path()).unwrap();
+        assert!(paged.contains("commit all"));
+        assert!(tui.current_action_kind == ActionKind::Help);
+    }
+
+    #[test]
+    fn finished_actions_show_how_long_they_took() {
+        assert_eq!(finished_progress(Duration::from_millis(300)), "");
+        assert_eq!(finished_progress(Duration::from_millis(2345)), "2.3s");
+    }
+
+    #[test]
+    fn opening_asks_first_when_confirm_open_is_on() {
+        let mut app = test_app(MockActions::new());
+        app.confirm_open = true;
+        app.editor_command = "false".into();
+        let mut tui = test_tui(ScriptedInput::default().keys("oe").keys("n"));
+
+        tui.run(&mut app).unwrap();
+
+        assert!(!app.has_action_result(ActionKind::OpenEditor));
+    }
+
+    #[test]
+    fn explorer_can_be_turned_off() {
+        let mut app = test_app(MockActions::new());
+        app.open_command = None;
+        let mut tui = test_tui(ScriptedInput::default().keys("of"));
+
+        tui.run(&mut app).unwrap();
 
-                    self.show_header(app, HeaderKind::Error)?;
-                    self.write.queue(Print("no match found"))?;
-                    return Ok(());
-                }
-                _ => (),
-            }
+        let result = app.get_cached_action_result(ActionKind::OpenExplorer);
+        assert_eq!(result.output, OPEN_TURNED_OFF);
+    }
+
+    #[test]
+    fn open_command_failures_are_shown() {
+        if cfg!(windows) {
+            return;
         }
+        let mut app = test_app(MockActions::new());
+        app.editor_command = "verco-missing-editor {path}".into();
+        let mut tui = test_tui(ScriptedInput::default().keys("oe"));
+
+        tui.run(&mut app).unwrap();
+
+        let result = app.get_cached_action_result(ActionKind::OpenEditor);
+        assert!(!result.success);
+        assert!(result
+            .output
+            .starts_with("could not run 'verco-missing-editor {path}'"));
     }
 
-    fn handle_input(
-        &mut self,
-        app: &Application,
-        prompt: &str,
-        initial: Option<&str>,
-    ) -> Result<Option<String>> {
-        self.show_header(app, HeaderKind::Waiting)?;
-        execute!(
-            self.write,
-            SetForegroundColor(ENTRY_COLOR),
-            Print(prompt),
-            ResetColor,
-            cursor::MoveToNextLine(1),
-            cursor::Show,
-        )?;
+    #[test]
+    fn open_command_that_runs_succeeds() {
+        if cfg!(windows) {
+            return;
+        }
+        let mut app = test_app(MockActions::new());
+        app.editor_command = "true".into();
+        let mut tui = test_tui(ScriptedInput::default().keys("oe"));
 
-        let initial = if let Some(initial) = initial {
-            initial
-        } else {
-            ""
+        tui.run(&mut app).unwrap();
+
+        assert!(app.get_cached_action_result(ActionKind::OpenEditor).success);
+    }
+
+    #[test]
+    fn views_are_gone_back_to() {
+        let input = ScriptedInput::default().keys("sl").key(KeyEvent {
+            code: KeyCode::Left,
+            modifiers: KeyModifiers::ALT,
+        });
+
+        let run = run(status_and_log_mock(), input);
+
+        let status = run.output.rfind("nothing to commit").unwrap();
+        assert!(status > run.output.rfind("1234 commit").unwrap());
+        assert_eq!(run.calls.iter().filter(|c| *c == "status").count(), 1);
+    }
+
+    #[test]
+    fn views_are_gone_forward_to() {
+        let alt = |code| KeyEvent {
+            code,
+            modifiers: KeyModifiers::ALT,
         };
-        let res = match input::read_line(initial) {
-            Ok(line) => {
-                if line.len() > 0 {
-                    Some(line)
-                } else {
-                    None
-                }
-            }
-            Err(_error) => None,
+        let input = ScriptedInput::default()
+            .keys("sl")
+            .key(alt(KeyCode::Left))
+            .key(alt(KeyCode::Right));
+
+        let run = run(status_and_log_mock(), input);
+
+        let log = run.output.rfind("1234 commit").unwrap();
+        assert!(log > run.output.rfind("nothing to commit").unwrap());
+        let logs = run.calls.iter().filter(|c| c.starts_with("log")).count();
+        assert_eq!(logs, 1);
+    }
+
+    fn status_and_log_mock() -> MockActions {
+        MockActions::new()
+            .with_result(
+                "status",
+                ActionResult::from_ok("nothing to commit\n".into()),
+            )
+            .with_result("log", ActionResult::from_ok("1234 commit\n".into()))
+    }
+
+    #[test]
+    fn bookmark_under_cursor_is_deleted() {
+        let branches = "feature (bookmark)\n";
+        let mock = MockActions::new().with_result(
+            "list_branches",
+            ActionResult::from_ok(branches.into()),
+        );
+        let input = ScriptedInput::default()
+            .keys("bk")
+            .line("release")
+            .keys("bbbK")
+            .line("");
+
+        let run = run(mock, input);
+
+        assert!(run.calls.contains(&"create_bookmark release".into()));
+        assert!(run
+            .prompts
+            .contains(&"bookmark to delete (feature): ".into()));
+        assert!(run.calls.contains(&"delete_bookmark feature".into()));
+    }
+
+    #[test]
+    fn resolving_every_conflict_asks_first() {
+        let mock = MockActions::new().with_changed_files(conflict_entries());
+
+        let run = run(mock, ScriptedInput::default().keys("ron"));
+
+        assert!(run.output.contains("taking the other side"));
+        assert!(run.output.contains("a.txt"));
+        assert!(!run.output.contains("b.txt"));
+        assert!(!run.calls.contains(&"take_other".into()));
+    }
+
+    #[test]
+    fn every_conflict_is_resolved_once_confirmed() {
+        let mock = MockActions::new().with_changed_files(conflict_entries());
+
+        let run = run(mock, ScriptedInput::default().keys("rly"));
+
+        assert!(run.calls.contains(&"take_local".into()));
+    }
+
+    fn conflict_entries() -> Vec<Entry> {
+        vec![
+            Entry {
+                filename: "a.txt".into(),
+                selected: false,
+                state: State::Unmerged,
+                columns: Some(['U', 'U']),
+            },
+            Entry {
+                filename: "b.txt".into(),
+                selected: false,
+                state: State::Modified,
+                columns: Some([' ', 'M']),
+            },
+        ]
+    }
+
+    #[test]
+    fn nothing_is_aborted_without_an_operation() {
+        let run = run(MockActions::new(), ScriptedInput::default().keys("ra"));
+
+        assert!(run.output.contains(NO_OPERATION_TO_ABORT));
+        assert!(run.prompts.is_empty());
+    }
+
+    #[test]
+    fn merge_is_kept_when_aborting_is_declined() {
+        let mock = MockActions::new().with_operation("merge");
+
+        let run = run(mock, ScriptedInput::default().keys("nran"));
+
+        assert!(run.output.contains("abort the merge?"));
+        assert!(!run.calls.contains(&"abort_operation".into()));
+    }
+
+    #[test]
+    fn merge_is_aborted_once_confirmed() {
+        let mock = MockActions::new().with_operation("merge");
+
+        let run = run(mock, ScriptedInput::default().keys("nray"));
+
+        assert!(run.calls.contains(&"abort_operation".into()));
+    }
+
+    #[test]
+    fn picked_phase_is_set() {
+        let input = ScriptedInput::default()
+            .keys("gP")
+            .line("1234")
+            .key(KeyEvent {
+                code: KeyCode::Down,
+                modifiers: KeyModifiers::NONE,
+            })
+            .key(KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::NONE,
+            });
+
+        let run = run(MockActions::new(), input);
+
+        assert!(run.calls.contains(&"set_phase 1234 draft".into()));
+    }
+
+    #[test]
+    fn public_phase_is_set_only_after_a_warning() {
+        let input = ScriptedInput::default()
+            .keys("gP")
+            .line("1234")
+            .key(KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::NONE,
+            })
+            .keys("n");
+
+        let run = run(MockActions::new(), input);
+
+        assert!(run.output.contains("can no longer be amended"));
+        assert!(!run.calls.iter().any(|c| c.starts_with("set_phase")));
+    }
+
+    #[test]
+    fn history_is_edited_with_a_step_for_each_selected_revision() {
+        let key = |code| KeyEvent {
+            code,
+            modifiers: KeyModifiers::NONE,
         };
-        self.write.execute(cursor::Hide)?;
-        Ok(res)
+        let revision = |hash: &str, name: &str| {
+            let entry = Entry {
+                filename: name.into(),
+                selected: false,
+                state: State::Clean,
+                columns: None,
+            };
+            (hash.to_string(), entry)
+        };
+        let mock = MockActions::new().with_revisions(vec![
+            revision("aaa111", "aaa first"),
+            revision("bbb222", "bbb second"),
+        ]);
+        let input = ScriptedInput::default()
+            .keys("He")
+            .line("aaa")
+            .key(key(KeyCode::Down))
+            .key(key(KeyCode::Enter))
+            // fold
+            .key(key(KeyCode::Down))
+            .key(key(KeyCode::Down))
+            .key(key(KeyCode::Enter))
+            .keys("y");
+
+        let run = run(mock, input);
+
+        assert!(run.output.contains("fold bbb second"));
+        assert!(run
+            .calls
+            .contains(&"edit_history aaa pick:aaa111 fold:bbb222".into()));
     }
 
-    fn show_result(
-        &mut self,
-        app: &Application,
-        result: &ActionResult,
-    ) -> Result<()> {
-        if app.has_pending_action_of_type(self.current_action_kind) {
-            self.show_header(app, HeaderKind::Waiting)?;
-        } else if result.success {
-            self.show_header(app, HeaderKind::Ok)?;
-        } else {
-            self.show_header(app, HeaderKind::Error)?;
-        }
+    #[test]
+    fn absorb_is_previewed_before_applying() {
+        let preview = "showing changes for a.txt\n  abc1234 -old\n";
+        let mock = MockActions::new().with_result(
+            "absorb_preview",
+            ActionResult::from_ok(preview.into()),
+        );
 
-        self.scroll_view.set_content(
-            &result.output[..],
-            self.current_action_kind,
-            self.terminal_size,
+        let run = run(mock, ScriptedInput::default().keys("can"));
+
+        assert!(run.output.contains("abc1234 -old"));
+        assert!(!run.calls.contains(&"absorb".into()));
+    }
+
+    #[test]
+    fn absorb_is_applied_once_confirmed() {
+        let run = run(MockActions::new(), ScriptedInput::default().keys("cay"));
+
+        assert!(run.calls.contains(&"absorb".into()));
+    }
+
+    #[test]
+    fn status_follows_changes_to_the_working_tree() {
+        let mock = MockActions::new()
+            .with_result("status", ActionResult::from_ok("M a.txt".into()));
+
+        let run = run(mock, ScriptedInput::default().keys("cc").line("x"));
+
+        assert_eq!(status_calls(&run), 1);
+        assert!(run.output.contains("M a.txt"));
+    }
+
+    #[test]
+    fn status_is_not_shown_after_actions_keeping_the_working_tree() {
+        let run = run(MockActions::new(), ScriptedInput::default().keys("l"));
+
+        assert_eq!(status_calls(&run), 0);
+    }
+
+    #[test]
+    fn status_stops_following_changes_once_toggled_off() {
+        let input = ScriptedInput::default().keys("vScc").line("x");
+
+        let run = run(MockActions::new(), input);
+
+        assert!(run.calls.contains(&"commit_all x".into()));
+        assert_eq!(status_calls(&run), 0);
+    }
+
+    fn status_calls(run: &TestRun) -> usize {
+        run.calls.iter().filter(|c| *c == "status").count()
+    }
+
+    #[test]
+    fn stash_under_cursor_is_shown() {
+        let stashes = "stash@{0}: WIP on master: 1234 tweak\n";
+        let mock = MockActions::new()
+            .with_result("list_stashes", ActionResult::from_ok(stashes.into()));
+        let input = ScriptedInput::default().keys("zlzs").line("");
+
+        let run = run(mock, input);
+
+        assert!(run.prompts.contains(&"show stash (stash@{0}): ".into()));
+        assert!(run.calls.contains(&"stash_show stash@{0}".into()));
+    }
+
+    #[test]
+    fn stash_under_cursor_is_popped() {
+        let stashes = "stash@{0}: WIP on master: 1234 tweak\n";
+        let mock = MockActions::new()
+            .with_result("list_stashes", ActionResult::from_ok(stashes.into()));
+        let input = ScriptedInput::default().keys("zlzp").line("");
+
+        let run = run(mock, input);
+
+        assert!(run.prompts.contains(
+            &"pop stash (empty for the newest) (stash@{0}): ".into()
+        ));
+        assert!(run.calls.contains(&"stash_pop stash@{0}".into()));
+    }
+
+    #[test]
+    fn empty_stash_name_pops_the_newest() {
+        let input = ScriptedInput::default().keys("zp").line("");
+        let run = run(MockActions::new(), input);
+        assert!(run.calls.contains(&"stash_pop ".into()));
+    }
+
+    #[test]
+    fn empty_stash_list_says_so() {
+        let input = ScriptedInput::default().keys("zl");
+        let run = run(MockActions::new(), input);
+        assert!(run.output.contains("no stashes"));
+    }
+
+    #[test]
+    fn canceled_prompt_does_not_commit() {
+        let input = ScriptedInput::default().keys("cc");
+
+        let run = run(MockActions::new(), input);
+
+        assert_eq!(run.prompts, ["commit message: "]);
+        assert!(!run.calls.iter().any(|c| c.starts_with("commit")));
+        assert!(run.output.contains("(commit all canceled)"));
+    }
+
+    #[test]
+    fn switching_repositories_waits_for_running_actions() {
+        let mock = MockActions::new().with_pending("fetch");
+
+        let run = run(mock, ScriptedInput::default().keys("for"));
+
+        assert!(run.output.contains("wait for fetch to finish"));
+    }
+
+    #[test]
+    fn switching_repositories_needs_a_sibling() {
+        let run = run(MockActions::new(), ScriptedInput::default().keys("or"));
+
+        assert!(run.output.contains("no other git or hg repositories"));
+    }
+
+    #[test]
+    fn blank_input_cancels_instead_of_running_the_action() {
+        let input = ScriptedInput::default().keys("tn").line("   ").keys("l");
+
+        let run = run(MockActions::new(), input);
+
+        assert!(!run.calls.iter().any(|c| c.starts_with("create_tag")));
+        let canceled = run.output.rfind("(new tag canceled)").unwrap();
+        assert!(canceled < run.output.rfind("log").unwrap());
+        let after_log = &run.output[run.output.rfind("\x1b[2J").unwrap()..];
+        assert!(!after_log.contains("canceled"));
+    }
+
+    #[test]
+    fn quiet_header_is_drawn_once_while_running() {
+        let mock = MockActions::new().with_pending("fetch");
+        let mut app = test_app(mock);
+        app.quiet = true;
+        let input = ScriptedInput::default()
+            .keys("f")
+            .sleep(Duration::from_millis(300));
+        let mut tui = test_tui(input);
+
+        tui.run(&mut app).unwrap();
+
+        let output = String::from_utf8_lossy(&tui.write);
+        assert_eq!(output.matches("running").count(), 1);
+        assert!(!output.contains("| 0s"));
+    }
+
+    #[test]
+    fn output_is_shown_while_the_action_runs() {
+        let mock = MockActions::new()
+            .with_pending("fetch")
+            .with_result("fetch", ActionResult::from_ok("from origin".into()));
+        let input = ScriptedInput::default()
+            .keys("f")
+            .sleep(Duration::from_millis(100));
+
+        let run = run(mock, input);
+
+        assert!(run.output.contains("from origin"));
+    }
+
+    #[test]
+    fn broken_config_is_not_reloaded() {
+        let mut app = test_app(MockActions::new());
+        app.quick_actions = vec!["fetch".into()];
+
+        let broken = Config {
+            git_path: "verco-missing-git".into(),
+            quick_actions: vec!["push".into()],
+            ..Config::default()
+        };
+        assert!(app.reload_config(broken).is_err());
+        assert_eq!(app.quick_actions, ["fetch"]);
+
+        let config = Config {
+            quick_actions: vec!["push".into()],
+            notify_after: Some(Duration::from_secs(9)),
+            ..Config::default()
+        };
+        assert!(app.reload_config(config).is_ok());
+        assert_eq!(app.quick_actions, ["push"]);
+        assert_eq!(app.notify_after, Some(Duration::from_secs(9)));
+    }
+
+    #[test]
+    fn rebound_keys_run_their_action_instead_of_the_built_in_ones() {
+        let defaults: Vec<_> = HELP_ACTIONS.iter().flatten().copied().collect();
+        assert!(KeyBindings::parse("", &defaults).is_ok());
+
+        let mock = MockActions::new();
+        let calls = mock.calls();
+        let mut app = test_app(mock);
+        app.key_bindings =
+            KeyBindings::parse("fetch gf\nstatus f", &defaults).unwrap();
+        let mut tui = test_tui(ScriptedInput::default().keys("gfh"));
+
+        tui.run(&mut app).unwrap();
+
+        let calls = calls.lock().unwrap().clone();
+        assert!(calls.contains(&"fetch".into()));
+        let help = &app.get_cached_action_result(ActionKind::Help).output;
+        assert!(help.contains("\tgf"));
+    }
+
+    #[test]
+    fn esc_cancels_running_action_before_quitting() {
+        let mock = MockActions::new().with_pending("fetch");
+        let input = ScriptedInput::default().keys("f").key(KeyEvent {
+            code: KeyCode::Esc,
+            modifiers: KeyModifiers::NONE,
+        });
+
+        let run = run(mock, input);
+
+        assert_eq!(
+            run.calls,
+            [
+                "get_current_changed_files",
+                "version",
+                "get_remotes",
+                "fetch",
+                "cancel fetch",
+                "get_current_changed_files"
+            ]
         );
-        self.scroll_view
-            .draw_content(&mut self.write, self.terminal_size)
+        assert!(run.output.contains("canceled"));
+        assert!(run.output.contains("may have partially completed"));
     }
 
-    fn show_current_key_chord(&mut self) -> Result<()> {
-        let TerminalSize { width, height } = self.terminal_size;
-        queue!(
-            self.write,
-            cursor::MoveTo(
-                width - self.current_key_chord.len() as u16,
-                height - 1
-            ),
-            Clear(ClearType::CurrentLine),
-            SetForegroundColor(ENTRY_COLOR),
-        )?;
-        for c in &self.current_key_chord {
-            self.write.queue(Print(c))?;
-        }
-        self.write.queue(ResetColor)?;
-        Ok(())
+    #[test]
+    fn esc_goes_back_to_help_before_quitting() {
+        let esc = KeyEvent {
+            code: KeyCode::Esc,
+            modifiers: KeyModifiers::NONE,
+        };
+        let input = ScriptedInput::default().keys("s").key(esc).keys("l");
+
+        let run = run(MockActions::new(), input);
+
+        // still running after the first esc
+        assert!(run.calls.contains(&"log 0 50 false true".into()));
+        let status = run.output.find(" status ").unwrap();
+        assert!(
+            run.output[status..].contains("press a key and peform an action")
+        );
     }
 
-    fn show_help(&mut self, app: &Application) -> Result<ActionResult> {
-        let mut write = Vec::with_capacity(1024);
+    #[test]
+    fn esc_quits_from_the_help_screen() {
+        let input = ScriptedInput::default()
+            .key(KeyEvent {
+                code: KeyCode::Esc,
+                modifiers: KeyModifiers::NONE,
+            })
+            .keys("s");
 
-        queue!(
-            &mut write,
-            Print(BIN_NAME),
-            Print(' '),
-            Print(VERSION),
-            cursor::MoveToNextLine(2),
-        )?;
+        let run = run(MockActions::new(), input);
 
-        if let Ok(version) = app.version_control.version() {
-            queue!(&mut write, Print(version), cursor::MoveToNextLine(2))?;
-        }
+        assert_eq!(run.calls, ["get_current_changed_files", "version"]);
+    }
 
-        write
-            .queue(Print("press a key and peform an action"))?
-            .queue(cursor::MoveToNextLine(2))?;
+    #[test]
+    fn long_commit_subjects_wrap_apart_from_the_body() {
+        let subject = "s".repeat(200);
+        let details = format!("revision abc\n\n{}\n\nbody\n", subject);
+        let mock = MockActions::new()
+            .with_result("revision_details", ActionResult::from_ok(details));
+        let input = ScriptedInput::default().keys("DI").line("abc");
 
-        Self::show_help_action(&mut write, "h", ActionKind::Help)?;
-        Self::show_help_action(&mut write, "q", ActionKind::Quit)?;
+        let run = run(mock, input);
 
-        write.queue(cursor::MoveToNextLine(1))?;
+        // 200 columns take two full rows of 80 and 40 of a third
+        let last_row = format!("\x1b[2K{}\x1b", "s".repeat(40));
+        let body = run.output.rfind("body").unwrap();
+        assert!(run.output.rfind(&last_row).unwrap() < body);
+        assert!(!run.output.contains(&"s".repeat(81)));
+    }
 
-        Self::show_help_action(&mut write, "s", ActionKind::Status)?;
-        Self::show_help_action(&mut write, "l", ActionKind::Log)?;
-        Self::show_help_action(&mut write, "LC", ActionKind::LogCount)?;
+    #[test]
+    fn long_lines_wrap() {
+        let mock = MockActions::new()
+            .with_result("status", ActionResult::from_ok(long_diff_line()));
+        let mut app = test_app(mock);
+        app.wrap_diffs = false;
+        let mut tui = test_tui(ScriptedInput::default().keys("s"));
 
-        Self::show_help_action(
-            &mut write,
-            "ee",
-            ActionKind::CurrentFullRevision,
-        )?;
-        Self::show_help_action(&mut write, "dd", ActionKind::CurrentDiffAll)?;
-        Self::show_help_action(
-            &mut write,
-            "ds",
-            ActionKind::CurrentDiffSelected,
-        )?;
-        Self::show_help_action(&mut write, "DC", ActionKind::RevisionChanges)?;
-        Self::show_help_action(&mut write, "DD", ActionKind::RevisionDiffAll)?;
-        Self::show_help_action(
-            &mut write,
-            "DS",
-            ActionKind::RevisionDiffSelected,
-        )?;
+        tui.run(&mut app).unwrap();
 
-        write.queue(cursor::MoveToNextLine(1))?;
+        assert!(String::from_utf8_lossy(&tui.write).contains(&second_row()));
+    }
 
-        Self::show_help_action(&mut write, "cc", ActionKind::CommitAll)?;
-        Self::show_help_action(&mut write, "cs", ActionKind::CommitSelected)?;
-        Self::show_help_action(&mut write, "u", ActionKind::Update)?;
-        Self::show_help_action(&mut write, "m", ActionKind::Merge)?;
-        Self::show_help_action(&mut write, "RA", ActionKind::RevertAll)?;
-        Self::show_help_action(&mut write, "rs", ActionKind::RevertSelected)?;
+    #[test]
+    fn diffs_wrap_like_other_output() {
+        let mock = MockActions::new().with_result(
+            "current_diff_all",
+            ActionResult::from_ok(long_diff_line()),
+        );
 
-        write.queue(cursor::MoveToNextLine(1))?;
+        let run = run(mock, ScriptedInput::default().keys("dd"));
 
-        Self::show_help_action(
-            &mut write,
-            "rr",
-            ActionKind::UnresolvedConflicts,
-        )?;
-        Self::show_help_action(&mut write, "ro", ActionKind::MergeTakingOther)?;
-        Self::show_help_action(&mut write, "rl", ActionKind::MergeTakingLocal)?;
+        assert!(run.output.contains(&second_row()));
+    }
 
-        write.queue(cursor::MoveToNextLine(1))?;
+    #[test]
+    fn diffs_kept_from_wrapping_are_cut_off() {
+        let mock = MockActions::new().with_result(
+            "current_diff_all",
+            ActionResult::from_ok(long_diff_line()),
+        );
+        let mut app = test_app(mock);
+        app.wrap_diffs = false;
+        let mut tui = test_tui(ScriptedInput::default().keys("dd"));
 
-        Self::show_help_action(&mut write, "f", ActionKind::Fetch)?;
-        Self::show_help_action(&mut write, "p", ActionKind::Pull)?;
-        Self::show_help_action(&mut write, "P", ActionKind::Push)?;
+        tui.run(&mut app).unwrap();
 
-        write.queue(cursor::MoveToNextLine(1))?;
+        assert!(!String::from_utf8_lossy(&tui.write).contains(&second_row()));
+    }
 
-        Self::show_help_action(&mut write, "tn", ActionKind::NewTag)?;
+    fn long_diff_line() -> String {
+        format!("+{}", "x".repeat(100))
+    }
 
-        write.queue(cursor::MoveToNextLine(1))?;
+    /// What is left of `long_diff_line` after the 80 columns of the first row
+    fn second_row() -> String {
+        format!("\x1b[2K{}\x1b", "x".repeat(21))
+    }
 
-        Self::show_help_action(&mut write, "bb", ActionKind::ListBranches)?;
-        Self::show_help_action(&mut write, "bn", ActionKind::NewBranch)?;
-        Self::show_help_action(&mut write, "bd", ActionKind::DeleteBranch)?;
+    #[test]
+    fn failed_status_leaves_no_counts() {
+        let mock = MockActions::new()
+            .with_changed_files(vec![Entry {
+                filename: "a.txt".into(),
+                selected: false,
+                state: State::Modified,
+                columns: None,
+            }])
+            .with_result(
+                "get_current_changed_files",
+                ActionResult::from_err("fatal: not a repository".into()),
+            );
+        let mut app = test_app(mock);
 
-        write.queue(cursor::MoveToNextLine(1))?;
+        app.refresh_status();
 
-        Self::show_help_action(&mut write, "x", ActionKind::CustomAction)?;
+        assert!(app.status_counts.is_none());
+        assert!(app.current_changed_files().is_err());
+        assert!(app.status_counts.is_none());
+    }
 
-        write.flush()?;
-        Ok(ActionResult::from_ok(String::from_utf8(write)?))
+    #[test]
+    fn wrapped_lines_scroll_a_row_at_a_time() {
+        let long = format!("{}{}", "a".repeat(80), "b".repeat(80));
+        let output = format!("{}\n{}", long, "line\n".repeat(30));
+        let mock = MockActions::new()
+            .with_result("status", ActionResult::from_ok(output));
+        let input = ScriptedInput::default().keys("s").key(KeyEvent {
+            code: KeyCode::Down,
+            modifiers: KeyModifiers::NONE,
+        });
+
+        let run = run(mock, input);
+
+        let top_row = format!("\x1b[2;1H\x1b[0m\x1b[2K{}", "b".repeat(80));
+        assert!(run.output.contains(&top_row));
     }
 
-    fn show_help_action<HW>(
-        write: &mut HW,
-        shortcut: &str,
-        action: ActionKind,
-    ) -> Result<()>
-    where
-        HW: Write,
-    {
-        queue!(
-            write,
-            SetForegroundColor(ENTRY_COLOR),
-            Print('\t'),
-            Print(shortcut),
-            ResetColor,
-            Print('\t'),
-            Print('\t'),
-            Print(action.name()),
-            cursor::MoveToNextLine(1),
-        )
+    #[test]
+    fn scrollback_keeps_the_previous_outputs() {
+        let mock = MockActions::new()
+            .with_result("status", ActionResult::from_ok("clean\n".into()))
+            .with_result("fetch", ActionResult::from_ok("fetched\n".into()));
+        let calls = mock.calls();
+        let mut app = test_app(mock);
+        app.output_mode = OutputMode::Scrollback;
+        let mut tui = test_tui(ScriptedInput::default().keys("sf"));
+
+        tui.run(&mut app).unwrap();
+
+        assert!(calls.lock().unwrap().contains(&"fetch".into()));
+        let output = String::from_utf8_lossy(&tui.write);
+        let fetch = output.rfind("00 fetch").unwrap();
+        let screen = output[..fetch].rfind("\x1b[2J").unwrap();
+        let status = output[screen..fetch].find("00 status").unwrap();
+        assert!(output[screen + status..fetch].contains("clean"));
+    }
+
+    #[test]
+    fn quick_actions_are_listed_first_with_their_keys() {
+        let mut app = test_app(MockActions::new());
+        app.quick_actions = vec!["push".into(), "unknown".into(), "log".into()];
+        let mut tui = test_tui(ScriptedInput::default());
+
+        let help = tui.show_help(&app).unwrap();
+
+        let help = diff::strip_ansi(&help.output);
+        let quick = help.find("quick actions").unwrap();
+        let push = help.find("\tP\t\tpush").unwrap();
+        let log = help.find("\tl\t\tlog").unwrap();
+        assert!(quick < push && push < log);
+        assert!(log < help.find("\th\t\thelp").unwrap());
+    }
+
+    #[test]
+    fn help_snapshot() {
+        let app = test_app(
+            MockActions::new()
+                .with_result("version", ActionResult::from_ok("git 2".into())),
+        );
+        let mut tui = test_tui(ScriptedInput::default());
+        tui.current_action_kind = ActionKind::Help;
+
+        let help = tui.show_help(&app).unwrap();
+        tui.show_result(&app, &help).unwrap();
+
+        assert_snapshot("help", &tui.write);
+    }
+
+    #[test]
+    fn result_snapshots() {
+        let app = test_app(MockActions::new());
+        let mut tui = test_tui(ScriptedInput::default());
+        tui.current_action_kind = ActionKind::Status;
+
+        let status = ActionResult::from_ok(
+            "On branch master\nnothing to commit, working tree clean\n".into(),
+        );
+        tui.show_result(&app, &status).unwrap();
+        assert_snapshot("result_ok", &tui.write);
+
+        tui.write.clear();
+        let mut status = ActionResult::from_ok("Already up to date.\n".into());
+        status.warnings = "warning: redirecting to origin\n".into();
+        tui.show_result(&app, &status).unwrap();
+        assert_snapshot("result_warnings", &tui.write);
+
+        tui.write.clear();
+        tui.current_action_kind = ActionKind::Push;
+        let push = ActionResult::from_err(
+            "fatal: Authentication failed for 'origin'\n".into(),
+        );
+        tui.show_result(&app, &push).unwrap();
+        assert_snapshot("result_error", &tui.write);
     }
 }
@@ -1,4 +1,8 @@
-use std::io::Write;
+use std::{
+    env,
+    io::Write,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 use crossterm::{
     cursor, handle_command, queue,
@@ -7,62 +11,31 @@ use crossterm::{
     Result,
 };
 
-pub const SELECTED_BG_COLOR: Color = Color::Rgb {
-    r: 80,
-    g: 80,
-    b: 80,
-};
-pub const ENTRY_COLOR: Color = Color::Rgb {
-    r: 255,
-    g: 180,
-    b: 100,
-};
-
-pub const LOG_COLORS: &[Color] = &[
-    Color::White,
-    Color::Rgb {
-        r: 211,
-        g: 153,
-        b: 33,
-    },
-    Color::Rgb {
-        r: 52,
-        g: 113,
-        b: 134,
-    },
-    Color::Rgb {
-        r: 137,
-        g: 151,
-        b: 29,
-    },
-    Color::Rgb {
-        r: 251,
-        g: 73,
-        b: 47,
-    },
-    Color::White,
+use crate::{select::StatusCounts, theme};
+
+/// Rgb values of the basic colors as most terminals show them, and whether
+/// they have a hue at all
+const BASIC_COLORS: &[(Color, (u8, u8, u8), bool)] = &[
+    (Color::Black, (0, 0, 0), false),
+    (Color::DarkGrey, (128, 128, 128), false),
+    (Color::Grey, (192, 192, 192), false),
+    (Color::White, (255, 255, 255), false),
+    (Color::DarkRed, (128, 0, 0), true),
+    (Color::DarkGreen, (0, 128, 0), true),
+    (Color::DarkYellow, (128, 128, 0), true),
+    (Color::DarkBlue, (0, 0, 128), true),
+    (Color::DarkMagenta, (128, 0, 128), true),
+    (Color::DarkCyan, (0, 128, 128), true),
+    (Color::Red, (255, 0, 0), true),
+    (Color::Green, (0, 255, 0), true),
+    (Color::Yellow, (255, 255, 0), true),
+    (Color::Blue, (0, 0, 255), true),
+    (Color::Magenta, (255, 0, 255), true),
+    (Color::Cyan, (0, 255, 255), true),
 ];
 
-const HEADER_COLOR: Color = Color::Black;
-const ACTION_COLOR: Color = Color::White;
-const HEADER_BG_WAITING_COLOR: Color = Color::Magenta;
-const HEADER_BG_WAITING_DARK_COLOR: Color = Color::DarkMagenta;
-const HEADER_BG_OK_COLOR: Color = Color::Green;
-const HEADER_BG_OK_DARK_COLOR: Color = Color::DarkGreen;
-const HEADER_BG_ERROR_COLOR: Color = Color::Red;
-const HEADER_BG_ERROR_DARK_COLOR: Color = Color::DarkRed;
-
-const FILTER_COLOR: Color = Color::Black;
-const FILTER_ACTIVE_BG_COLOR: Color = Color::Rgb {
-    r: 255,
-    g: 180,
-    b: 100,
-};
-const FILTER_INACTIVE_BG_COLOR: Color = Color::Rgb {
-    r: 180,
-    g: 90,
-    b: 50,
-};
+static BASIC_COLORS_ONLY: AtomicBool = AtomicBool::new(false);
+static CURSOR_STOPS_AT_ENDS: AtomicBool = AtomicBool::new(false);
 
 const HEADER_PREFIX: &str = concat!(env!("CARGO_PKG_NAME"), " @ ");
 const DIR_NAME_MAX_LENGTH: usize = 32;
@@ -73,15 +46,27 @@ pub enum HeaderKind {
     Error,
 }
 
+#[derive(Clone, Copy)]
 pub struct Header<'a> {
     pub action_name: &'a str,
     pub directory_name: &'a str,
+    /// Like `git 2.43.0`, after the directory name
+    pub backend: &'a str,
+    /// What the working tree is on, like `main ↑1`, after the backend. Left
+    /// out when the action name would have to be cut off for it
+    pub branch: &'a str,
+    /// Shown before the status, like a spinner while waiting
+    pub progress: &'a str,
+    /// Shown in the gap before the status when there is room
+    pub status_counts: Option<StatusCounts>,
 }
 
 impl<'a> Header<'a> {
     pub fn full_length(&self) -> usize {
         HEADER_PREFIX.len()
             + self.directory_name.len()
+            + self.backend_length()
+            + self.branch_length()
             + 3
             + self.action_name.len()
     }
@@ -89,9 +74,29 @@ impl<'a> Header<'a> {
     pub fn min_length(&self) -> usize {
         HEADER_PREFIX.len()
             + self.directory_name.len().min(DIR_NAME_MAX_LENGTH)
+            + self.backend_length()
+            + self.branch_length()
             + 3
             + self.action_name.len()
     }
+
+    /// With the ` on ` before it
+    fn branch_length(&self) -> usize {
+        if self.branch.is_empty() {
+            0
+        } else {
+            self.branch.chars().count() + 4
+        }
+    }
+
+    /// With the parentheses and the space before them
+    fn backend_length(&self) -> usize {
+        if self.backend.is_empty() {
+            0
+        } else {
+            self.backend.len() + 3
+        }
+    }
 }
 
 pub fn show_header<W>(
@@ -103,16 +108,33 @@ pub fn show_header<W>(
 where
     W: Write,
 {
+    queue!(write, Clear(ClearType::All))?;
+    draw_header(write, header, kind, terminal_size)?;
+    queue!(write, cursor::MoveTo(0, 1))
+}
+
+/// Draws only the header line leaving the rest of the screen and the cursor
+/// position untouched
+pub fn draw_header<W>(
+    write: &mut W,
+    header: Header,
+    kind: HeaderKind,
+    terminal_size: TerminalSize,
+) -> Result<()>
+where
+    W: Write,
+{
+    let theme = theme::current();
     let background_color = match kind {
-        HeaderKind::Waiting => HEADER_BG_WAITING_COLOR,
-        HeaderKind::Ok => HEADER_BG_OK_COLOR,
-        HeaderKind::Error => HEADER_BG_ERROR_COLOR,
+        HeaderKind::Waiting => theme.header_bg_waiting,
+        HeaderKind::Ok => theme.header_bg_ok,
+        HeaderKind::Error => theme.header_bg_error,
     };
 
     let background_dark_color = match kind {
-        HeaderKind::Waiting => HEADER_BG_WAITING_DARK_COLOR,
-        HeaderKind::Ok => HEADER_BG_OK_DARK_COLOR,
-        HeaderKind::Error => HEADER_BG_ERROR_DARK_COLOR,
+        HeaderKind::Waiting => theme.header_bg_waiting_dark,
+        HeaderKind::Ok => theme.header_bg_ok_dark,
+        HeaderKind::Error => theme.header_bg_error_dark,
     };
 
     let status = match kind {
@@ -120,11 +142,20 @@ where
         HeaderKind::Ok => "ok",
         HeaderKind::Error => "error",
     };
+    let status = if header.progress.is_empty() {
+        String::from(status)
+    } else {
+        format!("{} {}", header.progress, status)
+    };
+    let status = &status[..];
 
     let header_prefix;
     let directory_name;
+    let mut branch = header.branch;
+    let mut action_name = String::from(header.action_name);
 
-    let terminal_width = terminal_size.width as usize;
+    let region = Region::header(terminal_size);
+    let terminal_width = region.width as usize;
     let mut padding = 0;
 
     fn fit(
@@ -150,33 +181,87 @@ where
         directory_name = &header.directory_name
             [(header.directory_name.len() - DIR_NAME_MAX_LENGTH)..];
     } else {
-        panic!("window too small");
+        // what does not fit of the action name is cut off
+        header_prefix = HEADER_PREFIX;
+        let cut = header
+            .directory_name
+            .len()
+            .saturating_sub(DIR_NAME_MAX_LENGTH);
+        directory_name = &header.directory_name[cut..];
+        branch = "";
+        let fixed_length = header.min_length()
+            - header.branch_length()
+            - header.action_name.len()
+            + status.len()
+            + 2;
+        let width = terminal_width.saturating_sub(fixed_length);
+        if action_name.chars().count() > width {
+            action_name =
+                action_name.chars().take(width.saturating_sub(2)).collect();
+            action_name.push_str("..");
+        }
+        padding = 0;
     }
 
+    let counts = header
+        .status_counts
+        .map(|counts| counts.segments())
+        .unwrap_or_default();
+    // every count and the spaces around it
+    let counts_length = counts
+        .iter()
+        .map(|(text, _)| text.chars().count() + 1)
+        .sum::<usize>()
+        + 1;
+    let counts_padding = if !counts.is_empty() && padding > counts_length {
+        padding - counts_length
+    } else {
+        padding
+    };
+
     queue!(
         write,
-        Clear(ClearType::All),
-        cursor::MoveTo(0, 0),
-        SetBackgroundColor(background_color),
-        SetForegroundColor(HEADER_COLOR),
+        cursor::SavePosition,
+        cursor::MoveTo(region.x, region.y),
+        bg(background_color),
+        fg(theme.header),
         Print(header_prefix),
         Print(directory_name),
+    )?;
+    if !header.backend.is_empty() {
+        queue!(write, Print(" ("), Print(header.backend), Print(')'))?;
+    }
+    if !branch.is_empty() {
+        queue!(write, Print(" on "), Print(branch))?;
+    }
+    queue!(
+        write,
         Print(' '),
-        SetBackgroundColor(background_dark_color),
-        SetForegroundColor(ACTION_COLOR),
+        bg(background_dark_color),
+        fg(theme.header_action),
         Print(' '),
-        Print(header.action_name),
+        Print(action_name),
         Print(' '),
-        SetBackgroundColor(background_color),
-        SetForegroundColor(HEADER_COLOR),
-        Print(" ".repeat(padding)),
-        SetBackgroundColor(background_dark_color),
-        SetForegroundColor(ACTION_COLOR),
+        bg(background_color),
+        fg(theme.header),
+        Print(" ".repeat(counts_padding)),
+    )?;
+    if counts_padding < padding {
+        queue!(write, SetBackgroundColor(Color::Reset))?;
+        for (text, color) in &counts {
+            queue!(write, Print(' '), fg(*color), Print(text))?;
+        }
+        queue!(write, Print(' '))?;
+    }
+    queue!(
+        write,
+        bg(background_dark_color),
+        fg(theme.header_action),
         Print(' '),
         Print(status),
         Print(' '),
         ResetColor,
-        cursor::MoveToNextLine(1),
+        cursor::RestorePosition,
     )
 }
 
@@ -187,15 +272,173 @@ pub struct TerminalSize {
 }
 
 impl TerminalSize {
-    pub fn get() -> Result<Self> {
-        let size = terminal::size()?;
-        Ok(Self {
-            width: size.0,
-            height: size.1,
-        })
+    /// Queries the terminal size falling back to the `COLUMNS`/`LINES`
+    /// environment variables and then to 80x24
+    pub fn get() -> Self {
+        if let Ok((width, height)) = terminal::size() {
+            if width > 0 && height > 0 {
+                return Self { width, height };
+            }
+        }
+
+        fn env_dimension(name: &str, default: u16) -> u16 {
+            env::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .filter(|&v| v > 0)
+                .unwrap_or(default)
+        }
+
+        Self {
+            width: env_dimension("COLUMNS", 80),
+            height: env_dimension("LINES", 24),
+        }
+    }
+}
+
+/// A rectangle of the screen, in cells
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Region {
+    /// The top line
+    pub fn header(terminal_size: TerminalSize) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width: terminal_size.width,
+            height: 1,
+        }
+    }
+
+    /// Everything between the header and the filter bar at the bottom
+    pub fn body(terminal_size: TerminalSize) -> Self {
+        Self {
+            x: 0,
+            y: 1,
+            width: terminal_size.width,
+            height: terminal_size.height.saturating_sub(2),
+        }
+    }
+
+    /// The part of the region below its first `rows` and right of its
+    /// first `columns`
+    pub fn inset(self, columns: u16, rows: u16) -> Self {
+        Self {
+            x: self.x + columns.min(self.width),
+            y: self.y + rows.min(self.height),
+            width: self.width.saturating_sub(columns),
+            height: self.height.saturating_sub(rows),
+        }
     }
 }
 
+impl From<AvailableSize> for Region {
+    /// The body of a terminal with room for `available_size`
+    fn from(available_size: AvailableSize) -> Self {
+        Self {
+            x: 0,
+            y: 1,
+            width: available_size.width as u16,
+            height: available_size.height as u16,
+        }
+    }
+}
+
+/// Draws each of the plain `lines` on a row of `region`, cutting off what does
+/// not fit and blanking the rest of the region's width. Rows below the last
+/// line are left untouched. The cursor ends up at the region's left edge
+/// just below the last line drawn
+pub fn draw_text_in<'a, W, L>(
+    write: &mut W,
+    region: Region,
+    lines: L,
+) -> Result<()>
+where
+    W: Write,
+    L: IntoIterator<Item = &'a str>,
+{
+    let width = region.width as usize;
+    let mut y = region.y;
+    for line in lines.into_iter().take(region.height as usize) {
+        let clipped: String = line.chars().take(width).collect();
+        let padding = width - clipped.chars().count();
+        queue!(
+            write,
+            cursor::MoveTo(region.x, y),
+            Print(clipped),
+            Print(" ".repeat(padding)),
+        )?;
+        y += 1;
+    }
+    queue!(write, cursor::MoveTo(region.x, y))
+}
+
+/// Splits `line` into the parts that fit in rows `width` columns wide.
+/// Escape sequences take no room and stay whole, except that moving to the
+/// next line starts over at the first column. Tabs reach the next multiple
+/// of 8 columns and wide characters take two
+pub fn wrap_line(line: &str, width: usize) -> Vec<&str> {
+    let mut rows = Vec::new();
+    let mut row_start = 0;
+    let mut column = 0;
+    let mut chars = line.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == '\x1b' {
+            // skip "[" and everything up to the final letter
+            for (_, c) in &mut chars {
+                if c.is_ascii_alphabetic() {
+                    if c == 'E' {
+                        column = 0;
+                    }
+                    break;
+                }
+            }
+            continue;
+        }
+        let advance = |column| match c {
+            '\t' => 8 - column % 8,
+            c if is_wide(c) => 2,
+            _ => 1,
+        };
+        if column > 0 && column + advance(column) > width {
+            rows.push(&line[row_start..i]);
+            row_start = i;
+            column = 0;
+        }
+        column += advance(column);
+    }
+    rows.push(&line[row_start..]);
+    rows
+}
+
+/// Whether terminals draw `c` two columns wide, which holds for the CJK
+/// blocks, their full width forms and most emoji
+fn is_wide(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1100..=0x115f
+            | 0x2e80..=0x303e
+            | 0x3041..=0x33ff
+            | 0x3400..=0x4dbf
+            | 0x4e00..=0x9fff
+            | 0xa000..=0xa4cf
+            | 0xac00..=0xd7a3
+            | 0xf900..=0xfaff
+            | 0xfe30..=0xfe4f
+            | 0xff00..=0xff60
+            | 0xffe0..=0xffe6
+            | 0x1f300..=0x1f64f
+            | 0x1f900..=0x1f9ff
+            | 0x20000..=0x3fffd
+    )
+}
+
 #[derive(Clone, Copy)]
 pub struct AvailableSize {
     pub width: usize,
@@ -211,6 +454,31 @@ impl AvailableSize {
     }
 }
 
+/// What moving the cursor of a list past its first or last entry does
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Navigation {
+    /// Goes around to the other end
+    Wrap,
+    /// Stays on the entry it reached
+    Stop,
+}
+
+impl Navigation {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "wrap" => Some(Self::Wrap),
+            "stop" => Some(Self::Stop),
+            _ => None,
+        }
+    }
+}
+
+/// Makes cursors move like `navigation` from now on
+pub fn set_navigation(navigation: Navigation) {
+    CURSOR_STOPS_AT_ENDS
+        .store(navigation == Navigation::Stop, Ordering::Relaxed);
+}
+
 pub fn move_cursor(
     scroll: &mut usize,
     cursor: &mut usize,
@@ -224,16 +492,17 @@ pub fn move_cursor(
         return;
     }
 
+    let wraps = !CURSOR_STOPS_AT_ENDS.load(Ordering::Relaxed);
     let previous_cursor = *cursor;
     let target_cursor = *cursor as i32 + delta;
     *cursor = if target_cursor < 0 {
-        if previous_cursor == 0 {
+        if wraps && previous_cursor == 0 {
             (target_cursor + entry_count as i32) as usize % entry_count
         } else {
             0
         }
     } else if target_cursor >= entry_count as i32 {
-        if previous_cursor == entry_count - 1 {
+        if wraps && previous_cursor == entry_count - 1 {
             (target_cursor + entry_count as i32) as usize % entry_count
         } else {
             entry_count - 1
@@ -249,6 +518,7 @@ pub fn move_cursor(
     }
 }
 
+/// Whether `text` has the chars of `pattern` in order, ignoring case
 pub fn fuzzy_matches(text: &str, pattern: &[char]) -> bool {
     let pattern_len = pattern.len();
     let mut pattern_index = 0;
@@ -257,7 +527,7 @@ pub fn fuzzy_matches(text: &str, pattern: &[char]) -> bool {
             break;
         }
 
-        if pattern[pattern_index] == c {
+        if pattern[pattern_index].to_lowercase().eq(c.to_lowercase()) {
             pattern_index += 1;
         }
     }
@@ -278,16 +548,16 @@ where
     }
 
     let bg_color = if active {
-        FILTER_ACTIVE_BG_COLOR
+        theme::current().filter_active_bg
     } else {
-        FILTER_INACTIVE_BG_COLOR
+        theme::current().filter_inactive_bg
     };
 
     queue!(
         write,
         cursor::MoveTo(0, 9999),
-        SetBackgroundColor(bg_color),
-        SetForegroundColor(FILTER_COLOR),
+        bg(bg_color),
+        fg(theme::current().filter),
         Print('/'),
     )?;
 
@@ -298,3 +568,225 @@ where
     queue!(write, Clear(ClearType::UntilNewLine), ResetColor)?;
     Ok(())
 }
+
+/// Falls back to the basic colors unless `COLORTERM` reports truecolor support
+pub fn detect_color_support() {
+    let true_color = matches!(
+        env::var("COLORTERM").as_deref(),
+        Ok("truecolor") | Ok("24bit")
+    ) || env::var_os("WT_SESSION").is_some();
+    BASIC_COLORS_ONLY.store(!true_color, Ordering::Relaxed);
+}
+
+/// Terminals like serial consoles, where every redraw is slow enough to show
+pub fn is_slow_terminal() -> bool {
+    match env::var("TERM") {
+        Ok(term) => term == "dumb" || term.starts_with("vt"),
+        Err(_) => false,
+    }
+}
+
+/// Every color goes through here so rgb colors can be swapped for the closest
+/// basic one on terminals that can not show them
+pub fn fit_color(color: Color) -> Color {
+    match color {
+        Color::Rgb { r, g, b } if BASIC_COLORS_ONLY.load(Ordering::Relaxed) => {
+            closest_basic_color(r, g, b)
+        }
+        _ => color,
+    }
+}
+
+fn closest_basic_color(r: u8, g: u8, b: u8) -> Color {
+    // keep colors with a clear hue from turning into a shade of grey
+    let has_hue = r.max(g).max(b) - r.min(g).min(b) > 60;
+    let distance = |(br, bg, bb): (u8, u8, u8)| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(r, br) + d(g, bg) + d(b, bb)
+    };
+    BASIC_COLORS
+        .iter()
+        .filter(|(_, _, hue)| *hue == has_hue)
+        .min_by_key(|(_, rgb, _)| distance(*rgb))
+        .map(|(basic, _, _)| *basic)
+        .unwrap_or(Color::White)
+}
+
+pub fn fg(color: Color) -> SetForegroundColor {
+    SetForegroundColor(fit_color(color))
+}
+
+pub fn bg(color: Color) -> SetBackgroundColor {
+    SetBackgroundColor(fit_color(color))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::snapshot::assert_snapshot;
+
+    const TEST_TERMINAL_SIZE: TerminalSize = TerminalSize {
+        width: 60,
+        height: 10,
+    };
+
+    fn render_header(kind: HeaderKind, progress: &str) -> Vec<u8> {
+        let mut write = Vec::new();
+        let header = Header {
+            action_name: "status",
+            directory_name: "/home/user/repo",
+            backend: "",
+            branch: "",
+            progress,
+            status_counts: None,
+        };
+        show_header(&mut write, header, kind, TEST_TERMINAL_SIZE).unwrap();
+        write
+    }
+
+    #[test]
+    fn rgb_colors_fall_back_to_basic_colors() {
+        assert_eq!(closest_basic_color(80, 80, 80), Color::DarkGrey);
+        assert_eq!(closest_basic_color(255, 180, 100), Color::Yellow);
+        assert_eq!(closest_basic_color(255, 80, 80), Color::Red);
+        assert_eq!(closest_basic_color(52, 113, 134), Color::DarkCyan);
+        assert_eq!(closest_basic_color(255, 255, 255), Color::White);
+    }
+
+    #[test]
+    fn header_snapshots() {
+        assert_snapshot("header_ok", &render_header(HeaderKind::Ok, ""));
+        assert_snapshot(
+            "header_waiting",
+            &render_header(HeaderKind::Waiting, "/ 3s"),
+        );
+        assert_snapshot("header_error", &render_header(HeaderKind::Error, ""));
+    }
+
+    #[test]
+    fn text_is_clipped_to_its_region() {
+        let mut write = Vec::new();
+        let region = Region {
+            x: 2,
+            y: 3,
+            width: 4,
+            height: 2,
+        };
+        draw_text_in(&mut write, region, vec!["abcdef", "g", "hidden"])
+            .unwrap();
+        let output = String::from_utf8(write).unwrap();
+        assert_eq!(output, "\x1b[4;3Habcd\x1b[5;3Hg   \x1b[6;3H");
+    }
+
+    #[test]
+    fn long_action_names_are_cut_off() {
+        let mut write = Vec::new();
+        let header = Header {
+            action_name:
+                "revision diff all (3 lines of context, detecting moves)",
+            directory_name: "/home/user/repo",
+            backend: "",
+            branch: "",
+            progress: "",
+            status_counts: None,
+        };
+        show_header(&mut write, header, HeaderKind::Ok, TEST_TERMINAL_SIZE)
+            .unwrap();
+        let output = String::from_utf8(write).unwrap();
+        assert!(output.contains(" revision diff all (3 lines o.. "));
+    }
+
+    #[test]
+    fn status_counts_are_shown_when_they_fit() {
+        let mut write = Vec::new();
+        let header = Header {
+            action_name: "status",
+            directory_name: "/home/user/repo",
+            backend: "",
+            branch: "",
+            progress: "",
+            status_counts: Some(StatusCounts {
+                staged: Some(3),
+                modified: 2,
+                untracked: 1,
+                conflicted: 0,
+            }),
+        };
+        show_header(&mut write, header, HeaderKind::Ok, TEST_TERMINAL_SIZE)
+            .unwrap();
+        let output =
+            crate::diff::strip_ansi(&String::from_utf8(write).unwrap());
+        assert!(output.contains(" \u{25cf}3 +2 ?1 \u{2717}0  ok "));
+    }
+
+    #[test]
+    fn backend_follows_the_directory_name() {
+        let mut write = Vec::new();
+        let header = Header {
+            action_name: "status",
+            directory_name: "/home/user/repo",
+            backend: "hg 6.5.2",
+            branch: "",
+            progress: "",
+            status_counts: None,
+        };
+        show_header(&mut write, header, HeaderKind::Ok, TEST_TERMINAL_SIZE)
+            .unwrap();
+        let output =
+            crate::diff::strip_ansi(&String::from_utf8(write).unwrap());
+        assert!(output.contains("/home/user/repo (hg 6.5.2)  status "));
+    }
+
+    #[test]
+    fn branch_is_left_out_before_the_action_name_is_cut_off() {
+        let draw = |action_name| {
+            let mut write = Vec::new();
+            let header = Header {
+                action_name,
+                directory_name: "/home/user/repo",
+                backend: "",
+                branch: "feature \u{2191}2",
+                progress: "",
+                status_counts: None,
+            };
+            show_header(&mut write, header, HeaderKind::Ok, TEST_TERMINAL_SIZE)
+                .unwrap();
+            crate::diff::strip_ansi(&String::from_utf8(write).unwrap())
+        };
+
+        assert!(draw("status")
+            .contains("/home/user/repo on feature \u{2191}2  status "));
+        let long =
+            draw("revision diff all (3 lines of context, detecting moves)");
+        assert!(!long.contains("feature"));
+        assert!(long.contains(" revision diff all (3 lines o.. "));
+    }
+
+    #[test]
+    fn lines_wrap_without_splitting_escape_sequences() {
+        assert_eq!(wrap_line("abcdefg", 3), ["abc", "def", "g"]);
+        assert_eq!(wrap_line("abc", 3), ["abc"]);
+        assert_eq!(wrap_line("", 3), [""]);
+        assert_eq!(
+            wrap_line("\x1b[31mabcd\x1b[m", 2),
+            ["\x1b[31mab", "cd\x1b[m"]
+        );
+        assert_eq!(wrap_line("a\tb", 8), ["a\t", "b"]);
+        assert_eq!(wrap_line("abc\x1b[1Eabc", 3), ["abc\x1b[1Eabc"]);
+        assert_eq!(wrap_line("\u{e9}t\u{e9}", 2), ["\u{e9}t", "\u{e9}"]);
+        assert_eq!(
+            wrap_line("a\u{6f22}\u{5b57}", 4),
+            ["a\u{6f22}", "\u{5b57}"]
+        );
+    }
+
+    #[test]
+    fn filters_ignore_case() {
+        let pattern: Vec<_> = "readme".chars().collect();
+        assert!(fuzzy_matches("docs/README.md", &pattern));
+        let pattern: Vec<_> = "SRCmain".chars().collect();
+        assert!(fuzzy_matches("src/main.rs", &pattern));
+        assert!(!fuzzy_matches("src/lib.rs", &pattern));
+    }
+}
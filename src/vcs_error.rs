@@ -0,0 +1,106 @@
+use std::{fmt, io};
+
+#[derive(Debug)]
+pub enum VcsError {
+    Conflict(String),
+    /// Deleting a branch would lose commits that are not merged anywhere
+    Unmerged(String),
+    AuthFailed(String),
+    NotFound(String),
+    NotARepo(String),
+    CommandFailed {
+        stderr: String,
+        code: Option<i32>,
+    },
+    Io(io::Error),
+}
+
+impl VcsError {
+    /// Classifies a failed command by looking at what it printed to stderr
+    pub fn from_stderr(stderr: String, code: Option<i32>) -> Self {
+        let lowercase = stderr.to_lowercase();
+        let contains_any =
+            |patterns: &[&str]| patterns.iter().any(|p| lowercase.contains(p));
+
+        if contains_any(&["conflict", "unresolved"]) {
+            Self::Conflict(stderr)
+        } else if contains_any(&["not fully merged"]) {
+            Self::Unmerged(stderr)
+        } else if contains_any(&[
+            "authentication failed",
+            "authorization failed",
+            "permission denied",
+            "could not read username",
+            "could not read password",
+            "terminal prompts disabled",
+            "http error 401",
+            "http error 403",
+        ]) {
+            Self::AuthFailed(stderr)
+        } else if contains_any(&["not a git repository", "no repository found"])
+        {
+            Self::NotARepo(stderr)
+        } else if contains_any(&[
+            "unknown revision",
+            "bad revision",
+            "did not match any",
+            "not found",
+            "does not exist",
+            "not something we can merge",
+        ]) {
+            Self::NotFound(stderr)
+        } else {
+            Self::CommandFailed { stderr, code }
+        }
+    }
+
+    /// What the user could do next to get out of this error
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::Conflict(_) => Some(
+                "use 'rr' to list unresolved conflicts and 'ro'/'rl' to resolve them",
+            ),
+            Self::Unmerged(_) => Some(
+                "merge it first or pick it with 'bD' to be asked to force deleting it",
+            ),
+            Self::AuthFailed(_) => Some(
+                "check your credentials or ssh keys. use 'T' to run a fetch, pull or push again in the terminal, where they can be typed in",
+            ),
+            Self::NotFound(_) => Some(
+                "check the name you typed. 'l' lists revisions and 'bb' lists branches",
+            ),
+            Self::NotARepo(_) | Self::CommandFailed { .. } | Self::Io(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for VcsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Conflict(stderr)
+            | Self::Unmerged(stderr)
+            | Self::AuthFailed(stderr)
+            | Self::NotFound(stderr)
+            | Self::NotARepo(stderr) => f.write_str(stderr),
+            Self::CommandFailed { stderr, code } => {
+                if stderr.trim().is_empty() {
+                    match code {
+                        Some(code) => {
+                            write!(f, "command exited with code {}", code)
+                        }
+                        None => f.write_str("command was terminated"),
+                    }
+                } else {
+                    f.write_str(stderr)
+                }
+            }
+            Self::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<io::Error> for VcsError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
@@ -1,80 +1,700 @@
-use std::process::{Command, Stdio};
+use std::{
+    io::{self, Read, Write},
+    process::{Command, Stdio},
+    thread::{self, JoinHandle},
+    time::Instant,
+};
 
 use crate::{
-    action::{ActionTask, CommandTask},
-    select::Entry,
+    action::{ready, ActionResult, ActionTask, CommandTask},
+    command_log, profile,
+    select::{Entry, State},
+    vcs_error::VcsError,
 };
 
+pub const NO_BOOKMARKS: &str = "only hg has bookmarks";
+pub const NO_PHASES: &str = "only hg has phases";
+pub const NO_HISTORY_EDITING: &str = "only hg can edit history with histedit";
+pub const NO_ABSORB: &str = "only hg can absorb changes";
+pub const NO_COMMIT_TO_UNDO: &str = "there are no commits to undo";
+pub const MERGE_NOT_UNDONE: &str =
+    "the last commit is a merge, which can not be undone keeping its changes";
+pub const NO_SUBMODULES: &str =
+    "only git has submodules. hg updates subrepositories along with 'u'";
+pub const NO_SUBMODULES_HERE: &str =
+    "there are no submodules in this repository";
+pub const NO_UPSTREAMS: &str = "only git branches track an upstream";
+pub const NO_OPERATION_TO_ABORT: &str =
+    "there is no merge, rebase or cherry-pick in progress to abort";
+pub const NO_HUNK_STAGING: &str =
+    "only git can commit parts of a file. use 'cs' to commit whole files";
+
+/// Set by git for the hooks and editors it runs. Started from one of those,
+/// commands would work on that index or object store instead of the
+/// repository found here, whose directories are passed as `global_args`
+const INHERITED_GIT_ENV: &[&str] = &[
+    "GIT_DIR",
+    "GIT_WORK_TREE",
+    "GIT_INDEX_FILE",
+    "GIT_OBJECT_DIRECTORY",
+    "GIT_ALTERNATE_OBJECT_DIRECTORIES",
+    "GIT_COMMON_DIR",
+    "GIT_QUARANTINE_PATH",
+    "GIT_PREFIX",
+];
+
 pub trait VersionControlActions: Send {
-    fn executable_name(&self) -> &'static str;
+    /// Like `git` or `hg`
+    fn name(&self) -> &'static str;
+    fn executable_path(&self) -> &str;
     fn current_dir(&self) -> &str;
 
+    /// Arguments that go before the subcommand of every command
+    fn global_args(&self) -> Vec<String> {
+        Vec::new()
+    }
+
     fn command(&self) -> Command {
-        let mut command = Command::new(self.executable_name());
+        let mut command = Command::new(self.executable_path());
+        command.args(self.global_args());
         command.current_dir(self.current_dir());
         command.stdin(Stdio::null());
         command.stdout(Stdio::piped());
         command.stderr(Stdio::piped());
+        command.env("GIT_TERMINAL_PROMPT", "0");
+        for name in INHERITED_GIT_ENV {
+            command.env_remove(name);
+        }
         command
     }
 
+    /// Runs a command to completion and maps a non zero exit code to its stderr
+    fn run(&self, args: &[&str]) -> Result<CommandOutput, VcsError> {
+        let mut command = self.command();
+        command.args(args);
+        CommandOutput::from_command(&mut command)
+    }
+
     /// Sets the root of the current repository
-    fn set_root(&mut self) -> Result<(), String>;
+    fn set_root(&mut self) -> Result<(), VcsError>;
     /// Get the root of the current repository
     fn get_root(&self) -> &str;
 
-    fn get_current_changed_files(&self) -> Result<Vec<Entry>, String>;
+    /// Name of the branch the working tree is on
+    fn current_branch(&self) -> Result<String, VcsError>;
+    /// The current branch, or the short hash when on none, followed by how
+    /// many commits it is ahead and behind of its upstream, like `main ↑2`
+    fn head_summary(&self) -> Option<String> {
+        self.current_branch().ok()
+    }
+    /// Name of the remote branch tracked by the current branch
+    fn upstream_branch(&self) -> Result<String, VcsError>;
+    /// Name of the multi step operation, like a merge or rebase, that was
+    /// started but not finished yet
+    fn operation_in_progress(&self) -> Option<&'static str>;
+
+    fn get_current_changed_files(&self) -> Result<Vec<Entry>, VcsError>;
     fn get_revision_changed_files(
         &self,
         target: &str,
-    ) -> Result<Vec<Entry>, String>;
+    ) -> Result<Vec<Entry>, VcsError>;
 
-    fn version(&self) -> Result<String, String>;
+    /// Files whose contents differ between `target` and the working tree
+    fn get_changed_files_since(
+        &self,
+        target: &str,
+    ) -> Result<Vec<Entry>, VcsError>;
+
+    /// Whether changes go through an index before being committed
+    fn has_staging_area(&self) -> bool {
+        true
+    }
+
+    /// Whether there are bookmarks, which move along with new revisions
+    /// like git branches, besides branches
+    fn has_bookmarks(&self) -> bool {
+        false
+    }
+
+    /// Whether revisions have a `Phase` that limits what can be pushed and
+    /// rewritten
+    fn has_phases(&self) -> bool {
+        false
+    }
+
+    /// Whether a range of revisions can be rewritten with a `HistoryStep`
+    /// for each
+    fn has_history_editing(&self) -> bool {
+        false
+    }
+
+    /// Whether uncommitted changes can be amended into the draft revisions
+    /// that last touched the same lines
+    fn has_absorb(&self) -> bool {
+        false
+    }
+
+    /// Whether tags live on remotes apart from the history, so deleting one
+    /// there takes a push of its own
+    fn has_remote_tags(&self) -> bool {
+        false
+    }
+
+    /// Whether some hunks of a file can be committed without the others
+    fn has_hunk_staging(&self) -> bool {
+        false
+    }
+
+    /// Whether diffs can be computed with a `DiffAlgorithm`
+    fn has_diff_algorithms(&self) -> bool {
+        true
+    }
+
+    /// Whether `fetch` and `pull` without a remote go to all of them rather
+    /// than only the default one
+    fn has_all_remotes(&self) -> bool {
+        false
+    }
+
+    fn version(&self) -> Result<String, VcsError>;
 
     fn status(&self) -> Box<dyn ActionTask>;
     /// Shows the header and all diffs for the current revision
-    fn current_export(&self) -> Box<dyn ActionTask>;
-    fn log(&self, count: usize) -> Box<dyn ActionTask>;
+    fn current_export(&self, options: &DiffOptions) -> Box<dyn ActionTask>;
+    /// Lists `count` revisions older than the newest `skip` ones. With
+    /// `signatures` each one also tells whether its signature verifies, when
+    /// the vcs supports it. With `graph` lines drawing how the revisions
+    /// branch and merge go before and between them
+    fn log(
+        &self,
+        skip: usize,
+        count: usize,
+        signatures: bool,
+        graph: bool,
+    ) -> Box<dyn ActionTask>;
 
-    fn current_diff_all(&self) -> Box<dyn ActionTask>;
+    fn current_diff_all(&self, options: &DiffOptions) -> Box<dyn ActionTask>;
     fn current_diff_selected(
         &self,
         entries: &Vec<Entry>,
+        options: &DiffOptions,
     ) -> Box<dyn ActionTask>;
 
-    fn revision_changes(&self, target: &str) -> Box<dyn ActionTask>;
-    fn revision_diff_all(&self, target: &str) -> Box<dyn ActionTask>;
+    /// Diff of the changes to `file` alone, like `current_diff_selected`
+    /// with only that file selected
+    fn diff_file(
+        &self,
+        file: &str,
+        options: &DiffOptions,
+    ) -> Result<String, VcsError>;
+
+    /// Author, date, full message and changed files of `target`
+    fn revision_details(&self, target: &str) -> Result<String, VcsError>;
+    fn revision_changes(
+        &self,
+        target: &str,
+        options: &DiffOptions,
+    ) -> Box<dyn ActionTask>;
+    fn revision_diff_all(
+        &self,
+        target: &str,
+        options: &DiffOptions,
+    ) -> Box<dyn ActionTask>;
     fn revision_diff_selected(
         &self,
         target: &str,
         entries: &Vec<Entry>,
+        options: &DiffOptions,
     ) -> Box<dyn ActionTask>;
 
-    fn commit_all(&self, message: &str) -> Box<dyn ActionTask>;
+    fn commit_all(
+        &self,
+        message: &str,
+        options: &CommitOptions,
+    ) -> Box<dyn ActionTask>;
+    /// The uncommitted changes of `file` as a plain diff that `commit_patch`
+    /// can take hunks of
+    fn hunk_diff(&self, _file: &str) -> Result<String, VcsError> {
+        Err(VcsError::CommandFailed {
+            stderr: NO_HUNK_STAGING.into(),
+            code: None,
+        })
+    }
+    /// Commits the changes in `patch`, which has hunks of `hunk_diff`s,
+    /// leaving the rest of them in the working tree and what was staged
+    /// before in the index
+    fn commit_patch(
+        &self,
+        _message: &str,
+        _patch: &str,
+        _options: &CommitOptions,
+    ) -> Box<dyn ActionTask> {
+        ready(ActionResult::from_err(NO_HUNK_STAGING.into()))
+    }
     fn commit_selected(
         &self,
         message: &str,
         entries: &Vec<Entry>,
+        options: &CommitOptions,
     ) -> Box<dyn ActionTask>;
+    /// Takes back the last commit, leaving its changes in the working tree
+    /// to be committed again. Merges are refused
+    fn undo_last_commit(&self) -> Box<dyn ActionTask>;
     fn revert_all(&self) -> Box<dyn ActionTask>;
     fn revert_selected(&self, entries: &Vec<Entry>) -> Box<dyn ActionTask>;
+    /// Resets the selected files to their contents at `target`. Only the
+    /// index is touched unless `working_tree` is set
+    fn reset_selected(
+        &self,
+        target: &str,
+        entries: &[Entry],
+        working_tree: bool,
+    ) -> Box<dyn ActionTask>;
     fn update(&self, target: &str) -> Box<dyn ActionTask>;
+    /// Checks out the commits the current revision records for submodules,
+    /// cloning the missing ones with `init` and nested ones with `recursive`
+    fn update_submodules(
+        &self,
+        _init: bool,
+        _recursive: bool,
+    ) -> Box<dyn ActionTask> {
+        ready(ActionResult::from_err(NO_SUBMODULES.into()))
+    }
     fn merge(&self, target: &str) -> Box<dyn ActionTask>;
+    /// Applies the changes of `target` on top of the current revision
+    fn cherry_pick(&self, target: &str) -> Box<dyn ActionTask>;
+    /// Commits a cherry-pick that stopped at conflicts once they are resolved
+    fn continue_cherry_pick(&self) -> Box<dyn ActionTask>;
+    fn abort_cherry_pick(&self) -> Box<dyn ActionTask>;
+    /// Moves the commits of the current branch on top of `onto`
+    fn rebase(&self, onto: &str) -> Box<dyn ActionTask>;
+    /// Goes on with a rebase that stopped at conflicts once they are resolved
+    fn continue_rebase(&self) -> Box<dyn ActionTask>;
+    fn abort_rebase(&self) -> Box<dyn ActionTask>;
+    /// Gives up the `operation_in_progress`, going back to where the working
+    /// tree was before it started
+    fn abort_operation(&self) -> Box<dyn ActionTask>;
 
     fn conflicts(&self) -> Box<dyn ActionTask>;
     fn take_other(&self) -> Box<dyn ActionTask>;
     fn take_local(&self) -> Box<dyn ActionTask>;
 
-    fn fetch(&self) -> Box<dyn ActionTask>;
-    fn pull(&self) -> Box<dyn ActionTask>;
-    fn push(&self) -> Box<dyn ActionTask>;
+    /// Remotes to pick one to fetch, pull or push with, the one the current
+    /// branch tracks first
+    fn get_remotes(&self) -> Result<Vec<Entry>, VcsError>;
+    /// Shows each remote with its url
+    fn list_remotes(&self) -> Box<dyn ActionTask>;
+    fn add_remote(&self, name: &str, url: &str) -> Box<dyn ActionTask>;
+    fn remove_remote(&self, name: &str) -> Box<dyn ActionTask>;
+    /// The subcommand and arguments `fetch`, `pull` or `push` run for
+    /// `operation`
+    fn remote_args(
+        &self,
+        operation: RemoteOperation,
+        remote: Option<&str>,
+    ) -> Vec<String>;
+    /// Runs `args` to the end with the terminal's stdin, copying what the
+    /// command prints to the terminal as it comes, so prompts for credentials
+    /// and passphrases can be answered. Returns everything it printed
+    fn run_in_terminal(&self, args: &[String]) -> Result<String, VcsError> {
+        let mut command = self.command();
+        command.args(args);
+        command.env_remove("GIT_TERMINAL_PROMPT");
+        run_in_terminal(&mut command)
+    }
+    /// `remote` is `None` for the default ones
+    fn fetch(&self, remote: Option<&str>) -> Box<dyn ActionTask>;
+    fn pull(&self, remote: Option<&str>) -> Box<dyn ActionTask>;
+    fn push(&self, remote: Option<&str>) -> Box<dyn ActionTask>;
+    /// The current branch when pushing it fails for lack of an upstream to
+    /// push to, which `push_set_upstream` sets
+    fn branch_without_upstream(&self) -> Option<String> {
+        None
+    }
+    /// Pushes `branch` to `remote` and has it track the branch there
+    fn push_set_upstream(
+        &self,
+        _remote: &str,
+        _branch: &str,
+    ) -> Box<dyn ActionTask> {
+        ready(ActionResult::from_err(NO_UPSTREAMS.into()))
+    }
 
     fn create_tag(&self, name: &str) -> Box<dyn ActionTask>;
+    /// Tags to pick one from, the newest first
+    fn list_tags(&self) -> Result<Vec<Entry>, VcsError>;
+    /// Also deletes it from `remote` when tags are kept on remotes
+    fn delete_tag(
+        &self,
+        name: &str,
+        remote: Option<&str>,
+    ) -> Box<dyn ActionTask>;
     fn list_branches(&self) -> Box<dyn ActionTask>;
+    /// Local branches to pick one to check out from, the current one first
+    fn get_branches_for_checkout(&self) -> Result<Vec<Entry>, VcsError>;
     fn create_branch(&self, name: &str) -> Box<dyn ActionTask>;
     fn close_branch(&self, name: &str) -> Box<dyn ActionTask>;
+    /// Deletes the local branch `name`. Without `force` a branch with
+    /// commits that are merged nowhere else fails with `VcsError::Unmerged`
+    fn delete_branch(
+        &self,
+        name: &str,
+        force: bool,
+    ) -> Result<String, VcsError>;
+    /// Creates a bookmark on the current revision and makes it the active one
+    fn create_bookmark(&self, _name: &str) -> Box<dyn ActionTask> {
+        ready(ActionResult::from_err(NO_BOOKMARKS.into()))
+    }
+    fn delete_bookmark(&self, _name: &str) -> Box<dyn ActionTask> {
+        ready(ActionResult::from_err(NO_BOOKMARKS.into()))
+    }
+    fn show_phase(&self, _target: &str) -> Box<dyn ActionTask> {
+        ready(ActionResult::from_err(NO_PHASES.into()))
+    }
+    /// Moving a revision back to draft or secret is forced since picking the
+    /// phase already asked for it
+    fn set_phase(&self, _target: &str, _phase: Phase) -> Box<dyn ActionTask> {
+        ready(ActionResult::from_err(NO_PHASES.into()))
+    }
+    /// Revisions from `base` up to the working copy parent, oldest first,
+    /// each with its hash and an entry naming it by its short hash followed
+    /// by its summary
+    fn get_revisions_to_edit(
+        &self,
+        _base: &str,
+    ) -> Result<Vec<(String, Entry)>, VcsError> {
+        Err(VcsError::CommandFailed {
+            stderr: NO_HISTORY_EDITING.into(),
+            code: None,
+        })
+    }
+    /// Rewrites the revisions from `base` with one step for each hash, in
+    /// the order they are given. Folding keeps both messages
+    fn edit_history(
+        &self,
+        _base: &str,
+        _steps: &[(HistoryStep, String)],
+    ) -> Box<dyn ActionTask> {
+        ready(ActionResult::from_err(NO_HISTORY_EDITING.into()))
+    }
+    /// After the revision being edited was amended or its conflicts resolved
+    fn continue_history_edit(&self) -> Box<dyn ActionTask> {
+        ready(ActionResult::from_err(NO_HISTORY_EDITING.into()))
+    }
+    fn abort_history_edit(&self) -> Box<dyn ActionTask> {
+        ready(ActionResult::from_err(NO_HISTORY_EDITING.into()))
+    }
+    /// Which changes `absorb` would amend into which revisions, without
+    /// changing anything
+    fn absorb_preview(&self) -> Result<String, VcsError> {
+        Err(VcsError::CommandFailed {
+            stderr: NO_ABSORB.into(),
+            code: None,
+        })
+    }
+    fn absorb(&self) -> Box<dyn ActionTask> {
+        ready(ActionResult::from_err(NO_ABSORB.into()))
+    }
+    /// Lists local branches as `MergedBranch` lines telling whether each one
+    /// is merged into `base`
+    fn list_merged_branches(&self, base: &str) -> Box<dyn ActionTask>;
+    /// Deletes local branches that are already merged
+    fn delete_branches(&self, names: &[String]) -> Box<dyn ActionTask>;
+
+    /// Files that stashing with `include` would take out of the working tree
+    fn stash_files(
+        &self,
+        include: StashInclude,
+    ) -> Result<Vec<String>, VcsError>;
+    fn stash(
+        &self,
+        message: &str,
+        include: StashInclude,
+    ) -> Box<dyn ActionTask>;
+    /// File listing the revisions, like reformatting ones, that blame should
+    /// look past
+    fn blame_ignore_revs_file(&self) -> Option<String> {
+        None
+    }
+    /// Shows who last changed each line of `file`, as of `target` or the
+    /// working tree, optionally only for an inclusive range of lines.
+    /// Each line starts with the short hash of its revision
+    fn blame(
+        &self,
+        target: Option<&str>,
+        file: &str,
+        lines: Option<(usize, usize)>,
+        ignore_revs: bool,
+    ) -> Box<dyn ActionTask>;
+
+    fn list_stashes(&self) -> Box<dyn ActionTask>;
+    /// Applies a stash and drops it, the newest when `name` is empty
+    fn stash_pop(&self, name: &str) -> Box<dyn ActionTask>;
+    /// Shows the diff a stash would apply without applying it
+    fn stash_show(
+        &self,
+        name: &str,
+        options: &DiffOptions,
+    ) -> Box<dyn ActionTask>;
+}
+
+/// What commits get on top of their message and changes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CommitOptions {
+    /// Adds a `Signed-off-by` trailer with the committer
+    pub sign_off: bool,
+    /// Signs the commit with gpg, which only git can
+    pub gpg_sign: bool,
+}
+
+impl CommitOptions {
+    /// Short summary for the commit prompt, like "signed off". Empty when
+    /// commits are plain
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.sign_off {
+            parts.push("signed off");
+        }
+        if self.gpg_sign {
+            parts.push("gpg signed");
+        }
+        parts.join(", ")
+    }
 }
 
+/// How the commands showing diffs compute them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// Unchanged lines shown around each change
+    pub context: usize,
+    /// Whether lines that differ only in whitespace count as unchanged
+    pub ignore_whitespace: bool,
+    /// `None` leaves it to the version control configuration
+    pub algorithm: Option<DiffAlgorithm>,
+    /// Whether renamed, copied and moved code is told apart from code that
+    /// was removed and added
+    pub detect_moves: bool,
+    /// How similar, in percent, a file has to be to count as renamed or copied
+    pub move_threshold: usize,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            context: 3,
+            ignore_whitespace: false,
+            algorithm: None,
+            detect_moves: false,
+            move_threshold: 50,
+        }
+    }
+}
+
+impl DiffOptions {
+    /// Short summary for the header, like "3 lines of context"
+    pub fn describe(&self) -> String {
+        let mut description = match self.context {
+            1 => "1 line of context".into(),
+            context => format!("{} lines of context", context),
+        };
+        if self.ignore_whitespace {
+            description.push_str(", ignoring whitespace");
+        }
+        if let Some(algorithm) = self.algorithm {
+            description.push_str(", ");
+            description.push_str(algorithm.name());
+        }
+        if self.detect_moves {
+            description.push_str(", detecting moves");
+        }
+        description
+    }
+}
+
+/// How git lines up the old and new versions of a file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffAlgorithm {
+    Myers,
+    Minimal,
+    Patience,
+    Histogram,
+}
+
+impl DiffAlgorithm {
+    pub const ALL: [Self; 4] =
+        [Self::Myers, Self::Minimal, Self::Patience, Self::Histogram];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|a| a.name() == name)
+    }
+
+    /// The value of `--diff-algorithm`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Myers => "myers",
+            Self::Minimal => "minimal",
+            Self::Patience => "patience",
+            Self::Histogram => "histogram",
+        }
+    }
+
+    /// The one after `algorithm`, going back to the configured one after the
+    /// last
+    pub fn next(algorithm: Option<Self>) -> Option<Self> {
+        match algorithm {
+            None => Some(Self::ALL[0]),
+            Some(algorithm) => {
+                let index = Self::ALL.iter().position(|&a| a == algorithm);
+                index.and_then(|i| Self::ALL.get(i + 1).copied())
+            }
+        }
+    }
+}
+
+/// How the log and blame show when a revision was made
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum DateFormat {
+    /// Like `3 days ago`
+    Relative,
+    /// Like `2020-06-01`
+    #[default]
+    Short,
+    /// Like `2020-06-01 14:30:00 +0200`
+    Iso,
+    /// Like `2020-06-01T14:30:00+02:00`
+    IsoStrict,
+    /// A strftime format, like `%d %b %Y`
+    Custom(String),
+}
+
+impl DateFormat {
+    /// One of the names or `format:` followed by a strftime format
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "relative" => Some(Self::Relative),
+            "short" => Some(Self::Short),
+            "iso" => Some(Self::Iso),
+            "iso-strict" => Some(Self::IsoStrict),
+            _ => value
+                .strip_prefix("format:")
+                .filter(|format| !format.is_empty())
+                .map(|format| Self::Custom(format.into())),
+        }
+    }
+
+    /// git's `--date` option, which `%ad` in formats follows
+    pub fn git_arg(&self) -> String {
+        match self {
+            Self::Relative => "--date=relative".into(),
+            Self::Short => "--date=short".into(),
+            Self::Iso => "--date=iso".into(),
+            Self::IsoStrict => "--date=iso-strict".into(),
+            Self::Custom(format) => format!("--date=format:{}", format),
+        }
+    }
+
+    /// hg template showing the `date` of a revision
+    pub fn hg_template(&self) -> String {
+        match self {
+            Self::Relative => "{date|age}".into(),
+            Self::Short => "{date|shortdate}".into(),
+            Self::Iso => "{date|isodate}".into(),
+            Self::IsoStrict => "{date|rfc3339date}".into(),
+            Self::Custom(format) => format!(
+                "{{date(date, '{}')}}",
+                format.replace('\\', "\\\\").replace('\'', "\\'")
+            ),
+        }
+    }
+}
+
+/// Which files besides the tracked changes go into a stash
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StashInclude {
+    Tracked,
+    Untracked,
+    /// Untracked and ignored files
+    All,
+}
+
+/// Public revisions were shared and can no longer be rewritten, draft ones can
+/// and secret ones are not pushed either
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Phase {
+    Public,
+    Draft,
+    Secret,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 3] = [Phase::Public, Phase::Draft, Phase::Secret];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Public => "public",
+            Phase::Draft => "draft",
+            Phase::Secret => "secret",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|p| p.name() == name)
+    }
+}
+
+/// What happens to a revision when editing history
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HistoryStep {
+    Pick,
+    Drop,
+    /// Into the revision before it
+    Fold,
+    /// Stops after it so it can be amended
+    Edit,
+}
+
+impl HistoryStep {
+    pub const ALL: [HistoryStep; 4] = [
+        HistoryStep::Pick,
+        HistoryStep::Drop,
+        HistoryStep::Fold,
+        HistoryStep::Edit,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HistoryStep::Pick => "pick",
+            HistoryStep::Drop => "drop",
+            HistoryStep::Fold => "fold",
+            HistoryStep::Edit => "edit",
+        }
+    }
+}
+
+/// The name of the version control and the version number out of its version
+/// output, like `git 2.43.0`
+pub fn backend_name(version_control: &dyn VersionControlActions) -> String {
+    let version = version_control.version().unwrap_or_default();
+    match version_number(&version) {
+        Some(number) => format!("{} {}", version_control.name(), number),
+        None => version_control.name().into(),
+    }
+}
+
+/// The first word of the first line that starts with a digit
+fn version_number(version: &str) -> Option<&str> {
+    version
+        .lines()
+        .next()?
+        .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+        .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+/// Leaves the message it is given as it is. Commands run with no terminal for
+/// a real editor, so folding or continuing keeps the message there is
+pub const KEEP_MESSAGE_EDITOR: &str =
+    if cfg!(windows) { "rem" } else { "true" };
+
 pub fn task<F>(
     version_control: &dyn VersionControlActions,
     builder: F,
@@ -87,17 +707,153 @@ where
     Box::new(CommandTask::Waiting(command))
 }
 
-pub fn handle_command(command: &mut Command) -> Result<String, String> {
-    match command.output() {
-        Ok(output) => {
-            if output.status.success() {
-                String::from_utf8(output.stdout).map_err(|e| e.to_string())
+/// Says in plain words that `target` names no revision or more than one,
+/// instead of the backend's own message. Other errors are kept
+pub fn revision_error(target: &str, error: VcsError) -> VcsError {
+    let text = error.to_string();
+    if text.contains("is ambiguous") || text.contains("ambiguous identifier") {
+        VcsError::NotFound(format!(
+            "'{}' matches more than one revision. type more of its hash",
+            target
+        ))
+    } else if let VcsError::NotFound(_) = error {
+        VcsError::NotFound(format!("there is no revision '{}'", target))
+    } else {
+        error
+    }
+}
+
+/// Entries to pick a branch from, made of each branch name after `*` for the
+/// current branch or a space. The current branch goes first so the cursor
+/// starts on it
+pub fn branch_entries<'a, I>(branches: I) -> Vec<Entry>
+where
+    I: Iterator<Item = (&'a str, &'a str)>,
+{
+    let mut entries: Vec<_> = branches
+        .map(|(head, name)| Entry {
+            filename: name.into(),
+            selected: false,
+            state: if head == "*" {
+                State::Current
             } else {
-                String::from_utf8(output.stderr)
-                    .map_err(|e| e.to_string())
-                    .and_then(|o| Err(o))
-            }
+                State::Branch
+            },
+            columns: None,
+        })
+        .collect();
+    entries.sort_by_key(|e| !matches!(e.state, State::Current));
+    entries
+}
+
+/// The actions that talk to a remote, which may ask for credentials
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RemoteOperation {
+    Fetch,
+    Pull,
+    Push,
+}
+
+pub struct CommandOutput {
+    pub stdout: String,
+}
+
+impl CommandOutput {
+    pub fn from_command(command: &mut Command) -> Result<Self, VcsError> {
+        let label = profile::label(command);
+        let start = Instant::now();
+        let output = command.output();
+        let duration = start.elapsed();
+        if let Some(label) = command_log::label(command) {
+            let status = output.as_ref().ok().map(|output| output.status);
+            command_log::record(
+                &label,
+                status.and_then(|status| status.code()),
+                status.map_or(false, |status| status.success()),
+            );
+        }
+        let output = output?;
+        if let Some(label) = label {
+            profile::record(label, duration, output.status.success());
         }
-        Err(error) => Err(error.to_string()),
+
+        let stdout = String::from_utf8_lossy(&output.stdout[..]).into_owned();
+        let stderr = String::from_utf8_lossy(&output.stderr[..]).into_owned();
+        if output.status.success() {
+            Ok(Self { stdout })
+        } else {
+            Err(VcsError::from_stderr(stderr, output.status.code()))
+        }
+    }
+}
+
+fn run_in_terminal(command: &mut Command) -> Result<String, VcsError> {
+    fn copy_to<R, W>(pipe: Option<R>, mut to: W) -> JoinHandle<Vec<u8>>
+    where
+        R: 'static + Read + Send,
+        W: 'static + Write + Send,
+    {
+        thread::spawn(move || {
+            let mut bytes = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let mut buf = [0; 1024];
+                while let Ok(len @ 1..) = pipe.read(&mut buf) {
+                    // prompts do not end in a newline, so each read shows
+                    let _ = to.write_all(&buf[..len]);
+                    let _ = to.flush();
+                    bytes.extend_from_slice(&buf[..len]);
+                }
+            }
+            bytes
+        })
+    }
+
+    let label = command_log::label(command);
+    command.stdin(Stdio::inherit());
+    let mut child = command.spawn()?;
+    let stdout = copy_to(child.stdout.take(), io::stdout());
+    let stderr = copy_to(child.stderr.take(), io::stderr());
+    let status = child.wait()?;
+    if let Some(label) = label {
+        command_log::record(&label, status.code(), status.success());
+    }
+
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    let mut text = String::from_utf8_lossy(&stdout[..]).into_owned();
+    text.push_str(&String::from_utf8_lossy(&stderr[..]));
+    if status.success() {
+        Ok(text)
+    } else {
+        Err(VcsError::from_stderr(text, status.code()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_number_is_taken_from_the_first_line() {
+        assert_eq!(version_number("git version 2.43.0\n"), Some("2.43.0"));
+        assert_eq!(
+            version_number(
+                "Mercurial Distributed SCM (version 6.5.2)\n(see ...)\n"
+            ),
+            Some("6.5.2")
+        );
+        assert_eq!(version_number(""), None);
+    }
+
+    #[test]
+    fn commands_do_not_inherit_the_environment_of_a_git_hook() {
+        let command = crate::mock_actions::MockActions::new().command();
+        let removed: Vec<_> = command
+            .get_envs()
+            .filter(|(_, value)| value.is_none())
+            .map(|(name, _)| name.to_string_lossy().into_owned())
+            .collect();
+        assert!(removed.contains(&"GIT_INDEX_FILE".into()));
+        assert!(removed.contains(&"GIT_DIR".into()));
     }
 }